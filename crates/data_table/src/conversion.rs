@@ -0,0 +1,224 @@
+//! build the data table from the rows of the queries,
+//! the fields of the tab describe the columns and the types of the
+//! columns not in the tab are inferred from the values
+use crate::{
+    infer,
+    DataColumn,
+    DataTable,
+};
+use diwata_intel::{
+    data_container::QueryResult,
+    data_group::GroupResult,
+    Field,
+    Rows,
+    Tab,
+    Value,
+};
+
+impl<'a> From<&'a Field> for DataColumn {
+    fn from(field: &'a Field) -> Self {
+        DataColumn {
+            name: field.name.clone(),
+            description: field.description.clone(),
+            tags: vec![],
+            data_type: field.get_data_type().clone(),
+            is_primary: field.is_primary,
+        }
+    }
+}
+
+impl From<QueryResult> for DataTable {
+    /// the fields of the window are already arranged to match the columns
+    /// of the rows, an ad-hoc query without a window has all of its
+    /// column types inferred
+    fn from(result: QueryResult) -> Self {
+        let fields = match result.window {
+            Some(window) => window.main_tab.fields,
+            None => {
+                result
+                    .rows
+                    .columns
+                    .iter()
+                    .map(|column| Field::from_result_column(column))
+                    .collect()
+            }
+        };
+        from_fields(&fields, result.rows)
+    }
+}
+
+impl From<(Tab, Rows)> for DataTable {
+    fn from((tab, rows): (Tab, Rows)) -> Self {
+        let fields = tab.fields_for_columns(&rows.columns);
+        from_fields(&fields, rows)
+    }
+}
+
+impl<'a> From<&'a GroupResult> for DataTable {
+    /// a row for each group, with the columns the rows are grouped by
+    /// followed by the count and the aggregates of the group
+    fn from(result: &'a GroupResult) -> Self {
+        let names = result
+            .group_by
+            .iter()
+            .map(|column_name| column_name.name.clone())
+            .chain(std::iter::once("count".to_string()))
+            .chain(result.aggregates.iter().map(|aggregate| {
+                format!(
+                    "{} of {}",
+                    aggregate.aggregate.name(),
+                    aggregate.column_name.name
+                )
+            }));
+        let fields: Vec<Field> =
+            names.map(|name| Field::from_result_column(&name)).collect();
+        let mut rows =
+            Rows::new(fields.iter().map(|field| field.name.clone()).collect());
+        for group in result.groups.iter() {
+            let mut row = group.keys.clone();
+            row.push(Value::Bigint(group.count));
+            row.extend(group.aggregates.iter().cloned());
+            rows.push(row);
+        }
+        from_fields(&fields, rows)
+    }
+}
+
+/// the fields are in the same order as the columns of the rows
+fn from_fields(fields: &[Field], rows: Rows) -> DataTable {
+    let mut data = rows.data;
+    let columns = fields
+        .iter()
+        .enumerate()
+        .map(|(index, field)| {
+            let mut column = DataColumn::from(field);
+            // the columns not in the tab are typed as text
+            if field.is_computed {
+                column.data_type = infer::infer_column(&mut data, index);
+            }
+            column
+        })
+        .collect();
+    DataTable {
+        columns,
+        rows: data,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Type;
+    use diwata_intel::{
+        data_aggregate::{
+            Aggregate,
+            ColumnAggregate,
+        },
+        data_group::Group,
+        field::ColumnDetail,
+        ColumnName,
+        TableName,
+    };
+
+    fn rows(columns: &[&str], data: Vec<Vec<Value>>) -> Rows {
+        let mut rows = Rows::new(
+            columns.iter().map(|column| column.to_string()).collect(),
+        );
+        for row in data {
+            rows.push(row);
+        }
+        rows
+    }
+
+    fn text(value: &str) -> Value {
+        Value::Text(value.to_string())
+    }
+
+    fn types(table: &DataTable) -> Vec<Type> {
+        table
+            .columns
+            .iter()
+            .map(|column| column.data_type.clone())
+            .collect()
+    }
+
+    #[test]
+    fn query_without_window_is_inferred() {
+        let result = QueryResult {
+            window: None,
+            rows: rows(
+                &["film_id", "title"],
+                vec![
+                    vec![text("1"), text("ACADEMY DINOSAUR")],
+                    vec![text("2"), text("ACE GOLDFINGER")],
+                ],
+            ),
+            is_editable: false,
+        };
+        let table = DataTable::from(result);
+        assert_eq!(types(&table), vec![Type::Int, Type::Text]);
+        assert_eq!(table.rows[1], vec![Value::Int(2), text("ACE GOLDFINGER")]);
+    }
+
+    #[test]
+    fn only_computed_columns_of_tab_are_inferred() {
+        let title = Field {
+            column_detail: ColumnDetail::Simple(
+                ColumnName::from("title"),
+                Type::Varchar,
+            ),
+            is_computed: false,
+            ..Field::from_result_column("title")
+        };
+        let tab = Tab {
+            name: "Film".to_string(),
+            description: None,
+            table_name: TableName::from("film"),
+            fields: vec![title],
+            is_view: false,
+            display: None,
+        };
+        let rows =
+            rows(&["title", "rentals"], vec![vec![text("1984"), text("12")]]);
+        let fields = tab.fields_for_columns(&rows.columns);
+        assert_eq!(
+            fields
+                .iter()
+                .map(|field| field.is_computed)
+                .collect::<Vec<bool>>(),
+            vec![false, true]
+        );
+        let table = DataTable::from((tab, rows));
+        assert_eq!(types(&table), vec![Type::Varchar, Type::Int]);
+        // the text of the tab column is kept even if it looks like a number
+        assert_eq!(table.rows[0], vec![text("1984"), Value::Int(12)]);
+    }
+
+    #[test]
+    fn group_columns_are_named_and_typed() {
+        let result = GroupResult {
+            group_by: vec![ColumnName::from("rating")],
+            aggregates: vec![ColumnAggregate {
+                column_name: ColumnName::from("length"),
+                aggregate: Aggregate::Avg,
+            }],
+            groups: vec![Group {
+                keys: vec![text("PG")],
+                count: 194,
+                aggregates: vec![Value::Double(112.0)],
+            }],
+        };
+        let table = DataTable::from(&result);
+        let names: Vec<&str> = table
+            .columns
+            .iter()
+            .map(|column| column.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["rating", "count", "avg of length"]);
+        assert_eq!(types(&table), vec![Type::Text, Type::Bigint, Type::Double]);
+        assert_eq!(
+            table.rows[0],
+            vec![text("PG"), Value::Bigint(194), Value::Double(112.0)]
+        );
+    }
+}
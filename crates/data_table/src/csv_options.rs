@@ -0,0 +1,83 @@
+use crate::error::{
+    CsvError,
+    Reason,
+};
+
+/// How the csv is read, the defaults are for a comma separated,
+/// utf-8 encoded csv with a header
+#[derive(Debug, Clone, PartialEq)]
+pub struct CsvOptions {
+    pub delimiter: u8,
+    pub quote: u8,
+    /// when there is no header, the columns are matched by their position
+    pub has_header: bool,
+    pub encoding: Encoding,
+    /// collect the errors alongside the good rows instead of
+    /// failing at the first error
+    pub lenient: bool,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        CsvOptions {
+            delimiter: b',',
+            quote: b'"',
+            has_header: true,
+            encoding: Encoding::Utf8,
+            lenient: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Encoding {
+    Utf8,
+    /// ISO-8859-1, each byte is a character
+    Latin1,
+    Utf16Le,
+    Utf16Be,
+}
+
+impl Encoding {
+    /// decode the bytes into text, the byte order mark is removed
+    pub(crate) fn decode(&self, bytes: &[u8]) -> Result<String, CsvError> {
+        let invalid = |reason: String| {
+            CsvError::new(1, None, Reason::InvalidEncoding(reason))
+        };
+        match self {
+            Encoding::Utf8 => {
+                let bytes = if bytes.starts_with(&[0xEF, 0xBB, 0xBF]) {
+                    &bytes[3..]
+                } else {
+                    bytes
+                };
+                String::from_utf8(bytes.to_vec())
+                    .map_err(|e| invalid(e.to_string()))
+            }
+            Encoding::Latin1 => {
+                Ok(bytes.iter().map(|b| char::from(*b)).collect())
+            }
+            Encoding::Utf16Le | Encoding::Utf16Be => {
+                if bytes.len() % 2 != 0 {
+                    return Err(invalid("odd number of bytes".to_string()));
+                }
+                let units: Vec<u16> = bytes
+                    .chunks(2)
+                    .map(|pair| {
+                        if *self == Encoding::Utf16Le {
+                            u16::from_le_bytes([pair[0], pair[1]])
+                        } else {
+                            u16::from_be_bytes([pair[0], pair[1]])
+                        }
+                    })
+                    .collect();
+                let units = if units.first() == Some(&0xFEFF) {
+                    &units[1..]
+                } else {
+                    &units[..]
+                };
+                String::from_utf16(units).map_err(|e| invalid(e.to_string()))
+            }
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use std::{
+    error::Error,
+    fmt,
+};
+
+/// An error in loading a csv into the data table
+#[derive(Debug, Clone, PartialEq)]
+pub struct CsvError {
+    /// the line in the csv, the header is in line 1
+    pub line: u64,
+    /// the name of the column, when the error is about a column
+    pub column: Option<String>,
+    pub reason: Reason,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reason {
+    /// the column is not in the header of the csv
+    MissingColumn,
+    /// the record has less fields than the columns
+    MissingValue,
+    /// the value can not be converted into the column type
+    InvalidValue(String),
+    /// the record can not be parsed by the csv reader
+    Malformed(String),
+    /// the bytes can not be decoded with the encoding
+    InvalidEncoding(String),
+}
+
+impl CsvError {
+    pub(crate) fn new(line: u64, column: Option<&str>, reason: Reason) -> Self {
+        CsvError {
+            line,
+            column: column.map(ToString::to_string),
+            reason,
+        }
+    }
+}
+
+impl fmt::Display for CsvError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}", self.line)?;
+        if let Some(column) = &self.column {
+            write!(f, ", column {}", column)?;
+        }
+        match &self.reason {
+            Reason::MissingColumn => write!(f, ": not found in the header"),
+            Reason::MissingValue => write!(f, ": has no value"),
+            Reason::InvalidValue(reason) => {
+                write!(f, ": invalid value, {}", reason)
+            }
+            Reason::Malformed(reason) => write!(f, ": malformed, {}", reason),
+            Reason::InvalidEncoding(reason) => {
+                write!(f, ": invalid encoding, {}", reason)
+            }
+        }
+    }
+}
+
+impl Error for CsvError {}
+
+/// An error in evaluating a view query against the rows of the data table
+#[derive(Debug, Clone, PartialEq)]
+pub enum ViewError {
+    ParseError(String),
+    /// the query uses a part of sql the view engine does not evaluate
+    Unsupported(String),
+    UnknownColumn(String),
+    /// the column is selected in a grouped query but not in the GROUP BY
+    NotGrouped(String),
+    InvalidExpression(String),
+}
+
+impl fmt::Display for ViewError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ViewError::ParseError(e) => write!(f, "unable to parse sql: {}", e),
+            ViewError::Unsupported(e) => write!(f, "not supported: {}", e),
+            ViewError::UnknownColumn(column) => {
+                write!(f, "unknown column: {}", column)
+            }
+            ViewError::NotGrouped(column) => {
+                write!(f, "{} must appear in the GROUP BY", column)
+            }
+            ViewError::InvalidExpression(e) => {
+                write!(f, "invalid expression: {}", e)
+            }
+        }
+    }
+}
+
+impl Error for ViewError {}
@@ -0,0 +1,133 @@
+//! guess the type of the columns from untyped sources
+//! such as csv and the results of ad-hoc sql
+use crate::{
+    value,
+    Type,
+};
+use rustorm::Value;
+
+/// the number of values looked at to guess the type of a column
+pub const SAMPLE_SIZE: usize = 100;
+
+/// the types tried in order, the first type all the samples can be
+/// converted into is the type of the column
+const CANDIDATES: [Type; 7] = [
+    Type::Int,
+    Type::Bigint,
+    Type::Double,
+    Type::Bool,
+    Type::Date,
+    Type::Timestamp,
+    Type::TimestampTz,
+];
+
+/// guess the type of the text samples, empty texts are nulls.
+/// A column with no samples or which matches none of the types is text
+pub fn infer_type<'a, I>(samples: I) -> Type
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let samples: Vec<&str> = samples
+        .into_iter()
+        .filter(|sample| !sample.trim().is_empty())
+        .take(SAMPLE_SIZE)
+        .collect();
+    if samples.is_empty() {
+        return Type::Text;
+    }
+    CANDIDATES
+        .iter()
+        .find(|candidate| {
+            samples.iter().all(|sample| {
+                diwata_intel::parse_value(sample, candidate).is_ok()
+            })
+        })
+        .cloned()
+        .unwrap_or(Type::Text)
+}
+
+/// the type of the values in the column of the rows,
+/// the texts are converted when they are all of the guessed type
+pub(crate) fn infer_column(rows: &mut [Vec<Value>], index: usize) -> Type {
+    let sampled: Vec<&Value> = rows
+        .iter()
+        .filter_map(|row| row.get(index))
+        .filter(|value| **value != Value::Nil)
+        .take(SAMPLE_SIZE)
+        .collect();
+    let is_text = sampled.iter().all(|value| {
+        match value {
+            Value::Text(_) => true,
+            _ => false,
+        }
+    });
+    if !is_text {
+        return sampled
+            .first()
+            .cloned()
+            .and_then(value::value_type)
+            .unwrap_or(Type::Text);
+    }
+    let sql_type = infer_type(sampled.iter().map(|value| {
+        match value {
+            Value::Text(text) => text.as_str(),
+            _ => "",
+        }
+    }));
+    if sql_type == Type::Text {
+        return sql_type;
+    }
+    // the values after the samples may not be convertible
+    let mut converted = Vec::with_capacity(rows.len());
+    for row in rows.iter() {
+        let value = match row.get(index) {
+            Some(Value::Text(text)) => {
+                match diwata_intel::parse_value(text, &sql_type) {
+                    Ok(value) => value,
+                    Err(_) => return Type::Text,
+                }
+            }
+            Some(value) => value.clone(),
+            None => Value::Nil,
+        };
+        converted.push(value);
+    }
+    for (row, value) in rows.iter_mut().zip(converted.into_iter()) {
+        if let Some(cell) = row.get_mut(index) {
+            *cell = value;
+        }
+    }
+    sql_type
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn infer_numbers_and_dates() {
+        assert_eq!(infer_type(vec!["1", "", "42"]), Type::Int);
+        assert_eq!(infer_type(vec!["1", "9000000000"]), Type::Bigint);
+        assert_eq!(infer_type(vec!["1", "2.5"]), Type::Double);
+        assert_eq!(infer_type(vec!["true", "false"]), Type::Bool);
+        assert_eq!(infer_type(vec!["2019-01-31", "2019-02-01"]), Type::Date);
+        assert_eq!(
+            infer_type(vec!["2019-01-31 13:00:00", "2019-02-01T08:30:00"]),
+            Type::Timestamp
+        );
+        assert_eq!(infer_type(vec!["rust", "1"]), Type::Text);
+        assert_eq!(infer_type(vec!["", " "]), Type::Text);
+    }
+
+    #[test]
+    fn text_values_are_converted() {
+        let mut rows = vec![
+            vec![Value::Text("a".to_string()), Value::Text("1".to_string())],
+            vec![Value::Text("b".to_string()), Value::Nil],
+        ];
+        assert_eq!(infer_column(&mut rows, 0), Type::Text);
+        assert_eq!(infer_column(&mut rows, 1), Type::Int);
+        assert_eq!(rows[0][1], Value::Int(1));
+        assert_eq!(rows[0][0], Value::Text("a".to_string()));
+    }
+}
@@ -1,13 +1,37 @@
 #![deny(warnings)]
 #![deny(clippy::all)]
+pub use csv_options::{
+    CsvOptions,
+    Encoding,
+};
+pub use error::{
+    CsvError,
+    Reason,
+    ViewError,
+};
+pub use infer::infer_type;
 pub use rustorm::{
     types::SqlType as Type,
     Value,
 };
 
+use csv::StringRecord;
 use diwata_intel::DataRow;
-use rustorm::common;
-use sqlparser::sqlast::ASTNode;
+use sqlparser::{
+    dialect::GenericSqlDialect,
+    sqlast::{
+        ASTNode,
+        SQLStatement,
+    },
+    sqlparser::Parser,
+};
+
+mod conversion;
+mod csv_options;
+mod error;
+mod infer;
+mod value;
+mod view;
 
 /// A generic representation of rows that resembles homogeneos rows in a table
 #[derive(Debug)]
@@ -27,59 +51,130 @@ pub struct DataColumn {
 }
 
 impl DataColumn {
-    fn convert(&self, value: &Value) -> Value {
-        common::cast_type(value, &self.data_type)
+    fn convert(&self, text: &str) -> Result<Value, Reason> {
+        diwata_intel::parse_value(text, &self.data_type)
+            .map_err(|e| Reason::InvalidValue(format!("{:?}", e)))
     }
 }
 
 impl DataTable {
-    pub fn from_csv(columns: Vec<DataColumn>, csv: &str) -> Self {
-        let mut rdr = csv::Reader::from_reader(csv.as_bytes());
-        let mut column_names = vec![];
-        if let Ok(header) = rdr.headers() {
-            for h in header {
-                column_names.push(h)
-            }
+    /// read the rows of a comma separated csv with a header,
+    /// failing at the first row that can not be converted
+    pub fn from_csv(
+        columns: Vec<DataColumn>,
+        csv: &str,
+    ) -> Result<Self, CsvError> {
+        let (data_table, _errors) = Self::from_csv_with_options(
+            columns,
+            csv.as_bytes(),
+            &CsvOptions::default(),
+        )?;
+        Ok(data_table)
+    }
+
+    /// read the rows of the csv, in lenient mode the rows that can not be
+    /// converted are left out and their errors are returned alongside the table.
+    /// A column missing in the header fails in both modes
+    pub fn from_csv_with_options(
+        columns: Vec<DataColumn>,
+        csv: &[u8],
+        options: &CsvOptions,
+    ) -> Result<(Self, Vec<CsvError>), CsvError> {
+        let text = options.encoding.decode(csv)?;
+        let mut rdr = csv_reader(&text, options);
+        let field_record_pos: Vec<usize> = if options.has_header {
+            let header = rdr.headers().map_err(|e| malformed(1, &e))?;
+            let header_line =
+                header.position().map(|pos| pos.line()).unwrap_or(1);
+            columns
+                .iter()
+                .map(|field| {
+                    header
+                        .iter()
+                        .position(|column| field.name == column.trim())
+                        .ok_or_else(|| {
+                            CsvError::new(
+                                header_line,
+                                Some(&field.name),
+                                Reason::MissingColumn,
+                            )
+                        })
+                })
+                .collect::<Result<_, _>>()?
         } else {
-            panic!("error reading header in csv");
-        }
-        let mut field_record_pos = vec![];
-        for field in &columns {
-            if let Some(pos) =
-                column_names.iter().position(|column| field.name == *column)
-            {
-                field_record_pos.push(pos);
-            } else {
-                panic!("rows does not have this field: {}", field.name);
-            }
-        }
-        let mut tmp_rows: Vec<DataRow> = vec![];
+            (0..columns.len()).collect()
+        };
+        let mut rows: Vec<DataRow> = vec![];
+        let mut errors = vec![];
         for record in rdr.records() {
-            if let Ok(record) = record {
-                let mut tmp_row: DataRow = Vec::with_capacity(columns.len());
-                for value in record.iter() {
-                    tmp_row.push(Value::Text(value.to_string()));
-                }
-                tmp_rows.push(tmp_row);
+            let row = record
+                .map_err(|e| {
+                    let line = e.position().map(|pos| pos.line()).unwrap_or(0);
+                    malformed(line, &e)
+                })
+                .and_then(|record| {
+                    if is_blank(&record) {
+                        Ok(None)
+                    } else {
+                        convert_record(&columns, &field_record_pos, &record)
+                            .map(Some)
+                    }
+                });
+            match row {
+                Ok(Some(row)) => rows.push(row),
+                Ok(None) => (),
+                Err(e) if options.lenient => errors.push(e),
+                Err(e) => return Err(e),
             }
         }
-        println!("field record pos: {:?}", field_record_pos);
-        let mut rows: Vec<DataRow> = vec![];
-        for tmp_row in tmp_rows {
-            let mut row: DataRow = vec![];
-            for (i, field) in columns.iter().enumerate() {
-                let index = field_record_pos[i];
-                println!("index: {}", index);
-                println!("field: {:?}", field);
-                let tmp_value: &Value = &tmp_row[index];
-                println!("tmp_value: {:?}", tmp_value);
-                let value: Value = field.convert(tmp_value);
-                println!("value: {:?}", value);
-                row.push(value);
-            }
-            rows.push(row);
+        Ok((DataTable { columns, rows }, errors))
+    }
+
+    /// the columns of the csv with their types inferred from the first rows,
+    /// the columns are named by their position when there is no header
+    pub fn infer_columns(
+        csv: &[u8],
+        options: &CsvOptions,
+    ) -> Result<Vec<DataColumn>, CsvError> {
+        let text = options.encoding.decode(csv)?;
+        let mut rdr = csv_reader(&text, options);
+        let mut names: Vec<String> = if options.has_header {
+            rdr.headers()
+                .map_err(|e| malformed(1, &e))?
+                .iter()
+                .map(|header| header.trim().to_string())
+                .collect()
+        } else {
+            vec![]
+        };
+        // the malformed records are reported when the rows are read
+        let samples: Vec<StringRecord> = rdr
+            .records()
+            .filter_map(Result::ok)
+            .filter(|record| !is_blank(record))
+            .take(infer::SAMPLE_SIZE)
+            .collect();
+        if !options.has_header {
+            let width =
+                samples.iter().map(StringRecord::len).max().unwrap_or(0);
+            names = (1..=width).map(|n| format!("column_{}", n)).collect();
         }
-        DataTable { columns, rows }
+        let columns = names
+            .into_iter()
+            .enumerate()
+            .map(|(index, name)| {
+                DataColumn {
+                    name,
+                    description: None,
+                    tags: vec![],
+                    data_type: infer_type(
+                        samples.iter().filter_map(|record| record.get(index)),
+                    ),
+                    is_primary: false,
+                }
+            })
+            .collect();
+        Ok(columns)
     }
 
     /// add more rows into this view
@@ -89,12 +184,82 @@ impl DataTable {
         }
     }
 
-    /// derive a view based on the sql ast
-    pub fn get_views(&self, _view_sql: &ASTNode) -> Vec<DataTable> {
-        vec![]
+    /// derive a view based on the sql ast, the query is evaluated against
+    /// the rows already loaded in this table
+    pub fn get_views(
+        &self,
+        view_sql: &ASTNode,
+    ) -> Result<Vec<DataTable>, ViewError> {
+        match view_sql {
+            ASTNode::SQLSubquery(query) => {
+                Ok(vec![view::evaluate_query(self, query)?])
+            }
+            _ => Err(ViewError::Unsupported(view_sql.to_string())),
+        }
+    }
+
+    /// parse the select query and derive a view of this table,
+    /// the table in the FROM is this table regardless of its name
+    pub fn query(&self, sql: &str) -> Result<DataTable, ViewError> {
+        let dialect = GenericSqlDialect {};
+        let mut statements = Parser::parse_sql(&dialect, sql.to_string())
+            .map_err(|e| ViewError::ParseError(format!("{:?}", e)))?;
+        match (statements.pop(), statements.is_empty()) {
+            (Some(SQLStatement::SQLQuery(query)), true) => {
+                view::evaluate_query(self, &query)
+            }
+            _ => {
+                Err(ViewError::Unsupported(
+                    "only a single SELECT query".to_string(),
+                ))
+            }
+        }
     }
 }
 
+fn csv_reader<'t>(
+    text: &'t str,
+    options: &CsvOptions,
+) -> csv::Reader<&'t [u8]> {
+    csv::ReaderBuilder::new()
+        .delimiter(options.delimiter)
+        .quote(options.quote)
+        .has_headers(options.has_header)
+        .flexible(true)
+        .from_reader(text.as_bytes())
+}
+
+/// convert the fields of the record into the types of the columns
+fn convert_record(
+    columns: &[DataColumn],
+    field_record_pos: &[usize],
+    record: &StringRecord,
+) -> Result<DataRow, CsvError> {
+    let line = record.position().map(|pos| pos.line()).unwrap_or(0);
+    columns
+        .iter()
+        .zip(field_record_pos.iter())
+        .map(|(field, index)| {
+            record
+                .get(*index)
+                .ok_or(Reason::MissingValue)
+                .and_then(|text| field.convert(text))
+                .map_err(|reason| {
+                    CsvError::new(line, Some(&field.name), reason)
+                })
+        })
+        .collect()
+}
+
+/// a line with only spaces, such as the indention at the end of the csv
+fn is_blank(record: &StringRecord) -> bool {
+    record.iter().all(|field| field.trim().is_empty())
+}
+
+fn malformed(line: u64, e: &csv::Error) -> CsvError {
+    CsvError::new(line, None, Reason::Malformed(e.to_string()))
+}
+
 #[cfg(test)]
 mod test {
 
@@ -157,7 +322,7 @@ java,8,medium,true,large,jdk
                 is_primary: false,
             },
         ];
-        let dataview = DataTable::from_csv(columns, csv);
+        let dataview = DataTable::from_csv(columns, csv).unwrap();
         assert_eq!(dataview.columns.len(), 6);
         assert_eq!(dataview.columns[0].name, "pl");
         assert_eq!(dataview.rows[0][0], Value::Text("rust".to_string()));
@@ -166,5 +331,106 @@ java,8,medium,true,large,jdk
         assert_eq!(dataview.columns[2].name, "speed");
         assert_eq!(dataview.rows[0][2], Value::Text("fast".to_string()));
         assert_eq!(dataview.rows[1][2], Value::Text("fast".to_string()));
+        assert_eq!(dataview.rows[2][5], Value::Int(99));
+        assert_eq!(dataview.rows.len(), 4);
+    }
+
+    fn column(name: &str, data_type: Type) -> DataColumn {
+        DataColumn {
+            name: name.into(),
+            data_type,
+            description: None,
+            tags: vec![],
+            is_primary: false,
+        }
+    }
+
+    #[test]
+    fn missing_column_in_header() {
+        let csv = "pl,version\nrust,1\n";
+        let columns = vec![column("pl", Type::Text), column("vm", Type::Bool)];
+        let err = DataTable::from_csv(columns, csv).unwrap_err();
+        assert_eq!(err.line, 1);
+        assert_eq!(err.column, Some("vm".to_string()));
+        assert_eq!(err.reason, Reason::MissingColumn);
+    }
+
+    #[test]
+    fn invalid_value_reports_line_and_column() {
+        let csv = "pl,version\nrust,1\nc,ninety-nine\n";
+        let columns =
+            vec![column("pl", Type::Text), column("version", Type::Int)];
+        let err = DataTable::from_csv(columns, csv).unwrap_err();
+        assert_eq!(err.line, 3);
+        assert_eq!(err.column, Some("version".to_string()));
+        match err.reason {
+            Reason::InvalidValue(_) => (),
+            reason => panic!("unexpected reason: {:?}", reason),
+        }
+    }
+
+    #[test]
+    fn lenient_collects_errors_and_keeps_good_rows() {
+        let csv = "pl,version\nrust,1\nc,ninety-nine\njava\nhaskel,8\n";
+        let columns =
+            vec![column("pl", Type::Text), column("version", Type::Int)];
+        let options = CsvOptions {
+            lenient: true,
+            ..Default::default()
+        };
+        let (dataview, errors) =
+            DataTable::from_csv_with_options(columns, csv.as_bytes(), &options)
+                .unwrap();
+        assert_eq!(dataview.rows.len(), 2);
+        assert_eq!(dataview.rows[1][0], Value::Text("haskel".to_string()));
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].line, 3);
+        assert_eq!(errors[1].line, 4);
+        assert_eq!(errors[1].reason, Reason::MissingValue);
+    }
+
+    #[test]
+    fn no_header_matches_columns_by_position() {
+        let csv = "rust;'fast; safe'\nc;fast\n";
+        let columns =
+            vec![column("pl", Type::Text), column("speed", Type::Text)];
+        let options = CsvOptions {
+            delimiter: b';',
+            quote: b'\'',
+            has_header: false,
+            ..Default::default()
+        };
+        let (dataview, errors) =
+            DataTable::from_csv_with_options(columns, csv.as_bytes(), &options)
+                .unwrap();
+        assert!(errors.is_empty());
+        assert_eq!(dataview.rows.len(), 2);
+        assert_eq!(dataview.rows[0][1], Value::Text("fast; safe".to_string()));
+    }
+
+    #[test]
+    fn infer_columns_from_csv() {
+        let csv = "pl,version,released\nrust,1,2015-05-15\nc,99,\n";
+        let columns =
+            DataTable::infer_columns(csv.as_bytes(), &CsvOptions::default())
+                .unwrap();
+        assert_eq!(columns.len(), 3);
+        assert_eq!(columns[0].data_type, Type::Text);
+        assert_eq!(columns[1].data_type, Type::Int);
+        assert_eq!(columns[2].name, "released");
+        assert_eq!(columns[2].data_type, Type::Date);
+    }
+
+    #[test]
+    fn latin1_encoding() {
+        let csv = b"pl\ncaf\xe9\n";
+        let columns = vec![column("pl", Type::Text)];
+        let options = CsvOptions {
+            encoding: Encoding::Latin1,
+            ..Default::default()
+        };
+        let (dataview, _errors) =
+            DataTable::from_csv_with_options(columns, csv, &options).unwrap();
+        assert_eq!(dataview.rows[0][0], Value::Text("café".to_string()));
     }
 }
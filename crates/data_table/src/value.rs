@@ -0,0 +1,179 @@
+//! typed comparison and arithmetic of the values in the data table
+use crate::Type;
+use rustorm::Value;
+use std::cmp::Ordering;
+
+/// the sql type of the value, used to convert a text literal
+/// before it is compared to the value
+pub(crate) fn value_type(value: &Value) -> Option<Type> {
+    let sql_type = match value {
+        Value::Bool(_) => Type::Bool,
+        Value::Tinyint(_) => Type::Tinyint,
+        Value::Smallint(_) => Type::Smallint,
+        Value::Int(_) => Type::Int,
+        Value::Bigint(_) => Type::Bigint,
+        Value::Float(_) => Type::Float,
+        Value::Double(_) => Type::Double,
+        Value::BigDecimal(_) => Type::Numeric,
+        Value::Char(_) | Value::Text(_) | Value::ImageUri(_) => Type::Text,
+        Value::Json(_) => Type::Json,
+        Value::Uuid(_) => Type::Uuid,
+        Value::Date(_) => Type::Date,
+        Value::Time(_) => Type::Time,
+        Value::DateTime(_) => Type::Timestamp,
+        Value::Timestamp(_) => Type::TimestampTz,
+        _ => return None,
+    };
+    Some(sql_type)
+}
+
+pub(crate) fn as_i64(value: &Value) -> Option<i64> {
+    match value {
+        Value::Tinyint(v) => Some(i64::from(*v)),
+        Value::Smallint(v) => Some(i64::from(*v)),
+        Value::Int(v) => Some(i64::from(*v)),
+        Value::Bigint(v) => Some(*v),
+        _ => None,
+    }
+}
+
+pub(crate) fn as_f64(value: &Value) -> Option<f64> {
+    match value {
+        Value::Float(v) => Some(f64::from(*v)),
+        Value::Double(v) => Some(*v),
+        Value::BigDecimal(v) => v.to_string().parse().ok(),
+        _ => as_i64(value).map(|v| v as f64),
+    }
+}
+
+fn as_str(value: &Value) -> Option<&str> {
+    match value {
+        Value::Text(v) | Value::Json(v) | Value::ImageUri(v) => {
+            Some(v.as_str())
+        }
+        _ => None,
+    }
+}
+
+/// compare 2 values of the same kind, a text is converted into the type of
+/// the other value such as `'2019-01-31'` to a date.
+/// Null and values that can not be compared have no ordering
+pub(crate) fn compare(a: &Value, b: &Value) -> Option<Ordering> {
+    if let (Some(a), Some(b)) = (as_i64(a), as_i64(b)) {
+        return Some(a.cmp(&b));
+    }
+    if let (Some(a), Some(b)) = (as_f64(a), as_f64(b)) {
+        return a.partial_cmp(&b);
+    }
+    match (a, b) {
+        (Value::Nil, _) | (_, Value::Nil) => None,
+        (Value::Bool(a), Value::Bool(b)) => Some(a.cmp(b)),
+        (Value::Char(a), Value::Char(b)) => Some(a.cmp(b)),
+        (Value::Uuid(a), Value::Uuid(b)) => {
+            Some(a.to_string().cmp(&b.to_string()))
+        }
+        (Value::Date(a), Value::Date(b)) => Some(a.cmp(b)),
+        (Value::Time(a), Value::Time(b)) => Some(a.cmp(b)),
+        (Value::DateTime(a), Value::DateTime(b)) => Some(a.cmp(b)),
+        (Value::Timestamp(a), Value::Timestamp(b)) => Some(a.cmp(b)),
+        _ => {
+            match (as_str(a), as_str(b)) {
+                (Some(a), Some(b)) => Some(a.cmp(b)),
+                (Some(text), None) => {
+                    coerce(text, b).and_then(|a| compare(&a, b))
+                }
+                (None, Some(text)) => {
+                    coerce(text, a).and_then(|b| compare(a, &b))
+                }
+                (None, None) => None,
+            }
+        }
+    }
+}
+
+/// convert the text into the type of the other value
+fn coerce(text: &str, other: &Value) -> Option<Value> {
+    match value_type(other) {
+        Some(Type::Text) | None => None,
+        Some(sql_type) => diwata_intel::parse_value(text, &sql_type).ok(),
+    }
+}
+
+/// the order of the rows, nulls are placed last
+pub(crate) fn sort_order(a: &Value, b: &Value) -> Ordering {
+    match (a, b) {
+        (Value::Nil, Value::Nil) => Ordering::Equal,
+        (Value::Nil, _) => Ordering::Greater,
+        (_, Value::Nil) => Ordering::Less,
+        _ => compare(a, b).unwrap_or(Ordering::Equal),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Arithmetic {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+}
+
+/// integers stays integers, the rest are calculated as double.
+/// Returns None when the values are not numbers or divided by zero
+pub(crate) fn calculate(a: &Value, op: Arithmetic, b: &Value) -> Option<Value> {
+    if let (Some(a), Some(b)) = (as_i64(a), as_i64(b)) {
+        let value = match op {
+            Arithmetic::Add => a.checked_add(b),
+            Arithmetic::Subtract => a.checked_sub(b),
+            Arithmetic::Multiply => a.checked_mul(b),
+            Arithmetic::Divide => a.checked_div(b),
+            Arithmetic::Modulo => a.checked_rem(b),
+        };
+        return value.map(Value::Bigint);
+    }
+    let (a, b) = (as_f64(a)?, as_f64(b)?);
+    let value = match op {
+        Arithmetic::Add => a + b,
+        Arithmetic::Subtract => a - b,
+        Arithmetic::Multiply => a * b,
+        Arithmetic::Divide | Arithmetic::Modulo if b == 0.0 => return None,
+        Arithmetic::Divide => a / b,
+        Arithmetic::Modulo => a % b,
+    };
+    Some(Value::Double(value))
+}
+
+/// match the text with the sql LIKE pattern,
+/// `%` matches any text and `_` matches a single character
+pub(crate) fn is_like(text: &str, pattern: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    // a `%` can match an empty text
+    let skip_wildcards = |states: &mut Vec<bool>| {
+        for (i, p) in pattern.iter().enumerate() {
+            if *p == '%' && states[i] {
+                states[i + 1] = true;
+            }
+        }
+    };
+    // the positions in the pattern reached by the text so far
+    let mut states = vec![false; pattern.len() + 1];
+    states[0] = true;
+    skip_wildcards(&mut states);
+    for c in text.chars() {
+        let mut next = vec![false; pattern.len() + 1];
+        for (i, p) in pattern.iter().enumerate() {
+            if !states[i] {
+                continue;
+            }
+            match p {
+                '%' => next[i] = true,
+                '_' => next[i + 1] = true,
+                p if *p == c => next[i + 1] = true,
+                _ => (),
+            }
+        }
+        skip_wildcards(&mut next);
+        states = next;
+    }
+    states[pattern.len()]
+}
@@ -0,0 +1,778 @@
+//! evaluate a select query against the rows already loaded in a data table,
+//! so the rows can be filtered, sorted and grouped without a round trip
+//! to the server. The table in the FROM is the data table itself
+use crate::{
+    error::ViewError,
+    value::{
+        self,
+        Arithmetic,
+    },
+    DataColumn,
+    DataTable,
+    Type,
+};
+use diwata_intel::DataRow;
+use rustorm::Value;
+use sqlparser::sqlast::{
+    ASTNode,
+    SQLOperator,
+    SQLQuery,
+    SQLSelectItem,
+    SQLSetExpr,
+    Value as SqlValue,
+};
+use std::{
+    cmp::Ordering,
+    collections::{
+        HashMap,
+        HashSet,
+    },
+};
+
+const AGGREGATES: [&str; 5] = ["count", "sum", "avg", "min", "max"];
+
+/// the rows an expression is evaluated against
+#[derive(Clone, Copy)]
+enum Scope<'a> {
+    Row(&'a DataRow),
+    /// aggregates are evaluated over all the rows of the group,
+    /// the columns take the value of the first row
+    Group(&'a [&'a DataRow]),
+}
+
+/// a column of the view
+enum Output<'q> {
+    /// a column of the data table
+    Column(usize),
+    Expr(&'q ASTNode),
+}
+
+pub(crate) fn evaluate_query(
+    table: &DataTable,
+    query: &SQLQuery,
+) -> Result<DataTable, ViewError> {
+    let select = match query.body {
+        SQLSetExpr::Select(ref select) => select,
+        _ => {
+            return Err(ViewError::Unsupported(
+                "set operations such as UNION".to_string(),
+            ))
+        }
+    };
+    if !select.joins.is_empty() {
+        return Err(ViewError::Unsupported("joins".to_string()));
+    }
+    let evaluator = Evaluator {
+        columns: &table.columns,
+    };
+
+    let mut rows: Vec<&DataRow> = vec![];
+    for row in table.rows.iter() {
+        let is_selected = match select.selection {
+            Some(ref selection) => {
+                truth(&evaluator.eval(selection, Scope::Row(row))?)?
+                    == Some(true)
+            }
+            None => true,
+        };
+        if is_selected {
+            rows.push(row);
+        }
+    }
+
+    let is_grouped = !select.group_by.is_empty()
+        || select.having.is_some()
+        || select.projection.iter().any(|item| {
+            match item {
+                SQLSelectItem::UnnamedExpression(expr)
+                | SQLSelectItem::ExpressionWithAlias { expr, .. } => {
+                    has_aggregate(expr)
+                }
+                _ => false,
+            }
+        })
+        || query
+            .order_by
+            .iter()
+            .any(|order| has_aggregate(&order.expr));
+    let mut groups: Vec<Vec<&DataRow>> = if is_grouped {
+        evaluator.group(&rows, &select.group_by)?
+    } else {
+        rows.into_iter().map(|row| vec![row]).collect()
+    };
+    if let Some(ref having) = select.having {
+        let mut selected = vec![];
+        for group in groups {
+            if truth(&evaluator.eval(having, Scope::Group(&group))?)?
+                == Some(true)
+            {
+                selected.push(group);
+            }
+        }
+        groups = selected;
+    }
+
+    let (outputs, mut columns) = evaluator.outputs(&select.projection)?;
+    if is_grouped {
+        for output in outputs.iter() {
+            if let Output::Column(index) = output {
+                let is_in_group_by = select
+                    .group_by
+                    .iter()
+                    .any(|expr| evaluator.column_index(expr) == Some(*index));
+                if !is_in_group_by {
+                    return Err(ViewError::NotGrouped(
+                        table.columns[*index].name.to_string(),
+                    ));
+                }
+            }
+        }
+    }
+
+    let mut entries: Vec<(Vec<Value>, DataRow)> = vec![];
+    for group in groups.iter() {
+        let scope = Scope::Group(group);
+        let mut row = Vec::with_capacity(outputs.len());
+        for output in outputs.iter() {
+            let value = match output {
+                Output::Column(index) => {
+                    group.first().and_then(|first| first.get(*index)).cloned()
+                }
+                Output::Expr(expr) => Some(evaluator.eval(expr, scope)?),
+            };
+            row.push(value.unwrap_or(Value::Nil));
+        }
+        let mut keys = Vec::with_capacity(query.order_by.len());
+        for order in query.order_by.iter() {
+            let key = match output_position(&order.expr, &columns) {
+                Some(position) => row[position].clone(),
+                None => evaluator.eval(&order.expr, scope)?,
+            };
+            keys.push(key);
+        }
+        entries.push((keys, row));
+    }
+    // the sort is stable, rows with the same keys keep their order
+    entries.sort_by(|(a, _), (b, _)| {
+        query
+            .order_by
+            .iter()
+            .zip(a.iter().zip(b.iter()))
+            .map(|(order, (a, b))| {
+                let ordering = value::sort_order(a, b);
+                if order.asc == Some(false) {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            })
+            .find(|ordering| *ordering != Ordering::Equal)
+            .unwrap_or(Ordering::Equal)
+    });
+    let mut rows: Vec<DataRow> =
+        entries.into_iter().map(|(_keys, row)| row).collect();
+    if select.distinct {
+        let mut seen = HashSet::new();
+        rows.retain(|row| seen.insert(format!("{:?}", row)));
+    }
+    if let Some(ref limit) = query.limit {
+        match limit {
+            ASTNode::SQLValue(SqlValue::Long(limit)) => {
+                rows.truncate(*limit as usize)
+            }
+            _ => {
+                return Err(ViewError::InvalidExpression(format!(
+                    "LIMIT {}",
+                    limit.to_string()
+                )));
+            }
+        }
+    }
+    // the type of a calculated column is taken from its values
+    for (position, output) in outputs.iter().enumerate() {
+        if let Output::Expr(_) = output {
+            columns[position].data_type = rows
+                .iter()
+                .find_map(|row| value::value_type(&row[position]))
+                .unwrap_or(Type::Text);
+        }
+    }
+    Ok(DataTable { columns, rows })
+}
+
+/// the position of the selected column referred to in the ORDER BY,
+/// either by its name or by its position starting from 1
+fn output_position(expr: &ASTNode, columns: &[DataColumn]) -> Option<usize> {
+    match expr {
+        ASTNode::SQLValue(SqlValue::Long(position))
+            if *position >= 1 && (*position as usize) <= columns.len() =>
+        {
+            Some(*position as usize - 1)
+        }
+        ASTNode::SQLIdentifier(name) => {
+            let name = unquote(name);
+            columns.iter().position(|column| column.name == name)
+        }
+        _ => None,
+    }
+}
+
+fn has_aggregate(expr: &ASTNode) -> bool {
+    match expr {
+        ASTNode::SQLFunction { name, args, .. } => {
+            is_aggregate(&name.to_string()) || args.iter().any(has_aggregate)
+        }
+        ASTNode::SQLNested(expr)
+        | ASTNode::SQLIsNull(expr)
+        | ASTNode::SQLIsNotNull(expr)
+        | ASTNode::SQLUnary { expr, .. } => has_aggregate(expr),
+        ASTNode::SQLBinaryExpr { left, right, .. } => {
+            has_aggregate(left) || has_aggregate(right)
+        }
+        ASTNode::SQLBetween {
+            expr, low, high, ..
+        } => has_aggregate(expr) || has_aggregate(low) || has_aggregate(high),
+        ASTNode::SQLInList { expr, list, .. } => {
+            has_aggregate(expr) || list.iter().any(has_aggregate)
+        }
+        _ => false,
+    }
+}
+
+fn is_aggregate(function: &str) -> bool {
+    AGGREGATES.contains(&function.to_lowercase().as_str())
+}
+
+/// quoted identifiers such as `"first_name"` are matched without the quotes
+fn unquote(name: &str) -> &str {
+    name.trim_matches('"')
+}
+
+/// the truth of a boolean expression, null is neither true nor false
+fn truth(value: &Value) -> Result<Option<bool>, ViewError> {
+    match value {
+        Value::Bool(value) => Ok(Some(*value)),
+        Value::Nil => Ok(None),
+        value => {
+            Err(ViewError::InvalidExpression(format!(
+                "{:?} is not a boolean",
+                value
+            )))
+        }
+    }
+}
+
+fn from_truth(truth: Option<bool>) -> Value {
+    truth.map(Value::Bool).unwrap_or(Value::Nil)
+}
+
+fn literal(value: &SqlValue) -> Result<Value, ViewError> {
+    match value {
+        SqlValue::Long(v) => Ok(Value::Bigint(*v as i64)),
+        SqlValue::Double(v) => Ok(Value::Double(*v)),
+        SqlValue::SingleQuotedString(v) => Ok(Value::Text(v.to_string())),
+        SqlValue::Boolean(v) => Ok(Value::Bool(*v)),
+        SqlValue::Null => Ok(Value::Nil),
+        value => Err(ViewError::Unsupported(format!("{:?}", value))),
+    }
+}
+
+struct Evaluator<'a> {
+    columns: &'a [DataColumn],
+}
+
+impl<'a> Evaluator<'a> {
+    fn find_column(&self, name: &str) -> Result<usize, ViewError> {
+        let name = unquote(name);
+        self.columns
+            .iter()
+            .position(|column| column.name == name)
+            .or_else(|| {
+                self.columns
+                    .iter()
+                    .position(|column| column.name.eq_ignore_ascii_case(name))
+            })
+            .ok_or_else(|| ViewError::UnknownColumn(name.to_string()))
+    }
+
+    /// the index of the column when the expression is only a column
+    fn column_index(&self, expr: &ASTNode) -> Option<usize> {
+        match expr {
+            ASTNode::SQLIdentifier(name) => self.find_column(name).ok(),
+            // the table qualifier is the data table itself
+            ASTNode::SQLCompoundIdentifier(idents) => {
+                idents.last().and_then(|name| self.find_column(name).ok())
+            }
+            _ => None,
+        }
+    }
+
+    fn outputs<'q>(
+        &self,
+        projection: &'q [SQLSelectItem],
+    ) -> Result<(Vec<Output<'q>>, Vec<DataColumn>), ViewError> {
+        let mut outputs = vec![];
+        let mut columns = vec![];
+        for item in projection.iter() {
+            let (expr, alias) = match item {
+                SQLSelectItem::Wildcard
+                | SQLSelectItem::QualifiedWildcard(_) => {
+                    for (index, column) in self.columns.iter().enumerate() {
+                        outputs.push(Output::Column(index));
+                        columns.push(column.clone());
+                    }
+                    continue;
+                }
+                SQLSelectItem::UnnamedExpression(expr) => (expr, None),
+                SQLSelectItem::ExpressionWithAlias { expr, alias } => {
+                    (expr, Some(unquote(alias).to_string()))
+                }
+            };
+            match self.column_index(expr) {
+                Some(index) => {
+                    let mut column = self.columns[index].clone();
+                    if let Some(alias) = alias {
+                        column.name = alias;
+                    }
+                    outputs.push(Output::Column(index));
+                    columns.push(column);
+                }
+                None => {
+                    if let ASTNode::SQLIdentifier(name) = expr {
+                        return Err(ViewError::UnknownColumn(name.to_string()));
+                    }
+                    outputs.push(Output::Expr(expr));
+                    columns.push(DataColumn {
+                        name: alias.unwrap_or_else(|| expr.to_string()),
+                        description: None,
+                        tags: vec![],
+                        data_type: Type::Text,
+                        is_primary: false,
+                    });
+                }
+            }
+        }
+        Ok((outputs, columns))
+    }
+
+    /// group the rows with the same values of the GROUP BY expressions,
+    /// the groups are in the order they first appear.
+    /// Without GROUP BY all the rows are in 1 group
+    fn group<'r>(
+        &self,
+        rows: &[&'r DataRow],
+        group_by: &[ASTNode],
+    ) -> Result<Vec<Vec<&'r DataRow>>, ViewError> {
+        if group_by.is_empty() {
+            return Ok(vec![rows.to_vec()]);
+        }
+        let mut groups: Vec<Vec<&DataRow>> = vec![];
+        let mut group_index: HashMap<String, usize> = HashMap::new();
+        for &row in rows.iter() {
+            let mut keys = Vec::with_capacity(group_by.len());
+            for expr in group_by.iter() {
+                keys.push(self.eval(expr, Scope::Row(row))?);
+            }
+            let key = format!("{:?}", keys);
+            match group_index.get(&key) {
+                Some(index) => groups[*index].push(row),
+                None => {
+                    group_index.insert(key, groups.len());
+                    groups.push(vec![row]);
+                }
+            }
+        }
+        Ok(groups)
+    }
+
+    fn eval(&self, expr: &ASTNode, scope: Scope) -> Result<Value, ViewError> {
+        match expr {
+            ASTNode::SQLIdentifier(_) | ASTNode::SQLCompoundIdentifier(_) => {
+                let index = match self.column_index(expr) {
+                    Some(index) => index,
+                    None => {
+                        return Err(ViewError::UnknownColumn(expr.to_string()))
+                    }
+                };
+                let row = match scope {
+                    Scope::Row(row) => Some(row),
+                    Scope::Group(rows) => rows.first().cloned(),
+                };
+                // a row shorter than the columns has no value for it
+                Ok(row
+                    .and_then(|row| row.get(index).cloned())
+                    .unwrap_or(Value::Nil))
+            }
+            ASTNode::SQLValue(value) => literal(value),
+            ASTNode::SQLNested(expr) => self.eval(expr, scope),
+            ASTNode::SQLIsNull(expr) => {
+                Ok(Value::Bool(self.eval(expr, scope)? == Value::Nil))
+            }
+            ASTNode::SQLIsNotNull(expr) => {
+                Ok(Value::Bool(self.eval(expr, scope)? != Value::Nil))
+            }
+            ASTNode::SQLBetween {
+                expr,
+                negated,
+                low,
+                high,
+            } => {
+                let value = self.eval(expr, scope)?;
+                let low = self.eval(low, scope)?;
+                let high = self.eval(high, scope)?;
+                let is_between = and(
+                    value::compare(&value, &low)
+                        .map(|ordering| ordering != Ordering::Less),
+                    value::compare(&value, &high)
+                        .map(|ordering| ordering != Ordering::Greater),
+                );
+                Ok(from_truth(negate_if(is_between, *negated)))
+            }
+            ASTNode::SQLInList {
+                expr,
+                list,
+                negated,
+            } => {
+                let value = self.eval(expr, scope)?;
+                // null when none is equal but some can not be compared
+                let mut is_in = Some(false);
+                for item in list.iter() {
+                    match value::compare(&value, &self.eval(item, scope)?) {
+                        Some(Ordering::Equal) => {
+                            is_in = Some(true);
+                            break;
+                        }
+                        Some(_) => (),
+                        None => is_in = None,
+                    }
+                }
+                Ok(from_truth(negate_if(is_in, *negated)))
+            }
+            ASTNode::SQLUnary { operator, expr } => {
+                let value = self.eval(expr, scope)?;
+                match operator {
+                    SQLOperator::Not => {
+                        Ok(from_truth(truth(&value)?.map(|value| !value)))
+                    }
+                    SQLOperator::Plus => Ok(value),
+                    SQLOperator::Minus if value == Value::Nil => Ok(value),
+                    SQLOperator::Minus => {
+                        value::calculate(
+                            &Value::Bigint(0),
+                            Arithmetic::Subtract,
+                            &value,
+                        )
+                        .ok_or_else(|| invalid(expr))
+                    }
+                    _ => Err(ViewError::Unsupported(expr.to_string())),
+                }
+            }
+            ASTNode::SQLBinaryExpr { left, op, right } => {
+                self.binary(expr, left, op, right, scope)
+            }
+            ASTNode::SQLFunction { name, args, .. } => {
+                let name = name.to_string().to_lowercase();
+                self.function(expr, &name, args, scope)
+            }
+            _ => Err(ViewError::Unsupported(expr.to_string())),
+        }
+    }
+
+    fn binary(
+        &self,
+        expr: &ASTNode,
+        left: &ASTNode,
+        op: &SQLOperator,
+        right: &ASTNode,
+        scope: Scope,
+    ) -> Result<Value, ViewError> {
+        let left = self.eval(left, scope)?;
+        let right = self.eval(right, scope)?;
+        let ordering = || value::compare(&left, &right);
+        let arithmetic = match op {
+            SQLOperator::And => {
+                return Ok(from_truth(and(truth(&left)?, truth(&right)?)))
+            }
+            SQLOperator::Or => {
+                return Ok(from_truth(or(truth(&left)?, truth(&right)?)))
+            }
+            SQLOperator::Eq => {
+                return Ok(from_truth(ordering().map(|o| o == Ordering::Equal)))
+            }
+            SQLOperator::NotEq => {
+                return Ok(from_truth(ordering().map(|o| o != Ordering::Equal)))
+            }
+            SQLOperator::Lt => {
+                return Ok(from_truth(ordering().map(|o| o == Ordering::Less)))
+            }
+            SQLOperator::LtEq => {
+                return Ok(from_truth(
+                    ordering().map(|o| o != Ordering::Greater),
+                ))
+            }
+            SQLOperator::Gt => {
+                return Ok(from_truth(
+                    ordering().map(|o| o == Ordering::Greater),
+                ))
+            }
+            SQLOperator::GtEq => {
+                return Ok(from_truth(ordering().map(|o| o != Ordering::Less)))
+            }
+            SQLOperator::Like | SQLOperator::NotLike => {
+                let is_like = match (&left, &right) {
+                    (Value::Nil, _) | (_, Value::Nil) => None,
+                    (Value::Text(text), Value::Text(pattern)) => {
+                        Some(value::is_like(text, pattern))
+                    }
+                    _ => return Err(invalid(expr)),
+                };
+                return Ok(from_truth(negate_if(
+                    is_like,
+                    *op == SQLOperator::NotLike,
+                )));
+            }
+            SQLOperator::Plus => Arithmetic::Add,
+            SQLOperator::Minus => Arithmetic::Subtract,
+            SQLOperator::Multiply => Arithmetic::Multiply,
+            SQLOperator::Divide => Arithmetic::Divide,
+            SQLOperator::Modulus => Arithmetic::Modulo,
+            _ => return Err(ViewError::Unsupported(expr.to_string())),
+        };
+        if left == Value::Nil || right == Value::Nil {
+            return Ok(Value::Nil);
+        }
+        value::calculate(&left, arithmetic, &right).ok_or_else(|| invalid(expr))
+    }
+
+    fn function(
+        &self,
+        expr: &ASTNode,
+        name: &str,
+        args: &[ASTNode],
+        scope: Scope,
+    ) -> Result<Value, ViewError> {
+        if is_aggregate(name) {
+            let rows = match scope {
+                Scope::Group(rows) => rows,
+                Scope::Row(_) => {
+                    return Err(ViewError::InvalidExpression(format!(
+                        "aggregate is not allowed here: {}",
+                        expr.to_string()
+                    )))
+                }
+            };
+            return self.aggregate(expr, name, args, rows);
+        }
+        let mut values = Vec::with_capacity(args.len());
+        for arg in args.iter() {
+            values.push(self.eval(arg, scope)?);
+        }
+        match (name, values.as_slice()) {
+            ("coalesce", values) => {
+                Ok(values
+                    .iter()
+                    .find(|value| **value != Value::Nil)
+                    .cloned()
+                    .unwrap_or(Value::Nil))
+            }
+            ("lower", [Value::Text(v)]) => Ok(Value::Text(v.to_lowercase())),
+            ("upper", [Value::Text(v)]) => Ok(Value::Text(v.to_uppercase())),
+            ("length", [Value::Text(v)]) => {
+                Ok(Value::Bigint(v.chars().count() as i64))
+            }
+            ("lower", [Value::Nil])
+            | ("upper", [Value::Nil])
+            | ("length", [Value::Nil]) => Ok(Value::Nil),
+            ("lower", _) | ("upper", _) | ("length", _) => Err(invalid(expr)),
+            _ => Err(ViewError::Unsupported(format!("function {}", name))),
+        }
+    }
+
+    /// aggregate the values of the rows in the group, nulls are ignored
+    fn aggregate(
+        &self,
+        expr: &ASTNode,
+        name: &str,
+        args: &[ASTNode],
+        rows: &[&DataRow],
+    ) -> Result<Value, ViewError> {
+        let arg = match args {
+            [ASTNode::SQLWildcard] if name == "count" => {
+                return Ok(Value::Bigint(rows.len() as i64))
+            }
+            [arg] => arg,
+            _ => return Err(invalid(expr)),
+        };
+        let mut values = vec![];
+        for row in rows.iter() {
+            let value = self.eval(arg, Scope::Row(row))?;
+            if value != Value::Nil {
+                values.push(value);
+            }
+        }
+        if name == "count" {
+            return Ok(Value::Bigint(values.len() as i64));
+        }
+        if values.is_empty() {
+            return Ok(Value::Nil);
+        }
+        match name {
+            "min" | "max" => {
+                let wanted = if name == "min" {
+                    Ordering::Less
+                } else {
+                    Ordering::Greater
+                };
+                let mut extreme = values[0].clone();
+                for value in values.into_iter().skip(1) {
+                    match value::compare(&value, &extreme) {
+                        Some(ordering) if ordering == wanted => extreme = value,
+                        Some(_) => (),
+                        None => return Err(invalid(expr)),
+                    }
+                }
+                Ok(extreme)
+            }
+            _ => {
+                let is_integer =
+                    values.iter().all(|value| value::as_i64(value).is_some());
+                let mut sum = 0.0;
+                for value in values.iter() {
+                    sum += value::as_f64(value).ok_or_else(|| invalid(expr))?;
+                }
+                if name == "avg" {
+                    Ok(Value::Double(sum / values.len() as f64))
+                } else if is_integer {
+                    Ok(Value::Bigint(
+                        values.iter().filter_map(value::as_i64).sum(),
+                    ))
+                } else {
+                    Ok(Value::Double(sum))
+                }
+            }
+        }
+    }
+}
+
+fn invalid(expr: &ASTNode) -> ViewError {
+    ViewError::InvalidExpression(expr.to_string())
+}
+
+fn and(a: Option<bool>, b: Option<bool>) -> Option<bool> {
+    match (a, b) {
+        (Some(false), _) | (_, Some(false)) => Some(false),
+        (Some(true), Some(true)) => Some(true),
+        _ => None,
+    }
+}
+
+fn or(a: Option<bool>, b: Option<bool>) -> Option<bool> {
+    match (a, b) {
+        (Some(true), _) | (_, Some(true)) => Some(true),
+        (Some(false), Some(false)) => Some(false),
+        _ => None,
+    }
+}
+
+fn negate_if(truth: Option<bool>, negated: bool) -> Option<bool> {
+    truth.map(|truth| truth != negated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn column(name: &str, data_type: Type) -> DataColumn {
+        DataColumn {
+            name: name.into(),
+            description: None,
+            tags: vec![],
+            data_type,
+            is_primary: false,
+        }
+    }
+
+    fn films() -> DataTable {
+        let row = |title: &str, rating: &str, length: i32| {
+            vec![
+                Value::Text(title.to_string()),
+                Value::Text(rating.to_string()),
+                Value::Int(length),
+            ]
+        };
+        DataTable {
+            columns: vec![
+                column("title", Type::Text),
+                column("rating", Type::Text),
+                column("length", Type::Int),
+            ],
+            rows: vec![
+                row("ACADEMY DINOSAUR", "PG", 86),
+                row("ACE GOLDFINGER", "G", 48),
+                row("ADAPTATION HOLES", "NC-17", 50),
+                row("AFFAIR PREJUDICE", "G", 117),
+                row("AGENT TRUMAN", "PG", 169),
+            ],
+        }
+    }
+
+    #[test]
+    fn filter_sort_and_limit() {
+        let view = films()
+            .query(
+                "SELECT title, length FROM film WHERE length > 60 \
+                 AND title LIKE 'A%' ORDER BY length DESC LIMIT 2",
+            )
+            .unwrap();
+        assert_eq!(view.columns.len(), 2);
+        assert_eq!(view.columns[1].name, "length");
+        assert_eq!(view.rows.len(), 2);
+        assert_eq!(view.rows[0][0], Value::Text("AGENT TRUMAN".to_string()));
+        assert_eq!(view.rows[1][1], Value::Int(117));
+    }
+
+    #[test]
+    fn numbers_are_compared_as_numbers() {
+        let view = films()
+            .query("SELECT * FROM film WHERE length BETWEEN 48 AND 86.5")
+            .unwrap();
+        assert_eq!(view.rows.len(), 3);
+    }
+
+    #[test]
+    fn group_by_with_aggregates() {
+        let view = films()
+            .query(
+                "SELECT rating, count(*) AS films, sum(length), max(title) \
+                 FROM film GROUP BY rating HAVING count(*) > 1 ORDER BY 1",
+            )
+            .unwrap();
+        assert_eq!(view.rows.len(), 2);
+        assert_eq!(view.columns[1].name, "films");
+        assert_eq!(view.columns[1].data_type, Type::Bigint);
+        assert_eq!(view.rows[0][0], Value::Text("G".to_string()));
+        assert_eq!(view.rows[0][1], Value::Bigint(2));
+        assert_eq!(view.rows[0][2], Value::Bigint(165));
+        assert_eq!(
+            view.rows[0][3],
+            Value::Text("AFFAIR PREJUDICE".to_string())
+        );
+        assert_eq!(view.rows[1][2], Value::Bigint(255));
+    }
+
+    #[test]
+    fn ungrouped_column_is_an_error() {
+        let err = films()
+            .query("SELECT title, count(*) FROM film GROUP BY rating")
+            .unwrap_err();
+        assert_eq!(err, ViewError::NotGrouped("title".to_string()));
+    }
+
+    #[test]
+    fn unknown_column_is_an_error() {
+        let err = films()
+            .query("SELECT * FROM film WHERE year = 2006")
+            .unwrap_err();
+        assert_eq!(err, ViewError::UnknownColumn("year".to_string()));
+    }
+}
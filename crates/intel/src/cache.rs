@@ -1,5 +1,9 @@
 use crate::{
+    config,
     error::IntelError,
+    identifier_display::ColumnStatistics,
+    relation_inference,
+    view_intel,
     window::{
         self,
         Window,
@@ -130,6 +134,22 @@ impl CachePool {
         }
     }
 
+    /// the statistics of the columns, retrieved together with the tables
+    pub fn get_cached_statistics(
+        &mut self,
+        em: &mut EntityManager,
+        db_url: &str,
+    ) -> Result<ColumnStatistics, IntelError> {
+        self.ensure_cache(db_url);
+        if !self.has_table_cache(db_url) {
+            self.perform_table_caching(em, db_url)?;
+        }
+        match self.0.get(db_url) {
+            Some(cache) => Ok(cache.statistics.clone().unwrap_or_default()),
+            None => Err(IntelError::CacheServiceError),
+        }
+    }
+
     fn perform_table_caching(
         &mut self,
         em: &mut EntityManager,
@@ -162,6 +182,8 @@ pub struct Cache {
     pub windows: Option<Vec<Window>>,
     /// tables extraction is an expensive operation and doesn't change very often
     pub tables: Option<Vec<Table>>,
+    /// the statistics of the columns used in deriving the identifier display
+    pub statistics: Option<ColumnStatistics>,
 }
 
 impl Cache {
@@ -169,6 +191,7 @@ impl Cache {
         Cache {
             windows: None,
             tables: None,
+            statistics: None,
         }
     }
 
@@ -185,8 +208,18 @@ impl Cache {
         em: &mut EntityManager,
     ) -> Result<(), IntelError> {
         println!("----> ACTUAL TABLE CACHING");
-        let tables = em.get_all_tables()?;
+        let mut tables = em.get_all_tables()?;
+        let config = config::get_config();
+        // the views are given their keys first, so they can be referred to
+        // by the accepted relationships
+        view_intel::add_view_keys(em, &mut tables, &config);
+        relation_inference::add_virtual_foreign_keys(
+            em,
+            &mut tables,
+            &config.relations,
+        );
         self.tables = Some(tables);
+        self.statistics = Some(ColumnStatistics::retrieve(em));
         Ok(())
     }
 
@@ -197,7 +230,12 @@ impl Cache {
         println!("----> ACTUAL WINDOW CACHING");
         match self.tables {
             Some(ref tables) => {
-                self.windows = Some(window::derive_all_windows(&tables));
+                let statistics = self.statistics.clone().unwrap_or_default();
+                self.windows = Some(window::derive_all_windows(
+                    &tables,
+                    &config::get_config(),
+                    &statistics,
+                ));
                 Ok(())
             }
             None => {
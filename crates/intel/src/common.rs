@@ -1,12 +1,26 @@
 //! provides data service for window
-use crate::error::IntelError;
+use crate::{
+    data_export,
+    data_image,
+    error::IntelError,
+};
 use bigdecimal::BigDecimal;
+use chrono::{
+    DateTime,
+    NaiveDate,
+    NaiveDateTime,
+    NaiveTime,
+    Utc,
+};
 use rustorm::{
     common,
     types::SqlType,
+    Column,
     ColumnName,
     Dao,
     Rows,
+    Table,
+    TableName,
     Value,
 };
 use std::{
@@ -65,73 +79,368 @@ pub fn cast_record(
     new_rec
 }
 
-/// extract record id from comma separated value
+/// the column of the table that stores binary content such as files
+pub(crate) fn find_binary_column<'t>(
+    table: &'t Table,
+    column_name: &str,
+) -> Result<&'t Column, IntelError> {
+    let column = table
+        .columns
+        .iter()
+        .find(|column| column.name.name == column_name)
+        .ok_or_else(|| IntelError::UnknownColumn(column_name.to_string()))?;
+    match column.get_sql_type() {
+        SqlType::Blob => Ok(column),
+        sql_type => {
+            Err(IntelError::BinaryError(format!(
+                "{} is a {:?} column, not a binary column",
+                column_name, sql_type
+            )))
+        }
+    }
+}
+
+/// the WHERE clause matching the primary columns of the table to the values
+/// in the dao, the placeholders are numbered starting from `first_param`
+pub(crate) fn primary_filter(
+    table: &Table,
+    primary_dao: &Dao,
+    first_param: usize,
+) -> Result<(String, Vec<Value>), IntelError> {
+    let pk_columns = table.get_primary_columns();
+    if pk_columns.is_empty() {
+        return Err(IntelError::ParamParseError(format!(
+            "{} has no primary key",
+            table.name.name
+        )));
+    }
+    let mut filter = Vec::with_capacity(pk_columns.len());
+    let mut values = Vec::with_capacity(pk_columns.len());
+    for (i, pk) in pk_columns.iter().enumerate() {
+        let value = primary_dao.get_value(&pk.name.name).ok_or_else(|| {
+            IntelError::ParamParseError(format!(
+                "missing the value of the primary column {}",
+                pk.name.name
+            ))
+        })?;
+        filter.push(format!("\"{}\" = ${}", pk.name.name, first_param + i));
+        values.push(common::cast_type(value, &pk.get_sql_type()));
+    }
+    Ok((filter.join(" AND "), values))
+}
+
+/// the separator of the values of a composite primary key in the record id
+const RECORD_ID_SEPARATOR: char = ',';
+
+/// the escape character of the separator and of itself in the record id
+const RECORD_ID_ESCAPE: char = '\\';
+
+/// the comma separated values of the primary columns of a record,
+/// the commas and backslashes in the values are escaped with a backslash.
+/// This is the reverse of `extract_record_id`
+pub fn format_record_id(pk_values: &[&Value]) -> String {
+    pk_values
+        .iter()
+        .map(|value| {
+            let text = data_export::value_to_text(value);
+            let mut escaped = String::with_capacity(text.len());
+            for c in text.chars() {
+                if c == RECORD_ID_SEPARATOR || c == RECORD_ID_ESCAPE {
+                    escaped.push(RECORD_ID_ESCAPE);
+                }
+                escaped.push(c);
+            }
+            escaped
+        })
+        .collect::<Vec<String>>()
+        .join(&RECORD_ID_SEPARATOR.to_string())
+}
+
+/// the url of the page which opens the record in the detail view
+/// of the window of its table
+pub fn record_url(table_name: &TableName, pk_values: &[&Value]) -> String {
+    format!(
+        "/{}/record/{}",
+        data_image::encode_uri_component(&table_name.complete_name()),
+        data_image::encode_uri_component(&format_record_id(pk_values))
+    )
+}
+
+/// split the record id on the separators that are not escaped
+fn split_record_id(record_id: &str) -> Result<Vec<String>, IntelError> {
+    let mut splinters = vec![];
+    let mut splinter = String::new();
+    let mut chars = record_id.chars();
+    while let Some(c) = chars.next() {
+        if c == RECORD_ID_ESCAPE {
+            match chars.next() {
+                Some(escaped) => splinter.push(escaped),
+                None => {
+                    return Err(IntelError::ParamParseError(format!(
+                        "Invalid record id: {}, Error: ends with an escape",
+                        record_id
+                    )));
+                }
+            }
+        } else if c == RECORD_ID_SEPARATOR {
+            splinters.push(splinter);
+            splinter = String::new();
+        } else {
+            splinter.push(c);
+        }
+    }
+    splinters.push(splinter);
+    Ok(splinters)
+}
+
+/// extract the values of the primary columns from the record id,
+/// each value is parsed into the type of its primary column
 pub fn extract_record_id<'a>(
     record_id: &str,
     pk_types: &[&SqlType],
     pk_columns: &[&'a ColumnName],
 ) -> Result<Vec<(&'a ColumnName, Value)>, IntelError> {
-    let splinters: Vec<&str> = record_id.split(',').collect();
-    let mut record_id = Vec::with_capacity(splinters.len());
-    assert_eq!(splinters.len(), pk_types.len());
-    assert_eq!(pk_columns.len(), pk_types.len());
-    for (i, splinter) in splinters.iter().enumerate() {
-        let pk_type = pk_types[i];
-        let pk_column = pk_columns[i];
-        let value = match *pk_type {
-            SqlType::Int => {
-                let v = splinter.parse();
-                match v {
-                    Ok(v) => Value::Int(v),
-                    Err(e) => {
-                        return Err(IntelError::ParamParseError(format!(
-                            "Invalid for type {:?}: {}, Error: {}",
-                            pk_type, splinter, e
-                        )));
-                    }
-                }
-            }
-            SqlType::Smallint => {
-                let v = splinter.parse();
-                match v {
-                    Ok(v) => Value::Smallint(v),
-                    Err(e) => {
-                        return Err(IntelError::ParamParseError(format!(
-                            "Invalid for type {:?}: {}, Error: {}",
-                            pk_type, splinter, e
-                        )));
-                    }
-                }
+    let splinters = split_record_id(record_id)?;
+    let expected = pk_types.len();
+    if splinters.len() != expected || pk_columns.len() != expected {
+        return Err(IntelError::ParamParseError(format!(
+            "Invalid record id: {}, Error: expecting {} values, got {}",
+            record_id,
+            expected,
+            splinters.len()
+        )));
+    }
+    splinters
+        .iter()
+        .zip(pk_types.iter().zip(pk_columns.iter()))
+        .map(|(splinter, (pk_type, pk_column))| {
+            let value = match pk_type {
+                SqlType::Blob => parse_hex(splinter)?,
+                _ => parse_value(splinter, pk_type)?,
+            };
+            if value == Value::Nil {
+                return Err(IntelError::ParamParseError(format!(
+                    "Invalid record id: {}, Error: {} is empty",
+                    record_id, pk_column.name
+                )));
             }
-            SqlType::Uuid => {
-                let uuid = Uuid::parse_str(splinter);
-                match uuid {
-                    Ok(uuid) => Value::Uuid(uuid),
-                    Err(e) => {
-                        return Err(IntelError::ParamParseError(format!(
-                            "Invalid for type {:?}: {}, Error: {}",
-                            pk_type, splinter, e
-                        )));
-                    }
-                }
+            Ok((*pk_column, value))
+        })
+        .collect()
+}
+
+/// the dao of the primary columns of the record identified by the record id
+pub fn record_id_dao(
+    table: &Table,
+    record_id: &str,
+) -> Result<Dao, IntelError> {
+    let pk_types = table.get_primary_column_types();
+    let pk_columns = table.get_primary_column_names();
+    if pk_columns.is_empty() {
+        return Err(IntelError::NotFound);
+    }
+    let record_id = extract_record_id(record_id, &pk_types, &pk_columns)?;
+    let mut dao = Dao::new();
+    for (pk, value) in record_id.iter() {
+        dao.insert_value(&pk.name, value);
+    }
+    Ok(dao)
+}
+
+/// the blob in the `\\x0a1b` hex format of postgresql
+fn parse_hex(text: &str) -> Result<Value, IntelError> {
+    let invalid = || {
+        IntelError::ParamParseError(format!(
+            "Invalid for type {:?}: {}",
+            SqlType::Blob,
+            text
+        ))
+    };
+    let hex = text.trim().trim_start_matches("\\x");
+    if hex.len() % 2 != 0 || !hex.is_ascii() {
+        return Err(invalid());
+    }
+    let bytes = (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| invalid()))
+        .collect::<Result<Vec<u8>, IntelError>>()?;
+    Ok(Value::Blob(bytes))
+}
+
+/// parse the text input of the user into a value of the sql type,
+/// an empty text is a NULL value except for text types
+pub fn parse_value(
+    text: &str,
+    sql_type: &SqlType,
+) -> Result<Value, IntelError> {
+    let invalid = |e: &dyn std::fmt::Display| {
+        IntelError::ParamParseError(format!(
+            "Invalid for type {:?}: {}, Error: {}",
+            sql_type, text, e
+        ))
+    };
+    let trimmed = text.trim();
+    let is_text = match sql_type {
+        SqlType::Char | SqlType::Varchar | SqlType::Text => true,
+        _ => false,
+    };
+    if trimmed.is_empty() && !is_text {
+        return Ok(Value::Nil);
+    }
+    let value = match sql_type {
+        SqlType::Bool => {
+            match trimmed.to_lowercase().as_str() {
+                "true" | "t" | "yes" | "y" | "1" => Value::Bool(true),
+                "false" | "f" | "no" | "n" | "0" => Value::Bool(false),
+                _ => return Err(invalid(&"not a boolean")),
             }
-            SqlType::Numeric => {
-                let v = BigDecimal::from_str(splinter);
-                match v {
-                    Ok(v) => Value::BigDecimal(v),
-                    Err(e) => {
-                        return Err(IntelError::ParamParseError(format!(
-                            "Invalid for type {:?}: {}, Error: {}",
-                            pk_type, splinter, e
-                        )));
-                    }
+        }
+        SqlType::Tinyint => {
+            Value::Tinyint(trimmed.parse().map_err(|e| invalid(&e))?)
+        }
+        SqlType::Smallint => {
+            Value::Smallint(trimmed.parse().map_err(|e| invalid(&e))?)
+        }
+        SqlType::Int => Value::Int(trimmed.parse().map_err(|e| invalid(&e))?),
+        SqlType::Bigint => {
+            Value::Bigint(trimmed.parse().map_err(|e| invalid(&e))?)
+        }
+        SqlType::Real | SqlType::Float => {
+            Value::Float(trimmed.parse().map_err(|e| invalid(&e))?)
+        }
+        SqlType::Double => {
+            Value::Double(trimmed.parse().map_err(|e| invalid(&e))?)
+        }
+        SqlType::Numeric => {
+            Value::BigDecimal(
+                BigDecimal::from_str(trimmed).map_err(|e| invalid(&e))?,
+            )
+        }
+        SqlType::Uuid => {
+            Value::Uuid(Uuid::parse_str(trimmed).map_err(|e| invalid(&e))?)
+        }
+        SqlType::Date => {
+            Value::Date(
+                NaiveDate::parse_from_str(trimmed, "%Y-%m-%d")
+                    .map_err(|e| invalid(&e))?,
+            )
+        }
+        SqlType::Time | SqlType::TimeTz => {
+            let time = NaiveTime::parse_from_str(trimmed, "%H:%M:%S%.f")
+                .or_else(|_| NaiveTime::parse_from_str(trimmed, "%H:%M"))
+                .map_err(|e| invalid(&e))?;
+            Value::Time(time)
+        }
+        SqlType::Timestamp => Value::DateTime(parse_naive_datetime(trimmed)?),
+        SqlType::TimestampTz => {
+            let timestamp = match DateTime::parse_from_rfc3339(trimmed) {
+                Ok(timestamp) => timestamp.with_timezone(&Utc),
+                Err(_e) => {
+                    DateTime::from_utc(parse_naive_datetime(trimmed)?, Utc)
                 }
-            }
+            };
+            Value::Timestamp(timestamp)
+        }
+        SqlType::Json => {
+            serde_json::from_str::<serde_json::Value>(trimmed)
+                .map_err(|e| invalid(&e))?;
+            Value::Json(trimmed.to_string())
+        }
+        _ => Value::Text(text.to_string()),
+    };
+    Ok(value)
+}
+
+/// accepts both `2019-01-31 13:00:00` and `2019-01-31T13:00:00`,
+/// a date alone is at midnight
+fn parse_naive_datetime(text: &str) -> Result<NaiveDateTime, IntelError> {
+    let formats = [
+        "%Y-%m-%d %H:%M:%S%.f",
+        "%Y-%m-%dT%H:%M:%S%.f",
+        "%Y-%m-%d %H:%M",
+        "%Y-%m-%dT%H:%M",
+    ];
+    for format in formats.iter() {
+        if let Ok(datetime) = NaiveDateTime::parse_from_str(text, format) {
+            return Ok(datetime);
+        }
+    }
+    match NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+        Ok(date) => Ok(date.and_hms(0, 0, 0)),
+        Err(e) => {
+            Err(IntelError::ParamParseError(format!(
+                "Invalid timestamp: {}, Error: {}",
+                text, e
+            )))
+        }
+    }
+}
 
-            SqlType::Varchar => Value::Text(splinter.to_string()),
-            _ => panic!("primary with type {:?} is not yet covered", pk_type),
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn column_name(name: &str) -> ColumnName {
+        ColumnName {
+            name: name.to_string(),
+            table: None,
+            alias: None,
+        }
+    }
+
+    #[test]
+    fn record_id_round_trip() {
+        let values = vec![
+            (SqlType::Bigint, Value::Bigint(9_007_199_254_740_993)),
+            (SqlType::Text, Value::Text("Smith, John \\ Jr.".to_string())),
+            (SqlType::Date, Value::Date(NaiveDate::from_ymd(2019, 7, 1))),
+            (
+                SqlType::Timestamp,
+                Value::DateTime(
+                    NaiveDate::from_ymd(2019, 7, 1)
+                        .and_hms_milli(13, 5, 9, 250),
+                ),
+            ),
+            (SqlType::Blob, Value::Blob(vec![0x00, 0x2c, 0xff])),
+        ];
+        let pk_values: Vec<&Value> =
+            values.iter().map(|(_sql_type, value)| value).collect();
+        let pk_types: Vec<&SqlType> =
+            values.iter().map(|(sql_type, _value)| sql_type).collect();
+        let names: Vec<ColumnName> = (0..values.len())
+            .map(|i| column_name(&format!("pk{}", i)))
+            .collect();
+        let pk_columns: Vec<&ColumnName> = names.iter().collect();
+        let record_id = format_record_id(&pk_values);
+        let extracted =
+            extract_record_id(&record_id, &pk_types, &pk_columns).unwrap();
+        let extracted: Vec<&Value> =
+            extracted.iter().map(|(_pk, value)| value).collect();
+        assert_eq!(extracted, pk_values);
+    }
+
+    #[test]
+    fn invalid_record_ids_are_errors() {
+        let names = [column_name("a"), column_name("b")];
+        let pk_columns: Vec<&ColumnName> = names.iter().collect();
+        let pk_types = [&SqlType::Int, &SqlType::Uuid];
+        let extract = |record_id: &str| {
+            extract_record_id(record_id, &pk_types, &pk_columns)
         };
-        record_id.push((pk_column, value));
+        assert!(extract("1").is_err());
+        assert!(extract("1,2,3").is_err());
+        assert!(extract("x,2").is_err());
+        assert!(extract("1,").is_err());
+        assert!(extract("1,\\").is_err());
+        assert!(extract("1,67e55044-10b1-426f-9247-bb680e5fe0c8").is_ok());
+    }
+
+    #[test]
+    fn record_url_is_percent_encoded() {
+        let url = record_url(
+            &TableName::from("public.film_actor"),
+            &[&Value::Int(1), &Value::Text("a/b,c".to_string())],
+        );
+        assert_eq!(url, "/public.film_actor/record/1%2Ca%2Fb%5C%2Cc");
     }
-    Ok(record_id)
 }
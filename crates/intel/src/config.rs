@@ -0,0 +1,232 @@
+//! the configuration supplied by the user, which wins over
+//! what is derived from the tables by the heuristics
+use lazy_static::lazy_static;
+use rustorm::TableName;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::{
+    collections::BTreeMap,
+    sync::RwLock,
+};
+
+lazy_static! {
+    static ref CONFIG: RwLock<Config> = RwLock::new(Config::default());
+}
+
+#[derive(Debug, Default, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Config {
+    /// the identifier display of the tables, keyed by the table name
+    /// with or without the schema such as `public.film` or `film`
+    #[serde(default)]
+    pub displays: BTreeMap<String, DisplayConfig>,
+    /// the relationships of the tables which has no foreign key constraints
+    #[serde(default)]
+    pub relations: RelationConfig,
+    /// the columns which identify the records of the views, keyed by
+    /// the view name with or without the schema. These wins over the key
+    /// inferred from the definition of the view
+    #[serde(default)]
+    pub view_keys: BTreeMap<String, Vec<String>>,
+}
+
+/// the columns used as the identifier display of the records of a table
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct DisplayConfig {
+    pub columns: Vec<String>,
+    #[serde(default)]
+    pub separator: Option<String>,
+}
+
+/// the inferred relationships are only used when the inference is enabled,
+/// the accepted relationships are always used
+#[derive(Debug, Default, PartialEq, Clone, Serialize, Deserialize)]
+pub struct RelationConfig {
+    /// infer the relationships from the names and types of the columns
+    #[serde(default)]
+    pub infer: bool,
+    /// only use the inferred relationships whose sampled values
+    /// all exist in the referred table
+    #[serde(default)]
+    pub sample: bool,
+    #[serde(default)]
+    pub accept: Vec<RelationRef>,
+    #[serde(default)]
+    pub reject: Vec<RelationRef>,
+}
+
+/// the column of the table which refers to the primary key of the referred
+/// table, the tables are named with or without the schema
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct RelationRef {
+    pub table: String,
+    pub column: String,
+    pub referred_table: String,
+}
+
+impl RelationRef {
+    pub fn matches(
+        &self,
+        table_name: &TableName,
+        column_name: &str,
+        referred_table: &TableName,
+    ) -> bool {
+        is_named(&self.table, table_name)
+            && self.column == column_name
+            && is_named(&self.referred_table, referred_table)
+    }
+}
+
+impl RelationConfig {
+    pub fn is_rejected(
+        &self,
+        table_name: &TableName,
+        column_name: &str,
+        referred_table: &TableName,
+    ) -> bool {
+        self.reject
+            .iter()
+            .any(|r| r.matches(table_name, column_name, referred_table))
+    }
+}
+
+/// the name is the complete name of the table or the name without the schema
+fn is_named(name: &str, table_name: &TableName) -> bool {
+    name == table_name.complete_name() || name == table_name.name
+}
+
+impl Config {
+    /// the display of the table, matched with its schema first
+    pub fn display_for(
+        &self,
+        table_name: &TableName,
+    ) -> Option<&DisplayConfig> {
+        self.displays
+            .get(&table_name.complete_name())
+            .or_else(|| self.displays.get(&table_name.name))
+    }
+
+    /// the key columns of the view, matched with its schema first
+    pub fn view_key_for(&self, view_name: &TableName) -> Option<&Vec<String>> {
+        self.view_keys
+            .get(&view_name.complete_name())
+            .or_else(|| self.view_keys.get(&view_name.name))
+    }
+}
+
+/// set the configuration used when deriving the windows
+pub fn set_config(config: Config) {
+    match CONFIG.write() {
+        Ok(mut current) => *current = config,
+        Err(e) => error!("unable to set the config: {}", e),
+    }
+}
+
+pub fn get_config() -> Config {
+    match CONFIG.read() {
+        Ok(config) => config.clone(),
+        Err(e) => {
+            error!("unable to read the config: {}", e);
+            Config::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_with_schema_wins() {
+        let mut config = Config::default();
+        config.displays.insert(
+            "film".to_string(),
+            DisplayConfig {
+                columns: vec!["title".to_string()],
+                separator: None,
+            },
+        );
+        config.displays.insert(
+            "public.film".to_string(),
+            DisplayConfig {
+                columns: vec!["title".to_string(), "release_year".to_string()],
+                separator: Some(" - ".to_string()),
+            },
+        );
+        let display = config.display_for(&TableName::from("public.film"));
+        assert_eq!(display.map(|d| d.columns.len()), Some(2));
+        let display = config.display_for(&TableName::from("archive.film"));
+        assert_eq!(display.map(|d| d.columns.len()), Some(1));
+        assert!(config.display_for(&TableName::from("actor")).is_none());
+    }
+
+    #[test]
+    fn relations_match_with_or_without_schema() {
+        let relation = RelationRef {
+            table: "public.film".to_string(),
+            column: "language_id".to_string(),
+            referred_table: "language".to_string(),
+        };
+        let film = TableName::from("public.film");
+        let language = TableName::from("public.language");
+        assert!(relation.matches(&film, "language_id", &language));
+        assert!(!relation.matches(&film, "original_language_id", &language));
+        assert!(!relation.matches(
+            &TableName::from("archive.film"),
+            "language_id",
+            &language
+        ));
+        let config = RelationConfig {
+            reject: vec![relation],
+            ..Default::default()
+        };
+        assert!(config.is_rejected(&film, "language_id", &language));
+        assert!(!config.is_rejected(&language, "language_id", &film));
+    }
+
+    #[test]
+    fn unset_relations_are_defaults() {
+        let config: Config = serde_json::from_str(
+            r#"{
+                "relations": {
+                    "infer": true,
+                    "accept": [{
+                        "table": "rental",
+                        "column": "staff",
+                        "referred_table": "staff"
+                    }]
+                }
+            }"#,
+        )
+        .unwrap();
+        assert!(config.relations.infer);
+        assert!(!config.relations.sample);
+        assert_eq!(config.relations.accept.len(), 1);
+        assert!(config.displays.is_empty());
+    }
+
+    #[test]
+    fn view_keys_with_or_without_schema() {
+        let config: Config = serde_json::from_str(
+            r#"{
+                "view_keys": {
+                    "public.film_list": ["fid"],
+                    "sales_by_store": ["store"]
+                }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.view_key_for(&TableName::from("public.film_list")),
+            Some(&vec!["fid".to_string()])
+        );
+        assert!(config
+            .view_key_for(&TableName::from("archive.film_list"))
+            .is_none());
+        assert_eq!(
+            config.view_key_for(&TableName::from("public.sales_by_store")),
+            Some(&vec!["store".to_string()])
+        );
+    }
+}
@@ -1,4 +1,5 @@
 use crate::{
+    identifier_display::ColumnStatistics,
     window::GroupedWindow,
     Window,
 };
@@ -12,6 +13,9 @@ pub struct Context {
     pub tables: HashMap<TableName, Table>,
     pub windows: HashMap<TableName, Window>,
     pub grouped_window: Vec<GroupedWindow>,
+    /// the statistics the windows are derived with, so the display
+    /// of the tables without a window is derived the same way
+    pub statistics: ColumnStatistics,
 }
 
 impl Context {
@@ -0,0 +1,207 @@
+//! aggregates of the columns of a table, such as the total of a column,
+//! computed by the database over the rows that matches the filter
+use crate::{
+    data_container::Filter,
+    error::IntelError,
+    query_builder::Query,
+    Context,
+};
+use rustorm::{
+    types::SqlType,
+    ColumnName,
+    DaoManager,
+    TableName,
+    Value,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum Aggregate {
+    Count,
+    CountDistinct,
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+impl Aggregate {
+    pub fn all() -> [Aggregate; 6] {
+        [
+            Aggregate::Count,
+            Aggregate::CountDistinct,
+            Aggregate::Sum,
+            Aggregate::Avg,
+            Aggregate::Min,
+            Aggregate::Max,
+        ]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Aggregate::Count => "count",
+            Aggregate::CountDistinct => "count distinct",
+            Aggregate::Sum => "sum",
+            Aggregate::Avg => "avg",
+            Aggregate::Min => "min",
+            Aggregate::Max => "max",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::all()
+            .iter()
+            .find(|aggregate| aggregate.name() == name)
+            .cloned()
+    }
+
+    /// sum and avg only apply to numbers
+    pub fn applies_to(&self, sql_type: &SqlType) -> bool {
+        match self {
+            Aggregate::Sum | Aggregate::Avg => is_numeric_type(sql_type),
+            _ => true,
+        }
+    }
+
+    /// the aggregate of the column, which is qualified and quoted
+    /// with `query_builder::qualified_column`
+    pub(crate) fn to_sql(&self, column: &str) -> String {
+        match self {
+            Aggregate::Count => format!("COUNT({})", column),
+            Aggregate::CountDistinct => format!("COUNT(DISTINCT {})", column),
+            Aggregate::Sum => format!("SUM({})", column),
+            Aggregate::Avg => format!("AVG({})", column),
+            Aggregate::Min => format!("MIN({})", column),
+            Aggregate::Max => format!("MAX({})", column),
+        }
+    }
+}
+
+/// the aggregate of a column of the table
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct ColumnAggregate {
+    pub column_name: ColumnName,
+    pub aggregate: Aggregate,
+}
+
+fn is_numeric_type(sql_type: &SqlType) -> bool {
+    match sql_type {
+        SqlType::Tinyint
+        | SqlType::Smallint
+        | SqlType::Int
+        | SqlType::Bigint
+        | SqlType::Real
+        | SqlType::Float
+        | SqlType::Double
+        | SqlType::Numeric => true,
+        _ => false,
+    }
+}
+
+/// compute the aggregates in a single query over the rows of the table
+/// that matches the filter, the values are in the order of the aggregates
+pub fn aggregate_table(
+    context: &Context,
+    dm: &mut DaoManager,
+    table_name: &TableName,
+    filter: &Filter,
+    aggregates: &[ColumnAggregate],
+) -> Result<Vec<Value>, IntelError> {
+    if aggregates.is_empty() {
+        return Ok(vec![]);
+    }
+    let table = context.get_table(table_name).ok_or(IntelError::NotFound)?;
+    let mut query = Query::new(context, dm);
+    query.select();
+    query.enumerate_aggregates(table, aggregates)?;
+    query.from(table_name);
+    query.add_filter(table, filter)?;
+    let rows = query.collect_rows()?;
+    rows.data.into_iter().next().ok_or_else(|| {
+        IntelError::AggregateError("no row is returned".to_string())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_db;
+
+    #[test]
+    fn aggregate_names_are_reversible() {
+        for aggregate in Aggregate::all().iter() {
+            assert_eq!(
+                Aggregate::from_name(aggregate.name()),
+                Some(*aggregate)
+            );
+        }
+        assert_eq!(Aggregate::from_name("median"), None);
+    }
+
+    fn payment_context() -> Context {
+        test_db::context(vec![test_db::table(
+            "payment",
+            &[
+                ("PaymentId", SqlType::Int),
+                ("Amount", SqlType::Numeric),
+                ("PaymentDate", SqlType::Timestamp),
+            ],
+            vec![test_db::primary_key(&["PaymentId"])],
+        )])
+    }
+
+    /// the mixed case columns are quoted so they are not folded to lowercase
+    #[test]
+    fn aggregates_are_quoted() {
+        let context = payment_context();
+        let mut dm = test_db::sakila_dm();
+        let table = context.get_table(&TableName::from("payment")).unwrap();
+        let aggregates = [
+            ColumnAggregate {
+                column_name: ColumnName::from("PaymentId"),
+                aggregate: Aggregate::CountDistinct,
+            },
+            ColumnAggregate {
+                column_name: ColumnName::from("Amount"),
+                aggregate: Aggregate::Sum,
+            },
+        ];
+        let mut query = Query::new(&context, &mut dm);
+        query.select();
+        query.enumerate_aggregates(table, &aggregates).unwrap();
+        let (sql, _, _) = query.into_parts();
+        assert_eq!(
+            sql,
+            concat!(
+                r#"SELECT COUNT(DISTINCT payment."PaymentId") AS aggregate_0"#,
+                r#", SUM(payment."Amount") AS aggregate_1"#
+            )
+        );
+    }
+
+    #[test]
+    fn avg_of_a_date_is_rejected() {
+        let context = payment_context();
+        let mut dm = test_db::sakila_dm();
+        let aggregates = [ColumnAggregate {
+            column_name: ColumnName::from("PaymentDate"),
+            aggregate: Aggregate::Avg,
+        }];
+        let result = aggregate_table(
+            &context,
+            &mut dm,
+            &TableName::from("payment"),
+            &Filter::default(),
+            &aggregates,
+        );
+        match result {
+            Err(IntelError::AggregateError(message)) => {
+                assert_eq!(message, "avg of PaymentDate which is not a number")
+            }
+            result => panic!("expecting an aggregate error: {:?}", result),
+        }
+    }
+}
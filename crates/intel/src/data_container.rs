@@ -178,6 +178,77 @@ pub struct FrozenData {
     pub frozen_columns: Vec<usize>,
 }
 
+/// the conditions the rows of a tab must all satisfy
+#[derive(Debug, Default, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Filter {
+    pub conditions: Vec<Condition>,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Condition {
+    pub column_name: ColumnName,
+    pub operator: Operator,
+    /// not used in IsNull and IsNotNull
+    pub value: Value,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub enum Operator {
+    Equal,
+    NotEqual,
+    LessThan,
+    LessThanOrEqual,
+    GreaterThan,
+    GreaterThanOrEqual,
+    Like,
+    ILike,
+    IsNull,
+    IsNotNull,
+}
+
+impl Operator {
+    pub fn to_sql(&self) -> &'static str {
+        match self {
+            Operator::Equal => "=",
+            Operator::NotEqual => "<>",
+            Operator::LessThan => "<",
+            Operator::LessThanOrEqual => "<=",
+            Operator::GreaterThan => ">",
+            Operator::GreaterThanOrEqual => ">=",
+            Operator::Like => "LIKE",
+            Operator::ILike => "ILIKE",
+            Operator::IsNull => "IS NULL",
+            Operator::IsNotNull => "IS NOT NULL",
+        }
+    }
+
+    /// the operator is not compared to a value
+    pub fn is_unary(&self) -> bool {
+        match self {
+            Operator::IsNull | Operator::IsNotNull => true,
+            _ => false,
+        }
+    }
+}
+
+/// the ordering of the rows of a tab
+#[derive(Debug, Default, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Sort {
+    pub orders: Vec<Order>,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Order {
+    pub column_name: ColumnName,
+    pub direction: Direction,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub enum Direction {
+    Asc,
+    Desc,
+}
+
 /// Holds the result for a sql query
 /// If there are multiple records
 /// it will in Either::Left rows,
@@ -187,16 +258,35 @@ pub struct FrozenData {
 /// such as 1:1 records and related records in has_many and indirect table
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct QueryResult {
+    /// the window of the table in the query, the fields of the main tab
+    /// are arranged to match the columns of the rows
     pub window: Option<Window>,
     pub rows: Rows,
+    /// the rows can be edited and saved back to the table of the window
+    /// since all of its primary columns are in the rows
+    pub is_editable: bool,
 }
 
 impl QueryResult {
     /// When there are multiple results of records
     pub fn with_rows(window: Option<&Window>, rows: Rows) -> Self {
+        let is_editable = match window {
+            Some(window) => {
+                !window.is_view
+                    && window.main_tab.has_all_primary_columns(&rows.columns)
+            }
+            None => false,
+        };
+        let window = window.map(|window| {
+            let mut window = window.clone();
+            window.main_tab.fields =
+                window.main_tab.fields_for_columns(&rows.columns);
+            window
+        });
         QueryResult {
-            window: window.map(Clone::clone),
+            window,
             rows,
+            is_editable,
         }
     }
 }
@@ -230,6 +320,8 @@ pub struct RecordChangeset {
     pub indirect: Vec<(TableName, TableName, RecordAction, Rows)>,
 }
 
+/// the rows to be inserted and the rows to be updated,
+/// the rows for update must contain the primary columns of the table
 #[derive(Debug, Deserialize, Serialize)]
 pub struct SaveContainer {
     pub for_insert: (TableName, Rows),
@@ -262,6 +354,7 @@ pub struct IdentifierDisplay {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_db;
 
     #[test]
     fn test_record_changeset() {
@@ -855,4 +948,31 @@ mod tests {
         println!("result: {:#?}", result);
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn query_result_with_primary_columns_is_editable() {
+        let window = test_db::sakila_window("public.actor");
+        let rows = Rows::new(vec![
+            "last_name".to_string(),
+            "actor_id".to_string(),
+            "full_name".to_string(),
+        ]);
+        let result = QueryResult::with_rows(Some(&window), rows);
+        assert!(result.is_editable);
+        let fields = &result.window.unwrap().main_tab.fields;
+        let names: Vec<&str> =
+            fields.iter().map(|field| field.name.as_str()).collect();
+        assert_eq!(names, vec!["last_name", "actor_id", "full_name"]);
+        assert!(fields[1].is_primary);
+    }
+
+    #[test]
+    fn query_result_without_primary_columns_is_not_editable() {
+        let window = test_db::sakila_window("public.actor");
+        let rows = Rows::new(vec!["first_name".to_string()]);
+        let result = QueryResult::with_rows(Some(&window), rows);
+        assert!(!result.is_editable);
+        let result = QueryResult::with_rows(None, Rows::new(vec![]));
+        assert!(!result.is_editable);
+    }
 }
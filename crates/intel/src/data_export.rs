@@ -0,0 +1,733 @@
+//! export the rows of a tab or an ad-hoc query as csv, json or ndjson,
+//! the rows are fetched and written a batch at a time with a cursor,
+//! so in postgresql the whole result is never held in memory
+use crate::{
+    common,
+    data_container::{
+        Filter,
+        Sort,
+    },
+    data_read,
+    error::IntelError,
+    query_builder::Query,
+    query_parser,
+    sql_guard::{
+        self,
+        SqlOptions,
+        SqlViolation,
+        StatementKind,
+    },
+    tab::Tab,
+    Context,
+};
+use rustorm::{
+    types::SqlType,
+    Array,
+    Dao,
+    DaoManager,
+    Rows,
+    Table,
+    TableName,
+    Value,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use sqlparser::{
+    dialect::GenericSqlDialect,
+    sqlparser::Parser,
+};
+use std::{
+    collections::BTreeMap,
+    io::Write,
+};
+
+/// the number of rows fetched and written at a time
+pub const BATCH_SIZE: usize = 500;
+
+/// the name of the declared cursor in postgresql
+const CURSOR_NAME: &str = "diwata_export";
+
+/// the most rows exported from the databases which has no declared cursor,
+/// since all of the rows are held in memory
+pub const UNCURSORED_ROW_LIMIT: usize = 100_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+    /// newline delimited json, one object per line
+    Ndjson,
+}
+
+impl ExportFormat {
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension {
+            "csv" => Some(ExportFormat::Csv),
+            "json" => Some(ExportFormat::Json),
+            "ndjson" => Some(ExportFormat::Ndjson),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Json => "json",
+            ExportFormat::Ndjson => "ndjson",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Json => "application/json",
+            ExportFormat::Ndjson => "application/x-ndjson",
+        }
+    }
+}
+
+/// where the exported rows come from
+#[derive(Debug, Clone)]
+pub enum ExportSource {
+    /// the rows of the main tab of the window
+    MainTab {
+        table_name: TableName,
+        filter: Filter,
+        sort: Sort,
+    },
+    /// the rows in the has_many tab of the record
+    HasMany {
+        table_name: TableName,
+        record: Dao,
+        has_many_table: TableName,
+    },
+    /// the rows in the indirect tab of the record
+    Indirect {
+        table_name: TableName,
+        record: Dao,
+        linker: TableName,
+        indirect_table: TableName,
+    },
+    /// an ad-hoc sql query, the `$1` and `:name` placeholders are
+    /// bound to the params
+    Sql { sql: String, params: Vec<Value> },
+}
+
+/// the built query of the export
+#[derive(Debug)]
+pub struct ExportQuery {
+    sql: String,
+    params: Vec<Value>,
+    column_datatypes: BTreeMap<String, SqlType>,
+    /// the header of the columns, derived from the field names
+    headers: BTreeMap<String, String>,
+    /// the kind of the statement, checked again before the cursor is opened
+    kind: StatementKind,
+}
+
+/// build the query of the export source,
+/// when resolve_display is set the foreign key columns are replaced
+/// with the identifier display of the record it refers to.
+///
+/// An ad-hoc sql is checked with the sql guard and must be a SELECT query,
+/// the row limit is not imposed since the rows are streamed
+pub fn build_export_query(
+    context: &Context,
+    dm: &mut DaoManager,
+    source: &ExportSource,
+    resolve_display: bool,
+    options: &SqlOptions,
+) -> Result<ExportQuery, IntelError> {
+    match source {
+        ExportSource::MainTab {
+            table_name,
+            filter,
+            sort,
+        } => {
+            let main_table =
+                context.get_table(table_name).ok_or(IntelError::NotFound)?;
+            let window =
+                context.get_window(table_name).ok_or(IntelError::NotFound)?;
+            let mut query = Query::new(context, dm);
+            query.select();
+            enumerate_columns(&mut query, main_table, resolve_display);
+            query.from(&main_table.name);
+            query.add_filter(main_table, filter)?;
+            query.set_sort(main_table, sort)?;
+            Ok(ExportQuery::from_query(query, &window.main_tab))
+        }
+        ExportSource::HasMany {
+            table_name,
+            record,
+            has_many_table,
+        } => {
+            let main_table =
+                context.get_table(table_name).ok_or(IntelError::NotFound)?;
+            let window =
+                context.get_window(table_name).ok_or(IntelError::NotFound)?;
+            let has_many_tab = window
+                .has_many_tabs
+                .iter()
+                .find(|tab| tab.table_name == *has_many_table)
+                .ok_or(IntelError::NotFound)?;
+            let has_many_table = context
+                .get_table(has_many_table)
+                .ok_or(IntelError::NotFound)?;
+            check_record(main_table, record)?;
+            let mut query = Query::new(context, dm);
+            query.select();
+            enumerate_columns(&mut query, has_many_table, resolve_display);
+            query.from(&has_many_table.name);
+            query.left_join(&has_many_table.name, &main_table.name);
+            query.add_dao_filter(&main_table.name, record);
+            Ok(ExportQuery::from_query(query, has_many_tab))
+        }
+        ExportSource::Indirect {
+            table_name,
+            record,
+            linker,
+            indirect_table,
+        } => {
+            let main_table =
+                context.get_table(table_name).ok_or(IntelError::NotFound)?;
+            let window =
+                context.get_window(table_name).ok_or(IntelError::NotFound)?;
+            let indirect_tab = window
+                .indirect_tabs
+                .iter()
+                .find(|indirect_tab| {
+                    indirect_tab.linker == *linker
+                        && indirect_tab.tab.table_name == *indirect_table
+                })
+                .ok_or(IntelError::NotFound)?;
+            let indirect_table = context
+                .get_table(indirect_table)
+                .ok_or(IntelError::NotFound)?;
+            let linker_table =
+                context.get_table(linker).ok_or(IntelError::NotFound)?;
+            check_record(main_table, record)?;
+            let mut query = Query::new(context, dm);
+            query.select();
+            enumerate_columns(&mut query, indirect_table, resolve_display);
+            query.enumerate_linker_columns(
+                linker_table,
+                &main_table.name,
+                &indirect_table.name,
+            );
+            query.from(&indirect_table.name);
+            query.left_join(&indirect_table.name, linker);
+            query.left_join(linker, &main_table.name);
+            query.add_dao_filter(&main_table.name, record);
+            Ok(ExportQuery::from_query(
+                query,
+                &indirect_tab.tab_with_linker_fields(),
+            ))
+        }
+        ExportSource::Sql { sql, params } => {
+            let parameterized = data_read::parameterize_sql(sql, params)?;
+            let nulled_sql = parameterized.nulled_sql();
+            sql_guard::check_single_query(&nulled_sql, options)?;
+            let dialect = GenericSqlDialect {};
+            let window = match Parser::parse_sql(&dialect, nulled_sql) {
+                Ok(ast) => {
+                    ast.get(0)
+                        .and_then(query_parser::extract_table_name)
+                        .and_then(|table_name| {
+                            context.find_window(&TableName::from(&table_name))
+                        })
+                }
+                Err(_) => None,
+            };
+            Ok(ExportQuery {
+                sql: parameterized.sql,
+                params: params.to_vec(),
+                column_datatypes: BTreeMap::new(),
+                headers: window
+                    .map(|window| field_headers(&window.main_tab))
+                    .unwrap_or_default(),
+                kind: StatementKind::Query,
+            })
+        }
+    }
+}
+
+fn enumerate_columns(query: &mut Query, table: &Table, resolve_display: bool) {
+    if resolve_display {
+        query.enumerate_columns_with_display(table);
+    } else {
+        query.enumerate_columns(table);
+    }
+}
+
+/// the record is filtered with its values,
+/// so only the primary columns of the table are accepted
+fn check_record(table: &Table, record: &Dao) -> Result<(), IntelError> {
+    let primary_columns = table.get_primary_column_names();
+    for column in record.0.keys() {
+        if !primary_columns.iter().any(|pk| pk.name == *column) {
+            return Err(IntelError::UnknownColumn(column.to_string()));
+        }
+    }
+    Ok(())
+}
+
+/// the field name of each of the columns in the tab
+fn field_headers(tab: &Tab) -> BTreeMap<String, String> {
+    let mut headers = BTreeMap::new();
+    for field in tab.fields.iter() {
+        for column_name in field.column_names() {
+            headers
+                .insert(column_name.name.to_string(), field.name.to_string());
+        }
+    }
+    headers
+}
+
+impl ExportQuery {
+    fn from_query(query: Query, tab: &Tab) -> Self {
+        let (sql, params, column_datatypes) = query.into_parts();
+        ExportQuery {
+            sql,
+            params,
+            column_datatypes,
+            headers: field_headers(tab),
+            kind: StatementKind::Query,
+        }
+    }
+
+    /// the header of the column, the column name is used
+    /// when there is no matching field
+    fn header<'a>(&'a self, column: &'a str) -> &'a str {
+        match self.headers.get(column) {
+            Some(header) => header,
+            None => column,
+        }
+    }
+}
+
+/// fetch the rows of the query a batch at a time,
+/// postgresql uses a declared cursor inside a guarded transaction.
+/// The other databases have no declared cursor, the rows are read in a single
+/// result set and handed out a batch at a time, since paging with LIMIT and
+/// OFFSET over a query without ORDER BY could skip or repeat rows.
+/// The result set is capped to `UNCURSORED_ROW_LIMIT`, a larger export
+/// is refused before anything is written
+pub struct RowCursor<'a> {
+    dm: &'a mut DaoManager,
+    query: &'a ExportQuery,
+    options: &'a SqlOptions,
+    /// the rows which are not fetched yet, when there is no declared cursor
+    pending: Option<Rows>,
+    is_open: bool,
+    is_done: bool,
+}
+
+impl<'a> RowCursor<'a> {
+    pub fn open(
+        dm: &'a mut DaoManager,
+        query: &'a ExportQuery,
+        options: &'a SqlOptions,
+    ) -> Result<Self, IntelError> {
+        sql_guard::check_options(options)?;
+        if !options.allow_write && !query.kind.is_read_only() {
+            let kind = query.kind.clone();
+            return Err(SqlViolation::WriteNotAllowed(kind).into());
+        }
+        let bparams: Vec<&Value> = query.params.iter().collect();
+        let pending = if options.is_postgres {
+            data_read::begin_guarded_transaction(dm, options)?;
+            let declare = format!(
+                "DECLARE {} NO SCROLL CURSOR FOR {}",
+                CURSOR_NAME, query.sql
+            );
+            if let Err(e) = dm.execute_sql_with_return(&declare, &bparams) {
+                data_read::rollback(dm);
+                return Err(data_read::guarded_error(e, options));
+            }
+            None
+        } else {
+            // fetch 1 more than the limit to know if it is exceeded
+            let limited_sql = format!(
+                "SELECT * FROM ({}\n) AS export LIMIT {}",
+                query.sql,
+                UNCURSORED_ROW_LIMIT + 1
+            );
+            let rows = dm
+                .execute_sql_with_return(&limited_sql, &bparams)
+                .map_err(|e| data_read::guarded_error(e, options))?;
+            if rows.data.len() > UNCURSORED_ROW_LIMIT {
+                return Err(IntelError::ExportError(format!(
+                    "The export has more than {} rows, \
+                     only postgresql can export more",
+                    UNCURSORED_ROW_LIMIT
+                )));
+            }
+            Some(rows)
+        };
+        Ok(RowCursor {
+            dm,
+            query,
+            options,
+            pending,
+            is_open: options.is_postgres,
+            is_done: false,
+        })
+    }
+
+    /// the next batch of rows, None when all of the rows are fetched
+    pub fn fetch(&mut self) -> Result<Option<Rows>, IntelError> {
+        if self.is_done {
+            return Ok(None);
+        }
+        let rows = match self.pending {
+            Some(ref mut pending) => next_batch(pending),
+            None => {
+                let fetch = format!(
+                    "FETCH FORWARD {} FROM {}",
+                    BATCH_SIZE, CURSOR_NAME
+                );
+                let options = self.options;
+                self.dm
+                    .execute_sql_with_return(&fetch, &[])
+                    .map_err(|e| data_read::guarded_error(e, options))?
+            }
+        };
+        if rows.data.len() < BATCH_SIZE {
+            self.is_done = true;
+        }
+        if rows.data.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(common::cast_rows(rows, &self.query.column_datatypes)))
+        }
+    }
+
+    /// close the cursor and end the transaction
+    pub fn close(&mut self) -> Result<(), IntelError> {
+        if self.is_open {
+            self.is_open = false;
+            self.dm.execute_sql_with_return(
+                &format!("CLOSE {}", CURSOR_NAME),
+                &[],
+            )?;
+            self.dm.execute_sql_with_return("COMMIT", &[])?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> Drop for RowCursor<'a> {
+    /// the connection is returned to the pool,
+    /// so the transaction must not be left open
+    fn drop(&mut self) {
+        if self.is_open {
+            self.is_open = false;
+            data_read::rollback(self.dm);
+        }
+    }
+}
+
+/// take the next batch of rows out of the result set
+fn next_batch(rows: &mut Rows) -> Rows {
+    let rest = rows.data.split_off(BATCH_SIZE.min(rows.data.len()));
+    let mut batch = Rows::new(rows.columns.clone());
+    for data in std::mem::replace(&mut rows.data, rest) {
+        batch.push(data);
+    }
+    batch
+}
+
+/// writes the rows in the export format, the output is flushed
+/// after each batch of rows
+pub struct ExportWriter<'q, W: Write> {
+    out: W,
+    format: ExportFormat,
+    query: &'q ExportQuery,
+    row_count: usize,
+}
+
+impl<'q, W: Write> ExportWriter<'q, W> {
+    pub fn new(out: W, format: ExportFormat, query: &'q ExportQuery) -> Self {
+        ExportWriter {
+            out,
+            format,
+            query,
+            row_count: 0,
+        }
+    }
+
+    pub fn write_rows(&mut self, rows: &Rows) -> Result<(), IntelError> {
+        let headers: Vec<&str> = rows
+            .columns
+            .iter()
+            .map(|column| self.query.header(column))
+            .collect();
+        let mut buffer = vec![];
+        match self.format {
+            ExportFormat::Csv => {
+                let mut writer = csv::Writer::from_writer(&mut buffer);
+                if self.row_count == 0 {
+                    writer.write_record(&headers).map_err(export_error)?;
+                }
+                for data in rows.data.iter() {
+                    writer
+                        .write_record(data.iter().map(value_to_text))
+                        .map_err(export_error)?;
+                }
+                writer.flush().map_err(export_error)?;
+            }
+            ExportFormat::Json => {
+                for (i, data) in rows.data.iter().enumerate() {
+                    if self.row_count == 0 && i == 0 {
+                        buffer.extend_from_slice(b"[\n");
+                    } else {
+                        buffer.extend_from_slice(b",\n");
+                    }
+                    buffer.extend_from_slice(
+                        json_object(&headers, data).as_bytes(),
+                    );
+                }
+            }
+            ExportFormat::Ndjson => {
+                for data in rows.data.iter() {
+                    buffer.extend_from_slice(
+                        json_object(&headers, data).as_bytes(),
+                    );
+                    buffer.push(b'\n');
+                }
+            }
+        }
+        self.row_count += rows.data.len();
+        self.out.write_all(&buffer).map_err(export_error)?;
+        self.out.flush().map_err(export_error)
+    }
+
+    /// end the output, returning the writer
+    pub fn finish(mut self) -> Result<W, IntelError> {
+        if self.format == ExportFormat::Json {
+            if self.row_count == 0 {
+                self.out.write_all(b"[]\n").map_err(export_error)?;
+            } else {
+                self.out.write_all(b"\n]\n").map_err(export_error)?;
+            }
+        }
+        self.out.flush().map_err(export_error)?;
+        Ok(self.out)
+    }
+}
+
+/// export all the rows of the query, fetching and writing a batch at a time
+pub fn export_rows<W: Write>(
+    dm: &mut DaoManager,
+    query: &ExportQuery,
+    options: &SqlOptions,
+    format: ExportFormat,
+    out: W,
+) -> Result<W, IntelError> {
+    let mut cursor = RowCursor::open(dm, query, options)?;
+    let mut writer = ExportWriter::new(out, format, query);
+    while let Some(rows) = cursor.fetch()? {
+        writer.write_rows(&rows)?;
+    }
+    cursor.close()?;
+    writer.finish()
+}
+
+fn export_error<E: std::error::Error>(e: E) -> IntelError {
+    IntelError::ExportError(e.to_string())
+}
+
+/// the json object of the row, the keys are in the same order as the columns
+fn json_object(headers: &[&str], data: &[Value]) -> String {
+    let members: Vec<String> = headers
+        .iter()
+        .zip(data.iter())
+        .map(|(header, value)| {
+            format!(
+                "{}:{}",
+                serde_json::Value::from(*header),
+                value_to_json(value)
+            )
+        })
+        .collect();
+    format!("{{{}}}", members.join(","))
+}
+
+fn value_to_json(value: &Value) -> serde_json::Value {
+    match value {
+        Value::Nil => serde_json::Value::Null,
+        Value::Bool(v) => serde_json::Value::from(*v),
+        Value::Tinyint(v) => serde_json::Value::from(*v),
+        Value::Smallint(v) => serde_json::Value::from(*v),
+        Value::Int(v) => serde_json::Value::from(*v),
+        Value::Bigint(v) => serde_json::Value::from(*v),
+        Value::Float(v) => serde_json::Value::from(f64::from(*v)),
+        Value::Double(v) => serde_json::Value::from(*v),
+        Value::Json(v) => {
+            serde_json::from_str(v)
+                .unwrap_or_else(|_| serde_json::Value::from(v.to_string()))
+        }
+        Value::Array(Array::Text(v)) => serde_json::Value::from(v.clone()),
+        Value::Array(Array::Float(v)) => serde_json::Value::from(v.clone()),
+        // the precision of big decimals are retained as text
+        value => serde_json::Value::from(value_to_text(value)),
+    }
+}
+
+/// the text representation of the value in the exported file
+pub fn value_to_text(value: &Value) -> String {
+    match value {
+        Value::Nil => String::new(),
+        Value::Bool(v) => v.to_string(),
+        Value::Tinyint(v) => v.to_string(),
+        Value::Smallint(v) => v.to_string(),
+        Value::Int(v) => v.to_string(),
+        Value::Bigint(v) => v.to_string(),
+        Value::Float(v) => v.to_string(),
+        Value::Double(v) => v.to_string(),
+        Value::BigDecimal(v) => v.to_string(),
+        Value::Blob(v) => {
+            let hex: Vec<String> =
+                v.iter().map(|byte| format!("{:02x}", byte)).collect();
+            format!("\\x{}", hex.join(""))
+        }
+        Value::ImageUri(v) => v.to_string(),
+        Value::Char(v) => v.to_string(),
+        Value::Text(v) => v.to_string(),
+        Value::Json(v) => v.to_string(),
+        Value::Uuid(v) => v.to_string(),
+        Value::Date(v) => v.format("%Y-%m-%d").to_string(),
+        Value::Time(v) => v.to_string(),
+        Value::DateTime(v) => v.format("%Y-%m-%d %H:%M:%S%.f").to_string(),
+        Value::Timestamp(v) => v.to_rfc3339(),
+        Value::Array(Array::Text(v)) => v.join(","),
+        Value::Array(Array::Float(v)) => {
+            v.iter()
+                .map(ToString::to_string)
+                .collect::<Vec<String>>()
+                .join(",")
+        }
+        value => format!("{:?}", value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_db;
+
+    /// the quotes and the comma in the name are escaped in the csv
+    const ACTOR_SQL: &str = "SELECT actor_id, \
+        concat('Actor \"', actor_id, '\", jr') AS first_name \
+        FROM actor WHERE actor_id <= 2 ORDER BY actor_id";
+
+    /// the 1000 films span more than one batch
+    const FILM_SQL: &str = "SELECT film_id, title FROM film ORDER BY film_id";
+
+    fn export(sql: &str, format: ExportFormat, options: &SqlOptions) -> String {
+        let context = test_db::sakila_context();
+        let mut dm = test_db::sakila_dm();
+        let source = ExportSource::Sql {
+            sql: sql.to_string(),
+            params: vec![],
+        };
+        let query =
+            build_export_query(&context, &mut dm, &source, false, options)
+                .unwrap();
+        let out =
+            export_rows(&mut dm, &query, options, format, vec![]).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    fn postgres_options() -> SqlOptions {
+        SqlOptions {
+            is_postgres: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn csv_header_is_written_once() {
+        let csv = export(FILM_SQL, ExportFormat::Csv, &postgres_options());
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 1001);
+        assert_eq!(lines[0], "film_id,title");
+        assert!(lines[1..].iter().all(|line| *line != "film_id,title"));
+    }
+
+    #[test]
+    fn csv_values_are_escaped() {
+        let csv = export(ACTOR_SQL, ExportFormat::Csv, &postgres_options());
+        assert_eq!(
+            csv,
+            "actor_id,first_name\n\
+             1,\"Actor \"\"1\"\", jr\"\n\
+             2,\"Actor \"\"2\"\", jr\"\n"
+        );
+    }
+
+    #[test]
+    fn json_rows_across_batches() {
+        let json = export(FILM_SQL, ExportFormat::Json, &postgres_options());
+        let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.as_array().map(Vec::len), Some(1000));
+        assert_eq!(parsed[BATCH_SIZE]["film_id"], BATCH_SIZE + 1);
+        let empty = export(
+            "SELECT actor_id FROM actor WHERE actor_id < 0",
+            ExportFormat::Json,
+            &postgres_options(),
+        );
+        assert_eq!(empty, "[]\n");
+    }
+
+    #[test]
+    fn ndjson_keeps_column_order() {
+        let ndjson =
+            export(ACTOR_SQL, ExportFormat::Ndjson, &postgres_options());
+        let lines: Vec<&str> = ndjson.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines[0],
+            r#"{"actor_id":1,"first_name":"Actor \"1\", jr"}"#
+        );
+    }
+
+    #[test]
+    fn single_result_set_is_fetched_in_batches() {
+        let options = SqlOptions::default();
+        let csv = export(FILM_SQL, ExportFormat::Csv, &options);
+        let ids: Vec<&str> = csv
+            .lines()
+            .skip(1)
+            .map(|line| line.split(',').next().unwrap())
+            .collect();
+        let expected: Vec<String> =
+            (1..=1000).map(|id| id.to_string()).collect();
+        assert_eq!(ids, expected);
+    }
+
+    #[test]
+    fn single_result_set_is_capped() {
+        let context = test_db::sakila_context();
+        let mut dm = test_db::sakila_dm();
+        let options = SqlOptions::default();
+        let source = ExportSource::Sql {
+            sql: format!(
+                "SELECT generate_series(1, {}) AS n",
+                UNCURSORED_ROW_LIMIT + 1
+            ),
+            params: vec![],
+        };
+        let query =
+            build_export_query(&context, &mut dm, &source, false, &options)
+                .unwrap();
+        let exported =
+            export_rows(&mut dm, &query, &options, ExportFormat::Csv, vec![]);
+        match exported {
+            Err(IntelError::ExportError(_)) => (),
+            exported => panic!("expecting an export error: {:?}", exported),
+        }
+    }
+}
@@ -0,0 +1,262 @@
+//! group the rows of a table by one or more of its columns, with the count
+//! and the aggregates of each group and a pivot of the groups of 2 columns
+use crate::{
+    data_aggregate::ColumnAggregate,
+    data_container::Filter,
+    error::IntelError,
+    query_builder::Query,
+    Context,
+};
+use rustorm::{
+    ColumnName,
+    DaoManager,
+    TableName,
+    Value,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+/// only the first groups are retrieved
+pub const MAX_GROUPS: usize = 1000;
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct GroupRequest {
+    pub group_by: Vec<ColumnName>,
+    pub aggregates: Vec<ColumnAggregate>,
+    #[serde(default)]
+    pub filter: Filter,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Group {
+    /// the values of the group by columns, the foreign keys are
+    /// the identifier display of the record it refers to
+    pub keys: Vec<Value>,
+    pub count: i64,
+    /// in the order of the aggregates of the request
+    pub aggregates: Vec<Value>,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct GroupResult {
+    pub group_by: Vec<ColumnName>,
+    pub aggregates: Vec<ColumnAggregate>,
+    /// ordered by the keys
+    pub groups: Vec<Group>,
+}
+
+/// the groups of 2 columns laid out as rows and columns
+#[derive(Debug, PartialEq, Clone)]
+pub struct Pivot {
+    pub row_keys: Vec<Value>,
+    pub column_keys: Vec<Value>,
+    /// the cells of each row key in the order of the column keys,
+    /// None when there is no group for the pair of keys
+    pub cells: Vec<Vec<Option<Value>>>,
+}
+
+impl GroupResult {
+    /// pivot the groups when the rows are grouped by 2 columns,
+    /// the first column is laid out as rows and the second as columns.
+    /// The cells are the aggregate at the index or the count when None
+    pub fn pivot(&self, aggregate: Option<usize>) -> Option<Pivot> {
+        if self.group_by.len() != 2 {
+            return None;
+        }
+        if let Some(index) = aggregate {
+            if index >= self.aggregates.len() {
+                return None;
+            }
+        }
+        let mut row_keys: Vec<Value> = vec![];
+        let mut column_keys: Vec<Value> = vec![];
+        for group in self.groups.iter() {
+            if !row_keys.contains(&group.keys[0]) {
+                row_keys.push(group.keys[0].clone());
+            }
+            if !column_keys.contains(&group.keys[1]) {
+                column_keys.push(group.keys[1].clone());
+            }
+        }
+        let mut cells = vec![vec![None; column_keys.len()]; row_keys.len()];
+        for group in self.groups.iter() {
+            let row = row_keys.iter().position(|key| *key == group.keys[0]);
+            let column =
+                column_keys.iter().position(|key| *key == group.keys[1]);
+            if let (Some(row), Some(column)) = (row, column) {
+                cells[row][column] = match aggregate {
+                    Some(index) => group.aggregates.get(index).cloned(),
+                    None => Some(Value::Bigint(group.count)),
+                };
+            }
+        }
+        Some(Pivot {
+            row_keys,
+            column_keys,
+            cells,
+        })
+    }
+}
+
+/// group the rows of the table that matches the filter
+pub fn group_table(
+    context: &Context,
+    dm: &mut DaoManager,
+    table_name: &TableName,
+    request: &GroupRequest,
+) -> Result<GroupResult, IntelError> {
+    if request.group_by.is_empty() {
+        return Err(IntelError::AggregateError(
+            "no column to group the rows by".to_string(),
+        ));
+    }
+    let table = context.get_table(table_name).ok_or(IntelError::NotFound)?;
+    let mut query = Query::new(context, dm);
+    query.select();
+    query.enumerate_group_columns(table, &request.group_by)?;
+    query.append(", COUNT(*) AS group_count");
+    if !request.aggregates.is_empty() {
+        query.append(", ");
+        query.enumerate_aggregates(table, &request.aggregates)?;
+    }
+    query.from(table_name);
+    query.add_filter(table, &request.filter)?;
+    query.set_group_by(table, &request.group_by)?;
+    query.set_limit(MAX_GROUPS);
+    let rows = query.collect_rows()?;
+
+    let key_count = request.group_by.len();
+    let groups = rows
+        .data
+        .into_iter()
+        .map(|mut row| {
+            let aggregates = row.split_off(key_count + 1);
+            let count = row.pop().map(count_value).unwrap_or(0);
+            Group {
+                keys: row,
+                count,
+                aggregates,
+            }
+        })
+        .collect();
+    Ok(GroupResult {
+        group_by: request.group_by.clone(),
+        aggregates: request.aggregates.clone(),
+        groups,
+    })
+}
+
+fn count_value(value: Value) -> i64 {
+    match value {
+        Value::Bigint(v) => v,
+        Value::Int(v) => i64::from(v),
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        data_aggregate::Aggregate,
+        test_db,
+    };
+    use rustorm::types::SqlType;
+
+    fn column(name: &str) -> ColumnName {
+        ColumnName {
+            name: name.to_string(),
+            table: None,
+            alias: None,
+        }
+    }
+
+    fn group(keys: &[&str], count: i64, total: i64) -> Group {
+        Group {
+            keys: keys
+                .iter()
+                .map(|key| Value::Text(key.to_string()))
+                .collect(),
+            count,
+            aggregates: vec![Value::Bigint(total)],
+        }
+    }
+
+    #[test]
+    fn pivot_of_2_columns() {
+        let result = GroupResult {
+            group_by: vec![column("rating"), column("language_id")],
+            aggregates: vec![ColumnAggregate {
+                column_name: column("length"),
+                aggregate: Aggregate::Sum,
+            }],
+            groups: vec![
+                group(&["G", "English"], 2, 180),
+                group(&["G", "Italian"], 1, 90),
+                group(&["PG", "Italian"], 3, 300),
+            ],
+        };
+        let pivot = result.pivot(None).unwrap();
+        assert_eq!(
+            pivot.row_keys,
+            vec![Value::Text("G".into()), Value::Text("PG".into())]
+        );
+        assert_eq!(pivot.column_keys.len(), 2);
+        assert_eq!(pivot.cells[0][0], Some(Value::Bigint(2)));
+        assert_eq!(pivot.cells[1][0], None);
+
+        let pivot = result.pivot(Some(0)).unwrap();
+        assert_eq!(pivot.cells[1][1], Some(Value::Bigint(300)));
+        assert_eq!(result.pivot(Some(1)), None);
+    }
+
+    #[test]
+    fn no_pivot_of_a_single_column() {
+        let result = GroupResult {
+            group_by: vec![column("rating")],
+            aggregates: vec![],
+            groups: vec![group(&["G"], 2, 0)],
+        };
+        assert_eq!(result.pivot(None), None);
+    }
+
+    /// the foreign key is replaced with the display of the store
+    /// and both sides of the join to the store are quoted
+    #[test]
+    fn group_by_mixed_case_foreign_key() {
+        let context = test_db::context(vec![
+            test_db::table(
+                "store",
+                &[("StoreId", SqlType::Int), ("StoreName", SqlType::Text)],
+                vec![test_db::primary_key(&["StoreId"])],
+            ),
+            test_db::table(
+                "staff",
+                &[("StaffId", SqlType::Int), ("StoreId", SqlType::Int)],
+                vec![
+                    test_db::primary_key(&["StaffId"]),
+                    test_db::foreign_key(&["StoreId"], "store", &["StoreId"]),
+                ],
+            ),
+        ]);
+        let mut dm = test_db::sakila_dm();
+        let table = context.get_table(&TableName::from("staff")).unwrap();
+        let group_by = [column("StoreId")];
+        let mut query = Query::new(&context, &mut dm);
+        query.select();
+        query.enumerate_group_columns(table, &group_by).unwrap();
+        query.set_group_by(table, &group_by).unwrap();
+        let (sql, _, _) = query.into_parts();
+        assert!(sql.contains("FROM store AS display"), "{}", sql);
+        assert!(
+            sql.contains(
+                r#"WHERE display."StoreId" = staff."StoreId") AS group_0"#
+            ),
+            "{}",
+            sql
+        );
+        assert!(sql.contains(r#"GROUP BY staff."StoreId""#), "{}", sql);
+    }
+}
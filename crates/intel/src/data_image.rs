@@ -0,0 +1,407 @@
+//! detect the images in the values of the columns by sniffing their content,
+//! blobs are recognized by the magic bytes of the image formats and texts
+//! by being a data uri or an url to an image file.
+//! The images in the rows are replaced with the uri where the server
+//! serves the image of the record, so the bytes are not sent with the rows
+use crate::{
+    common,
+    error::IntelError,
+    Context,
+};
+use rustorm::{
+    types::SqlType,
+    Column,
+    ColumnName,
+    Dao,
+    DaoManager,
+    Rows,
+    Table,
+    TableName,
+    Value,
+};
+
+/// the path where the images of the records are served
+pub const IMAGE_PATH: &str = "/image";
+
+/// the extensions of the image files linked in the text columns
+const IMAGE_EXTENSIONS: [&str; 8] =
+    ["png", "jpg", "jpeg", "gif", "webp", "bmp", "svg", "ico"];
+
+/// the image of a record
+#[derive(Debug, PartialEq)]
+pub enum Image {
+    /// the image is stored in the column
+    Bytes { mime: &'static str, bytes: Vec<u8> },
+    /// the column has an url or a data uri of the image
+    Uri(String),
+}
+
+/// the mime type of the image from the magic bytes of its format
+pub fn sniff_mime(bytes: &[u8]) -> Option<&'static str> {
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else if bytes.starts_with(b"\xff\xd8\xff") {
+        Some("image/jpeg")
+    } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+        Some("image/gif")
+    } else if bytes.len() >= 12
+        && bytes.starts_with(b"RIFF")
+        && &bytes[8..12] == b"WEBP"
+    {
+        Some("image/webp")
+    } else if bytes.starts_with(b"BM") && bytes.len() > 14 {
+        Some("image/bmp")
+    } else if bytes.starts_with(b"\x00\x00\x01\x00") {
+        Some("image/x-icon")
+    } else if is_svg(bytes) {
+        Some("image/svg+xml")
+    } else {
+        None
+    }
+}
+
+/// the content type of the binary content, the common file formats
+/// besides the images are also recognized by their magic bytes
+pub fn sniff_content_type(bytes: &[u8]) -> &'static str {
+    if let Some(mime) = sniff_mime(bytes) {
+        mime
+    } else if bytes.starts_with(b"%PDF-") {
+        "application/pdf"
+    } else if bytes.starts_with(b"PK\x03\x04") {
+        "application/zip"
+    } else if bytes.starts_with(b"\x1f\x8b") {
+        "application/gzip"
+    } else if !bytes.is_empty() && std::str::from_utf8(bytes).is_ok() {
+        "text/plain"
+    } else {
+        "application/octet-stream"
+    }
+}
+
+/// the extension of the file with the content type
+pub fn file_extension(content_type: &str) -> &'static str {
+    match content_type {
+        "image/png" => "png",
+        "image/jpeg" => "jpg",
+        "image/gif" => "gif",
+        "image/webp" => "webp",
+        "image/bmp" => "bmp",
+        "image/x-icon" => "ico",
+        "image/svg+xml" => "svg",
+        "application/pdf" => "pdf",
+        "application/zip" => "zip",
+        "application/gzip" => "gz",
+        "text/plain" => "txt",
+        _ => "bin",
+    }
+}
+
+/// an svg is a text that starts with an xml declaration or the svg tag
+fn is_svg(bytes: &[u8]) -> bool {
+    let head = &bytes[..bytes.len().min(256)];
+    match std::str::from_utf8(head) {
+        Ok(head) => {
+            let head = head.trim_start();
+            head.starts_with("<svg")
+                || (head.starts_with("<?xml") && head.contains("<svg"))
+        }
+        Err(_) => false,
+    }
+}
+
+/// a data uri of an image or an url which path is an image file.
+/// The urls are redirected to, so only the http urls and the paths of
+/// this site are accepted, a path starting with `//` or `/\` is the url
+/// of another site
+pub fn is_image_uri(text: &str) -> bool {
+    let text = text.trim();
+    if text.starts_with("data:image/") {
+        return true;
+    }
+    let is_url = text.starts_with("http://")
+        || text.starts_with("https://")
+        || (text.starts_with('/')
+            && !text.starts_with("//")
+            && !text.starts_with("/\\"));
+    if !is_url || text.contains(char::is_whitespace) {
+        return false;
+    }
+    let path = text.split(|c| c == '?' || c == '#').next().unwrap_or("");
+    match path.rsplit('.').next() {
+        Some(extension) => {
+            IMAGE_EXTENSIONS.contains(&extension.to_lowercase().as_str())
+        }
+        None => false,
+    }
+}
+
+/// the uri where the server serves the image in the column of the record
+pub fn image_uri(
+    table_name: &TableName,
+    column_name: &ColumnName,
+    record_id: &str,
+) -> String {
+    format!(
+        "{}/{}/{}/{}",
+        IMAGE_PATH,
+        encode_uri_component(&table_name.complete_name()),
+        encode_uri_component(&column_name.name),
+        encode_uri_component(record_id)
+    )
+}
+
+/// percent encode everything except the unreserved characters
+pub(crate) fn encode_uri_component(text: &str) -> String {
+    text.bytes()
+        .map(|byte| {
+            match byte {
+                b'A'..=b'Z'
+                | b'a'..=b'z'
+                | b'0'..=b'9'
+                | b'-'
+                | b'_'
+                | b'.'
+                | b'~' => (byte as char).to_string(),
+                _ => format!("%{:02X}", byte),
+            }
+        })
+        .collect()
+}
+
+/// the image uri of the value, if it is an image
+fn image_value(
+    table: &Table,
+    column_name: &str,
+    value: &Value,
+    record_id: Option<&str>,
+) -> Option<Value> {
+    match value {
+        Value::Blob(bytes) => {
+            sniff_mime(bytes)?;
+            let column = table
+                .columns
+                .iter()
+                .find(|column| column.name.name == column_name)?;
+            Some(Value::ImageUri(image_uri(
+                &table.name,
+                &column.name,
+                record_id?,
+            )))
+        }
+        Value::Text(text) if is_image_uri(text) => {
+            Some(Value::ImageUri(text.to_string()))
+        }
+        _ => None,
+    }
+}
+
+/// the record id of the row, None when the table has no primary key
+/// or the primary columns are not in the row
+fn row_record_id(
+    table: &Table,
+    columns: &[String],
+    row: &[Value],
+) -> Option<String> {
+    let pk_columns = table.get_primary_column_names();
+    if pk_columns.is_empty() {
+        return None;
+    }
+    let pk_values = pk_columns
+        .iter()
+        .map(|pk| {
+            columns
+                .iter()
+                .position(|column| *column == pk.name)
+                .and_then(|index| row.get(index))
+        })
+        .collect::<Option<Vec<&Value>>>()?;
+    Some(common::format_record_id(&pk_values))
+}
+
+/// replace the images in the rows of the table with their uri
+pub fn show_images(table: &Table, rows: &mut Rows) {
+    let columns = &rows.columns;
+    for row in rows.data.iter_mut() {
+        let record_id = row_record_id(table, columns, row);
+        for (index, value) in row.iter_mut().enumerate() {
+            let image = image_value(
+                table,
+                &columns[index],
+                value,
+                record_id.as_ref().map(String::as_str),
+            );
+            if let Some(image) = image {
+                *value = image;
+            }
+        }
+    }
+}
+
+/// replace the images in the record with their uri
+pub fn show_record_images(table: &Table, record: &mut Dao) {
+    let columns: Vec<String> = record.0.keys().cloned().collect();
+    let row: Vec<Value> = record.0.values().cloned().collect();
+    let record_id = row_record_id(table, &columns, &row);
+    for (column, value) in record.0.iter_mut() {
+        let image = image_value(
+            table,
+            column,
+            value,
+            record_id.as_ref().map(String::as_str),
+        );
+        if let Some(image) = image {
+            *value = image;
+        }
+    }
+}
+
+/// the value to be written to the column, the uri of an image stored in
+/// the column stands for the unchanged image and is not written back
+pub(crate) fn stored_value(column: &Column, value: &Value) -> Option<Value> {
+    match value {
+        Value::ImageUri(uri) => {
+            match column.get_sql_type() {
+                SqlType::Char | SqlType::Varchar | SqlType::Text => {
+                    Some(Value::Text(uri.to_string()))
+                }
+                _ => None,
+            }
+        }
+        value => Some(value.clone()),
+    }
+}
+
+/// retrieve the image in the column of the record
+pub fn fetch_image(
+    context: &Context,
+    dm: &mut DaoManager,
+    table_name: &TableName,
+    column_name: &str,
+    record_id: &str,
+) -> Result<Image, IntelError> {
+    let table = context.get_table(table_name).ok_or(IntelError::NotFound)?;
+    let column = table
+        .columns
+        .iter()
+        .find(|column| column.name.name == column_name)
+        .ok_or_else(|| IntelError::UnknownColumn(column_name.to_string()))?;
+    let pk_types = table.get_primary_column_types();
+    let pk_columns = table.get_primary_column_names();
+    if pk_columns.is_empty() {
+        return Err(IntelError::NotFound);
+    }
+    let record_id =
+        common::extract_record_id(record_id, &pk_types, &pk_columns)?;
+    let filter: Vec<String> = record_id
+        .iter()
+        .enumerate()
+        .map(|(i, (pk, _value))| format!("\"{}\" = ${}", pk.name, i + 1))
+        .collect();
+    let sql = format!(
+        "SELECT \"{}\" FROM {} WHERE {}",
+        column.name.name,
+        table.name.safe_complete_name(),
+        filter.join(" AND ")
+    );
+    let params: Vec<&Value> =
+        record_id.iter().map(|(_pk, value)| value).collect();
+    let rows = dm.execute_sql_with_return(&sql, &params)?;
+    let value = rows
+        .data
+        .into_iter()
+        .next()
+        .and_then(|row| row.into_iter().next())
+        .ok_or(IntelError::NotFound)?;
+    let not_an_image =
+        || IntelError::ImageError(format!("{} is not an image", column_name));
+    match value {
+        Value::Blob(bytes) => {
+            let mime = sniff_mime(&bytes).ok_or_else(not_an_image)?;
+            Ok(Image::Bytes { mime, bytes })
+        }
+        Value::Text(text) | Value::ImageUri(text) => {
+            if is_image_uri(&text) {
+                Ok(Image::Uri(text))
+            } else {
+                Err(not_an_image())
+            }
+        }
+        _ => Err(not_an_image()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sniff_image_formats() {
+        assert_eq!(
+            sniff_mime(b"\x89PNG\r\n\x1a\n\x00\x00\x00\rIHDR"),
+            Some("image/png")
+        );
+        assert_eq!(
+            sniff_mime(b"\xff\xd8\xff\xe0\x00\x10JFIF"),
+            Some("image/jpeg")
+        );
+        assert_eq!(sniff_mime(b"GIF89a\x01\x00"), Some("image/gif"));
+        assert_eq!(
+            sniff_mime(b"RIFF\x00\x00\x00\x00WEBPVP8 "),
+            Some("image/webp")
+        );
+        assert_eq!(
+            sniff_mime(b"<?xml version=\"1.0\"?>\n<svg xmlns=\"\"></svg>"),
+            Some("image/svg+xml")
+        );
+        assert_eq!(sniff_mime(b"%PDF-1.4"), None);
+        assert_eq!(sniff_mime(b""), None);
+    }
+
+    #[test]
+    fn sniff_file_formats() {
+        assert_eq!(sniff_content_type(b"%PDF-1.4"), "application/pdf");
+        assert_eq!(
+            sniff_content_type(b"PK\x03\x04\x14\x00"),
+            "application/zip"
+        );
+        assert_eq!(sniff_content_type(b"hello"), "text/plain");
+        assert_eq!(
+            sniff_content_type(b"\x00\xff\xfe\x01"),
+            "application/octet-stream"
+        );
+        assert_eq!(file_extension(sniff_content_type(b"GIF89a")), "gif");
+    }
+
+    #[test]
+    fn image_uris() {
+        assert!(is_image_uri("data:image/png;base64,iVBORw0KGgo="));
+        assert!(is_image_uri("https://example.com/photos/1.JPG?size=small"));
+        assert!(is_image_uri("/static/logo.svg"));
+        assert!(!is_image_uri("https://example.com/index.html"));
+        assert!(!is_image_uri("a picture.png"));
+        assert!(!is_image_uri("logo.png"));
+    }
+
+    #[test]
+    fn only_http_and_same_site_uris() {
+        assert!(!is_image_uri("//evil.example.com/x.png"));
+        assert!(!is_image_uri("/\\evil.example.com/x.png"));
+        assert!(!is_image_uri("javascript:alert(1)//x.png"));
+        assert!(!is_image_uri("ftp://example.com/x.png"));
+        assert!(is_image_uri("/images/x.png"));
+    }
+
+    #[test]
+    fn image_uri_is_percent_encoded() {
+        let uri = image_uri(
+            &TableName::from("public.product"),
+            &ColumnName {
+                name: "photo".to_string(),
+                table: None,
+                alias: None,
+            },
+            "a/b,1",
+        );
+        assert_eq!(uri, "/image/public.product/photo/a%2Fb%2C1");
+    }
+}
@@ -0,0 +1,448 @@
+//! import the rows of a csv into a table,
+//! the headers of the csv are mapped to the columns of the table
+//! and each row is converted into the types of the columns.
+//! A dry-run reports the rows that can not be converted without inserting anything
+use crate::{
+    common,
+    data_modify,
+    error::IntelError,
+    query_builder::{
+        self,
+        Query,
+    },
+    Context,
+};
+use rustorm::{
+    Column,
+    Dao,
+    DaoManager,
+    Table,
+    TableName,
+    Value,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::collections::HashMap;
+
+/// the header of the csv and the column of the table it is imported into
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ColumnMapping {
+    pub header: String,
+    /// the header is skipped when not mapped to a column
+    pub column: Option<String>,
+    /// the values are the identifier display of the records
+    /// referred to by the foreign column
+    pub by_display: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportRequest {
+    pub csv: String,
+    /// the headers are mapped to the columns with the same name when empty
+    pub mappings: Vec<ColumnMapping>,
+    pub dry_run: bool,
+}
+
+/// a row of the csv that can not be imported
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RowError {
+    /// the line in the csv, the headers are in line 1
+    pub line: u64,
+    /// the header of the value that can not be converted
+    pub header: Option<String>,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportSummary {
+    pub table_name: TableName,
+    /// the columns of the table the headers can be mapped to
+    pub columns: Vec<String>,
+    pub mappings: Vec<ColumnMapping>,
+    pub is_dry_run: bool,
+    pub row_count: usize,
+    /// the number of rows inserted, or to be inserted in a dry-run
+    pub valid_count: usize,
+    pub errors: Vec<RowError>,
+}
+
+/// convert the rows of the csv and insert the valid rows in one transaction,
+/// nothing is inserted in a dry-run
+pub fn import_csv(
+    context: &Context,
+    dm: &mut DaoManager,
+    table_name: &TableName,
+    request: &ImportRequest,
+) -> Result<ImportSummary, IntelError> {
+    let table = context.get_table(table_name).ok_or(IntelError::NotFound)?;
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::Headers)
+        .from_reader(request.csv.as_bytes());
+    let headers: Vec<String> = reader
+        .headers()
+        .map_err(|e| IntelError::ImportError(e.to_string()))?
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+    let mappings = if request.mappings.is_empty() {
+        auto_map(table, &headers)
+    } else {
+        request.mappings.clone()
+    };
+    let targets = mapping_targets(context, table, &headers, &mappings)?;
+
+    let mut errors = missing_columns(table, &targets);
+    let has_missing_columns = !errors.is_empty();
+    let mut resolver = DisplayResolver::default();
+    let mut records = vec![];
+    let mut row_count = 0;
+    for (index, record) in reader.records().enumerate() {
+        row_count += 1;
+        // the headers are in line 1
+        let fallback_line = index as u64 + 2;
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                errors.push(RowError {
+                    line: e
+                        .position()
+                        .map(|pos| pos.line())
+                        .unwrap_or(fallback_line),
+                    header: None,
+                    reason: e.to_string(),
+                });
+                continue;
+            }
+        };
+        let line = record
+            .position()
+            .map(|pos| pos.line())
+            .unwrap_or(fallback_line);
+        let mut dao = Dao::new();
+        let mut is_valid = true;
+        for target in targets.iter() {
+            let text = record.get(target.index).unwrap_or("");
+            let value = match target.display {
+                Some(ref display) if !text.trim().is_empty() => {
+                    resolver.resolve(context, dm, display, text.trim())?
+                }
+                _ => convert(text, target.column),
+            };
+            match value {
+                Ok(value) => dao.insert_value(&target.column.name.name, &value),
+                Err(reason) => {
+                    is_valid = false;
+                    errors.push(RowError {
+                        line,
+                        header: Some(headers[target.index].to_string()),
+                        reason,
+                    });
+                }
+            }
+        }
+        if is_valid {
+            records.push(dao);
+        }
+    }
+    if has_missing_columns {
+        records.clear();
+    }
+    if !request.dry_run && !records.is_empty() {
+        data_modify::import_records(context, dm, table, &records)?;
+    }
+    Ok(ImportSummary {
+        table_name: table.name.clone(),
+        columns: table
+            .columns
+            .iter()
+            .map(|column| column.name.name.to_string())
+            .collect(),
+        mappings,
+        is_dry_run: request.dry_run,
+        row_count,
+        valid_count: records.len(),
+        errors,
+    })
+}
+
+/// map each header to the column with the same name,
+/// a header named after the table referred to by a foreign column
+/// is mapped to the foreign column with its values resolved by display
+pub fn auto_map(table: &Table, headers: &[String]) -> Vec<ColumnMapping> {
+    let column_names: Vec<&str> = table
+        .columns
+        .iter()
+        .map(|column| column.name.name.as_str())
+        .collect();
+    let referred_tables: Vec<(&str, &str)> = table
+        .get_foreign_keys()
+        .into_iter()
+        .filter(|fk| fk.columns.len() == 1)
+        .map(|fk| (fk.columns[0].name.as_str(), fk.foreign_table.name.as_str()))
+        .collect();
+    headers
+        .iter()
+        .map(|header| match_header(header, &column_names, &referred_tables))
+        .collect()
+}
+
+fn match_header(
+    header: &str,
+    column_names: &[&str],
+    referred_tables: &[(&str, &str)],
+) -> ColumnMapping {
+    let normalized = normalize_name(header);
+    if let Some(column) = column_names
+        .iter()
+        .find(|column| normalize_name(column) == normalized)
+    {
+        return ColumnMapping {
+            header: header.to_string(),
+            column: Some(column.to_string()),
+            by_display: false,
+        };
+    }
+    let referred_column = referred_tables
+        .iter()
+        .find(|(_column, referred)| normalize_name(referred) == normalized)
+        .map(|(column, _referred)| column.to_string());
+    ColumnMapping {
+        header: header.to_string(),
+        by_display: referred_column.is_some(),
+        column: referred_column,
+    }
+}
+
+/// headers such as `First Name` matches the column `first_name`
+fn normalize_name(name: &str) -> String {
+    name.trim()
+        .to_lowercase()
+        .chars()
+        .map(|c| if c == ' ' || c == '-' { '_' } else { c })
+        .collect()
+}
+
+/// the column a header is imported into
+struct Target<'t> {
+    /// the index of the header in the csv record
+    index: usize,
+    column: &'t Column,
+    display: Option<DisplayLookup>,
+}
+
+/// the query to find the referred record from its identifier display,
+/// the text of the display is added as the param of the query
+struct DisplayLookup {
+    sql: String,
+    referred_table: String,
+}
+
+fn mapping_targets<'t>(
+    context: &Context,
+    table: &'t Table,
+    headers: &[String],
+    mappings: &[ColumnMapping],
+) -> Result<Vec<Target<'t>>, IntelError> {
+    let mut targets: Vec<Target> = vec![];
+    for mapping in mappings.iter() {
+        let column_name = match mapping.column {
+            Some(ref column_name) => column_name,
+            None => continue,
+        };
+        let index = headers
+            .iter()
+            .position(|header| *header == mapping.header)
+            .ok_or_else(|| IntelError::UnknownColumn(mapping.header.clone()))?;
+        let column = table
+            .columns
+            .iter()
+            .find(|column| column.name.name == *column_name)
+            .ok_or_else(|| IntelError::UnknownColumn(column_name.clone()))?;
+        if targets
+            .iter()
+            .any(|target| target.column.name == column.name)
+        {
+            return Err(IntelError::ImportError(format!(
+                "More than 1 header is mapped to {}",
+                column_name
+            )));
+        }
+        let display = if mapping.by_display {
+            Some(display_lookup(context, table, column)?)
+        } else {
+            None
+        };
+        targets.push(Target {
+            index,
+            column,
+            display,
+        });
+    }
+    if targets.is_empty() {
+        return Err(IntelError::ImportError(
+            "None of the headers is mapped to a column".to_string(),
+        ));
+    }
+    Ok(targets)
+}
+
+fn display_lookup(
+    context: &Context,
+    table: &Table,
+    column: &Column,
+) -> Result<DisplayLookup, IntelError> {
+    let not_resolvable = |reason: &str| {
+        IntelError::ImportError(format!(
+            "{} can not be resolved by display: {}",
+            column.name.name, reason
+        ))
+    };
+    let foreign_key = table
+        .get_foreign_keys()
+        .into_iter()
+        .find(|fk| fk.columns.len() == 1 && fk.columns[0] == column.name)
+        .ok_or_else(|| not_resolvable("not a foreign column"))?;
+    let referred_table = context
+        .get_table(&foreign_key.foreign_table)
+        .ok_or_else(|| not_resolvable("the referred table is not found"))?;
+    let display = query_builder::display_expression(
+        context,
+        referred_table,
+        "display",
+    )
+    .ok_or_else(|| not_resolvable("the referred table has no display"))?;
+    let sql = format!(
+        "SELECT display.\"{}\" FROM {} AS display WHERE {} = ",
+        foreign_key.referred_columns[0].name.replace('"', "\"\""),
+        referred_table.name.safe_complete_name(),
+        display
+    );
+    Ok(DisplayLookup {
+        sql,
+        referred_table: referred_table.name.name.to_string(),
+    })
+}
+
+/// the referred records are looked up only once for each of the display
+#[derive(Default)]
+struct DisplayResolver {
+    cache: HashMap<(String, String), Result<Value, String>>,
+}
+
+impl DisplayResolver {
+    fn resolve(
+        &mut self,
+        context: &Context,
+        dm: &mut DaoManager,
+        lookup: &DisplayLookup,
+        text: &str,
+    ) -> Result<Result<Value, String>, IntelError> {
+        let key = (lookup.sql.to_string(), text.to_string());
+        if let Some(resolved) = self.cache.get(&key) {
+            return Ok(resolved.clone());
+        }
+        let mut query = Query::new(context, dm);
+        query.append(&lookup.sql);
+        query.add_param(&Value::Text(text.to_string()));
+        // 2 is enough to know if the display is ambiguous
+        query.set_limit(2);
+        let rows = query.collect_rows()?;
+        let resolved = match rows.data.len() {
+            1 => Ok(rows.data[0][0].clone()),
+            0 => {
+                Err(format!(
+                    "No {} is displayed as {}",
+                    lookup.referred_table, text
+                ))
+            }
+            _ => {
+                Err(format!(
+                    "More than 1 {} is displayed as {}",
+                    lookup.referred_table, text
+                ))
+            }
+        };
+        self.cache.insert(key, resolved.clone());
+        Ok(resolved)
+    }
+}
+
+/// convert the text into the column type,
+/// an empty text is a null for the columns that are not text
+fn convert(text: &str, column: &Column) -> Result<Value, String> {
+    let value = common::parse_value(text, &column.get_sql_type()).map_err(
+        |e| match e {
+            IntelError::ParamParseError(reason) => reason,
+            e => format!("{:?}", e),
+        },
+    )?;
+    if value == Value::Nil
+        && column.is_not_null()
+        && !column.has_generated_default()
+    {
+        Err(format!("{} requires a value", column.name.name))
+    } else {
+        Ok(value)
+    }
+}
+
+/// the columns that requires a value but no header is mapped to it,
+/// these are reported on the line of the headers
+fn missing_columns(table: &Table, targets: &[Target]) -> Vec<RowError> {
+    table
+        .columns
+        .iter()
+        .filter(|column| {
+            column.is_not_null()
+                && !column.has_generated_default()
+                && !targets
+                    .iter()
+                    .any(|target| target.column.name == column.name)
+        })
+        .map(|column| {
+            RowError {
+                line: 1,
+                header: None,
+                reason: format!(
+                    "{} requires a value but no header is mapped to it",
+                    column.name.name
+                ),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn headers_are_matched_ignoring_case_and_spaces() {
+        let columns = ["film_id", "title", "language_id"];
+        let referred = [("language_id", "language")];
+        let mapping = match_header("Title", &columns, &referred);
+        assert_eq!(mapping.column, Some("title".to_string()));
+        assert!(!mapping.by_display);
+        let mapping = match_header(" Film Id", &columns, &referred);
+        assert_eq!(mapping.column, Some("film_id".to_string()));
+    }
+
+    #[test]
+    fn header_named_after_referred_table_is_resolved_by_display() {
+        let columns = ["film_id", "title", "language_id"];
+        let referred = [("language_id", "language")];
+        let mapping = match_header("Language", &columns, &referred);
+        assert_eq!(mapping.column, Some("language_id".to_string()));
+        assert!(mapping.by_display);
+    }
+
+    #[test]
+    fn unmatched_header_is_skipped() {
+        let columns = ["film_id", "title"];
+        let mapping = match_header("rating", &columns, &[]);
+        assert_eq!(mapping.column, None);
+        assert!(!mapping.by_display);
+    }
+}
@@ -8,17 +8,28 @@ use crate::{
         RecordChangeset,
         SaveContainer,
     },
+    data_image,
+    data_read,
     error::IntelError,
+    query_builder::{
+        self,
+        Query,
+    },
     tab::{
         self,
         IndirectTab,
         Tab,
     },
-    table_intel,
+    table_intel::{
+        self,
+        TableIntel,
+    },
     window::Window,
+    Context,
 };
 use rustorm::{
     self,
+    Column,
     ColumnName,
     Dao,
     DaoManager,
@@ -29,6 +40,43 @@ use rustorm::{
     Value,
 };
 
+/// the largest content that can be uploaded into a binary column
+pub const BINARY_SIZE_LIMIT: usize = 10 * 1024 * 1024;
+
+/// replace the content of the binary column of the record
+/// with the uploaded bytes, returning the updated record
+pub fn update_binary(
+    dm: &mut DaoManager,
+    table: &Table,
+    primary_dao: &Dao,
+    column_name: &str,
+    bytes: Vec<u8>,
+) -> Result<Dao, IntelError> {
+    if bytes.len() > BINARY_SIZE_LIMIT {
+        return Err(IntelError::BinaryError(format!(
+            "The uploaded content of {} bytes exceeds the limit of {} bytes",
+            bytes.len(),
+            BINARY_SIZE_LIMIT
+        )));
+    }
+    let column = common::find_binary_column(table, column_name)?;
+    let (filter, pk_values) = common::primary_filter(table, primary_dao, 2)?;
+    let sql = format!(
+        "UPDATE {} SET \"{}\" = $1 WHERE {} RETURNING *",
+        table.name.complete_name(),
+        column.name.name,
+        filter
+    );
+    let content = Value::Blob(bytes);
+    let mut params: Vec<&Value> = vec![&content];
+    params.extend(pk_values.iter());
+    let mut updated = dm
+        .execute_sql_with_maybe_one_return(&sql, &params)?
+        .ok_or(IntelError::NotFound)?;
+    data_image::show_record_images(table, &mut updated);
+    Ok(updated)
+}
+
 /// delete the records with the following record_ids
 /// return the total number of records deleted
 pub fn delete_records(
@@ -85,22 +133,29 @@ fn delete_records_from_single_primary_column(
     Ok(rows)
 }
 
+/// save the inserted and updated rows, returning the records
+/// as they are in the database after the changes
 pub fn save_container(
     dm: &mut DaoManager,
     tables: &[Table],
     container: &SaveContainer,
-) -> Result<(), IntelError> {
+) -> Result<Vec<Dao>, IntelError> {
     let &(ref table_name_for_insert, ref rows_insert) = &container.for_insert;
     let &(ref table_name_for_update, ref rows_update) = &container.for_update;
     let table_for_insert =
-        table_intel::get_table(table_name_for_insert, tables).unwrap();
+        table_intel::get_table(table_name_for_insert, tables)
+            .ok_or(IntelError::NotFound)?;
     let table_for_update =
-        table_intel::get_table(table_name_for_update, tables).unwrap();
+        table_intel::get_table(table_name_for_update, tables)
+            .ok_or(IntelError::NotFound)?;
+    let mut saved = vec![];
     if rows_insert.iter().count() > 0 {
-        insert_rows_to_table(dm, table_for_insert, rows_insert)?;
+        let inserted = insert_rows_to_table(dm, table_for_insert, rows_insert)?;
+        saved.extend(inserted.iter());
     }
-    update_records_in_table(dm, table_for_update, rows_update)?;
-    Ok(())
+    let updated = update_records_in_table(dm, table_for_update, rows_update)?;
+    saved.extend(updated);
+    Ok(saved)
 }
 
 pub fn save_changeset(
@@ -390,7 +445,9 @@ fn link_new_for_indirect_table(
     Ok(())
 }
 
-/// create a record in linker table using the primary key of main and indirect record
+/// create a record in linker table using the primary key of main and indirect record,
+/// the keys of the other tables linked by an n-ary linker are the extra columns
+/// of the indirect record
 fn create_linker_record(
     main_table: &Table,
     main_record: &Dao,
@@ -402,20 +459,30 @@ fn create_linker_record(
         linker_table.get_local_foreign_columns_pair_to_table(&main_table.name);
     let indirect_fk_pair = linker_table
         .get_local_foreign_columns_pair_to_table(&indirect_table.name);
-    assert_eq!(main_fk_pair.len(), 1);
-    assert_eq!(indirect_fk_pair.len(), 1);
-    let (main_linker_local, main_linker_refferred) = main_fk_pair[0];
-    let (indirect_linker_local, indirect_linker_refferred) =
-        indirect_fk_pair[0];
-    let main_pk_value = main_record
-        .get_value(&main_linker_refferred.name)
-        .expect("must have a value");
-    let indirect_pk_value = indirect_record
-        .get_value(&indirect_linker_refferred.name)
-        .expect("must have a value");
     let mut linker_record = Dao::new();
-    linker_record.insert_value(&main_linker_local.name, main_pk_value);
-    linker_record.insert_value(&indirect_linker_local.name, indirect_pk_value);
+    for (main_linker_local, main_linker_refferred) in main_fk_pair {
+        let main_pk_value = main_record
+            .get_value(&main_linker_refferred.name)
+            .expect("must have a value");
+        linker_record.insert_value(&main_linker_local.name, main_pk_value);
+    }
+    for (indirect_linker_local, indirect_linker_refferred) in indirect_fk_pair {
+        let indirect_pk_value = indirect_record
+            .get_value(&indirect_linker_refferred.name)
+            .expect("must have a value");
+        linker_record
+            .insert_value(&indirect_linker_local.name, indirect_pk_value);
+    }
+    // the extra columns of the link, such as the role of the actor in the film
+    for column in TableIntel(linker_table)
+        .get_linker_extra_columns(&main_table.name, &indirect_table.name)
+    {
+        let alias =
+            IndirectTab::linker_column_alias(&linker_table.name, &column.name);
+        if let Some(value) = indirect_record.get_value(&alias) {
+            linker_record.insert_value(&column.name.name, value);
+        }
+    }
     Ok(linker_record)
 }
 
@@ -465,9 +532,21 @@ fn update_record_in_table(
     let table_name = &main_table.name;
     let mut params = vec![];
     let mut sql = format!("UPDATE {} ", table_name.complete_name());
-    let columns = main_table.get_non_primary_columns();
+    // only the columns in the record are updated, the record
+    // may came from a query that selected only some of the columns
+    let columns: Vec<(&Column, Value)> = main_table
+        .get_non_primary_columns()
+        .into_iter()
+        .filter_map(|col| {
+            let value = record.get_value(&col.name.name)?;
+            data_image::stored_value(col, value).map(|value| (col, value))
+        })
+        .collect();
+    if columns.is_empty() {
+        return Ok(record.clone());
+    }
     sql += "SET ";
-    for (i, col) in columns.iter().enumerate() {
+    for (i, (col, value)) in columns.iter().enumerate() {
         let col_sql_type = col.get_sql_type();
         if i > 0 {
             sql += ", ";
@@ -483,9 +562,6 @@ fn update_record_in_table(
         if col_sql_type.is_array_type() {
             sql += &format!("::{}", col_sql_type.name());
         }
-        let value = record.get_value(&col.name.name);
-        assert!(value.is_some());
-        let value = value.unwrap();
         let casted_value =
             rustorm::common::cast_type(&value, &col.get_sql_type());
         params.push(casted_value);
@@ -512,7 +588,9 @@ fn update_record_in_table(
     println!("sql: {}", sql);
     println!("params: {:?}", params);
     let bparams: Vec<&Value> = params.iter().collect();
-    dm.execute_sql_with_one_return(&sql, &bparams)
+    let mut updated: Dao = dm.execute_sql_with_one_return(&sql, &bparams)?;
+    data_image::show_record_images(main_table, &mut updated);
+    Ok(updated)
 }
 
 /// insert rows all at once in one query
@@ -568,6 +646,69 @@ fn insert_rows_to_table(
     Ok(rows)
 }
 
+/// insert the imported records in one transaction,
+/// none of the records are inserted when one of them or the commit fails
+pub fn import_records(
+    context: &Context,
+    dm: &mut DaoManager,
+    table: &Table,
+    records: &[Dao],
+) -> Result<Vec<Dao>, IntelError> {
+    dm.execute_sql_with_return("BEGIN", &[])?;
+    let mut inserted = Vec::with_capacity(records.len());
+    for record in records {
+        match insert_imported_record(context, dm, table, record) {
+            Ok(record) => inserted.push(record),
+            Err(e) => {
+                data_read::rollback(dm);
+                return Err(e.into());
+            }
+        }
+    }
+    if let Err(e) = dm.execute_sql_with_return("COMMIT", &[]) {
+        data_read::rollback(dm);
+        return Err(e.into());
+    }
+    Ok(inserted)
+}
+
+/// unlike in creating a new record, the primary columns are also inserted
+/// when the imported record has a value for it
+fn insert_imported_record(
+    context: &Context,
+    dm: &mut DaoManager,
+    table: &Table,
+    record: &Dao,
+) -> Result<Dao, DbError> {
+    let values: Vec<(&Column, &Value)> = table
+        .columns
+        .iter()
+        .filter_map(|col| {
+            record.get_value(&col.name.name).map(|value| (col, value))
+        })
+        .filter(|(col, value)| !(value.is_nil() && col.has_generated_default()))
+        .collect();
+    let mut query = Query::new(context, dm);
+    query.append(&format!("INSERT INTO {} (", table.name.complete_name()));
+    for (i, (col, _value)) in values.iter().enumerate() {
+        if i > 0 {
+            query.append(", ");
+        }
+        query.append(&query_builder::quoted_name(&col.name.name));
+    }
+    query.append(") VALUES (");
+    for (i, (col, value)) in values.iter().enumerate() {
+        if i > 0 {
+            query.append(", ");
+        }
+        let casted_value =
+            rustorm::common::cast_type(value, &col.get_sql_type());
+        query.add_param(&casted_value);
+    }
+    query.append(") RETURNING *");
+    query.collect_one_record()
+}
+
 /// check if all values in these columns are nill,
 /// so we can skip it when column is skippable
 fn are_all_nil(column: &str, rows: &Rows) -> bool {
@@ -646,6 +787,7 @@ fn insert_record_to_table(
     dm.execute_sql_with_one_return(&sql, &bparams)
 }
 
+/// insert the primary columns and the extra columns of the linker record
 fn insert_record_to_linker_table(
     dm: &mut DaoManager,
     linker_table: &Table,
@@ -653,45 +795,29 @@ fn insert_record_to_linker_table(
 ) -> Result<Dao, DbError> {
     let table_name = &linker_table.name;
     let mut params = vec![];
-    let mut sql = format!("INSERT INTO {} ", table_name.complete_name());
-    let columns = &linker_table.get_primary_columns();
-    sql += "(";
-    for (i, col) in columns.iter().enumerate() {
-        let value = record.get_value(&col.name.name);
-        if let Some(value) = value {
+    let mut column_names = vec![];
+    let mut placeholders = vec![];
+    for col in linker_table.columns.iter() {
+        if let Some(value) = record.get_value(&col.name.name) {
             if value.is_nil()
                 && col.is_not_null()
                 && col.has_generated_default()
             {
-            } else {
-                if i > 0 {
-                    sql += ", ";
-                }
-                sql += &format!("{} ", col.name.name);
-            }
-        }
-    }
-    sql += ") ";
-    sql += "VALUES (";
-    for (i, col) in columns.iter().enumerate() {
-        let value = record.get_value(&col.name.name);
-        if let Some(value) = value {
-            if value.is_nil()
-                && col.is_not_null()
-                && col.has_generated_default()
-            {
-            } else {
-                if i > 0 {
-                    sql += ", ";
-                }
-                sql += &format!("${} ", params.len() + 1);
-                let casted_value =
-                    rustorm::common::cast_type(&value, &col.get_sql_type());
-                params.push(casted_value);
+                continue;
             }
+            let casted_value =
+                rustorm::common::cast_type(&value, &col.get_sql_type());
+            params.push(casted_value);
+            column_names.push(col.name.name.to_string());
+            placeholders.push(format!("${}", params.len()));
         }
     }
-    sql += ") RETURNING *";
+    let sql = format!(
+        "INSERT INTO {} ({}) VALUES ({}) RETURNING *",
+        table_name.complete_name(),
+        column_names.join(", "),
+        placeholders.join(", ")
+    );
     println!("sql: {}", sql);
     println!("params: {:?}", params);
     let bparams: Vec<&Value> = params.iter().collect();
@@ -1,31 +1,59 @@
 use crate::{
+    common,
     data_container::{
         AppData,
+        Filter,
         QueryResult,
         RecordDetail,
+        Sort,
         WindowData,
     },
+    data_image,
     error::IntelError,
     query_builder::Query,
     query_parser,
+    query_plan::QueryPlan,
+    sql_guard::{
+        self,
+        SqlOptions,
+        SqlViolation,
+        StatementKind,
+    },
+    sql_params::{
+        ParameterizedSql,
+        Placeholder,
+    },
     Context,
 };
 use rustorm::{
+    ColumnName,
     Dao,
     DaoManager,
     DatabaseName,
     DbError,
     EntityManager,
     Rows,
+    Table,
     TableName,
+    Value,
+};
+#[cfg(feature = "with-postgres")]
+use rustorm::{
+    error::PlatformError,
+    pg::PostgresError,
 };
 use sqlparser::{
     dialect::GenericSqlDialect,
+    sqlast::SQLStatement,
     sqlparser::Parser,
 };
 
 mod detail_record;
 
+/// the SQLSTATE of a query which is cancelled, such as by the statement timeout
+#[cfg(feature = "with-postgres")]
+const QUERY_CANCELED: &str = "57014";
+
 pub fn get_database_name(
     em: &mut EntityManager,
 ) -> Result<Option<DatabaseName>, DbError> {
@@ -50,33 +78,240 @@ pub fn fetch_detail(
     )
 }
 
+/// the content of the binary column of the record
+pub fn fetch_binary(
+    context: &Context,
+    dm: &mut DaoManager,
+    table_name: &TableName,
+    column_name: &str,
+    primary_dao: &Dao,
+) -> Result<Vec<u8>, IntelError> {
+    let table = context.get_table(table_name).ok_or(IntelError::NotFound)?;
+    let column = common::find_binary_column(table, column_name)?;
+    let (filter, pk_values) = common::primary_filter(table, primary_dao, 1)?;
+    let sql = format!(
+        "SELECT \"{}\" FROM {} WHERE {}",
+        column.name.name,
+        table.name.complete_name(),
+        filter
+    );
+    let params: Vec<&Value> = pk_values.iter().collect();
+    let rows = dm.execute_sql_with_return(&sql, &params)?;
+    let value = rows
+        .data
+        .into_iter()
+        .next()
+        .and_then(|row| row.into_iter().next())
+        .ok_or(IntelError::NotFound)?;
+    match value {
+        Value::Blob(bytes) => Ok(bytes),
+        Value::Nil => Ok(vec![]),
+        value => {
+            Err(IntelError::BinaryError(format!(
+                "{} has a non binary value: {:?}",
+                column_name, value
+            )))
+        }
+    }
+}
+
+/// execute an ad-hoc sql query, imposing the restrictions
+/// of the supplied options, the `$1` and `:name` placeholders are bound
+/// to the params in the order of the placeholders
 pub fn execute_sql_query(
     context: &Context,
-    em: &mut EntityManager,
+    _em: &mut EntityManager,
     dm: &mut DaoManager,
     sql: &str,
-) -> Result<QueryResult, DbError> {
+    params: &[Value],
+    options: &SqlOptions,
+) -> Result<QueryResult, IntelError> {
+    let parameterized = parameterize_sql(sql, params)?;
+    let nulled_sql = parameterized.nulled_sql();
+    let kinds = sql_guard::check_statements(&nulled_sql, options)?;
+    let rows =
+        execute_guarded_sql(dm, &parameterized.sql, params, &kinds, options)?;
     let dialect = GenericSqlDialect {};
-    let ast = Parser::parse_sql(&dialect, sql.to_string());
-    println!("{:#?}", ast);
-    let rows = dm.execute_sql_with_return(&sql, &[])?;
-    let window = if let Ok(ast) = ast {
-        if !ast.is_empty() {
-            if let Some(table_name) = query_parser::extract_table_name(&ast[0])
-            {
-                let table_name = TableName::from(&table_name);
-                context.find_window(&table_name)
-            } else {
-                None
+    let ast = Parser::parse_sql(&dialect, nulled_sql).unwrap_or_default();
+    let window = ast
+        .get(0)
+        .and_then(query_parser::extract_table_name)
+        .and_then(|table_name| {
+            context.find_window(&TableName::from(&table_name))
+        });
+    let table = window
+        .and_then(|window| context.get_table(&window.main_tab.table_name));
+    let is_traced = match (ast.get(0), table) {
+        (Some(statement), Some(table)) => {
+            is_traced_to_table(statement, table, &rows.columns)
+        }
+        _ => false,
+    };
+    let mut result = QueryResult::with_rows(window, rows);
+    // the edited values are saved to the columns with the same name
+    result.is_editable = result.is_editable && is_traced;
+    Ok(result)
+}
+
+/// each of the columns which has the name of a column of the table
+/// is selected as is from the table, and no column is selected twice
+fn is_traced_to_table(
+    statement: &SQLStatement,
+    table: &Table,
+    columns: &[String],
+) -> bool {
+    let table_columns =
+        match query_parser::extract_table_columns(statement, table) {
+            Some(table_columns) => table_columns,
+            None => return false,
+        };
+    let is_unique = columns
+        .iter()
+        .enumerate()
+        .all(|(i, column)| !columns[..i].contains(column));
+    is_unique
+        && columns.iter().all(|column| {
+            table_columns.contains(column)
+                || table
+                    .get_column(&ColumnName::from(column.as_str()))
+                    .is_none()
+        })
+}
+
+/// rewrite the named placeholders and check that each placeholder has a value
+pub(crate) fn parameterize_sql(
+    sql: &str,
+    params: &[Value],
+) -> Result<ParameterizedSql, IntelError> {
+    let parameterized = ParameterizedSql::parse(sql);
+    if parameterized.placeholders.len() != params.len() {
+        return Err(IntelError::ParamParseError(format!(
+            "Expecting {} parameters, got {}",
+            parameterized.placeholders.len(),
+            params.len()
+        )));
+    }
+    Ok(parameterized)
+}
+
+/// the placeholders in the sql with the type inferred from the column
+/// each one is compared against
+pub fn get_sql_placeholders(context: &Context, sql: &str) -> Vec<Placeholder> {
+    let mut parameterized = ParameterizedSql::parse(sql);
+    parameterized.infer_types(context);
+    parameterized.placeholders
+}
+
+/// Execute the sql with the restrictions of the options,
+/// the row limit is imposed on a single query by wrapping it, the closing
+/// parenthesis is on its own line so a trailing `--` comment does not swallow it.
+/// Other statements are only checked against the limit after they are executed
+fn execute_guarded_sql(
+    dm: &mut DaoManager,
+    sql: &str,
+    params: &[Value],
+    kinds: &[StatementKind],
+    options: &SqlOptions,
+) -> Result<Rows, IntelError> {
+    let is_single_query = kinds.len() == 1 && kinds[0].is_read_only();
+    let limited_sql = match options.row_limit {
+        // fetch 1 more than the limit to know if it is exceeded
+        Some(row_limit) if is_single_query => {
+            format!(
+                "SELECT * FROM ({}\n) AS limited_result LIMIT {}",
+                sql.trim().trim_end_matches(';'),
+                row_limit + 1
+            )
+        }
+        _ => sql.to_string(),
+    };
+    let bparams: Vec<&Value> = params.iter().collect();
+    let need_transaction = options.is_postgres
+        && (!options.allow_write || options.statement_timeout.is_some());
+    let rows = if need_transaction {
+        begin_guarded_transaction(dm, options)?;
+        match dm.execute_sql_with_return(&limited_sql, &bparams) {
+            Ok(rows) => {
+                dm.execute_sql_with_return("COMMIT", &[])?;
+                rows
+            }
+            Err(e) => {
+                rollback(dm);
+                return Err(guarded_error(e, options));
             }
-        } else {
-            println!("Warning: there are {} statements", ast.len());
-            None
         }
     } else {
-        None
+        dm.execute_sql_with_return(&limited_sql, &bparams)?
     };
-    Ok(QueryResult::with_rows(window, rows))
+    match options.row_limit {
+        Some(row_limit) if rows.data.len() > row_limit => {
+            Err(SqlViolation::RowLimitExceeded(row_limit).into())
+        }
+        _ => Ok(rows),
+    }
+}
+
+/// Begin a transaction which is read only when the user is not allowed to write,
+/// the statement timeout is local to the transaction so it does not leak
+/// into the next use of the pooled connection.
+/// Note: this relies on postgresql `BEGIN READ ONLY` and `SET LOCAL statement_timeout`
+pub(crate) fn begin_guarded_transaction(
+    dm: &mut DaoManager,
+    options: &SqlOptions,
+) -> Result<(), IntelError> {
+    if options.allow_write {
+        dm.execute_sql_with_return("BEGIN", &[])?;
+    } else {
+        dm.execute_sql_with_return("BEGIN READ ONLY", &[])?;
+    }
+    if let Some(timeout) = options.statement_timeout {
+        let set_timeout = format!("SET LOCAL statement_timeout = {}", timeout);
+        if let Err(e) = dm.execute_sql_with_return(&set_timeout, &[]) {
+            rollback(dm);
+            return Err(e.into());
+        }
+    }
+    Ok(())
+}
+
+/// rollback the transaction after an error,
+/// a failed rollback is only logged so the original error is kept
+pub(crate) fn rollback(dm: &mut DaoManager) {
+    if let Err(e) = dm.execute_sql_with_return("ROLLBACK", &[]) {
+        info!("Unable to rollback the transaction: {:?}", e);
+    }
+}
+
+/// the error of the guarded sql, a query that is cancelled
+/// by the statement timeout is reported as a violation
+pub(crate) fn guarded_error(e: DbError, options: &SqlOptions) -> IntelError {
+    match options.statement_timeout {
+        Some(timeout) if is_statement_timeout(&e) => {
+            SqlViolation::StatementTimeout(timeout).into()
+        }
+        _ => e.into(),
+    }
+}
+
+/// postgresql cancels the query with the SQLSTATE 57014 (query_canceled)
+/// when it exceeded the statement_timeout
+#[cfg(feature = "with-postgres")]
+fn is_statement_timeout(e: &DbError) -> bool {
+    match e {
+        DbError::PlatformError(PlatformError::PostgresError(
+            PostgresError::SqlError(e, _),
+        )) => {
+            e.code()
+                .map(|state| state.code() == QUERY_CANCELED)
+                .unwrap_or(false)
+        }
+        _ => false,
+    }
+}
+
+#[cfg(not(feature = "with-postgres"))]
+fn is_statement_timeout(_e: &DbError) -> bool {
+    false
 }
 
 pub fn get_window_main_table_data(
@@ -93,36 +328,181 @@ pub fn get_window_main_table_data(
     Ok(QueryResult::with_rows(window, rows))
 }
 
-fn fetch_main_table_data(
+/// the page of the rows of the main tab that matches the filter,
+/// in the order of the sort. The total rows are the rows that matches
+/// the filter
+pub fn get_filtered_main_table_data(
     context: &Context,
-    em: &mut EntityManager,
     dm: &mut DaoManager,
     table_name: &TableName,
+    filter: &Filter,
+    sort: &Sort,
     page: usize,
     page_size: usize,
-) -> Result<Rows, IntelError> {
-    let main_table = context
-        .get_table(table_name)
-        .expect("there should be table");
-
+) -> Result<QueryResult, IntelError> {
+    let main_table =
+        context.get_table(table_name).ok_or(IntelError::NotFound)?;
     let mut query = Query::new(context, dm);
     query.select();
-    query.enumerate_columns(&main_table);
+    query.enumerate_columns(main_table);
     query.from(table_name);
+    query.add_filter(main_table, filter)?;
+    query.set_sort(main_table, sort)?;
     query.set_page(page, page_size);
     let mut rows = query.collect_rows()?;
+    data_image::show_images(main_table, &mut rows);
+    rows.count = Some(count_filtered_rows(context, dm, main_table, filter)?);
+    let window = context.get_window(table_name);
+    Ok(QueryResult::with_rows(window, rows))
+}
+
+/// the number of rows of the table that matches the filter
+fn count_filtered_rows(
+    context: &Context,
+    dm: &mut DaoManager,
+    table: &Table,
+    filter: &Filter,
+) -> Result<usize, IntelError> {
+    let mut query = Query::new(context, dm);
+    query.select();
+    query.append("COUNT(*) ");
+    query.from(&table.name);
+    query.add_filter(table, filter)?;
+    let rows = query.collect_rows()?;
+    match rows.data.first().and_then(|row| row.first()) {
+        Some(Value::Bigint(count)) => Ok(*count as usize),
+        Some(Value::Int(count)) => Ok(*count as usize),
+        _ => Ok(0),
+    }
+}
+
+fn fetch_main_table_data(
+    context: &Context,
+    em: &mut EntityManager,
+    dm: &mut DaoManager,
+    table_name: &TableName,
+    page: usize,
+    page_size: usize,
+) -> Result<Rows, IntelError> {
+    let mut query =
+        build_main_table_query(context, dm, table_name, page, page_size)?;
+    let mut rows = query.collect_rows()?;
+    if let Some(main_table) = context.get_table(table_name) {
+        data_image::show_images(main_table, &mut rows);
+    }
     println!("Returning: {} rows", rows.data.len());
     let row_count = em.get_total_records(table_name)?;
     rows.count = Some(row_count);
     Ok(rows)
 }
 
+fn build_main_table_query<'c>(
+    context: &'c Context,
+    dm: &'c mut DaoManager,
+    table_name: &TableName,
+    page: usize,
+    page_size: usize,
+) -> Result<Query<'c>, IntelError> {
+    let main_table =
+        context.get_table(table_name).ok_or(IntelError::NotFound)?;
+
+    let mut query = Query::new(context, dm);
+    query.select();
+    query.enumerate_columns(&main_table);
+    query.from(table_name);
+    query.set_page(page, page_size);
+    Ok(query)
+}
+
+/// explain the query used in retrieving the page of the window main tab,
+/// when analyzed the query is executed with the restrictions of the options
+pub fn explain_window_main_table_query(
+    context: &Context,
+    dm: &mut DaoManager,
+    table_name: &TableName,
+    page: usize,
+    page_size: usize,
+    analyze: bool,
+    options: &SqlOptions,
+) -> Result<QueryPlan, IntelError> {
+    let query =
+        build_main_table_query(context, dm, table_name, page, page_size)?;
+    let (sql, params, _) = query.into_parts();
+    explain_guarded_sql(
+        dm,
+        &sql,
+        &params,
+        StatementKind::Query,
+        analyze,
+        options,
+    )
+}
+
+/// explain an ad-hoc SELECT query, when analyzed the query is actually executed
+/// and therefore is subject to the same restrictions as executing it
+pub fn explain_sql_query(
+    dm: &mut DaoManager,
+    sql: &str,
+    params: &[Value],
+    analyze: bool,
+    options: &SqlOptions,
+) -> Result<QueryPlan, IntelError> {
+    let parameterized = parameterize_sql(sql, params)?;
+    sql_guard::check_single_query(&parameterized.nulled_sql(), options)?;
+    let mut plan = explain_guarded_sql(
+        dm,
+        &parameterized.sql,
+        params,
+        StatementKind::Query,
+        analyze,
+        options,
+    )?;
+    plan.sql = sql.to_string();
+    Ok(plan)
+}
+
+fn explain_guarded_sql(
+    dm: &mut DaoManager,
+    sql: &str,
+    params: &[Value],
+    kind: StatementKind,
+    analyze: bool,
+    options: &SqlOptions,
+) -> Result<QueryPlan, IntelError> {
+    // the plan is only 1 row
+    let explain_options = SqlOptions {
+        row_limit: None,
+        ..options.clone()
+    };
+    let rows = execute_guarded_sql(
+        dm,
+        &QueryPlan::explain_sql(sql, analyze),
+        params,
+        &[kind],
+        &explain_options,
+    )?;
+    QueryPlan::from_rows(&rows, sql, analyze)
+}
+
 pub fn retrieve_app_data(
     context: &Context,
     em: &mut EntityManager,
     dm: &mut DaoManager,
     table_name: Option<TableName>,
     page_size: usize,
+) -> Result<AppData, IntelError> {
+    retrieve_app_data_with_record(context, em, dm, table_name, None, page_size)
+}
+
+/// the app data with the window of the table opened in the detail view
+/// of the record identified by the record id
+pub fn retrieve_app_data_with_record(
+    context: &Context,
+    em: &mut EntityManager,
+    dm: &mut DaoManager,
+    table_name: Option<TableName>,
+    record_id: Option<&str>,
+    page_size: usize,
 ) -> Result<AppData, IntelError> {
     let grouped_window = context.grouped_window.clone();
     println!("table_name: {:#?}", table_name);
@@ -131,7 +511,7 @@ pub fn retrieve_app_data(
     } else {
         &grouped_window[0].window_names[0].table_name
     };
-    let rows = fetch_main_table_data(
+    let mut rows = fetch_main_table_data(
         context,
         em,
         dm,
@@ -139,13 +519,82 @@ pub fn retrieve_app_data(
         1,
         page_size,
     )?;
+    let record_detail = match record_id {
+        Some(record_id) => {
+            let main_table = context
+                .get_table(retrieve_table_name)
+                .ok_or(IntelError::NotFound)?;
+            let primary_dao = common::record_id_dao(main_table, record_id)?;
+            let record_detail = fetch_detail(
+                context,
+                em,
+                dm,
+                retrieve_table_name,
+                &primary_dao,
+                page_size,
+            )?;
+            include_record(&mut rows, &primary_dao, &record_detail.record);
+            Some(record_detail)
+        }
+        None => None,
+    };
     let first_window = context
         .get_window(retrieve_table_name)
         .expect("expecting a window");
-    let first_window_data = WindowData::from_rows(rows);
+    let mut first_window_data = WindowData::from_rows(rows);
+    if let Some(record_detail) = record_detail {
+        first_window_data.set_record_detail(record_detail);
+    }
     Ok(AppData {
         grouped_window,
         windows: vec![first_window.clone()],
         window_data: vec![first_window_data],
     })
 }
+
+/// put the record at the top of the rows when it is not in the rows,
+/// so the detail view of the record can be opened from its row
+fn include_record(rows: &mut Rows, primary_dao: &Dao, record: &Dao) {
+    let position = |column: &str| rows.columns.iter().position(|c| c == column);
+    let is_included = rows.data.iter().any(|row| {
+        primary_dao.0.iter().all(|(column, value)| {
+            position(column).and_then(|index| row.get(index)) == Some(value)
+        })
+    });
+    if !is_included {
+        let row = rows
+            .columns
+            .iter()
+            .map(|column| record.0.get(column).cloned().unwrap_or(Value::Nil))
+            .collect();
+        rows.data.insert(0, row);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_db;
+
+    /// the shared url of a deleted record is not found
+    #[test]
+    fn missing_record_is_not_found() {
+        let context = test_db::sakila_context();
+        let mut em = test_db::sakila_em();
+        let mut dm = test_db::sakila_dm();
+        let app_data = retrieve_app_data_with_record(
+            &context,
+            &mut em,
+            &mut dm,
+            Some(TableName::from("public.actor")),
+            Some("999999"),
+            10,
+        );
+        match app_data {
+            Err(IntelError::NotFound) => (),
+            app_data => {
+                panic!("expecting not found: {:?}", app_data.map(|_| ()))
+            }
+        }
+    }
+}
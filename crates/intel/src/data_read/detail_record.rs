@@ -1,5 +1,6 @@
 use crate::{
     data_container::RecordDetail,
+    data_image,
     error::IntelError,
     query_builder::Query,
     Context,
@@ -36,7 +37,10 @@ pub fn get_selected_record_detail(
         .get_window(table_name)
         .expect("should have a window");
 
-    let record: Dao = query.collect_one_record()?;
+    // the record may have been deleted since its id was taken
+    let mut record: Dao =
+        query.collect_maybe_record()?.ok_or(IntelError::NotFound)?;
+    data_image::show_record_images(main_table, &mut record);
 
     println!("Getting one ones");
     let mut one_one_records: Vec<(TableName, Option<Dao>)> =
@@ -122,7 +126,10 @@ fn get_one_one_record(
     query.add_dao_filter(&main_table.name, &primary_dao);
     query.set_limit(page_size);
 
-    let one_one_record = query.collect_maybe_record()?;
+    let mut one_one_record = query.collect_maybe_record()?;
+    if let Some(ref mut one_one_record) = one_one_record {
+        data_image::show_record_images(one_one_table, one_one_record);
+    }
     Ok(one_one_record)
 }
 
@@ -149,6 +156,7 @@ fn get_has_many_records(
     query.set_limit(page_size);
     let mut rows = query.collect_rows()?;
     rows.count = Some(em.get_total_records(&has_many_table.name)?);
+    data_image::show_images(has_many_table, &mut rows);
     Ok(rows)
 }
 
@@ -165,9 +173,15 @@ fn get_indirect_records(
     let indirect_table = context
         .get_table(indirect_table_name)
         .expect("table should exist");
+    let linker = context.get_table(linker_table).expect("table should exist");
     let mut query = Query::new(context, dm);
     query.select();
     query.enumerate_columns(&indirect_table);
+    query.enumerate_linker_columns(
+        linker,
+        &main_table.name,
+        &indirect_table.name,
+    );
 
     query.add_table_datatypes(&indirect_table);
     query.from(&main_table.name);
@@ -177,5 +191,6 @@ fn get_indirect_records(
     query.set_limit(page_size);
     let mut rows = query.collect_rows()?;
     rows.count = Some(em.get_total_records(&indirect_table.name)?);
+    data_image::show_images(indirect_table, &mut rows);
     Ok(rows)
 }
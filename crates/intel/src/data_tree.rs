@@ -0,0 +1,283 @@
+//! the records of a table which refers to itself, such as the categories
+//! and their parent category, are shown as a tree.
+//! The children are retrieved one level at a time as the tree is expanded
+use crate::{
+    common,
+    data_export,
+    error::IntelError,
+    query_builder::{
+        self,
+        Query,
+    },
+    table_intel::TableIntel,
+    Context,
+};
+use rustorm::{
+    ColumnName,
+    DaoManager,
+    Rows,
+    Table,
+    TableName,
+    Value,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+/// the children of a record which are retrieved at a time
+const CHILDREN_LIMIT: usize = 500;
+
+/// the ancestors of a record which are retrieved,
+/// this also stops the recursion when the references has a cycle
+const MAX_DEPTH: usize = 100;
+
+/// the foreign key of the table which refers to the table itself
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct SelfReference {
+    /// the columns which refers to the parent record
+    pub parent_columns: Vec<ColumnName>,
+    /// the primary columns of the parent record, in the same order
+    /// as the parent columns
+    pub referred_columns: Vec<ColumnName>,
+}
+
+impl SelfReference {
+    fn pairs(&self) -> impl Iterator<Item = (&ColumnName, &ColumnName)> {
+        self.parent_columns.iter().zip(self.referred_columns.iter())
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct TreeNode {
+    /// the values of the primary columns of the record
+    pub record_id: String,
+    /// the identifier display of the record
+    pub display: String,
+    pub has_children: bool,
+}
+
+/// the records whose parent is the record identified by the record id,
+/// the records which has no parent are the roots of the tree
+pub fn get_tree_children(
+    context: &Context,
+    dm: &mut DaoManager,
+    table_name: &TableName,
+    parent: Option<&str>,
+) -> Result<Vec<TreeNode>, IntelError> {
+    let (table, self_reference) = get_tree_table(context, table_name)?;
+    let mut query = Query::new(context, dm);
+    query.append(&format!(
+        "SELECT {} \nFROM {} AS node \n",
+        node_columns(context, table, &self_reference),
+        table_name.safe_complete_name()
+    ));
+    match parent {
+        Some(parent) => {
+            let parent_dao = common::record_id_dao(table, parent)?;
+            for (i, (parent_column, referred_column)) in
+                self_reference.pairs().enumerate()
+            {
+                let value = parent_dao
+                    .get_value(&referred_column.name)
+                    .ok_or(IntelError::NotFound)?;
+                query.append(if i == 0 { "WHERE " } else { "AND " });
+                query.append(&format!(
+                    "{} = ",
+                    aliased_column("node", parent_column)
+                ));
+                query.add_param(value);
+            }
+        }
+        None => {
+            for (i, parent_column) in
+                self_reference.parent_columns.iter().enumerate()
+            {
+                query.append(if i == 0 { "WHERE " } else { "AND " });
+                query.append(&format!(
+                    "{} IS NULL ",
+                    aliased_column("node", parent_column)
+                ));
+            }
+        }
+    }
+    query.append(&format!(
+        "\nORDER BY tree_display \nLIMIT {} ",
+        CHILDREN_LIMIT
+    ));
+    let rows = query.collect_rows()?;
+    Ok(to_tree_nodes(table, &rows))
+}
+
+/// the record and its ancestors up to the root, ordered from the root,
+/// retrieved with a recursive query
+pub fn get_tree_ancestors(
+    context: &Context,
+    dm: &mut DaoManager,
+    table_name: &TableName,
+    record_id: &str,
+) -> Result<Vec<TreeNode>, IntelError> {
+    let (table, self_reference) = get_tree_table(context, table_name)?;
+    let record_dao = common::record_id_dao(table, record_id)?;
+    let mut query = Query::new(context, dm);
+    query.append(&format!(
+        "WITH RECURSIVE ancestors AS (\nSELECT node.*, 0 AS tree_depth \
+         \nFROM {} AS node \n",
+        table_name.safe_complete_name()
+    ));
+    for (i, pk) in table.get_primary_column_names().iter().enumerate() {
+        let value =
+            record_dao.get_value(&pk.name).ok_or(IntelError::NotFound)?;
+        query.append(if i == 0 { "WHERE " } else { "AND " });
+        query.append(&format!("{} = ", aliased_column("node", pk)));
+        query.add_param(value);
+    }
+    let join: Vec<String> = self_reference
+        .pairs()
+        .map(|(parent_column, referred_column)| {
+            format!(
+                "{} = {}",
+                aliased_column("node", referred_column),
+                aliased_column("ancestors", parent_column)
+            )
+        })
+        .collect();
+    query.append(&format!(
+        "\nUNION ALL \nSELECT node.*, ancestors.tree_depth + 1 \
+         \nFROM {} AS node \nJOIN ancestors ON {} \
+         \nWHERE ancestors.tree_depth < {} \n) \n",
+        table_name.safe_complete_name(),
+        join.join(" AND "),
+        MAX_DEPTH
+    ));
+    query.append(&format!(
+        "SELECT {} \nFROM ancestors AS node \nORDER BY node.tree_depth DESC ",
+        node_columns(context, table, &self_reference)
+    ));
+    let rows = query.collect_rows()?;
+    Ok(to_tree_nodes(table, &rows))
+}
+
+fn get_tree_table<'c>(
+    context: &'c Context,
+    table_name: &TableName,
+) -> Result<(&'c Table, SelfReference), IntelError> {
+    let table = context.get_table(table_name).ok_or(IntelError::NotFound)?;
+    let self_reference = TableIntel(table)
+        .get_self_reference()
+        .ok_or(IntelError::NotFound)?;
+    Ok((table, self_reference))
+}
+
+/// the primary columns, the identifier display and whether the record
+/// has children, of the records aliased as `node`
+fn node_columns(
+    context: &Context,
+    table: &Table,
+    self_reference: &SelfReference,
+) -> String {
+    let mut columns: Vec<String> = table
+        .get_primary_column_names()
+        .iter()
+        .map(|pk| aliased_column("node", pk))
+        .collect();
+    let display = query_builder::display_expression(context, table, "node")
+        .unwrap_or_else(|| "''".to_string());
+    columns.push(format!("{} AS tree_display", display));
+    let child_condition: Vec<String> = self_reference
+        .pairs()
+        .map(|(parent_column, referred_column)| {
+            format!(
+                "{} = {}",
+                aliased_column("child", parent_column),
+                aliased_column("node", referred_column)
+            )
+        })
+        .collect();
+    columns.push(format!(
+        "EXISTS (SELECT 1 FROM {} AS child WHERE {}) AS has_children",
+        table.name.safe_complete_name(),
+        child_condition.join(" AND ")
+    ));
+    columns.join(", ")
+}
+
+/// the column prefixed with the alias of the table, the column name is quoted
+/// the same way as the qualified columns of the query builder
+fn aliased_column(alias: &str, column_name: &ColumnName) -> String {
+    format!(
+        "{}.{}",
+        alias,
+        query_builder::quoted_name(&column_name.name)
+    )
+}
+
+/// the records are identified by their primary columns and are displayed
+/// with their record id when they have no identifier display
+fn to_tree_nodes(table: &Table, rows: &Rows) -> Vec<TreeNode> {
+    let primary_columns = table.get_primary_column_names();
+    rows.iter()
+        .map(|dao| {
+            let pk_values: Vec<&Value> = primary_columns
+                .iter()
+                .filter_map(|pk| dao.get_value(&pk.name))
+                .collect();
+            let record_id = common::format_record_id(&pk_values);
+            let display = dao
+                .get_value("tree_display")
+                .map(data_export::value_to_text)
+                .filter(|display| !display.is_empty())
+                .unwrap_or_else(|| record_id.clone());
+            TreeNode {
+                record_id,
+                display,
+                has_children: dao
+                    .get_value("has_children")
+                    .map(is_true)
+                    .unwrap_or(false),
+            }
+        })
+        .collect()
+}
+
+/// the boolean of the EXISTS, which is a number in some databases
+fn is_true(value: &Value) -> bool {
+    match value {
+        Value::Bool(v) => *v,
+        Value::Tinyint(v) => *v != 0,
+        Value::Smallint(v) => *v != 0,
+        Value::Int(v) => *v != 0,
+        Value::Bigint(v) => *v != 0,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exists_as_bool_or_number() {
+        assert!(is_true(&Value::Bool(true)));
+        assert!(is_true(&Value::Int(1)));
+        assert!(is_true(&Value::Bigint(1)));
+        assert!(!is_true(&Value::Bool(false)));
+        assert!(!is_true(&Value::Int(0)));
+        assert!(!is_true(&Value::Nil));
+    }
+
+    #[test]
+    fn parent_columns_pair_with_the_referred_columns() {
+        let self_reference = SelfReference {
+            parent_columns: vec![ColumnName::from("parent_id")],
+            referred_columns: vec![ColumnName::from("category_id")],
+        };
+        let pairs: Vec<(&str, &str)> = self_reference
+            .pairs()
+            .map(|(parent, referred)| {
+                (parent.name.as_str(), referred.name.as_str())
+            })
+            .collect();
+        assert_eq!(pairs, vec![("parent_id", "category_id")]);
+    }
+}
@@ -0,0 +1,491 @@
+//! the entity relationship diagram of the tables, the relations are the ones
+//! classified by the table intel, which are also the tabs of the windows
+use crate::{
+    table_intel::{
+        self,
+        TableIntel,
+    },
+    Context,
+};
+use rustorm::{
+    Table,
+    TableName,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::collections::{
+    HashSet,
+    VecDeque,
+};
+
+/// how far the tables are included from the center table,
+/// when no depth is specified
+pub const DEFAULT_DEPTH: usize = 1;
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum RelationKind {
+    /// the referred table owns the table, the records are 1:1
+    Owned,
+    /// the linker table refers to the table
+    Linker,
+    /// the table refers to the referred table
+    HasOne,
+    /// the records of the referring table refers to the table
+    HasMany,
+    /// the table is linked to the other table through a linker table
+    Indirect,
+}
+
+impl RelationKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            RelationKind::Owned => "owned",
+            RelationKind::Linker => "linker",
+            RelationKind::HasOne => "has_one",
+            RelationKind::HasMany => "has_many",
+            RelationKind::Indirect => "indirect",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct ErdColumn {
+    pub name: String,
+    pub data_type: String,
+    pub is_primary: bool,
+    pub is_foreign: bool,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct ErdNode {
+    pub table_name: TableName,
+    pub columns: Vec<ErdColumn>,
+    pub is_linker: bool,
+    pub is_owned: bool,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct ErdEdge {
+    pub from: TableName,
+    pub to: TableName,
+    pub kind: RelationKind,
+    /// the linker table of the indirect relation
+    pub via: Option<TableName>,
+}
+
+impl ErdEdge {
+    /// the edge relates the same tables, regardless of the direction
+    fn is_same_relation(&self, other: &ErdEdge) -> bool {
+        self.via == other.via
+            && ((self.from == other.from && self.to == other.to)
+                || (self.from == other.to && self.to == other.from))
+    }
+
+    fn label(&self) -> String {
+        match self.via {
+            Some(ref via) => {
+                format!("{} via {}", self.kind.name(), via.complete_name())
+            }
+            None => self.kind.name().to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Erd {
+    pub nodes: Vec<ErdNode>,
+    pub edges: Vec<ErdEdge>,
+}
+
+/// the tables to be included in the diagram
+#[derive(Debug, Default, Clone)]
+pub struct ErdFilter {
+    /// only the tables in this schema
+    pub schema: Option<String>,
+    /// only the tables related to this table
+    pub center: Option<TableName>,
+    /// the number of relations away from the center table,
+    /// defaults to `DEFAULT_DEPTH`
+    pub depth: Option<usize>,
+}
+
+/// the diagram of the tables in the context that matches the filter
+pub fn get_erd(context: &Context, filter: &ErdFilter) -> Erd {
+    let mut tables: Vec<Table> = context.tables.values().cloned().collect();
+    tables.sort_by_key(|table| table.name.complete_name());
+    Erd::from_tables(&tables).filter(filter)
+}
+
+impl Erd {
+    pub fn from_tables(tables: &[Table]) -> Self {
+        let mut erd = Erd {
+            nodes: tables.iter().map(create_node).collect(),
+            edges: vec![],
+        };
+        for table in tables {
+            let table_intel = TableIntel(table);
+            if table_intel.is_linker_table() {
+                for linked in table_intel.get_linked_tablenames() {
+                    if let Some(linked) = table_intel::get_table(linked, tables)
+                    {
+                        erd.add_edge(table, linked, RelationKind::Linker, None);
+                    }
+                }
+                continue;
+            }
+            for owned in table_intel.get_one_one_tables(tables) {
+                erd.add_edge(table, owned, RelationKind::Owned, None);
+            }
+            for has_many in table_intel.get_has_many_tables(tables) {
+                erd.add_edge(table, has_many, RelationKind::HasMany, None);
+            }
+            for indirect in table_intel.get_indirect_tables(tables) {
+                erd.add_edge(
+                    table,
+                    indirect.indirect_table,
+                    RelationKind::Indirect,
+                    Some(indirect.linker),
+                );
+            }
+        }
+        // the has_one of the referring tables are mostly the other side of
+        // the has_many, owned and linker relations, which are not repeated
+        for table in tables {
+            let table_intel = TableIntel(table);
+            for has_one in table_intel.get_has_one_tables(tables) {
+                erd.add_edge(table, has_one, RelationKind::HasOne, None);
+            }
+        }
+        erd
+    }
+
+    /// add the edge, unless the tables are already related
+    fn add_edge(
+        &mut self,
+        from: &Table,
+        to: &Table,
+        kind: RelationKind,
+        via: Option<&Table>,
+    ) {
+        let edge = ErdEdge {
+            from: from.name.clone(),
+            to: to.name.clone(),
+            kind,
+            via: via.map(|via| via.name.clone()),
+        };
+        if !self.edges.iter().any(|e| e.is_same_relation(&edge)) {
+            self.edges.push(edge);
+        }
+    }
+
+    fn get_node(&self, table_name: &TableName) -> Option<&ErdNode> {
+        self.nodes
+            .iter()
+            .find(|node| node.table_name == *table_name)
+    }
+
+    /// find the node matching the complete name first,
+    /// then the name without the schema
+    fn find_node(&self, table_name: &TableName) -> Option<&ErdNode> {
+        self.get_node(table_name).or_else(|| {
+            self.nodes
+                .iter()
+                .find(|node| node.table_name.name == table_name.name)
+        })
+    }
+
+    /// the nodes in the schema, then only the nodes within the depth of the
+    /// center table and the edges between the remaining nodes
+    pub fn filter(self, filter: &ErdFilter) -> Self {
+        let mut erd = self;
+        if let Some(ref schema) = filter.schema {
+            erd = erd.retain_nodes(|node| {
+                node.table_name.schema.as_ref() == Some(schema)
+            });
+        }
+        if let Some(ref center) = filter.center {
+            let depth = filter.depth.unwrap_or(DEFAULT_DEPTH);
+            let within = match erd.find_node(center) {
+                Some(node) => erd.tables_within(&node.table_name, depth),
+                None => HashSet::new(),
+            };
+            erd = erd.retain_nodes(|node| within.contains(&node.table_name));
+        }
+        erd
+    }
+
+    fn retain_nodes<F>(self, f: F) -> Self
+    where
+        F: Fn(&ErdNode) -> bool,
+    {
+        let nodes: Vec<ErdNode> = self.nodes.into_iter().filter(f).collect();
+        let is_included = |table_name: &TableName| {
+            nodes.iter().any(|n| n.table_name == *table_name)
+        };
+        let edges = self
+            .edges
+            .into_iter()
+            .filter(|edge| is_included(&edge.from) && is_included(&edge.to))
+            .collect();
+        Erd { nodes, edges }
+    }
+
+    /// the tables that are at most depth relations away from the center,
+    /// the relations are followed in both directions
+    fn tables_within(
+        &self,
+        center: &TableName,
+        depth: usize,
+    ) -> HashSet<TableName> {
+        let mut within = HashSet::new();
+        within.insert(center.clone());
+        let mut queue = VecDeque::new();
+        queue.push_back((center.clone(), 0));
+        while let Some((table_name, distance)) = queue.pop_front() {
+            if distance >= depth {
+                continue;
+            }
+            for edge in &self.edges {
+                let related = if edge.from == table_name {
+                    &edge.to
+                } else if edge.to == table_name {
+                    &edge.from
+                } else {
+                    continue;
+                };
+                if within.insert(related.clone()) {
+                    queue.push_back((related.clone(), distance + 1));
+                }
+            }
+        }
+        within
+    }
+
+    /// the diagram as a graphviz dot, the tables are records which lists
+    /// the columns and the edges are labeled with the relation kind
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph erd {\n");
+        dot += "    rankdir=LR;\n";
+        dot += "    node [shape=record, fontsize=10];\n";
+        dot += "    edge [fontsize=9];\n";
+        for node in &self.nodes {
+            let columns: String = node
+                .columns
+                .iter()
+                .map(|column| {
+                    let mut line =
+                        format!("{} : {}", column.name, column.data_type);
+                    if column.is_primary {
+                        line += " (pk)";
+                    }
+                    if column.is_foreign {
+                        line += " (fk)";
+                    }
+                    format!("{}\\l", escape_record(&line))
+                })
+                .collect();
+            let style = if node.is_linker { ", style=dashed" } else { "" };
+            dot += &format!(
+                "    {} [label=\"{{{}|{}}}\"{}];\n",
+                quote(&node.table_name.complete_name()),
+                escape_record(&node.table_name.complete_name()),
+                columns,
+                style
+            );
+        }
+        for edge in &self.edges {
+            let style = match edge.kind {
+                RelationKind::Indirect => ", style=dashed",
+                RelationKind::Owned => ", arrowhead=diamond",
+                _ => "",
+            };
+            dot += &format!(
+                "    {} -> {} [label={}{}];\n",
+                quote(&edge.from.complete_name()),
+                quote(&edge.to.complete_name()),
+                quote(&edge.label()),
+                style
+            );
+        }
+        dot += "}\n";
+        dot
+    }
+}
+
+fn create_node(table: &Table) -> ErdNode {
+    let table_intel = TableIntel(table);
+    let primary = table.get_primary_column_names();
+    let foreign = table.get_foreign_column_names();
+    ErdNode {
+        table_name: table.name.clone(),
+        columns: table
+            .columns
+            .iter()
+            .map(|column| ErdColumn {
+                name: column.name.name.to_string(),
+                data_type: column.specification.sql_type.name().to_string(),
+                is_primary: primary.contains(&&column.name),
+                is_foreign: foreign.contains(&&column.name),
+            })
+            .collect(),
+        is_linker: table_intel.is_linker_table(),
+        is_owned: table_intel.is_owned_table(),
+    }
+}
+
+/// a quoted dot identifier
+fn quote(id: &str) -> String {
+    format!("\"{}\"", id.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// escape the characters which has meaning in the label of a record node
+fn escape_record(text: &str) -> String {
+    let mut escaped = String::new();
+    for ch in text.chars() {
+        match ch {
+            '{' | '}' | '|' | '<' | '>' | '"' | '\\' => {
+                escaped.push('\\');
+                escaped.push(ch);
+            }
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(name: &str) -> ErdNode {
+        ErdNode {
+            table_name: TableName::from(name),
+            columns: vec![ErdColumn {
+                name: "id".to_string(),
+                data_type: "int".to_string(),
+                is_primary: true,
+                is_foreign: false,
+            }],
+            is_linker: false,
+            is_owned: false,
+        }
+    }
+
+    fn edge(from: &str, to: &str, kind: RelationKind) -> ErdEdge {
+        ErdEdge {
+            from: TableName::from(from),
+            to: TableName::from(to),
+            kind,
+            via: None,
+        }
+    }
+
+    /// users -> product -> category, and review in another schema
+    fn sample() -> Erd {
+        Erd {
+            nodes: vec![
+                node("bazaar.users"),
+                node("bazaar.product"),
+                node("bazaar.category"),
+                node("review.review"),
+            ],
+            edges: vec![
+                edge("bazaar.users", "bazaar.product", RelationKind::HasMany),
+                edge(
+                    "bazaar.category",
+                    "bazaar.product",
+                    RelationKind::HasMany,
+                ),
+                edge("bazaar.users", "review.review", RelationKind::HasMany),
+            ],
+        }
+    }
+
+    fn node_names(erd: &Erd) -> Vec<String> {
+        erd.nodes
+            .iter()
+            .map(|node| node.table_name.complete_name())
+            .collect()
+    }
+
+    #[test]
+    fn filter_by_schema() {
+        let filter = ErdFilter {
+            schema: Some("bazaar".to_string()),
+            ..Default::default()
+        };
+        let erd = sample().filter(&filter);
+        assert_eq!(
+            node_names(&erd),
+            vec!["bazaar.users", "bazaar.product", "bazaar.category"]
+        );
+        assert_eq!(erd.edges.len(), 2);
+    }
+
+    #[test]
+    fn filter_by_center_and_depth() {
+        let center = ErdFilter {
+            center: Some(TableName::from("users")),
+            ..Default::default()
+        };
+        let erd = sample().filter(&center);
+        assert_eq!(
+            node_names(&erd),
+            vec!["bazaar.users", "bazaar.product", "review.review"]
+        );
+        let deeper = ErdFilter {
+            depth: Some(2),
+            ..center
+        };
+        assert_eq!(sample().filter(&deeper).nodes.len(), 4);
+        let unknown = ErdFilter {
+            center: Some(TableName::from("bazaar.missing")),
+            ..Default::default()
+        };
+        assert_eq!(sample().filter(&unknown), Erd::default());
+    }
+
+    #[test]
+    fn relations_are_not_repeated() {
+        let mut erd = sample();
+        let users =
+            edge("bazaar.product", "bazaar.users", RelationKind::HasOne);
+        assert!(erd.edges.iter().any(|e| e.is_same_relation(&users)));
+        let indirect = ErdEdge {
+            via: Some(TableName::from("bazaar.user_review")),
+            ..users
+        };
+        assert!(!erd.edges.iter().any(|e| e.is_same_relation(&indirect)));
+        erd.edges.push(indirect);
+        assert_eq!(erd.edges.len(), 4);
+    }
+
+    #[test]
+    fn dot_output() {
+        let mut erd = sample();
+        erd.edges.push(ErdEdge {
+            via: Some(TableName::from("bazaar.user_review")),
+            ..edge("bazaar.users", "review.review", RelationKind::Indirect)
+        });
+        let dot = erd.to_dot();
+        assert!(dot.starts_with("digraph erd {\n"));
+        assert!(dot.contains(
+            "    \"bazaar.users\" [label=\"{bazaar.users|id : int (pk)\\l}\"];\n"
+        ));
+        assert!(dot.contains(
+            "    \"bazaar.users\" -> \"bazaar.product\" [label=\"has_many\"];\n"
+        ));
+        assert!(dot.contains(
+            "[label=\"indirect via bazaar.user_review\", style=dashed];\n"
+        ));
+        assert!(dot.ends_with("}\n"));
+    }
+
+    #[test]
+    fn record_labels_are_escaped() {
+        assert_eq!(escape_record("a|b {c} <d>"), "a\\|b \\{c\\} \\<d\\>");
+        assert_eq!(quote("say \"hi\""), "\"say \\\"hi\\\"\"");
+    }
+}
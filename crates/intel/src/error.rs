@@ -1,3 +1,4 @@
+use crate::sql_guard::SqlViolation;
 use rustorm::DbError;
 use std::{
     error::Error,
@@ -12,6 +13,22 @@ pub enum IntelError {
     /// no matching window
     NoMatching,
     NotFound,
+    /// the ad-hoc sql query is rejected by the guard
+    SqlViolation(SqlViolation),
+    /// the column used in the filter or sort is not in the table
+    UnknownColumn(String),
+    /// the output of the explain is not a query plan
+    PlanError(String),
+    /// unable to write the exported rows
+    ExportError(String),
+    /// the csv or its mapping to the table columns is invalid
+    ImportError(String),
+    /// the aggregate can not be applied to the type of the column
+    AggregateError(String),
+    /// the value in the column is not an image
+    ImageError(String),
+    /// the column is not binary or the uploaded content is too large
+    BinaryError(String),
 }
 
 impl fmt::Display for IntelError {
@@ -27,3 +44,9 @@ impl From<DbError> for IntelError {
         IntelError::DbError(e)
     }
 }
+
+impl From<SqlViolation> for IntelError {
+    fn from(e: SqlViolation) -> Self {
+        IntelError::SqlViolation(e)
+    }
+}
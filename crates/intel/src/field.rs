@@ -21,6 +21,9 @@ pub struct Field {
     pub is_primary: bool,
     /// column name
     pub column_detail: ColumnDetail,
+    /// not a column of the table, such as a computed column
+    /// or a column of a joined table, its data type is not known
+    pub is_computed: bool,
 }
 
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
@@ -123,6 +126,27 @@ impl Field {
             info: None,
             is_primary: in_primary,
             column_detail,
+            is_computed: false,
+        }
+    }
+
+    /// a field for a column in the query result that is not
+    /// from the table, such as a computed column or a column of a joined table
+    pub fn from_result_column(column: &str) -> Self {
+        Field {
+            name: column.to_string(),
+            description: None,
+            info: None,
+            is_primary: false,
+            column_detail: ColumnDetail::Simple(
+                ColumnName {
+                    name: column.to_string(),
+                    table: None,
+                    alias: None,
+                },
+                SqlType::Text,
+            ),
+            is_computed: true,
         }
     }
 
@@ -167,6 +191,7 @@ impl Field {
             info: referred_table.comment.to_owned(),
             is_primary: in_primary,
             column_detail,
+            is_computed: false,
         }
     }
 
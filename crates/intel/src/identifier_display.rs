@@ -0,0 +1,373 @@
+//! Score the columns of a table on how well they identify its records
+//! to a person, such as the name of a product or the title of a film.
+//! The best scoring column is used as the identifier display of the table
+//! unless the display of the table is set in the config
+use crate::{
+    config::Config,
+    data_container::IdentifierDisplay,
+};
+use rustorm::{
+    column::Capacity,
+    table::TableKey,
+    types::SqlType,
+    Column,
+    ColumnName,
+    EntityManager,
+    Table,
+    TableName,
+};
+use rustorm_dao::{
+    Dao,
+    FromDao,
+};
+use std::collections::HashMap;
+
+/// the minimum score of a column to be used as the identifier display
+const MIN_SCORE: i32 = 40;
+
+/// the score of a column named like the name of the record, it wins
+/// over the first and last name of a person
+const NAME_SCORE: i32 = 100;
+
+/// varchar of up to this length are short enough to be displayed
+const SHORT_LENGTH: i32 = 100;
+
+/// the names of the columns that usually identify a record
+const IDENTIFIER_NAMES: [&str; 2] = ["name", "title"];
+const LIKELY_NAMES: [&str; 5] = ["username", "email", "label", "code", "slug"];
+
+/// the distinct ratio of the columns, retrieved from the statistics
+/// of the database. A ratio of 1.0 means all the values are different
+#[derive(Debug, Default, Clone)]
+pub struct ColumnStatistics {
+    distinct_ratios: HashMap<(String, String), f64>,
+}
+
+/// what is known of the tables besides their definition
+#[derive(Debug, Clone, Copy)]
+pub struct DisplayHints<'a> {
+    pub config: &'a Config,
+    pub statistics: &'a ColumnStatistics,
+}
+
+/// a row of the statistics query
+struct DistinctRatio {
+    table_name: String,
+    column_name: String,
+    distinct_ratio: f64,
+}
+
+impl FromDao for DistinctRatio {
+    fn from_dao(dao: &Dao) -> Self {
+        DistinctRatio {
+            table_name: dao.get("table_name").unwrap_or_default(),
+            column_name: dao.get("column_name").unwrap_or_default(),
+            distinct_ratio: dao.get("distinct_ratio").unwrap_or(0.0),
+        }
+    }
+}
+
+impl ColumnStatistics {
+    /// the statistics collected by postgresql, a negative n_distinct is
+    /// already a ratio of the rows while a positive one is a count.
+    /// Other databases and tables that are not analyzed have no statistics
+    pub fn retrieve(em: &mut EntityManager) -> Self {
+        let sql = "SELECT s.schemaname || '.' || s.tablename AS table_name,
+                s.attname::text AS column_name,
+                CASE WHEN s.n_distinct < 0 THEN -s.n_distinct::float8
+                    WHEN c.reltuples > 0
+                    THEN LEAST(s.n_distinct::float8 / c.reltuples::float8, 1.0)
+                    ELSE 0.0
+                END AS distinct_ratio
+            FROM pg_stats s
+            JOIN pg_namespace n ON n.nspname = s.schemaname
+            JOIN pg_class c ON c.relnamespace = n.oid
+                AND c.relname = s.tablename
+            WHERE s.schemaname NOT IN ('pg_catalog', 'information_schema')";
+        let ratios: Result<Vec<DistinctRatio>, _> =
+            em.execute_sql_with_return(sql, &[]);
+        match ratios {
+            Ok(ratios) => {
+                ColumnStatistics {
+                    distinct_ratios: ratios
+                        .into_iter()
+                        .map(|ratio| {
+                            (
+                                (ratio.table_name, ratio.column_name),
+                                ratio.distinct_ratio,
+                            )
+                        })
+                        .collect(),
+                }
+            }
+            Err(e) => {
+                info!("No column statistics: {}", e);
+                ColumnStatistics::default()
+            }
+        }
+    }
+
+    pub fn distinct_ratio(
+        &self,
+        table_name: &TableName,
+        column_name: &ColumnName,
+    ) -> Option<f64> {
+        self.distinct_ratios
+            .get(&(table_name.complete_name(), column_name.name.to_string()))
+            .cloned()
+    }
+}
+
+/// what is considered when scoring a column
+#[derive(Debug, Default)]
+struct Candidate<'a> {
+    table_name: &'a str,
+    column_name: &'a str,
+    is_text: bool,
+    /// primary and foreign columns are not displayed
+    is_key: bool,
+    is_unique: bool,
+    is_not_null: bool,
+    max_length: Option<i32>,
+    distinct_ratio: Option<f64>,
+}
+
+impl<'a> Candidate<'a> {
+    fn from_column(
+        table: &'a Table,
+        column: &'a Column,
+        statistics: &ColumnStatistics,
+    ) -> Self {
+        let sql_type = &column.specification.sql_type;
+        let is_text = match sql_type {
+            SqlType::Text | SqlType::Varchar | SqlType::Char => true,
+            _ => false,
+        };
+        let is_key = table.get_primary_column_names().contains(&&column.name)
+            || table.get_foreign_column_names().contains(&&column.name);
+        let is_unique = table.table_key.iter().any(|key| {
+            match key {
+                TableKey::UniqueKey(key) => key.columns == [column.name.clone()],
+                _ => false,
+            }
+        });
+        let max_length = match column.specification.capacity {
+            Some(Capacity::Limit(limit)) => Some(limit),
+            _ => None,
+        };
+        Candidate {
+            table_name: &table.name.name,
+            column_name: &column.name.name,
+            is_text,
+            is_key,
+            is_unique,
+            is_not_null: column.is_not_null(),
+            max_length,
+            distinct_ratio: statistics
+                .distinct_ratio(&table.name, &column.name),
+        }
+    }
+
+    /// None when the column can not be used as the identifier display
+    fn score(&self) -> Option<i32> {
+        if !self.is_text || self.is_key {
+            return None;
+        }
+        let table_name = self.table_name;
+        let column_name = self.column_name;
+        let mut score = 0;
+        if IDENTIFIER_NAMES.contains(&column_name)
+            || column_name == table_name
+            || column_name == format!("{}_name", table_name)
+        {
+            score += NAME_SCORE;
+        } else if LIKELY_NAMES.contains(&column_name) {
+            score += 60;
+        } else if column_name.ends_with("_name") {
+            score += 40;
+        }
+        if self.is_unique {
+            score += 50;
+        }
+        if self.is_not_null {
+            score += 20;
+        }
+        match self.distinct_ratio {
+            Some(ratio) if ratio >= 0.9 => score += 30,
+            Some(ratio) if ratio < 0.1 => score -= 30,
+            _ => (),
+        }
+        match self.max_length {
+            Some(length) if length <= SHORT_LENGTH => score += 20,
+            _ => (),
+        }
+        Some(score)
+    }
+}
+
+/// the display of the table in the config, the columns
+/// not in the table are ignored
+fn configured_display(
+    table: &Table,
+    config: &Config,
+    pk: &[ColumnName],
+) -> Option<IdentifierDisplay> {
+    let display = config.display_for(&table.name)?;
+    let columns: Vec<ColumnName> = display
+        .columns
+        .iter()
+        .filter_map(|name| {
+            table
+                .columns
+                .iter()
+                .find(|column| column.name.name == *name)
+                .map(|column| column.name.clone())
+        })
+        .collect();
+    if columns.is_empty() {
+        warn!(
+            "None of the display columns of {} is found",
+            table.name.name
+        );
+        return None;
+    }
+    Some(IdentifierDisplay {
+        columns,
+        separator: display.separator.clone(),
+        pk: pk.to_vec(),
+    })
+}
+
+/// the last name and first name of a person
+fn person_display(
+    table: &Table,
+    pk: &[ColumnName],
+) -> Option<IdentifierDisplay> {
+    let find = |names: &[&str]| {
+        table
+            .columns
+            .iter()
+            .find(|column| names.contains(&column.name.name.as_str()))
+    };
+    let firstname = find(&["first_name", "firstname"])?;
+    let lastname = find(&["last_name", "lastname"])?;
+    Some(IdentifierDisplay {
+        columns: vec![lastname.name.clone(), firstname.name.clone()],
+        separator: Some(", ".into()),
+        pk: pk.to_vec(),
+    })
+}
+
+/// derive the identifier display of the table, the display in the config
+/// wins over the best scoring column of the table
+pub fn derive_display(
+    table: &Table,
+    hints: &DisplayHints,
+) -> IdentifierDisplay {
+    let pk: Vec<ColumnName> = table
+        .get_primary_column_names()
+        .into_iter()
+        .cloned()
+        .collect();
+    if let Some(display) = configured_display(table, hints.config, &pk) {
+        return display;
+    }
+    let best = table
+        .columns
+        .iter()
+        .filter_map(|column| {
+            Candidate::from_column(table, column, hints.statistics)
+                .score()
+                .map(|score| (score, column))
+        })
+        .filter(|(score, _column)| *score >= MIN_SCORE)
+        // the first of the columns with the same score
+        .fold(
+            None,
+            |best: Option<(i32, &Column)>, (score, column)| match best {
+                Some((best_score, _)) if best_score >= score => best,
+                _ => Some((score, column)),
+            },
+        );
+    let person = person_display(table, &pk);
+    match (best, person) {
+        (Some((score, _column)), Some(person)) if score < NAME_SCORE => {
+            return person;
+        }
+        (None, Some(person)) => return person,
+        _ => (),
+    }
+    if let Some((_score, column)) = best {
+        return IdentifierDisplay {
+            columns: vec![column.name.clone()],
+            separator: None,
+            pk,
+        };
+    }
+    // if there is only 1 non primary column use it as the identifier column
+    let non_pk_columns = table.get_non_primary_columns();
+    let columns = if non_pk_columns.len() == 1 {
+        vec![non_pk_columns[0].name.clone()]
+    } else {
+        vec![]
+    };
+    IdentifierDisplay {
+        columns,
+        separator: None,
+        pk,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text_column<'a>(
+        table_name: &'a str,
+        column_name: &'a str,
+    ) -> Candidate<'a> {
+        Candidate {
+            table_name,
+            column_name,
+            is_text: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn names_and_titles_score_high() {
+        assert_eq!(text_column("film", "title").score(), Some(100));
+        assert_eq!(text_column("category", "category").score(), Some(100));
+        assert_eq!(text_column("country", "country_name").score(), Some(100));
+        assert_eq!(text_column("users", "email").score(), Some(60));
+        assert_eq!(text_column("film", "description").score(), Some(0));
+    }
+
+    #[test]
+    fn keys_and_non_text_are_not_displayed() {
+        let mut candidate = text_column("film", "title");
+        candidate.is_key = true;
+        assert_eq!(candidate.score(), None);
+        let mut candidate = text_column("film", "length");
+        candidate.is_text = false;
+        assert_eq!(candidate.score(), None);
+    }
+
+    #[test]
+    fn unique_short_and_distinct_columns_qualify() {
+        let mut candidate = text_column("product", "sku");
+        assert!(candidate.score().unwrap() < MIN_SCORE);
+        candidate.is_unique = true;
+        candidate.is_not_null = true;
+        candidate.max_length = Some(20);
+        candidate.distinct_ratio = Some(1.0);
+        assert_eq!(candidate.score(), Some(120));
+    }
+
+    #[test]
+    fn repeating_values_score_low() {
+        let mut candidate = text_column("film", "rating_name");
+        candidate.distinct_ratio = Some(0.01);
+        assert!(candidate.score().unwrap() < MIN_SCORE);
+    }
+}
@@ -6,18 +6,40 @@ extern crate log;
 
 pub mod cache;
 mod common;
+pub mod config;
 mod context;
+pub mod data_aggregate;
 pub mod data_container;
+pub mod data_export;
+pub mod data_group;
+pub mod data_image;
+pub mod data_import;
 pub mod data_modify;
 pub mod data_read;
+pub mod data_tree;
+pub mod erd;
 pub mod error;
 pub mod field;
+pub mod identifier_display;
 mod query_builder;
+pub mod query_history;
 mod query_parser;
+pub mod query_plan;
+pub mod relation_inference;
+pub mod sql_guard;
+pub mod sql_params;
 pub mod tab;
 pub mod table_intel;
+#[cfg(test)]
+mod test_db;
+pub mod view_intel;
 pub mod window;
 
+pub use common::{
+    format_record_id,
+    parse_value,
+    record_url,
+};
 pub use context::Context;
 pub use data_container::{
     DataRow,
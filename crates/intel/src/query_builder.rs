@@ -1,9 +1,24 @@
 use crate::{
     common,
+    data_aggregate::ColumnAggregate,
+    data_container::{
+        Direction,
+        Filter,
+        Operator,
+        Sort,
+    },
+    error::IntelError,
+    tab::{
+        IndirectTab,
+        Tab,
+    },
+    table_intel::TableIntel,
     Context,
 };
 use rustorm::{
     types::SqlType,
+    Column,
+    ColumnName,
     Dao,
     DaoManager,
     DbError,
@@ -22,6 +37,8 @@ pub struct Query<'c> {
     joined_tables: Vec<TableName>,
     pub params: Vec<Value>,
     column_datatypes: BTreeMap<String, SqlType>,
+    /// a WHERE clause is already added, succeeding conditions are added with AND
+    has_where: bool,
 }
 
 impl<'c> Query<'c> {
@@ -33,6 +50,7 @@ impl<'c> Query<'c> {
             joined_tables: vec![],
             params: vec![],
             column_datatypes: BTreeMap::new(),
+            has_where: false,
             dm,
         }
     }
@@ -58,11 +76,7 @@ impl<'c> Query<'c> {
             if i > 0 {
                 self.append(", ")
             }
-            self.append(&format!(
-                "{}.{}",
-                &table.safe_name(),
-                column.name.name
-            ));
+            self.append(&qualified_column(table, column));
             if let Some(cast) = column.cast_as() {
                 self.append(&format!("::{} ", cast.name()));
             }
@@ -70,6 +84,166 @@ impl<'c> Query<'c> {
         self.add_table_datatypes(table);
     }
 
+    /// enumerate the extra columns of the linker table after the columns
+    /// of the indirect table, aliased so they don't clash with each other
+    pub fn enumerate_linker_columns(
+        &mut self,
+        linker: &Table,
+        main_table: &TableName,
+        indirect_table: &TableName,
+    ) {
+        for column in TableIntel(linker)
+            .get_linker_extra_columns(main_table, indirect_table)
+        {
+            let alias =
+                IndirectTab::linker_column_alias(&linker.name, &column.name);
+            self.append(&format!(", {}", qualified_column(linker, column)));
+            if let Some(cast) = column.cast_as() {
+                self.append(&format!("::{} ", cast.name()));
+            }
+            self.append(&format!(" AS {}", quoted_name(&alias)));
+            self.column_datatypes.insert(alias, column.get_sql_type());
+        }
+    }
+
+    /// enumerate the columns of the table, the foreign key columns are replaced
+    /// with the identifier display of the record it refers to
+    pub fn enumerate_columns_with_display(&mut self, table: &Table) {
+        for (i, column) in table.columns.iter().enumerate() {
+            if i > 0 {
+                self.append(", ")
+            }
+            match self.display_subquery(table, column) {
+                Some(subquery) => {
+                    self.append(&format!(
+                        "{} AS {}",
+                        subquery,
+                        quoted_name(&column.name.name)
+                    ))
+                }
+                None => {
+                    self.append(&qualified_column(table, column));
+                    if let Some(cast) = column.cast_as() {
+                        self.append(&format!("::{} ", cast.name()));
+                    }
+                    self.column_datatypes.insert(
+                        column.name.name.clone(),
+                        column.get_sql_type(),
+                    );
+                }
+            }
+        }
+    }
+
+    /// a subquery of the display columns of the record referred by the foreign column,
+    /// only foreign keys with a single column are replaced
+    fn display_subquery(
+        &self,
+        table: &Table,
+        column: &Column,
+    ) -> Option<String> {
+        let foreign_key = table
+            .get_foreign_keys()
+            .into_iter()
+            .find(|fk| fk.columns.len() == 1 && fk.columns[0] == column.name)?;
+        let referred_table =
+            self.context.get_table(&foreign_key.foreign_table)?;
+        Some(format!(
+            "(SELECT {} FROM {} AS display WHERE display.{} = {})",
+            display_expression(self.context, referred_table, "display")?,
+            referred_table.name.safe_complete_name(),
+            quoted_name(&foreign_key.referred_columns[0].name),
+            qualified_column(table, column)
+        ))
+    }
+
+    /// the aggregates of the columns, aliased by their position such as
+    /// `aggregate_0`
+    pub fn enumerate_aggregates(
+        &mut self,
+        table: &Table,
+        aggregates: &[ColumnAggregate],
+    ) -> Result<(), IntelError> {
+        for (i, column_aggregate) in aggregates.iter().enumerate() {
+            let column = find_column(table, &column_aggregate.column_name)?;
+            let aggregate = column_aggregate.aggregate;
+            if !aggregate.applies_to(&column.get_sql_type()) {
+                return Err(IntelError::AggregateError(format!(
+                    "{} of {} which is not a number",
+                    aggregate.name(),
+                    column.name.name
+                )));
+            }
+            if i > 0 {
+                self.append(", ");
+            }
+            self.append(&format!(
+                "{} AS aggregate_{}",
+                aggregate.to_sql(&qualified_column(table, column)),
+                i
+            ));
+        }
+        Ok(())
+    }
+
+    /// the columns the rows are grouped by, aliased by their position such as
+    /// `group_0`. The foreign keys are replaced with the identifier display
+    /// of the record it refers to
+    pub fn enumerate_group_columns(
+        &mut self,
+        table: &Table,
+        group_by: &[ColumnName],
+    ) -> Result<(), IntelError> {
+        for (i, column_name) in group_by.iter().enumerate() {
+            let column = find_column(table, column_name)?;
+            if i > 0 {
+                self.append(", ");
+            }
+            let alias = format!("group_{}", i);
+            match self.display_subquery(table, column) {
+                Some(subquery) => {
+                    self.append(&format!("{} AS {}", subquery, alias))
+                }
+                None => {
+                    self.append(&format!(
+                        "{} AS {}",
+                        qualified_column(table, column),
+                        alias
+                    ));
+                    self.column_datatypes.insert(alias, column.get_sql_type());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// group the rows by the columns, the groups are ordered by
+    /// the group columns as they are enumerated
+    pub fn set_group_by(
+        &mut self,
+        table: &Table,
+        group_by: &[ColumnName],
+    ) -> Result<(), IntelError> {
+        let columns = group_by
+            .iter()
+            .map(|column_name| {
+                find_column(table, column_name)
+                    .map(|column| qualified_column(table, column))
+            })
+            .collect::<Result<Vec<String>, IntelError>>()?;
+        if columns.is_empty() {
+            return Ok(());
+        }
+        let order: Vec<String> =
+            (0..columns.len()).map(|i| format!("group_{}", i)).collect();
+        self.append(&format!(
+            "\nGROUP BY {} \nORDER BY {} ",
+            columns.join(", "),
+            order.join(", ")
+        ));
+        Ok(())
+    }
+
     /// add the data types of table columns that are not part of the main tables
     /// ie. the data type of the look up tables
     pub fn add_table_datatypes(&mut self, table: &Table) {
@@ -128,36 +302,95 @@ impl<'c> Query<'c> {
         }
     }
 
+    /// WHERE for the first condition, AND for the succeeding ones
+    fn append_condition_keyword(&mut self) {
+        if self.has_where {
+            self.append("AND ");
+        } else {
+            self.append("WHERE ");
+            self.has_where = true;
+        }
+    }
+
     pub fn add_dao_filter(&mut self, table_name: &TableName, dao: &Dao) {
-        self.append("WHERE ");
         for (column, value) in dao.0.iter() {
-            self.append(&format!("{}.{} = ", table_name.name, column));
+            self.append_condition_keyword();
+            self.append(&format!(
+                "{}.{} = ",
+                table_name.name,
+                quoted_name(column)
+            ));
             self.add_param(value);
         }
     }
 
-    /*
-    pub fn set_sort(&mut self, sort: Sort) {
-        if !sort.orders.is_empty() {
-            self.append("ORDER BY ");
-            for (i, order) in sort.orders.iter().enumerate() {
-                if i > 0 {
-                    self.append(", ");
+    /// add the conditions of the filter, the columns must be in the table
+    pub fn add_filter(
+        &mut self,
+        table: &Table,
+        filter: &Filter,
+    ) -> Result<(), IntelError> {
+        for condition in filter.conditions.iter() {
+            let column = find_column(table, &condition.column_name)?;
+            self.append_condition_keyword();
+            match condition.operator {
+                Operator::Like | Operator::ILike => {
+                    self.append(&format!(
+                        "CAST({} AS TEXT) {} ",
+                        qualified_column(table, column),
+                        condition.operator.to_sql()
+                    ));
+                    self.add_param(&condition.value);
                 }
-                self.append(&format!(
-                    "{} ",
-                    order.column_name.safe_complete_name()
-                ));
-                if order.direction == Direction::Asc {
-                    self.append("ASC ");
-                }
-                if order.direction == Direction::Desc {
-                    self.append("DESC ");
+                _ => {
+                    self.append(&format!(
+                        "{} {} ",
+                        qualified_column(table, column),
+                        condition.operator.to_sql()
+                    ));
+                    if !condition.operator.is_unary() {
+                        let value = match &condition.value {
+                            Value::Text(text) => {
+                                common::parse_value(
+                                    text,
+                                    &column.get_sql_type(),
+                                )?
+                            }
+                            value => value.clone(),
+                        };
+                        self.add_param(&value);
+                    }
                 }
             }
         }
+        Ok(())
+    }
+
+    /// order the rows, the columns must be in the table
+    pub fn set_sort(
+        &mut self,
+        table: &Table,
+        sort: &Sort,
+    ) -> Result<(), IntelError> {
+        for (i, order) in sort.orders.iter().enumerate() {
+            let column = find_column(table, &order.column_name)?;
+            if i == 0 {
+                self.append("ORDER BY ");
+            } else {
+                self.append(", ");
+            }
+            let direction = match order.direction {
+                Direction::Asc => "ASC",
+                Direction::Desc => "DESC",
+            };
+            self.append(&format!(
+                "{} {} ",
+                qualified_column(table, column),
+                direction
+            ));
+        }
+        Ok(())
     }
-    */
 
     pub fn set_page(&mut self, page: usize, page_size: usize) {
         self.set_limit(page_size);
@@ -171,6 +404,12 @@ impl<'c> Query<'c> {
         self.append(&format!("\nLIMIT {} ", page_size));
     }
 
+    /// the built sql, its params and the data types of the columns,
+    /// used when the rows are retrieved in other ways such as with a cursor
+    pub fn into_parts(self) -> (String, Vec<Value>, BTreeMap<String, SqlType>) {
+        (self.sql, self.params, self.column_datatypes)
+    }
+
     pub fn collect_rows(&mut self) -> Result<Rows, DbError> {
         println!("SQL: {}", self.sql);
         println!("params: {:?}", self.params);
@@ -180,6 +419,7 @@ impl<'c> Query<'c> {
         result.map(|rows| common::cast_rows(rows, &self.column_datatypes))
     }
 
+    /// the record of the built query, None when there is no matching row
     pub fn collect_maybe_record(&mut self) -> Result<Option<Dao>, DbError> {
         println!("SQL: {}", self.sql);
         println!("params: {:?}", self.params);
@@ -200,3 +440,122 @@ impl<'c> Query<'c> {
         Ok(common::cast_record(record, &self.column_datatypes))
     }
 }
+
+pub(crate) fn find_column<'t>(
+    table: &'t Table,
+    column_name: &ColumnName,
+) -> Result<&'t Column, IntelError> {
+    table
+        .columns
+        .iter()
+        .find(|column| column.name.name == column_name.name)
+        .ok_or_else(|| IntelError::UnknownColumn(column_name.name.to_string()))
+}
+
+/// the column prefixed with the table, the column name is quoted
+/// so names with uppercase letters or reserved words are kept as is
+fn qualified_column(table: &Table, column: &Column) -> String {
+    format!("{}.{}", table.safe_name(), quoted_name(&column.name.name))
+}
+
+/// the name in double quotes, with the double quotes in it escaped
+pub(crate) fn quoted_name(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// the text expression of the identifier display columns of the table,
+/// the columns are prefixed with the alias of the table.
+/// The display of the window of the table is used when there is one,
+/// otherwise it is derived with the same statistics of the columns
+pub(crate) fn display_expression(
+    context: &Context,
+    table: &Table,
+    alias: &str,
+) -> Option<String> {
+    let display = match context.get_window(&table.name) {
+        Some(window) => window.main_tab.display.clone()?,
+        None => {
+            Tab::derive_dropdowninfo(table, &context.statistics)?.display
+        }
+    };
+    if display.columns.is_empty() {
+        return None;
+    }
+    let separator = display
+        .separator
+        .as_ref()
+        .map(String::as_str)
+        .unwrap_or(" ")
+        .replace('\'', "''");
+    let display_columns: Vec<String> = display
+        .columns
+        .iter()
+        .map(|display_column| {
+            format!(
+                "COALESCE(CAST({}.{} AS TEXT), '')",
+                alias,
+                quoted_name(&display_column.name)
+            )
+        })
+        .collect();
+    Some(display_columns.join(&format!(" || '{}' || ", separator)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_db;
+
+    /// the extra column of the linker and its alias keep their case
+    #[test]
+    fn linker_columns_are_quoted() {
+        let context = test_db::context(vec![
+            test_db::table(
+                "film",
+                &[("film_id", SqlType::Int), ("title", SqlType::Text)],
+                vec![test_db::primary_key(&["film_id"])],
+            ),
+            test_db::table(
+                "category",
+                &[("category_id", SqlType::Int), ("name", SqlType::Text)],
+                vec![test_db::primary_key(&["category_id"])],
+            ),
+            test_db::table(
+                "film_category",
+                &[
+                    ("film_id", SqlType::Int),
+                    ("category_id", SqlType::Int),
+                    ("SortOrder", SqlType::Int),
+                ],
+                vec![
+                    test_db::primary_key(&["film_id", "category_id"]),
+                    test_db::foreign_key(&["film_id"], "film", &["film_id"]),
+                    test_db::foreign_key(
+                        &["category_id"],
+                        "category",
+                        &["category_id"],
+                    ),
+                ],
+            ),
+        ]);
+        let mut dm = test_db::sakila_dm();
+        let linker = context
+            .get_table(&TableName::from("film_category"))
+            .unwrap();
+        let mut query = Query::new(&context, &mut dm);
+        query.enumerate_linker_columns(
+            linker,
+            &TableName::from("film"),
+            &TableName::from("category"),
+        );
+        let (sql, _, column_datatypes) = query.into_parts();
+        assert_eq!(
+            sql,
+            r#", film_category."SortOrder" AS "film_category_SortOrder""#
+        );
+        assert_eq!(
+            column_datatypes.get("film_category_SortOrder"),
+            Some(&SqlType::Int)
+        );
+    }
+}
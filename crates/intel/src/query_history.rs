@@ -0,0 +1,47 @@
+//! The record of ad-hoc sql queries executed by the users
+//! and the queries they have saved for later use
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+/// An entry of an executed ad-hoc sql query
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct QueryHistory {
+    pub id: Option<i64>,
+    /// the user who executed the query, None when login is not required
+    pub username: Option<String>,
+    /// the database the query was executed on, without the password
+    pub db_url: String,
+    pub sql: String,
+    /// in milliseconds
+    pub duration: u64,
+    /// the number of rows returned or affected
+    pub row_count: Option<usize>,
+    /// the error message when the query failed
+    pub error: Option<String>,
+    /// when the query was executed, as recorded by the store
+    pub created: Option<String>,
+}
+
+impl QueryHistory {
+    pub fn is_success(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// A named query saved by the user
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct SavedQuery {
+    /// None when the query is not yet saved
+    pub id: Option<i64>,
+    /// the owner of this query, set by the server
+    pub username: Option<String>,
+    pub db_url: Option<String>,
+    pub name: String,
+    pub sql: String,
+    pub tags: Vec<String>,
+    /// shared queries are visible to the other users
+    pub is_shared: bool,
+    pub created: Option<String>,
+}
@@ -1,3 +1,9 @@
+use crate::sql_guard::StatementKind;
+use rustorm::{
+    ColumnName,
+    Table,
+    TableName,
+};
 use sqlparser::sqlast::*;
 
 pub(in crate) fn extract_table_name(
@@ -20,3 +26,144 @@ pub(in crate) fn extract_table_name(
         None
     }
 }
+
+/// classify the statement whether it only reads or it modifies the database
+pub(in crate) fn classify_statement(
+    sql_statement: &SQLStatement,
+) -> StatementKind {
+    match sql_statement {
+        SQLStatement::SQLQuery(_) => StatementKind::Query,
+        SQLStatement::SQLInsert { .. } => StatementKind::Insert,
+        SQLStatement::SQLUpdate { .. } => StatementKind::Update,
+        SQLStatement::SQLDelete { .. } => StatementKind::Delete,
+        SQLStatement::SQLCopy { .. } => StatementKind::Copy,
+        _ => StatementKind::Ddl,
+    }
+}
+
+/// the columns of the query which are selected as is from the table,
+/// when the table is the only table in the FROM and there are no joins,
+/// common table expressions or grouping.
+/// A column which is aliased or computed is not traced back to the table,
+/// even when it has the name of a column of the table
+pub(in crate) fn extract_table_columns(
+    sql_statement: &SQLStatement,
+    table: &Table,
+) -> Option<Vec<String>> {
+    let query = match sql_statement {
+        SQLStatement::SQLQuery(query) if query.ctes.is_empty() => query,
+        _ => return None,
+    };
+    let select = match query.body {
+        SQLSetExpr::Select(ref select) => select,
+        _ => return None,
+    };
+    if !select.joins.is_empty()
+        || !select.group_by.is_empty()
+        || select.having.is_some()
+    {
+        return None;
+    }
+    let qualifier = match select.relation {
+        Some(TableFactor::Table {
+            ref name,
+            ref alias,
+            ..
+        }) => {
+            let name = name.to_string();
+            let unquoted: Vec<&str> = name.split('.').map(unquote).collect();
+            let table_name = TableName::from(unquoted.join(".").as_str());
+            let is_same_table = table_name.name == table.name.name
+                && (table_name.schema.is_none()
+                    || table_name.schema == table.name.schema);
+            if !is_same_table {
+                return None;
+            }
+            match alias {
+                Some(alias) => unquote(alias).to_string(),
+                None => table_name.name,
+            }
+        }
+        _ => return None,
+    };
+    let all_columns = || table.columns.iter().map(|c| c.name.name.to_string());
+    let has_column =
+        |name: &str| table.get_column(&ColumnName::from(name)).is_some();
+    let mut columns = vec![];
+    for item in select.projection.iter() {
+        match item {
+            SQLSelectItem::Wildcard => columns.extend(all_columns()),
+            SQLSelectItem::QualifiedWildcard(name) => {
+                if unquote(&name.to_string()) == qualifier {
+                    columns.extend(all_columns());
+                }
+            }
+            SQLSelectItem::UnnamedExpression(ASTNode::SQLIdentifier(name)) => {
+                let name = unquote(name);
+                if has_column(name) {
+                    columns.push(name.to_string());
+                }
+            }
+            SQLSelectItem::UnnamedExpression(
+                ASTNode::SQLCompoundIdentifier(idents),
+            ) if idents.len() >= 2 => {
+                let name = unquote(&idents[idents.len() - 1]);
+                let ident_qualifier = unquote(&idents[idents.len() - 2]);
+                if ident_qualifier == qualifier && has_column(name) {
+                    columns.push(name.to_string());
+                }
+            }
+            _ => (),
+        }
+    }
+    Some(columns)
+}
+
+/// quoted identifiers such as `"first_name"` are matched without the quotes
+fn unquote(name: &str) -> &str {
+    name.trim_matches('"')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_db;
+    use sqlparser::{
+        dialect::GenericSqlDialect,
+        sqlparser::Parser,
+    };
+
+    fn table_columns(sql: &str, table: &Table) -> Option<Vec<String>> {
+        let ast = Parser::parse_sql(&GenericSqlDialect {}, sql.to_string())
+            .expect("must parse");
+        extract_table_columns(&ast[0], table)
+    }
+
+    #[test]
+    fn aliased_columns_are_not_table_columns() {
+        let tables = test_db::sakila_tables();
+        let actor = test_db::find_table(&tables, "public.actor");
+        let columns = table_columns(
+            "SELECT a.actor_id, first_name AS last_name, \"last_name\",
+                upper(first_name) FROM actor a",
+            actor,
+        );
+        assert_eq!(
+            columns,
+            Some(vec!["actor_id".to_string(), "last_name".to_string()])
+        );
+    }
+
+    #[test]
+    fn joined_columns_are_not_table_columns() {
+        let tables = test_db::sakila_tables();
+        let actor = test_db::find_table(&tables, "public.actor");
+        let columns = table_columns(
+            "SELECT actor.* FROM actor JOIN film_actor USING (actor_id)",
+            actor,
+        );
+        assert_eq!(columns, None);
+        let columns = table_columns("SELECT * FROM public.film", actor);
+        assert_eq!(columns, None);
+    }
+}
@@ -0,0 +1,205 @@
+//! The query plan of a SELECT statement
+//! parsed from the json output of `EXPLAIN (FORMAT JSON)`
+use crate::error::IntelError;
+use rustorm::{
+    Rows,
+    Value,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use serde_json::Value as JsonValue;
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct QueryPlan {
+    /// the sql that was explained
+    pub sql: String,
+    /// whether the query was actually executed to get the timings
+    pub analyze: bool,
+    pub root: PlanNode,
+    /// in milliseconds
+    pub planning_time: Option<f64>,
+    /// in milliseconds, only available when analyzed
+    pub execution_time: Option<f64>,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct PlanNode {
+    pub node_type: String,
+    pub relation_name: Option<String>,
+    pub startup_cost: f64,
+    pub total_cost: f64,
+    pub plan_rows: f64,
+    /// in milliseconds, per loop
+    pub actual_total_time: Option<f64>,
+    pub actual_rows: Option<f64>,
+    pub actual_loops: Option<f64>,
+    pub children: Vec<PlanNode>,
+}
+
+impl QueryPlan {
+    /// wrap the sql in an EXPLAIN statement
+    pub fn explain_sql(sql: &str, analyze: bool) -> String {
+        format!(
+            "EXPLAIN (ANALYZE {}, FORMAT JSON) {}",
+            analyze,
+            sql.trim().trim_end_matches(';')
+        )
+    }
+
+    /// the rows returned by postgresql contains 1 row and 1 column
+    /// which is the json of the plan
+    pub fn from_rows(
+        rows: &Rows,
+        sql: &str,
+        analyze: bool,
+    ) -> Result<Self, IntelError> {
+        let json = match rows.data.first().and_then(|row| row.first()) {
+            Some(Value::Json(json)) => json,
+            Some(Value::Text(json)) => json,
+            _ => {
+                return Err(IntelError::PlanError(
+                    "explain did not return a json plan".into(),
+                ));
+            }
+        };
+        Self::from_json(json, sql, analyze)
+    }
+
+    pub fn from_json(
+        json: &str,
+        sql: &str,
+        analyze: bool,
+    ) -> Result<Self, IntelError> {
+        let parsed: JsonValue = serde_json::from_str(json)
+            .map_err(|e| IntelError::PlanError(e.to_string()))?;
+        let explained = match parsed {
+            JsonValue::Array(ref array) if !array.is_empty() => &array[0],
+            ref explained => explained,
+        };
+        let root = explained
+            .get("Plan")
+            .and_then(PlanNode::from_json)
+            .ok_or_else(|| {
+                IntelError::PlanError("there is no Plan in json".into())
+            })?;
+        Ok(QueryPlan {
+            sql: sql.to_string(),
+            analyze,
+            root,
+            planning_time: explained
+                .get("Planning Time")
+                .and_then(JsonValue::as_f64),
+            execution_time: explained
+                .get("Execution Time")
+                .and_then(JsonValue::as_f64),
+        })
+    }
+
+    /// the cost or time of the whole query, used as the basis
+    /// of how expensive each of the node is
+    pub fn total(&self) -> f64 {
+        self.root.inclusive()
+    }
+}
+
+impl PlanNode {
+    fn from_json(json: &JsonValue) -> Option<Self> {
+        let number = |key: &str| json.get(key).and_then(JsonValue::as_f64);
+        let children = match json.get("Plans") {
+            Some(JsonValue::Array(plans)) => {
+                plans.iter().filter_map(PlanNode::from_json).collect()
+            }
+            _ => vec![],
+        };
+        Some(PlanNode {
+            node_type: json.get("Node Type")?.as_str()?.to_string(),
+            relation_name: json
+                .get("Relation Name")
+                .and_then(JsonValue::as_str)
+                .map(ToString::to_string),
+            startup_cost: number("Startup Cost").unwrap_or(0.0),
+            total_cost: number("Total Cost").unwrap_or(0.0),
+            plan_rows: number("Plan Rows").unwrap_or(0.0),
+            actual_total_time: number("Actual Total Time"),
+            actual_rows: number("Actual Rows"),
+            actual_loops: number("Actual Loops"),
+            children,
+        })
+    }
+
+    /// the time of this node including its children when analyzed,
+    /// otherwise the estimated total cost
+    pub fn inclusive(&self) -> f64 {
+        match self.actual_total_time {
+            Some(time) => time * self.actual_loops.unwrap_or(1.0),
+            None => self.total_cost,
+        }
+    }
+
+    /// the time or cost spent on this node alone, excluding its children
+    pub fn exclusive(&self) -> f64 {
+        let children: f64 = self.children.iter().map(Self::inclusive).sum();
+        let exclusive = self.inclusive() - children;
+        if exclusive < 0.0 {
+            0.0
+        } else {
+            exclusive
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_analyzed_plan() {
+        let json = r#"
+[
+  {
+    "Plan": {
+      "Node Type": "Hash Join",
+      "Startup Cost": 1.5,
+      "Total Cost": 40.0,
+      "Plan Rows": 100,
+      "Actual Total Time": 2.0,
+      "Actual Rows": 100,
+      "Actual Loops": 1,
+      "Plans": [
+        {
+          "Node Type": "Seq Scan",
+          "Relation Name": "film",
+          "Startup Cost": 0.0,
+          "Total Cost": 30.0,
+          "Plan Rows": 1000,
+          "Actual Total Time": 1.5,
+          "Actual Rows": 1000,
+          "Actual Loops": 1
+        }
+      ]
+    },
+    "Planning Time": 0.1,
+    "Execution Time": 2.2
+  }
+]
+        "#;
+        let plan = QueryPlan::from_json(json, "SELECT", true).unwrap();
+        assert_eq!(plan.root.node_type, "Hash Join");
+        assert_eq!(plan.root.children.len(), 1);
+        assert_eq!(plan.root.children[0].relation_name, Some("film".into()));
+        assert_eq!(plan.execution_time, Some(2.2));
+        assert_eq!(plan.total(), 2.0);
+        assert_eq!(plan.root.exclusive(), 0.5);
+    }
+
+    #[test]
+    fn unanalyzed_plan_uses_cost() {
+        let json =
+            r#"[{"Plan": {"Node Type": "Seq Scan", "Total Cost": 12.5}}]"#;
+        let plan = QueryPlan::from_json(json, "SELECT", false).unwrap();
+        assert_eq!(plan.total(), 12.5);
+        assert_eq!(plan.execution_time, None);
+    }
+}
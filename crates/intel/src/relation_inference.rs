@@ -0,0 +1,298 @@
+//! the relationships of the tables which has no foreign key constraints,
+//! inferred from the names and the types of the columns.
+//! The relationships are added to the tables as virtual foreign keys,
+//! so the windows are derived the same as with the actual foreign keys
+use crate::{
+    config::RelationConfig,
+    query_builder,
+};
+use rustorm::{
+    table::{
+        ForeignKey,
+        TableKey,
+    },
+    types::SqlType,
+    ColumnName,
+    Dao,
+    EntityManager,
+    FromDao,
+    Table,
+    TableName,
+};
+
+/// the number of values of the column which are checked
+/// to exist in the referred table
+const SAMPLE_SIZE: usize = 100;
+
+/// the column of the table that refers to the primary key of the referred table
+#[derive(Debug, PartialEq, Clone)]
+pub struct InferredRelation {
+    pub table_name: TableName,
+    pub column_name: ColumnName,
+    pub referred_table: TableName,
+    pub referred_column: ColumnName,
+}
+
+impl InferredRelation {
+    fn to_foreign_key(&self) -> ForeignKey {
+        ForeignKey {
+            name: Some(format!(
+                "virtual_{}_{}_fkey",
+                self.table_name.name, self.column_name.name
+            )),
+            columns: vec![self.column_name.clone()],
+            foreign_table: self.referred_table.clone(),
+            referred_columns: vec![self.referred_column.clone()],
+        }
+    }
+}
+
+/// the inferred relationships that are not rejected, confirmed by sampling
+/// when set in the config, and the accepted relationships are added to the
+/// tables as foreign keys
+pub fn add_virtual_foreign_keys(
+    em: &mut EntityManager,
+    tables: &mut [Table],
+    config: &RelationConfig,
+) {
+    let mut relations = vec![];
+    if config.infer {
+        for relation in infer_relations(tables) {
+            if config.is_rejected(
+                &relation.table_name,
+                &relation.column_name.name,
+                &relation.referred_table,
+            ) {
+                continue;
+            }
+            if config.sample && !is_confirmed_by_sampling(em, &relation) {
+                info!("Unconfirmed relationship: {:?}", relation);
+                continue;
+            }
+            relations.push(relation);
+        }
+    }
+    for accepted in &config.accept {
+        let relation = tables.iter().find_map(|table| {
+            let column = table
+                .columns
+                .iter()
+                .find(|column| column.name.name == accepted.column)?;
+            tables.iter().find_map(|referred| {
+                let referred_column = single_primary_column(referred)?;
+                if accepted.matches(
+                    &table.name,
+                    &column.name.name,
+                    &referred.name,
+                ) {
+                    Some(InferredRelation {
+                        table_name: table.name.clone(),
+                        column_name: column.name.clone(),
+                        referred_table: referred.name.clone(),
+                        referred_column: referred_column.clone(),
+                    })
+                } else {
+                    None
+                }
+            })
+        });
+        match relation {
+            Some(relation) => {
+                if !relations.contains(&relation) {
+                    relations.push(relation);
+                }
+            }
+            None => {
+                warn!("The accepted relationship is not found: {:?}", accepted)
+            }
+        }
+    }
+    for relation in relations {
+        if let Some(table) =
+            tables.iter_mut().find(|t| t.name == relation.table_name)
+        {
+            info!("Virtual foreign key: {:?}", relation);
+            table
+                .table_key
+                .push(TableKey::ForeignKey(relation.to_foreign_key()));
+        }
+    }
+}
+
+/// the columns which are not foreign keys and are named after another table
+/// such as `language_id` or `languageid` which has the same type as the
+/// single primary key of the table `language`.
+/// A column which matches more than one table is ambiguous,
+/// unless only one of them is in the same schema
+pub fn infer_relations(tables: &[Table]) -> Vec<InferredRelation> {
+    let mut relations = vec![];
+    for table in tables {
+        let primary = table.get_primary_column_names();
+        let foreign = table.get_foreign_column_names();
+        for column in &table.columns {
+            if foreign.contains(&&column.name) {
+                continue;
+            }
+            let candidates: Vec<(&Table, &ColumnName)> = tables
+                .iter()
+                .filter_map(|referred| {
+                    let referred_column = single_primary_column(referred)?;
+                    let referred_type = referred
+                        .get_column(referred_column)?
+                        .specification
+                        .sql_type
+                        .clone();
+                    // the primary key of the table is not a reference to itself
+                    let is_own_key = referred.name == table.name
+                        && primary.contains(&&column.name);
+                    if !is_own_key
+                        && is_named_after(
+                            &column.name.name,
+                            &referred.name.name,
+                        )
+                        && is_compatible(
+                            &column.specification.sql_type,
+                            &referred_type,
+                        )
+                    {
+                        Some((referred, referred_column))
+                    } else {
+                        None
+                    }
+                })
+                .collect();
+            let same_schema: Vec<&(&Table, &ColumnName)> = candidates
+                .iter()
+                .filter(|(referred, _)| {
+                    referred.name.schema == table.name.schema
+                })
+                .collect();
+            let candidate =
+                match (candidates.as_slice(), same_schema.as_slice()) {
+                    ([candidate], _) => Some(candidate),
+                    (_, [candidate]) => Some(*candidate),
+                    _ => None,
+                };
+            if let Some((referred, referred_column)) = candidate {
+                relations.push(InferredRelation {
+                    table_name: table.name.clone(),
+                    column_name: column.name.clone(),
+                    referred_table: referred.name.clone(),
+                    referred_column: (*referred_column).clone(),
+                });
+            }
+        }
+    }
+    relations
+}
+
+fn single_primary_column(table: &Table) -> Option<&ColumnName> {
+    match table.get_primary_column_names().as_slice() {
+        [primary] => Some(*primary),
+        _ => None,
+    }
+}
+
+/// the column is named after the table such as `film_id` or `filmid`,
+/// a table named in plural such as `users` also matches `user_id`
+fn is_named_after(column_name: &str, table_name: &str) -> bool {
+    let column_name = column_name.to_lowercase();
+    let table_name = table_name.to_lowercase();
+    let singular = if table_name.ends_with('s') {
+        &table_name[..table_name.len() - 1]
+    } else {
+        &table_name
+    };
+    [table_name.as_str(), singular].iter().any(|name| {
+        !name.is_empty()
+            && (column_name == format!("{}_id", name)
+                || column_name == format!("{}id", name))
+    })
+}
+
+/// the values of the types can be compared to each other
+fn is_compatible(sql_type: &SqlType, referred_type: &SqlType) -> bool {
+    sql_type == referred_type
+        || (is_integer(sql_type) && is_integer(referred_type))
+        || (is_text(sql_type) && is_text(referred_type))
+}
+
+fn is_integer(sql_type: &SqlType) -> bool {
+    match sql_type {
+        SqlType::Tinyint
+        | SqlType::Smallint
+        | SqlType::Int
+        | SqlType::Bigint => true,
+        _ => false,
+    }
+}
+
+fn is_text(sql_type: &SqlType) -> bool {
+    match sql_type {
+        SqlType::Text | SqlType::Varchar | SqlType::Char => true,
+        _ => false,
+    }
+}
+
+/// the number of the sampled values that are not in the referred table
+struct Missing(i64);
+
+impl FromDao for Missing {
+    fn from_dao(dao: &Dao) -> Self {
+        Missing(dao.get("missing").unwrap_or(-1))
+    }
+}
+
+/// all the sampled values of the column exist in the referred table
+fn is_confirmed_by_sampling(
+    em: &mut EntityManager,
+    relation: &InferredRelation,
+) -> bool {
+    let sql = format!(
+        "SELECT COUNT(*) AS missing
+            FROM (SELECT {column} AS value FROM {table}
+                WHERE {column} IS NOT NULL LIMIT {limit}) AS sample
+            WHERE NOT EXISTS (SELECT 1 FROM {referred}
+                WHERE {referred}.{referred_column} = sample.value)",
+        column = query_builder::quoted_name(&relation.column_name.name),
+        table = relation.table_name.safe_complete_name(),
+        limit = SAMPLE_SIZE,
+        referred = relation.referred_table.safe_complete_name(),
+        referred_column =
+            query_builder::quoted_name(&relation.referred_column.name),
+    );
+    let missing: Result<Vec<Missing>, _> =
+        em.execute_sql_with_return(&sql, &[]);
+    match missing {
+        Ok(missing) => missing.first().map(|m| m.0 == 0).unwrap_or(false),
+        Err(e) => {
+            info!("Unable to sample the relationship {:?}: {}", relation, e);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn columns_named_after_tables() {
+        assert!(is_named_after("language_id", "language"));
+        assert!(is_named_after("LanguageId", "language"));
+        assert!(is_named_after("user_id", "users"));
+        assert!(is_named_after("users_id", "users"));
+        assert!(!is_named_after("original_language_id", "language"));
+        assert!(!is_named_after("language", "language"));
+        assert!(!is_named_after("id", "s"));
+    }
+
+    #[test]
+    fn compatible_types() {
+        assert!(is_compatible(&SqlType::Int, &SqlType::Bigint));
+        assert!(is_compatible(&SqlType::Varchar, &SqlType::Text));
+        assert!(is_compatible(&SqlType::Uuid, &SqlType::Uuid));
+        assert!(!is_compatible(&SqlType::Int, &SqlType::Uuid));
+        assert!(!is_compatible(&SqlType::Text, &SqlType::Int));
+    }
+}
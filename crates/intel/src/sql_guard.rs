@@ -0,0 +1,266 @@
+//! Guards the execution of ad-hoc sql queries
+//! Each statement is classified from the parsed sql ast
+//! and statements that modifies the database are rejected
+//! unless the user is allowed to write
+use crate::query_parser;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use sqlparser::{
+    dialect::GenericSqlDialect,
+    sqlast::SQLStatement,
+    sqlparser::Parser,
+};
+use std::fmt;
+
+/// The restrictions imposed when executing an ad-hoc sql query
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SqlOptions {
+    /// allow statements other than SELECT
+    pub allow_write: bool,
+    /// statement timeout in milliseconds, only postgresql can enforce it
+    /// so the queries are rejected in the other databases when it is set
+    pub statement_timeout: Option<u64>,
+    /// the maximum number of rows a query can return
+    pub row_limit: Option<usize>,
+    /// the read only transaction is only imposed in postgresql,
+    /// the other databases rely on the check of the statements
+    pub is_postgres: bool,
+}
+
+/// the kind of statement, derived from the sql ast
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub enum StatementKind {
+    Query,
+    Insert,
+    Update,
+    Delete,
+    Copy,
+    /// create, alter, drop and the rest of the statements
+    Ddl,
+}
+
+impl StatementKind {
+    pub fn is_read_only(&self) -> bool {
+        *self == StatementKind::Query
+    }
+}
+
+/// The reason why an ad-hoc sql query is rejected
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub enum SqlViolation {
+    /// the sql can not be parsed, therefore it can not be verified
+    ParseError(String),
+    /// there is no statement in the sql
+    EmptyStatement,
+    /// the user is not allowed to execute this kind of statement
+    WriteNotAllowed(StatementKind),
+    /// the query took longer than the timeout in milliseconds
+    StatementTimeout(u64),
+    /// the timeout in milliseconds can not be enforced in the database
+    TimeoutNotSupported(u64),
+    /// the query returned more than the allowed number of rows
+    RowLimitExceeded(usize),
+    /// only a SELECT statement can be used here
+    NotAQuery(StatementKind),
+    /// only a single statement can be used here, the number of statements found
+    MultipleStatements(usize),
+}
+
+impl fmt::Display for SqlViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SqlViolation::ParseError(e) => {
+                write!(f, "Unable to parse the sql query: {}", e)
+            }
+            SqlViolation::EmptyStatement => {
+                write!(f, "There is no statement to execute")
+            }
+            SqlViolation::WriteNotAllowed(kind) => {
+                write!(f, "{:?} statement is not allowed for this user", kind)
+            }
+            SqlViolation::StatementTimeout(timeout) => {
+                write!(f, "The query exceeded the timeout of {}ms", timeout)
+            }
+            SqlViolation::TimeoutNotSupported(timeout) => {
+                write!(
+                    f,
+                    "The timeout of {}ms can only be enforced in postgresql",
+                    timeout
+                )
+            }
+            SqlViolation::RowLimitExceeded(limit) => {
+                write!(f, "The query returned more than {} rows", limit)
+            }
+            SqlViolation::NotAQuery(kind) => {
+                write!(f, "Expecting a SELECT statement, found {:?}", kind)
+            }
+            SqlViolation::MultipleStatements(count) => {
+                write!(f, "Expecting a single statement, found {}", count)
+            }
+        }
+    }
+}
+
+/// parse the sql and classify each of its statements
+pub fn parse_statements(
+    sql: &str,
+) -> Result<Vec<(SQLStatement, StatementKind)>, SqlViolation> {
+    let dialect = GenericSqlDialect {};
+    let statements = Parser::parse_sql(&dialect, sql.to_string())
+        .map_err(|e| SqlViolation::ParseError(format!("{:?}", e)))?;
+    if statements.is_empty() {
+        return Err(SqlViolation::EmptyStatement);
+    }
+    Ok(statements
+        .into_iter()
+        .map(|statement| {
+            let kind = query_parser::classify_statement(&statement);
+            (statement, kind)
+        })
+        .collect())
+}
+
+/// check the sql against the options,
+/// returns the kind of the first statement when allowed
+pub fn check_sql(
+    sql: &str,
+    options: &SqlOptions,
+) -> Result<StatementKind, SqlViolation> {
+    let kinds = check_statements(sql, options)?;
+    Ok(kinds[0].clone())
+}
+
+/// check the sql against the options, the sql must be exactly one SELECT
+/// query such as when it is explained, since the analyze executes it
+pub fn check_single_query(
+    sql: &str,
+    options: &SqlOptions,
+) -> Result<(), SqlViolation> {
+    let kinds = check_statements(sql, options)?;
+    if kinds.len() > 1 {
+        return Err(SqlViolation::MultipleStatements(kinds.len()));
+    }
+    match kinds.into_iter().next() {
+        Some(StatementKind::Query) => Ok(()),
+        Some(kind) => Err(SqlViolation::NotAQuery(kind)),
+        None => Err(SqlViolation::EmptyStatement),
+    }
+}
+
+/// the options can be enforced in the database,
+/// a query is not executed with a timeout that is silently ignored
+pub fn check_options(options: &SqlOptions) -> Result<(), SqlViolation> {
+    match options.statement_timeout {
+        Some(timeout) if !options.is_postgres => {
+            Err(SqlViolation::TimeoutNotSupported(timeout))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// check the sql against the options,
+/// returns the kind of each of the statements when allowed
+pub fn check_statements(
+    sql: &str,
+    options: &SqlOptions,
+) -> Result<Vec<StatementKind>, SqlViolation> {
+    check_options(options)?;
+    match parse_statements(sql) {
+        Ok(statements) => {
+            if !options.allow_write {
+                if let Some((_, kind)) =
+                    statements.iter().find(|(_, kind)| !kind.is_read_only())
+                {
+                    return Err(SqlViolation::WriteNotAllowed(kind.clone()));
+                }
+            }
+            Ok(statements.into_iter().map(|(_, kind)| kind).collect())
+        }
+        // users that can write are not restricted to what the parser can understand
+        Err(SqlViolation::ParseError(_)) if options.allow_write => {
+            Ok(vec![StatementKind::Ddl])
+        }
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn select_is_allowed() {
+        let options = SqlOptions::default();
+        let kind = check_sql("SELECT * FROM actor", &options);
+        assert_eq!(kind, Ok(StatementKind::Query));
+    }
+
+    #[test]
+    fn delete_is_rejected() {
+        let options = SqlOptions::default();
+        let kind = check_sql("DELETE FROM actor", &options);
+        assert_eq!(
+            kind,
+            Err(SqlViolation::WriteNotAllowed(StatementKind::Delete))
+        );
+    }
+
+    #[test]
+    fn delete_is_allowed_for_writers() {
+        let options = SqlOptions {
+            allow_write: true,
+            ..Default::default()
+        };
+        let kind = check_sql("DELETE FROM actor", &options);
+        assert_eq!(kind, Ok(StatementKind::Delete));
+    }
+
+    #[test]
+    fn each_statement_is_classified() {
+        let options = SqlOptions::default();
+        let kinds = check_statements("SELECT 1; SELECT 2", &options);
+        assert_eq!(kinds, Ok(vec![StatementKind::Query, StatementKind::Query]));
+    }
+
+    #[test]
+    fn timeout_is_rejected_outside_postgres() {
+        let options = SqlOptions {
+            statement_timeout: Some(5000),
+            ..Default::default()
+        };
+        let kind = check_sql("SELECT * FROM actor", &options);
+        assert_eq!(kind, Err(SqlViolation::TimeoutNotSupported(5000)));
+        let options = SqlOptions {
+            is_postgres: true,
+            ..options
+        };
+        let kind = check_sql("SELECT * FROM actor", &options);
+        assert_eq!(kind, Ok(StatementKind::Query));
+    }
+
+    #[test]
+    fn statements_after_the_query_are_rejected() {
+        let options = SqlOptions {
+            allow_write: true,
+            ..Default::default()
+        };
+        assert_eq!(check_single_query("SELECT 1", &options), Ok(()));
+        assert_eq!(
+            check_single_query("SELECT 1; DELETE FROM actor", &options),
+            Err(SqlViolation::MultipleStatements(2))
+        );
+        assert_eq!(
+            check_single_query("DELETE FROM actor", &options),
+            Err(SqlViolation::NotAQuery(StatementKind::Delete))
+        );
+    }
+
+    #[test]
+    fn unparsable_is_rejected() {
+        let options = SqlOptions::default();
+        let kind = check_sql("DROP everything please", &options);
+        assert!(kind.is_err());
+    }
+}
@@ -0,0 +1,515 @@
+//! Placeholders in the ad-hoc sql query
+//! Both the positional `$1` and the named `:name` placeholders are supported,
+//! named placeholders are rewritten into positional ones so the values can be
+//! bound the same way as `Query::add_param`
+use crate::{
+    query_parser,
+    Context,
+};
+use rustorm::{
+    types::SqlType,
+    TableName,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use sqlparser::{
+    dialect::GenericSqlDialect,
+    sqlparser::Parser,
+};
+
+/// A parameter of the sql query
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Placeholder {
+    /// `$1` for positional or `:name` for named placeholders
+    pub name: String,
+    /// the type of the column the placeholder is compared against,
+    /// Text when it can not be inferred
+    pub sql_type: SqlType,
+}
+
+/// The sql with all of its placeholders rewritten as `$n`,
+/// the nth placeholder corresponds to `$n`
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct ParameterizedSql {
+    pub sql: String,
+    pub placeholders: Vec<Placeholder>,
+    /// the byte range of each of the `$n` occurence in the rewritten sql
+    /// and the index of its placeholder
+    occurences: Vec<(usize, usize, usize)>,
+}
+
+impl ParameterizedSql {
+    /// scan the sql for placeholders, skipping the string literals,
+    /// quoted identifiers, comments, `::` type casts and array slices `[1:n]`
+    pub fn parse(sql: &str) -> Self {
+        let chars: Vec<char> = sql.chars().collect();
+        let mut rewritten = String::with_capacity(sql.len());
+        // placeholders found in the order they appear
+        let mut found: Vec<(usize, usize, PlaceholderRef)> = vec![];
+        // inside the brackets of an array subscript
+        let mut bracket_depth = 0;
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            let next = chars.get(i + 1).cloned();
+            match c {
+                '[' => {
+                    bracket_depth += 1;
+                    rewritten.push(c);
+                    i += 1;
+                }
+                ']' => {
+                    bracket_depth = bracket_depth.max(1) - 1;
+                    rewritten.push(c);
+                    i += 1;
+                }
+                '\'' | '"' => {
+                    let end = find_closing_quote(&chars, i);
+                    rewritten.extend(&chars[i..end]);
+                    i = end;
+                }
+                '-' if next == Some('-') => {
+                    let end =
+                        find_from(&chars, i, &['\n']).unwrap_or(chars.len());
+                    rewritten.extend(&chars[i..end]);
+                    i = end;
+                }
+                '/' if next == Some('*') => {
+                    let end = find_sequence(&chars, i + 2, &['*', '/'])
+                        .map(|end| end + 2)
+                        .unwrap_or(chars.len());
+                    rewritten.extend(&chars[i..end]);
+                    i = end;
+                }
+                '$' if next.map(|n| n.is_ascii_digit()).unwrap_or(false) => {
+                    let end = take_while(&chars, i + 1, |c| c.is_ascii_digit());
+                    let number: String = chars[i + 1..end].iter().collect();
+                    let start = rewritten.len();
+                    rewritten.push('$');
+                    rewritten.push_str(&number);
+                    match number.parse() {
+                        Ok(n) if n > 0 => {
+                            found.push((
+                                start,
+                                rewritten.len(),
+                                PlaceholderRef::Positional(n),
+                            ))
+                        }
+                        _ => (),
+                    }
+                    i = end;
+                }
+                '$' => {
+                    // dollar quoted string: $$...$$ or $tag$...$tag$
+                    let tag_end = take_while(&chars, i + 1, is_ident_char);
+                    if chars.get(tag_end) == Some(&'$') {
+                        let tag = &chars[i..=tag_end];
+                        let end = find_sequence(&chars, tag_end + 1, tag)
+                            .map(|end| end + tag.len())
+                            .unwrap_or(chars.len());
+                        rewritten.extend(&chars[i..end]);
+                        i = end;
+                    } else {
+                        rewritten.push(c);
+                        i += 1;
+                    }
+                }
+                ':' if next == Some(':') => {
+                    rewritten.push_str("::");
+                    i += 2;
+                }
+                ':' if bracket_depth == 0
+                    && next.map(is_ident_start).unwrap_or(false) =>
+                {
+                    let end = take_while(&chars, i + 1, is_ident_char);
+                    let name: String = chars[i + 1..end].iter().collect();
+                    let start = rewritten.len();
+                    // replaced with the positional placeholder later
+                    rewritten.push_str(&format!(":{}", name));
+                    found.push((
+                        start,
+                        rewritten.len(),
+                        PlaceholderRef::Named(name),
+                    ));
+                    i = end;
+                }
+                _ => {
+                    rewritten.push(c);
+                    i += 1;
+                }
+            }
+        }
+        Self::number_placeholders(rewritten, found)
+    }
+
+    /// positional placeholders keeps their number,
+    /// named placeholders are numbered after the highest positional placeholder
+    fn number_placeholders(
+        rewritten: String,
+        found: Vec<(usize, usize, PlaceholderRef)>,
+    ) -> Self {
+        let max_positional = found
+            .iter()
+            .filter_map(|(_, _, placeholder)| {
+                match placeholder {
+                    PlaceholderRef::Positional(n) => Some(*n),
+                    PlaceholderRef::Named(_) => None,
+                }
+            })
+            .max()
+            .unwrap_or(0);
+        let mut placeholders: Vec<Placeholder> = (1..=max_positional)
+            .map(|n| {
+                Placeholder {
+                    name: format!("${}", n),
+                    sql_type: SqlType::Text,
+                }
+            })
+            .collect();
+        let mut sql = String::with_capacity(rewritten.len());
+        let mut occurences = vec![];
+        let mut last = 0;
+        for (start, end, placeholder) in found {
+            let index = match placeholder {
+                PlaceholderRef::Positional(n) => n - 1,
+                PlaceholderRef::Named(name) => {
+                    let name = format!(":{}", name);
+                    match placeholders.iter().position(|p| p.name == name) {
+                        Some(index) => index,
+                        None => {
+                            placeholders.push(Placeholder {
+                                name,
+                                sql_type: SqlType::Text,
+                            });
+                            placeholders.len() - 1
+                        }
+                    }
+                }
+            };
+            sql.push_str(&rewritten[last..start]);
+            let new_start = sql.len();
+            sql.push_str(&format!("${}", index + 1));
+            occurences.push((new_start, sql.len(), index));
+            last = end;
+        }
+        sql.push_str(&rewritten[last..]);
+        ParameterizedSql {
+            sql,
+            placeholders,
+            occurences,
+        }
+    }
+
+    pub fn has_placeholders(&self) -> bool {
+        !self.placeholders.is_empty()
+    }
+
+    /// the sql with NULL in place of the placeholders,
+    /// used for parsing the sql which does not understand placeholders
+    pub fn nulled_sql(&self) -> String {
+        let mut sql = String::with_capacity(self.sql.len());
+        let mut last = 0;
+        for (start, end, _index) in self.occurences.iter() {
+            sql.push_str(&self.sql[last..*start]);
+            sql.push_str("NULL");
+            last = *end;
+        }
+        sql.push_str(&self.sql[last..]);
+        sql
+    }
+
+    /// infer the type of each placeholder from the column it is compared against,
+    /// the column is looked up in the tables mentioned in the sql
+    pub fn infer_types(&mut self, context: &Context) {
+        let tables = self.mentioned_tables(context);
+        for (start, end, index) in self.occurences.iter() {
+            let column = compared_column_before(&self.sql[..*start])
+                .or_else(|| compared_column_after(&self.sql[*end..]));
+            if let Some(column) = column {
+                let sql_type = tables.iter().find_map(|table_name| {
+                    let table = context.get_table(table_name)?;
+                    table
+                        .columns
+                        .iter()
+                        .find(|col| col.name.name == column)
+                        .map(|col| col.get_sql_type())
+                });
+                if let Some(sql_type) = sql_type {
+                    self.placeholders[*index].sql_type = sql_type;
+                }
+            }
+        }
+    }
+
+    /// the main table of the query comes first,
+    /// then the tables whose name appears in the sql
+    fn mentioned_tables(&self, context: &Context) -> Vec<TableName> {
+        let dialect = GenericSqlDialect {};
+        let main_table = Parser::parse_sql(&dialect, self.nulled_sql())
+            .ok()
+            .and_then(|ast| {
+                ast.first().and_then(query_parser::extract_table_name)
+            })
+            .map(|table_name| TableName::from(&table_name));
+        let lowercased = self.sql.to_lowercase();
+        let mut tables: Vec<TableName> = main_table.into_iter().collect();
+        for table_name in context.tables.keys() {
+            if !tables.contains(table_name)
+                && contains_word(&lowercased, &table_name.name.to_lowercase())
+            {
+                tables.push(table_name.clone());
+            }
+        }
+        tables
+    }
+}
+
+enum PlaceholderRef {
+    Positional(usize),
+    Named(String),
+}
+
+const COMPARISON_OPERATORS: &[&str] = &["<=", ">=", "<>", "!=", "=", "<", ">"];
+const COMPARISON_KEYWORDS: &[&str] = &["like", "ilike"];
+
+/// `column = $1`, returns the name of the column without the table or alias
+fn compared_column_before(sql: &str) -> Option<String> {
+    let sql = sql.trim_end();
+    let sql = strip_operator_end(sql)?.trim_end();
+    // `column in ($1, ..`
+    let ident_end = sql.len();
+    let ident_start = sql
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| is_ident_char(*c) || *c == '.' || *c == '"')
+        .last()
+        .map(|(i, _)| i)?;
+    column_name(&sql[ident_start..ident_end])
+}
+
+/// `$1 = column`
+fn compared_column_after(sql: &str) -> Option<String> {
+    let sql = sql.trim_start();
+    let sql = strip_operator_start(sql)?.trim_start();
+    let ident_end = sql
+        .char_indices()
+        .take_while(|(_, c)| is_ident_char(*c) || *c == '.' || *c == '"')
+        .last()
+        .map(|(i, c)| i + c.len_utf8())?;
+    column_name(&sql[..ident_end])
+}
+
+fn strip_operator_end(sql: &str) -> Option<&str> {
+    for op in COMPARISON_OPERATORS {
+        if sql.ends_with(op) {
+            return Some(&sql[..sql.len() - op.len()]);
+        }
+    }
+    for keyword in COMPARISON_KEYWORDS {
+        if let Some(before) = strip_keyword_end(sql, keyword) {
+            if !before.chars().last().map(is_ident_char).unwrap_or(false) {
+                return Some(before);
+            }
+        }
+    }
+    // `column in ($1`
+    if sql.ends_with('(') {
+        let before = sql[..sql.len() - 1].trim_end();
+        return strip_keyword_end(before, " in");
+    }
+    None
+}
+
+fn strip_operator_start(sql: &str) -> Option<&str> {
+    for op in COMPARISON_OPERATORS {
+        if sql.starts_with(op) {
+            return Some(&sql[op.len()..]);
+        }
+    }
+    for keyword in COMPARISON_KEYWORDS {
+        if let Some(after) = strip_keyword_start(sql, keyword) {
+            if after.starts_with(' ') {
+                return Some(after);
+            }
+        }
+    }
+    None
+}
+
+/// the sql before the keyword at the end of the sql, the ascii keyword is
+/// matched ignoring the case without lowercasing the sql, since lowercasing
+/// changes the byte length of some characters such as `İ`
+fn strip_keyword_end<'a>(sql: &'a str, keyword: &str) -> Option<&'a str> {
+    let before = sql.len().checked_sub(keyword.len())?;
+    match sql.get(before..) {
+        Some(end) if end.eq_ignore_ascii_case(keyword) => Some(&sql[..before]),
+        _ => None,
+    }
+}
+
+/// the sql after the keyword at the start of the sql
+fn strip_keyword_start<'a>(sql: &'a str, keyword: &str) -> Option<&'a str> {
+    match sql.get(..keyword.len()) {
+        Some(start) if start.eq_ignore_ascii_case(keyword) => {
+            Some(&sql[keyword.len()..])
+        }
+        _ => None,
+    }
+}
+
+/// the last part of `schema.table.column` without the quotes
+fn column_name(ident: &str) -> Option<String> {
+    let column = ident.rsplit('.').next()?.trim_matches('"');
+    if column.is_empty() || column.chars().all(|c| c.is_ascii_digit()) {
+        None
+    } else {
+        Some(column.to_string())
+    }
+}
+
+fn contains_word(haystack: &str, word: &str) -> bool {
+    haystack.match_indices(word).any(|(i, _)| {
+        let before = haystack[..i].chars().last();
+        let after = haystack[i + word.len()..].chars().next();
+        !before.map(is_ident_char).unwrap_or(false)
+            && !after.map(is_ident_char).unwrap_or(false)
+    })
+}
+
+fn is_ident_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_'
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn take_while<F>(chars: &[char], from: usize, predicate: F) -> usize
+where
+    F: Fn(char) -> bool,
+{
+    let mut i = from;
+    while i < chars.len() && predicate(chars[i]) {
+        i += 1;
+    }
+    i
+}
+
+fn find_from(chars: &[char], from: usize, needles: &[char]) -> Option<usize> {
+    (from..chars.len()).find(|i| needles.contains(&chars[*i]))
+}
+
+fn find_sequence(
+    chars: &[char],
+    from: usize,
+    sequence: &[char],
+) -> Option<usize> {
+    if sequence.is_empty() || chars.len() < sequence.len() {
+        return None;
+    }
+    (from..=chars.len() - sequence.len())
+        .find(|i| &chars[*i..*i + sequence.len()] == sequence)
+}
+
+/// the position after the closing quote, doubled quotes are escaped quotes
+fn find_closing_quote(chars: &[char], start: usize) -> usize {
+    let quote = chars[start];
+    let mut i = start + 1;
+    while i < chars.len() {
+        if chars[i] == quote {
+            if chars.get(i + 1) == Some(&quote) {
+                i += 2;
+                continue;
+            }
+            return i + 1;
+        }
+        i += 1;
+    }
+    chars.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn named_placeholders_are_rewritten() {
+        let parsed = ParameterizedSql::parse(
+            "SELECT * FROM film WHERE title = :title AND length > :len OR title LIKE :title",
+        );
+        assert_eq!(
+            parsed.sql,
+            "SELECT * FROM film WHERE title = $1 AND length > $2 OR title LIKE $1"
+        );
+        let names: Vec<&str> = parsed
+            .placeholders
+            .iter()
+            .map(|p| p.name.as_str())
+            .collect();
+        assert_eq!(names, vec![":title", ":len"]);
+    }
+
+    #[test]
+    fn casts_and_literals_are_skipped() {
+        let parsed = ParameterizedSql::parse(
+            "SELECT ':no', $$ $1 $$, '2019'::date -- :comment\n FROM film WHERE film_id = $1",
+        );
+        assert_eq!(parsed.placeholders.len(), 1);
+        assert_eq!(parsed.placeholders[0].name, "$1");
+        assert_eq!(
+            parsed.nulled_sql(),
+            "SELECT ':no', $$ $1 $$, '2019'::date -- :comment\n FROM film WHERE film_id = NULL"
+        );
+    }
+
+    #[test]
+    fn named_are_numbered_after_positional() {
+        let parsed =
+            ParameterizedSql::parse("SELECT * FROM t WHERE a = $2 AND b = :b");
+        assert_eq!(parsed.sql, "SELECT * FROM t WHERE a = $2 AND b = $3");
+        assert_eq!(parsed.placeholders.len(), 3);
+    }
+
+    #[test]
+    fn compared_column() {
+        assert_eq!(
+            compared_column_before("WHERE f.\"film_id\" = "),
+            Some("film_id".to_string())
+        );
+        assert_eq!(
+            compared_column_before("WHERE title LIKE "),
+            Some("title".to_string())
+        );
+        assert_eq!(
+            compared_column_before("WHERE actor_id IN ("),
+            Some("actor_id".to_string())
+        );
+        assert_eq!(
+            compared_column_after(" <= length"),
+            Some("length".to_string())
+        );
+        assert_eq!(compared_column_before("LIMIT "), None);
+    }
+
+    #[test]
+    fn non_ascii_before_the_keyword() {
+        assert_eq!(
+            compared_column_before("WHERE İstanbul LIKE "),
+            Some("İstanbul".to_string())
+        );
+        assert_eq!(compared_column_before("İİ"), None);
+        assert_eq!(compared_column_after(" LİKE title"), None);
+    }
+
+    #[test]
+    fn array_slices_are_not_placeholders() {
+        let parsed = ParameterizedSql::parse(
+            "SELECT arr[1:n], arr[:m] FROM t WHERE a = :a",
+        );
+        assert_eq!(parsed.sql, "SELECT arr[1:n], arr[:m] FROM t WHERE a = $1");
+        assert_eq!(parsed.placeholders.len(), 1);
+        assert_eq!(parsed.placeholders[0].name, ":a");
+    }
+}
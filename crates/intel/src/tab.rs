@@ -1,19 +1,27 @@
 use rustorm::{
+    Column,
     ColumnName,
     TableName,
 };
 
 use crate::{
+    config,
     data_container::{
         DropdownInfo,
         IdentifierDisplay,
     },
-    field::Field,
-};
-use rustorm::{
-    Column,
-    Table,
+    field::{
+        ColumnDetail,
+        Field,
+    },
+    identifier_display::{
+        self,
+        ColumnStatistics,
+        DisplayHints,
+    },
+    table_intel::TableIntel,
 };
+use rustorm::Table;
 use serde::{
     Deserialize,
     Serialize,
@@ -38,11 +46,54 @@ pub struct Tab {
 pub struct IndirectTab {
     pub linker: TableName,
     pub tab: Tab,
+    /// the extra columns of the linker table, such as the role of the actor
+    /// in the film, which are shown together with the indirect records
+    pub linker_fields: Vec<Field>,
 }
 
 impl IndirectTab {
-    pub fn new(linker: TableName, tab: Tab) -> Self {
-        IndirectTab { linker, tab }
+    pub fn new(main_table: &TableName, linker: &Table, tab: Tab) -> Self {
+        let linker_fields = TableIntel(linker)
+            .get_linker_extra_columns(main_table, &tab.table_name)
+            .into_iter()
+            .map(|column| Self::linker_field(linker, column))
+            .collect();
+        IndirectTab {
+            linker: linker.name.clone(),
+            tab,
+            linker_fields,
+        }
+    }
+
+    /// the extra column of the linker is aliased in the indirect records,
+    /// so it doesn't clash with the columns of the indirect table
+    pub fn linker_column_alias(
+        linker: &TableName,
+        column_name: &ColumnName,
+    ) -> String {
+        format!("{}_{}", linker.name, column_name.name)
+    }
+
+    /// the field of the extra column, which refers to its alias
+    fn linker_field(linker: &Table, column: &Column) -> Field {
+        let mut field = Field::from_column(linker, column);
+        field.column_detail = ColumnDetail::Simple(
+            ColumnName {
+                name: Self::linker_column_alias(&linker.name, &column.name),
+                table: None,
+                alias: None,
+            },
+            column.specification.sql_type.clone(),
+        );
+        field
+    }
+
+    /// the tab of the indirect records followed by the extra fields of the
+    /// linker, in the same order as the columns of the indirect records
+    pub fn tab_with_linker_fields(&self) -> Tab {
+        let mut tab = self.tab.clone();
+        tab.fields.extend(self.linker_fields.iter().cloned());
+        tab
     }
 }
 
@@ -51,9 +102,10 @@ impl Tab {
         table: &Table,
         name: Option<String>,
         tables: &[Table],
+        hints: &DisplayHints,
     ) -> Self {
         let fields = Self::derive_fields(table, tables);
-        let display = Self::derive_display(table);
+        let display = identifier_display::derive_display(table, hints);
         let tab_name = match name {
             Some(name) => name,
             None => table.name.name.to_string(),
@@ -64,7 +116,7 @@ impl Tab {
             table_name: table.name.to_owned(),
             fields,
             is_view: table.is_view,
-            display,
+            display: Some(display),
         }
     }
 
@@ -78,104 +130,20 @@ impl Tab {
             .collect()
     }
 
-    pub fn derive_dropdowninfo(table: &Table) -> Option<DropdownInfo> {
-        match Self::derive_display(table) {
-            Some(display) => {
-                Some(DropdownInfo {
-                    source: table.name.clone(),
-                    display,
-                })
-            }
-            None => None,
-        }
-    }
-
-    /// an identifier column alone by itself
-    #[allow(clippy::if_same_then_else)]
-    fn is_identifier_column(table: &Table, column: &Column) -> bool {
-        let table_name = &table.name.name;
-        let column_name = &column.name.name;
-        if column_name == "name" {
-            true
-        } else if column_name == table_name {
-            true
-        } else if *column_name == format!("{}_name", table_name) {
-            true
-        } else if column_name == "title" {
-            true
-        } else if table_name == "user" || table_name == "users" {
-            column_name == "name"
-                || column_name == "username"
-                || column_name == "email"
-        } else {
-            false
-        }
-    }
-
-    // TODO: also make a function to do derive_image_display to detect
-    // images that are displayeable
-    fn derive_display(table: &Table) -> Option<IdentifierDisplay> {
-        let columns = &table.columns;
-        let pk: Vec<ColumnName> = table
-            .get_primary_column_names()
-            .iter()
-            .map(|ref column| (**column).to_owned())
-            .collect();
-
-        let non_pk_columns: Vec<ColumnName> = table
-            .get_non_primary_columns()
-            .iter()
-            .map(|column| column.name.to_owned())
-            .collect();
-
-        let single_identifier = columns
-            .iter()
-            .find(|column| Self::is_identifier_column(&table, column));
-        if let Some(single_identifier) = single_identifier {
-            return Some(IdentifierDisplay {
-                columns: vec![single_identifier.name.clone()],
-                separator: None,
-                pk: pk.clone(),
-            });
-        }
-        // if there is only 1 non primary column use it as the identifier column
-        else if non_pk_columns.len() == 1 {
-            return Some(IdentifierDisplay {
-                columns: non_pk_columns,
-                separator: None,
-                pk: pk.clone(),
-            });
-        }
-        // match the column name regardless of the table name
-        else {
-            let maybe_firstname = columns.iter().find(|column| {
-                let column_name = &column.name.name;
-                *column_name == "first_name" || *column_name == "firstname"
-            });
-
-            let maybe_lastname = columns.iter().find(|column| {
-                let column_name = &column.name.name;
-                *column_name == "last_name" || *column_name == "lastname"
-            });
-            if let Some(lastname) = maybe_lastname {
-                if let Some(firstname) = maybe_firstname {
-                    return Some(IdentifierDisplay {
-                        columns: vec![
-                            lastname.name.clone(),
-                            firstname.name.clone(),
-                        ],
-                        separator: Some(", ".into()),
-                        pk: pk.clone(),
-                    });
-                }
-            }
-        }
-
-        // always have idenfier display
-        Some(IdentifierDisplay {
-            columns: vec![],
-            separator: None,
-            pk: pk.clone(),
+    /// the dropdown of the table when the window of the table is not
+    /// available, derived with the same statistics as the windows
+    pub fn derive_dropdowninfo(
+        table: &Table,
+        statistics: &ColumnStatistics,
+    ) -> Option<DropdownInfo> {
+        let hints = DisplayHints {
+            config: &config::get_config(),
+            statistics,
+        };
+        let display = identifier_display::derive_display(table, &hints);
+        Some(DropdownInfo {
+            source: table.name.clone(),
+            display,
         })
     }
 
@@ -188,6 +156,36 @@ impl Tab {
         }
     }
 
+    /// the fields arranged to match the columns of a query result
+    pub fn fields_for_columns(&self, columns: &[String]) -> Vec<Field> {
+        columns
+            .iter()
+            .map(|column| {
+                match self.fields.iter().find(|field| {
+                    field.first_column_name().map(|c| &c.name) == Some(column)
+                }) {
+                    Some(field) => field.clone(),
+                    None => Field::from_result_column(column),
+                }
+            })
+            .collect()
+    }
+
+    /// all of the primary columns of this table are in the columns,
+    /// so the records can be identified
+    pub fn has_all_primary_columns(&self, columns: &[String]) -> bool {
+        let primary_columns: Vec<&ColumnName> = self
+            .fields
+            .iter()
+            .filter(|field| field.is_primary)
+            .flat_map(|field| field.column_names())
+            .collect();
+        !primary_columns.is_empty()
+            && primary_columns
+                .iter()
+                .all(|pk| columns.iter().any(|column| *column == pk.name))
+    }
+
     pub fn has_column_name(&self, column_name: &ColumnName) -> bool {
         self.fields
             .iter()
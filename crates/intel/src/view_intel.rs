@@ -0,0 +1,938 @@
+//! the views has no primary key and foreign keys, these are inferred by
+//! tracing the columns of the view back to the columns of the tables
+//! in its definition. The key of the view can also be set in the config.
+//! The keys are added to the views, so the windows of the views can show
+//! the detail of the records and their related records
+use crate::config::Config;
+use rustorm::{
+    table::{
+        ForeignKey,
+        Key,
+        TableKey,
+    },
+    ColumnName,
+    Dao,
+    EntityManager,
+    FromDao,
+    Table,
+    TableName,
+};
+use sqlparser::{
+    dialect::GenericSqlDialect,
+    sqlast::{
+        ASTNode,
+        JoinConstraint,
+        JoinOperator,
+        SQLOperator,
+        SQLSelect,
+        SQLSelectItem,
+        SQLSetExpr,
+        SQLStatement,
+        TableFactor,
+    },
+    sqlparser::Parser,
+};
+
+/// the column of the view which is selected from the column of a table
+#[derive(Debug, PartialEq, Clone)]
+pub struct ViewColumnSource {
+    pub view_column: String,
+    /// the alias of the table in the view or its name
+    pub qualifier: String,
+    pub table_name: TableName,
+    pub column_name: String,
+}
+
+/// a table in the FROM and the JOINs of the view
+#[derive(Debug, PartialEq, Clone)]
+pub struct ViewRelation {
+    /// the alias of the table in the view or its name
+    pub qualifier: String,
+    pub table_name: TableName,
+    /// the table is on the outer side of a join, its columns can be null
+    pub is_nullable: bool,
+}
+
+/// a column of a table in the view, the table is referred to by its qualifier
+#[derive(Debug, PartialEq, Clone)]
+pub struct QualifiedColumn {
+    pub qualifier: String,
+    pub column_name: String,
+}
+
+/// the tables in the FROM and JOINs of the view, in the order they appear
+/// and the columns of the view which are selected from them
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct ViewTrace {
+    pub relations: Vec<ViewRelation>,
+    pub columns: Vec<ViewColumnSource>,
+    /// the columns of the tables which are equal in the join conditions
+    /// and in the WHERE of the view
+    pub joined_columns: Vec<(QualifiedColumn, QualifiedColumn)>,
+    /// the tables and the subqueries which are not in the tables,
+    /// they can repeat the records of the other tables
+    pub untraced_relations: usize,
+    pub is_distinct: bool,
+}
+
+/// a table in the view definition, referred to by its alias or its name
+struct Relation<'t> {
+    qualifier: String,
+    table: &'t Table,
+    is_nullable: bool,
+}
+
+/// add the key set in the config or the key inferred from the definition
+/// of the view, and the foreign keys of the columns which are selected
+/// from the foreign keys of the tables
+pub fn add_view_keys(
+    em: &mut EntityManager,
+    tables: &mut [Table],
+    config: &Config,
+) {
+    let mut view_keys: Vec<(usize, Vec<TableKey>)> = vec![];
+    for (index, view) in tables.iter().enumerate() {
+        if !view.is_view || !view.get_primary_column_names().is_empty() {
+            continue;
+        }
+        let trace = get_view_definition(em, &view.name)
+            .and_then(|definition| trace_view(&definition, tables))
+            .unwrap_or_default();
+        let key = match config.view_key_for(&view.name) {
+            Some(key) => configured_key(view, key),
+            None => infer_view_key(&trace, tables),
+        };
+        let mut table_keys: Vec<TableKey> =
+            infer_view_foreign_keys(view, &trace, tables)
+                .into_iter()
+                .map(TableKey::ForeignKey)
+                .collect();
+        if let Some(key) = key {
+            info!("View key of {}: {:?}", view.name.complete_name(), key);
+            table_keys.push(TableKey::PrimaryKey(Key {
+                name: None,
+                columns: key
+                    .iter()
+                    .map(|c| ColumnName::from(c.as_str()))
+                    .collect(),
+            }));
+        }
+        view_keys.push((index, table_keys));
+    }
+    for (index, table_keys) in view_keys {
+        tables[index].table_key.extend(table_keys);
+    }
+}
+
+/// the configured key, when all of its columns are in the view
+fn configured_key(view: &Table, key: &[String]) -> Option<Vec<String>> {
+    let is_valid = !key.is_empty()
+        && key
+            .iter()
+            .all(|c| view.columns.iter().any(|column| column.name.name == *c));
+    if is_valid {
+        Some(key.to_vec())
+    } else {
+        warn!(
+            "The key {:?} is not in the columns of the view {}",
+            key,
+            view.name.complete_name()
+        );
+        None
+    }
+}
+
+struct ViewDefinition(Option<String>);
+
+impl FromDao for ViewDefinition {
+    fn from_dao(dao: &Dao) -> Self {
+        ViewDefinition(dao.get("definition").ok())
+    }
+}
+
+/// the select statement of the view, from the information schema
+fn get_view_definition(
+    em: &mut EntityManager,
+    view_name: &TableName,
+) -> Option<String> {
+    let schema = view_name.schema.clone().unwrap_or_default();
+    let result: Result<Vec<ViewDefinition>, _> = em.execute_sql_with_return(
+        "SELECT view_definition AS definition
+            FROM information_schema.views
+            WHERE table_name = $1 AND ($2 = '' OR table_schema = $2)",
+        &[&view_name.name, &schema],
+    );
+    match result {
+        Ok(definitions) => definitions.into_iter().find_map(|d| d.0),
+        Err(e) => {
+            info!(
+                "Unable to get the definition of the view {}: {}",
+                view_name.complete_name(),
+                e
+            );
+            None
+        }
+    }
+}
+
+/// trace the columns of the view to the columns of the tables,
+/// only the columns which are selected as is are traced.
+/// Aggregated views and set operations such as UNION are not traced
+/// since their records are not the records of the tables
+pub fn trace_view(definition: &str, tables: &[Table]) -> Option<ViewTrace> {
+    let sql = definition.trim().trim_end_matches(';').to_string();
+    let statements = match Parser::parse_sql(&GenericSqlDialect {}, sql) {
+        Ok(statements) => statements,
+        Err(e) => {
+            info!("Unable to parse the view definition: {:?}", e);
+            return None;
+        }
+    };
+    let query = match statements.first() {
+        Some(SQLStatement::SQLQuery(query)) => query,
+        _ => return None,
+    };
+    let select = match query.body {
+        SQLSetExpr::Select(ref select) => select,
+        _ => return None,
+    };
+    if !select.group_by.is_empty() {
+        return None;
+    }
+    let (relations, untraced_relations) = get_relations(select, tables);
+    let mut columns = vec![];
+    for item in select.projection.iter() {
+        match item {
+            SQLSelectItem::Wildcard => {
+                for relation in relations.iter() {
+                    columns.extend(all_columns(relation));
+                }
+            }
+            SQLSelectItem::QualifiedWildcard(name) => {
+                let qualifier = unquote(&name.to_string()).to_string();
+                if let Some(relation) =
+                    relations.iter().find(|r| r.qualifier == qualifier)
+                {
+                    columns.extend(all_columns(relation));
+                }
+            }
+            SQLSelectItem::UnnamedExpression(expr) => {
+                if let Some((relation, column_name)) =
+                    resolve_column(expr, &relations)
+                {
+                    columns.push(ViewColumnSource {
+                        view_column: column_name.to_string(),
+                        qualifier: relation.qualifier.to_string(),
+                        table_name: relation.table.name.clone(),
+                        column_name: column_name.to_string(),
+                    });
+                }
+            }
+            SQLSelectItem::ExpressionWithAlias { expr, alias } => {
+                if let Some((relation, column_name)) =
+                    resolve_column(expr, &relations)
+                {
+                    columns.push(ViewColumnSource {
+                        view_column: unquote(alias).to_string(),
+                        qualifier: relation.qualifier.to_string(),
+                        table_name: relation.table.name.clone(),
+                        column_name: column_name.to_string(),
+                    });
+                }
+            }
+        }
+    }
+    let joined_columns = get_joined_columns(select, &relations);
+    Some(ViewTrace {
+        relations: relations
+            .iter()
+            .map(|relation| ViewRelation {
+                qualifier: relation.qualifier.to_string(),
+                table_name: relation.table.name.clone(),
+                is_nullable: relation.is_nullable,
+            })
+            .collect(),
+        columns,
+        joined_columns,
+        untraced_relations,
+        is_distinct: select.distinct,
+    })
+}
+
+/// the tables in the FROM and the JOINs which are in the tables
+/// and the number of the other tables and subqueries.
+/// The joined table of a LEFT JOIN and the tables before a RIGHT JOIN
+/// can be null
+fn get_relations<'t>(
+    select: &SQLSelect,
+    tables: &'t [Table],
+) -> (Vec<Relation<'t>>, usize) {
+    let mut relations: Vec<Relation> = vec![];
+    let mut untraced = 0;
+    let factors = select
+        .relation
+        .iter()
+        .map(|relation| (relation, None))
+        .chain(
+            select
+                .joins
+                .iter()
+                .map(|join| (&join.relation, Some(&join.join_operator))),
+        );
+    for (factor, join_operator) in factors {
+        let (is_nullable, nulls_previous) = match join_operator {
+            Some(JoinOperator::LeftOuter(_)) => (true, false),
+            Some(JoinOperator::RightOuter(_)) => (false, true),
+            Some(JoinOperator::FullOuter(_)) => (true, true),
+            _ => (false, false),
+        };
+        if nulls_previous {
+            for relation in relations.iter_mut() {
+                relation.is_nullable = true;
+            }
+        }
+        let relation = match factor {
+            TableFactor::Table { name, .. } => {
+                let name = name.to_string();
+                let table_name = TableName::from(unquote_all(&name).as_str());
+                let table = find_table(&table_name, tables);
+                match (table, factor_qualifier(factor)) {
+                    (Some(table), Some(qualifier)) => {
+                        Some(Relation {
+                            qualifier,
+                            table,
+                            is_nullable,
+                        })
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        match relation {
+            Some(relation) => relations.push(relation),
+            None => untraced += 1,
+        }
+    }
+    (relations, untraced)
+}
+
+/// the alias of the table or its name when it has no alias
+fn factor_qualifier(factor: &TableFactor) -> Option<String> {
+    match factor {
+        TableFactor::Table { name, alias, .. } => {
+            match alias {
+                Some(alias) => Some(unquote(alias).to_string()),
+                None => {
+                    let name = unquote_all(&name.to_string());
+                    Some(TableName::from(name.as_str()).name)
+                }
+            }
+        }
+        _ => None,
+    }
+}
+
+impl<'t> Relation<'t> {
+    fn has_column(&self, column: &str) -> bool {
+        self.table.get_column(&ColumnName::from(column)).is_some()
+    }
+
+    fn qualified_column(&self, column: &str) -> QualifiedColumn {
+        QualifiedColumn {
+            qualifier: self.qualifier.to_string(),
+            column_name: column.to_string(),
+        }
+    }
+}
+
+/// the columns which are equal in the ON of the joins and in the WHERE,
+/// the USING of a join is the same column of the joined table and of the
+/// tables before it
+fn get_joined_columns(
+    select: &SQLSelect,
+    relations: &[Relation],
+) -> Vec<(QualifiedColumn, QualifiedColumn)> {
+    let mut joined_columns = vec![];
+    for join in select.joins.iter() {
+        let constraint = match &join.join_operator {
+            JoinOperator::Inner(constraint)
+            | JoinOperator::LeftOuter(constraint)
+            | JoinOperator::RightOuter(constraint)
+            | JoinOperator::FullOuter(constraint) => constraint,
+            _ => continue,
+        };
+        match constraint {
+            JoinConstraint::On(expr) => {
+                add_equal_columns(expr, relations, &mut joined_columns)
+            }
+            JoinConstraint::Using(columns) => {
+                let position =
+                    factor_qualifier(&join.relation).and_then(|qualifier| {
+                        relations.iter().position(|relation| {
+                            relation.qualifier == qualifier
+                        })
+                    });
+                let position = match position {
+                    Some(position) => position,
+                    None => continue,
+                };
+                let joined = &relations[position];
+                for column in columns.iter().map(|column| unquote(column)) {
+                    for relation in relations[..position]
+                        .iter()
+                        .filter(|relation| relation.has_column(column))
+                    {
+                        joined_columns.push((
+                            relation.qualified_column(column),
+                            joined.qualified_column(column),
+                        ));
+                    }
+                }
+            }
+            JoinConstraint::Natural => (),
+        }
+    }
+    if let Some(selection) = &select.selection {
+        add_equal_columns(selection, relations, &mut joined_columns);
+    }
+    joined_columns
+}
+
+/// the columns compared with `=` in the conditions joined by AND
+fn add_equal_columns(
+    expr: &ASTNode,
+    relations: &[Relation],
+    joined_columns: &mut Vec<(QualifiedColumn, QualifiedColumn)>,
+) {
+    match expr {
+        ASTNode::SQLBinaryExpr {
+            left,
+            op: SQLOperator::And,
+            right,
+        } => {
+            add_equal_columns(left, relations, joined_columns);
+            add_equal_columns(right, relations, joined_columns);
+        }
+        ASTNode::SQLBinaryExpr {
+            left,
+            op: SQLOperator::Eq,
+            right,
+        } => {
+            let column = |expr| {
+                resolve_column(expr, relations)
+                    .map(|(relation, name)| relation.qualified_column(&name))
+            };
+            if let (Some(left), Some(right)) = (column(left), column(right)) {
+                joined_columns.push((left, right));
+            }
+        }
+        ASTNode::SQLNested(expr) => {
+            add_equal_columns(expr, relations, joined_columns)
+        }
+        _ => (),
+    }
+}
+
+/// the table with the same name, the schema is matched when it is specified
+fn find_table<'t>(
+    table_name: &TableName,
+    tables: &'t [Table],
+) -> Option<&'t Table> {
+    tables.iter().find(|table| {
+        table.name.name == table_name.name
+            && (table_name.schema.is_none()
+                || table.name.schema == table_name.schema)
+    })
+}
+
+fn all_columns(relation: &Relation) -> Vec<ViewColumnSource> {
+    relation
+        .table
+        .columns
+        .iter()
+        .map(|column| ViewColumnSource {
+            view_column: column.name.name.to_string(),
+            qualifier: relation.qualifier.to_string(),
+            table_name: relation.table.name.clone(),
+            column_name: column.name.name.to_string(),
+        })
+        .collect()
+}
+
+/// the table and the column of the expression when it is only a column,
+/// a column without the table qualifier is looked up in all the tables
+fn resolve_column<'r, 't>(
+    expr: &ASTNode,
+    relations: &'r [Relation<'t>],
+) -> Option<(&'r Relation<'t>, String)> {
+    match expr {
+        ASTNode::SQLIdentifier(name) => {
+            let name = unquote(name);
+            let mut found = relations
+                .iter()
+                .filter(|relation| relation.has_column(name));
+            // an ambiguous column is not traced
+            match (found.next(), found.next()) {
+                (Some(relation), None) => Some((relation, name.to_string())),
+                _ => None,
+            }
+        }
+        ASTNode::SQLCompoundIdentifier(idents) if idents.len() >= 2 => {
+            let name = unquote(&idents[idents.len() - 1]);
+            let qualifier = unquote(&idents[idents.len() - 2]);
+            relations
+                .iter()
+                .find(|relation| relation.qualifier == qualifier)
+                .filter(|relation| relation.has_column(name))
+                .map(|relation| (relation, name.to_string()))
+        }
+        ASTNode::SQLNested(expr) | ASTNode::SQLCast { expr, .. } => {
+            resolve_column(expr, relations)
+        }
+        _ => None,
+    }
+}
+
+/// the key of the view is the primary key of a table which reaches all the
+/// other tables through their many to one foreign keys, since the joins do
+/// not repeat its records. Otherwise it is the primary keys of all the
+/// tables combined, when none of them can be null.
+/// Views with DISTINCT or with tables that are not traced have no key
+pub fn infer_view_key(
+    trace: &ViewTrace,
+    tables: &[Table],
+) -> Option<Vec<String>> {
+    if trace.is_distinct
+        || trace.untraced_relations > 0
+        || trace.relations.is_empty()
+    {
+        return None;
+    }
+    let keyed = trace
+        .relations
+        .iter()
+        .filter(|relation| !relation.is_nullable)
+        .filter(|relation| reaches_all_relations(trace, tables, relation))
+        .find_map(|relation| relation_key(trace, tables, relation));
+    if keyed.is_some() {
+        return keyed;
+    }
+    if trace.relations.iter().any(|relation| relation.is_nullable) {
+        return None;
+    }
+    let keys = trace
+        .relations
+        .iter()
+        .map(|relation| relation_key(trace, tables, relation))
+        .collect::<Option<Vec<Vec<String>>>>()?;
+    Some(keys.concat())
+}
+
+/// the view columns of the primary key of the table, when all of its
+/// primary columns are in the view
+fn relation_key(
+    trace: &ViewTrace,
+    tables: &[Table],
+    relation: &ViewRelation,
+) -> Option<Vec<String>> {
+    let table = tables
+        .iter()
+        .find(|table| table.name == relation.table_name)?;
+    let primary_columns = table.get_primary_column_names();
+    if primary_columns.is_empty() {
+        return None;
+    }
+    primary_columns
+        .iter()
+        .map(|primary| {
+            trace
+                .columns
+                .iter()
+                .find(|source| {
+                    source.qualifier == relation.qualifier
+                        && source.column_name == primary.name
+                })
+                .map(|source| source.view_column.to_string())
+        })
+        .collect()
+}
+
+/// all the other tables are reached from the table, directly or through
+/// the tables it reaches, by joining a foreign key to the referred columns
+fn reaches_all_relations(
+    trace: &ViewTrace,
+    tables: &[Table],
+    keyed: &ViewRelation,
+) -> bool {
+    let mut reached: Vec<&ViewRelation> = vec![keyed];
+    while let Some(next) = trace.relations.iter().find(|relation| {
+        !reached.contains(relation)
+            && reached
+                .iter()
+                .any(|from| is_many_to_one(trace, tables, from, relation))
+    }) {
+        reached.push(next);
+    }
+    reached.len() == trace.relations.len()
+}
+
+/// the table is joined by all the columns of a foreign key
+/// to the columns it refers to
+fn is_many_to_one(
+    trace: &ViewTrace,
+    tables: &[Table],
+    from: &ViewRelation,
+    to: &ViewRelation,
+) -> bool {
+    let table = tables.iter().find(|table| table.name == from.table_name);
+    let table = match table {
+        Some(table) => table,
+        None => return false,
+    };
+    table.get_foreign_keys().iter().any(|foreign_key| {
+        foreign_key.foreign_table == to.table_name
+            && foreign_key.columns.len() == foreign_key.referred_columns.len()
+            && foreign_key
+                .columns
+                .iter()
+                .zip(foreign_key.referred_columns.iter())
+                .all(|(column, referred)| {
+                    trace.is_joined(
+                        &from.qualifier,
+                        &column.name,
+                        &to.qualifier,
+                        &referred.name,
+                    )
+                })
+    })
+}
+
+impl ViewTrace {
+    /// the 2 columns are equal in the conditions of the view
+    fn is_joined(
+        &self,
+        qualifier1: &str,
+        column1: &str,
+        qualifier2: &str,
+        column2: &str,
+    ) -> bool {
+        let is_column = |c: &QualifiedColumn, qualifier: &str, column: &str| {
+            c.qualifier == qualifier && c.column_name == column
+        };
+        self.joined_columns.iter().any(|(left, right)| {
+            (is_column(left, qualifier1, column1)
+                && is_column(right, qualifier2, column2))
+                || (is_column(left, qualifier2, column2)
+                    && is_column(right, qualifier1, column1))
+        })
+    }
+}
+
+/// the columns of the view which are selected from the single column
+/// foreign keys of the tables refers to the same tables
+pub fn infer_view_foreign_keys(
+    view: &Table,
+    trace: &ViewTrace,
+    tables: &[Table],
+) -> Vec<ForeignKey> {
+    trace
+        .columns
+        .iter()
+        .filter(|source| {
+            view.columns
+                .iter()
+                .any(|c| c.name.name == source.view_column)
+        })
+        .filter_map(|source| {
+            let table = tables
+                .iter()
+                .find(|table| table.name == source.table_name)?;
+            let foreign_key =
+                table.get_foreign_keys().into_iter().find(|fk| {
+                    fk.columns.len() == 1
+                        && fk.columns[0].name == source.column_name
+                })?;
+            Some(ForeignKey {
+                name: Some(format!(
+                    "view_{}_{}_fkey",
+                    view.name.name, source.view_column
+                )),
+                columns: vec![ColumnName::from(source.view_column.as_str())],
+                foreign_table: foreign_key.foreign_table.clone(),
+                referred_columns: foreign_key.referred_columns.clone(),
+            })
+        })
+        .collect()
+}
+
+/// quoted identifiers such as `"first_name"` are matched without the quotes
+fn unquote(name: &str) -> &str {
+    name.trim_matches('"')
+}
+
+/// the quotes of each part of the qualified name such as `"public"."film"`
+fn unquote_all(name: &str) -> String {
+    name.split('.').map(unquote).collect::<Vec<_>>().join(".")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_db;
+    use rustorm::types::SqlType;
+
+    /// the films with their language and the actors of the films
+    fn tables() -> Vec<Table> {
+        vec![
+            test_db::table(
+                "language",
+                &[("language_id", SqlType::Int), ("name", SqlType::Text)],
+                vec![test_db::primary_key(&["language_id"])],
+            ),
+            test_db::table(
+                "film",
+                &[
+                    ("film_id", SqlType::Int),
+                    ("title", SqlType::Text),
+                    ("language_id", SqlType::Int),
+                ],
+                vec![
+                    test_db::primary_key(&["film_id"]),
+                    test_db::foreign_key(
+                        &["language_id"],
+                        "language",
+                        &["language_id"],
+                    ),
+                ],
+            ),
+            test_db::table(
+                "actor",
+                &[
+                    ("actor_id", SqlType::Int),
+                    ("first_name", SqlType::Text),
+                    ("last_name", SqlType::Text),
+                ],
+                vec![test_db::primary_key(&["actor_id"])],
+            ),
+            test_db::table(
+                "film_actor",
+                &[("actor_id", SqlType::Int), ("film_id", SqlType::Int)],
+                vec![
+                    test_db::primary_key(&["actor_id", "film_id"]),
+                    test_db::foreign_key(&["actor_id"], "actor", &["actor_id"]),
+                    test_db::foreign_key(&["film_id"], "film", &["film_id"]),
+                ],
+            ),
+        ]
+    }
+
+    fn film_list() -> Table {
+        let mut view = test_db::table(
+            "film_list",
+            &[
+                ("fid", SqlType::Int),
+                ("title", SqlType::Text),
+                ("language_id", SqlType::Int),
+            ],
+            vec![],
+        );
+        view.is_view = true;
+        view
+    }
+
+    fn strings(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    fn key(definition: &str) -> Option<Vec<String>> {
+        let tables = tables();
+        let trace = trace_view(definition, &tables).expect("must be traced");
+        infer_view_key(&trace, &tables)
+    }
+
+    #[test]
+    fn quoted_names() {
+        assert_eq!(unquote("\"first_name\""), "first_name");
+        assert_eq!(unquote_all("\"public\".\"film\""), "public.film");
+        assert_eq!(unquote_all("film"), "film");
+    }
+
+    #[test]
+    fn unknown_tables_are_not_traced() {
+        let trace =
+            trace_view("SELECT film.film_id AS fid FROM film;", &[]).unwrap();
+        assert!(trace.relations.is_empty());
+        assert!(trace.columns.is_empty());
+        assert_eq!(trace.untraced_relations, 1);
+        assert_eq!(infer_view_key(&trace, &[]), None);
+        assert_eq!(infer_view_key(&ViewTrace::default(), &[]), None);
+    }
+
+    #[test]
+    fn aggregated_views_are_not_traced() {
+        let definition =
+            "SELECT film_id, COUNT(*) FROM film_actor GROUP BY film_id";
+        assert_eq!(trace_view(definition, &[]), None);
+        assert_eq!(trace_view("not a select", &[]), None);
+    }
+
+    #[test]
+    fn aliases_and_qualified_wildcards_are_traced() {
+        let trace = trace_view(
+            "SELECT f.*, l.name AS language FROM film AS f \
+             JOIN language AS l ON f.language_id = l.language_id",
+            &tables(),
+        )
+        .unwrap();
+        let qualifiers: Vec<&str> = trace
+            .relations
+            .iter()
+            .map(|relation| relation.qualifier.as_str())
+            .collect();
+        assert_eq!(qualifiers, vec!["f", "l"]);
+        let columns: Vec<(&str, &str, &str)> = trace
+            .columns
+            .iter()
+            .map(|source| {
+                (
+                    source.view_column.as_str(),
+                    source.qualifier.as_str(),
+                    source.column_name.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            columns,
+            vec![
+                ("film_id", "f", "film_id"),
+                ("title", "f", "title"),
+                ("language_id", "f", "language_id"),
+                ("language", "l", "name"),
+            ]
+        );
+        assert!(trace.is_joined("l", "language_id", "f", "language_id"));
+    }
+
+    #[test]
+    fn ambiguous_columns_are_not_traced() {
+        let trace = trace_view(
+            "SELECT film_id, title FROM film \
+             JOIN film_actor ON film.film_id = film_actor.film_id",
+            &tables(),
+        )
+        .unwrap();
+        let columns: Vec<&str> = trace
+            .columns
+            .iter()
+            .map(|source| source.view_column.as_str())
+            .collect();
+        assert_eq!(columns, vec!["title"]);
+    }
+
+    /// the films are not repeated by joining the language of each film,
+    /// but the languages are repeated for each of their films
+    #[test]
+    fn key_of_the_many_side() {
+        assert_eq!(
+            key("SELECT f.film_id, f.title, l.name FROM film f \
+                 JOIN language l ON f.language_id = l.language_id"),
+            Some(strings(&["film_id"]))
+        );
+        assert_eq!(
+            key("SELECT l.language_id, l.name, f.title FROM language l \
+                 JOIN film f ON f.language_id = l.language_id"),
+            None
+        );
+    }
+
+    #[test]
+    fn key_of_the_linker() {
+        assert_eq!(
+            key("SELECT fa.actor_id, fa.film_id, a.first_name, l.name \
+                 FROM film_actor fa \
+                 JOIN actor a ON a.actor_id = fa.actor_id \
+                 JOIN film f ON f.film_id = fa.film_id \
+                 JOIN language l ON l.language_id = f.language_id"),
+            Some(strings(&["actor_id", "film_id"]))
+        );
+        assert_eq!(
+            key("SELECT film_actor.actor_id, film_actor.film_id, title \
+                 FROM film_actor JOIN film USING (film_id)"),
+            Some(strings(&["actor_id", "film_id"]))
+        );
+    }
+
+    /// the tables which are not joined by their foreign keys
+    /// repeat the records of each other
+    #[test]
+    fn keys_are_combined() {
+        assert_eq!(
+            key("SELECT f.film_id, a.actor_id AS actor, f.title \
+                 FROM film f CROSS JOIN actor a"),
+            Some(strings(&["film_id", "actor"]))
+        );
+        assert_eq!(
+            key("SELECT f.film_id, l.language_id AS lid FROM film f \
+                 JOIN language l ON l.name = f.title"),
+            Some(strings(&["film_id", "lid"]))
+        );
+        assert_eq!(
+            key("SELECT f.film_id, l.name FROM film f \
+                 JOIN language l ON l.name = f.title"),
+            None
+        );
+    }
+
+    #[test]
+    fn no_key_of_distinct_and_nullable_tables() {
+        assert_eq!(key("SELECT DISTINCT f.film_id, f.title FROM film f"), None);
+        // the language of a film can be missing, the film is not repeated
+        assert_eq!(
+            key("SELECT f.film_id, l.name FROM film f \
+                 LEFT JOIN language l ON f.language_id = l.language_id"),
+            Some(strings(&["film_id"]))
+        );
+        assert_eq!(
+            key("SELECT f.film_id, l.language_id FROM film f \
+                 RIGHT JOIN language l ON f.language_id = l.language_id"),
+            None
+        );
+        assert_eq!(
+            key("SELECT f.film_id, a.actor_id FROM film f \
+                 LEFT JOIN actor a ON a.first_name = f.title"),
+            None
+        );
+    }
+
+    #[test]
+    fn foreign_keys_of_the_view() {
+        let tables = tables();
+        let trace = trace_view(
+            "SELECT f.film_id AS fid, f.title, f.language_id FROM film f",
+            &tables,
+        )
+        .unwrap();
+        assert_eq!(
+            infer_view_foreign_keys(&film_list(), &trace, &tables),
+            vec![ForeignKey {
+                name: Some("view_film_list_language_id_fkey".to_string()),
+                columns: vec![ColumnName::from("language_id")],
+                foreign_table: TableName::from("language"),
+                referred_columns: vec![ColumnName::from("language_id")],
+            }]
+        );
+        assert_eq!(infer_view_key(&trace, &tables), Some(strings(&["fid"])));
+    }
+
+    #[test]
+    fn configured_key_is_in_the_view() {
+        let view = film_list();
+        assert_eq!(
+            configured_key(&view, &strings(&["fid"])),
+            Some(strings(&["fid"]))
+        );
+        assert_eq!(configured_key(&view, &strings(&["film_id"])), None);
+        assert_eq!(configured_key(&view, &[]), None);
+    }
+}
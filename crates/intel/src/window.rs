@@ -1,4 +1,10 @@
 use crate::{
+    config::Config,
+    data_tree::SelfReference,
+    identifier_display::{
+        ColumnStatistics,
+        DisplayHints,
+    },
     tab::{
         IndirectTab,
         Tab,
@@ -49,6 +55,10 @@ pub struct Window {
     pub indirect_tabs: Vec<IndirectTab>,
 
     pub is_view: bool,
+
+    /// the main table refers to itself, such as the parent of a category,
+    /// its records can be shown as a tree
+    pub self_reference: Option<SelfReference>,
 }
 
 impl Window {
@@ -59,21 +69,24 @@ impl Window {
         has_many: &[&Table],
         indirect: &[IndirectTable],
         all_tables: &[Table],
+        hints: &DisplayHints,
     ) -> Self {
-        let main_tab: Tab = Tab::from_table(main_table, None, all_tables);
+        let main_tab: Tab =
+            Tab::from_table(main_table, None, all_tables, hints);
         let one_one_tabs: Vec<Tab> = one_one
             .iter()
-            .map(|t| Tab::from_table(t, None, all_tables))
+            .map(|t| Tab::from_table(t, None, all_tables, hints))
             .collect();
         let has_one_tabs: Vec<Tab> = has_one
             .iter()
-            .map(|t| Tab::from_table(t, None, all_tables))
+            .map(|t| Tab::from_table(t, None, all_tables, hints))
             .collect();
         let has_many_tabs: Vec<Tab> = has_many
             .iter()
-            .map(|t| Tab::from_table(t, None, all_tables))
+            .map(|t| Tab::from_table(t, None, all_tables, hints))
             .collect();
         let is_view = main_tab.is_view;
+        let self_reference = TableIntel(main_table).get_self_reference();
 
         let indirect_tabs: Vec<IndirectTab> = indirect
             .iter()
@@ -89,8 +102,14 @@ impl Window {
                     None
                 };
                 IndirectTab::new(
-                    t.linker.name.clone(),
-                    Tab::from_table(t.indirect_table, tab_name, all_tables),
+                    &main_table.name,
+                    t.linker,
+                    Tab::from_table(
+                        t.indirect_table,
+                        tab_name,
+                        all_tables,
+                        hints,
+                    ),
                 )
             })
             .collect();
@@ -104,6 +123,7 @@ impl Window {
             has_many_tabs,
             indirect_tabs,
             is_view,
+            self_reference,
         }
     }
 
@@ -151,8 +171,15 @@ pub struct GroupedWindow {
 
 /// extract all the tables and create a window object for each that can
 /// be a window, cache them for later use, so as not to keeping redoing
-/// analytical and calculations
-pub fn derive_all_windows(tables: &[Table]) -> Vec<Window> {
+/// analytical and calculations.
+/// The identifier display of the tables are derived with the statistics of
+/// the columns unless they are set in the config
+pub fn derive_all_windows(
+    tables: &[Table],
+    config: &Config,
+    statistics: &ColumnStatistics,
+) -> Vec<Window> {
+    let hints = DisplayHints { config, statistics };
     let mut all_windows = Vec::with_capacity(tables.len());
     for table in tables {
         let table_intel = TableIntel(table);
@@ -173,6 +200,7 @@ pub fn derive_all_windows(tables: &[Table]) -> Vec<Window> {
                 &has_many_tables,
                 &indirect_tables,
                 &tables,
+                &hints,
             );
             all_windows.push(window);
         }
@@ -193,7 +221,11 @@ mod tests {
         assert!(em.is_ok());
         let em = em.unwrap();
         let tables = em.get_all_tables().unwrap();
-        let windows = derive_all_windows(&tables);
+        let windows = derive_all_windows(
+            &tables,
+            &Config::default(),
+            &ColumnStatistics::default(),
+        );
         //assert_eq!(windows.len(), 12); // 12 when not including owned windows
         assert_eq!(windows.len(), 26); // 26 when owned tables can be windows too
     }
@@ -1,23 +1,71 @@
 use crate::{
     credentials::Credentials,
     error::ServiceError,
+    export,
     global,
     session,
+    store,
 };
 use actix_web::{
+    error::BlockingError,
     web,
     Error,
     HttpRequest,
     HttpResponse,
 };
 use diwata_intel::{
+    data_aggregate::{
+        self,
+        ColumnAggregate,
+    },
+    data_container::{
+        Filter,
+        QueryResult,
+        SaveContainer,
+        Sort,
+    },
+    data_group::{
+        self,
+        GroupRequest,
+        GroupResult,
+    },
+    data_import::{
+        self,
+        ImportRequest,
+        ImportSummary,
+    },
+    data_modify,
     data_read,
+    data_tree::{
+        self,
+        TreeNode,
+    },
+    error::IntelError,
+    query_history::{
+        QueryHistory,
+        SavedQuery,
+    },
+    query_plan::QueryPlan,
+    sql_guard::{
+        SqlViolation,
+        StatementKind,
+    },
+    sql_params::Placeholder,
     Dao,
     TableName,
+    Value,
 };
 use futures::future::Future;
-use serde::Deserialize;
-use std::convert::TryFrom;
+use log::warn;
+use rustorm::Table;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::{
+    convert::TryFrom,
+    time::Instant,
+};
 
 pub fn require_credentials(req: &HttpRequest) -> Result<(), ServiceError> {
     let is_required = global::is_login_required().unwrap();
@@ -69,9 +117,25 @@ pub fn windows(
 #[derive(Deserialize)]
 pub struct SqlParam {
     sql: String,
+    /// ron serialized values of the placeholders in the sql
+    params: Option<String>,
 }
 
-// FIXME: learn how to do custom error in actix
+/// deserialize the ron serialized values of the placeholders
+pub(crate) fn deserialize_params(
+    params: &Option<String>,
+) -> Result<Vec<Value>, ServiceError> {
+    match params {
+        Some(params) => {
+            ron::de::from_str(params)
+                .map_err(|e| ServiceError::GenericError(e.to_string()))
+        }
+        None => Ok(vec![]),
+    }
+}
+
+/// executes the ad-hoc sql query with the restrictions imposed to the user,
+/// violations are serialized in the response body with a BAD_REQUEST status
 pub fn sql(
     req: HttpRequest,
     sql_param: web::Query<SqlParam>,
@@ -79,27 +143,496 @@ pub fn sql(
     require_credentials(&req).expect("Should have credentials");
     let credentials: Result<Credentials, ServiceError> =
         TryFrom::try_from(&req);
+    let credentials = credentials.ok();
 
-    web::block(move || {
-        let context = session::create_context(credentials.ok())
-            .expect("unable to create context");
+    web::block(move || -> Result<QueryResult, ServiceError> {
+        let params = deserialize_params(&sql_param.params)?;
+        execute_and_record_sql(credentials, &sql_param.sql, &params)
+    })
+    .then(ron_response_with_violation)
+}
+
+/// the placeholders in the sql with their inferred types
+pub fn sql_params(
+    req: HttpRequest,
+    sql_param: web::Query<SqlParam>,
+) -> impl Future<Item = HttpResponse, Error = Error> {
+    require_credentials(&req).expect("Should have credentials");
+    let credentials: Result<Credentials, ServiceError> =
+        TryFrom::try_from(&req);
+    let credentials = credentials.ok();
 
-        let mut dm = global::get_pool_dm().expect("must get dm");
-        let mut em = global::get_pool_em().expect("must get em");
-        data_read::execute_sql_query(&context, &mut em, &mut dm, &sql_param.sql)
+    web::block(move || -> Result<Vec<Placeholder>, ServiceError> {
+        let context = session::create_context(credentials)?;
+        Ok(data_read::get_sql_placeholders(&context, &sql_param.sql))
     })
-    .from_err()
-    .then(move |rows| {
-        match rows {
-            Ok(rows) => {
-                Ok(HttpResponse::Ok().body(
-                    ron::ser::to_string(&rows)
-                        .expect("unable to serialize to ron"),
-                ))
+    .then(ron_response_with_violation)
+}
+
+/// save the edited rows of a query result, the ron serialized container
+/// is posted in the body since it can be too large for a url.
+/// The saved records are returned so the rows can be refreshed
+pub fn save_container(
+    req: HttpRequest,
+    body: String,
+) -> impl Future<Item = HttpResponse, Error = Error> {
+    require_credentials(&req).expect("Should have credentials");
+    let credentials: Result<Credentials, ServiceError> =
+        TryFrom::try_from(&req);
+    let credentials = credentials.ok();
+
+    web::block(move || -> Result<Vec<Dao>, ServiceError> {
+        let username = credentials.as_ref().map(|c| c.username.to_string());
+        let db_url = global::get_db_url()?;
+        let sql_options = global::get_sql_guard()?
+            .options_for(username.as_ref().map(String::as_str), &db_url);
+        if !sql_options.allow_write {
+            return Err(ServiceError::from(IntelError::SqlViolation(
+                SqlViolation::WriteNotAllowed(StatementKind::Update),
+            )));
+        }
+        let container: SaveContainer = ron::de::from_str(&body)
+            .map_err(|e| ServiceError::GenericError(e.to_string()))?;
+        let context = session::create_context(credentials.clone())?;
+        let tables: Vec<Table> = context.tables.values().cloned().collect();
+        let (_em, mut dm) = crate::session::get_em_dm(credentials)?;
+        let saved = data_modify::save_container(&mut dm, &tables, &container)?;
+        Ok(saved)
+    })
+    .then(ron_response_with_violation)
+}
+
+/// import the rows of a csv into the table, the ron serialized request
+/// is posted in the body since the csv can be large.
+/// A dry-run is allowed for users that are not allowed to write
+pub fn import(
+    req: HttpRequest,
+    table_name_param: web::Path<String>,
+    body: String,
+) -> impl Future<Item = HttpResponse, Error = Error> {
+    require_credentials(&req).expect("Should have credentials");
+    let credentials: Result<Credentials, ServiceError> =
+        TryFrom::try_from(&req);
+    let credentials = credentials.ok();
+
+    web::block(move || -> Result<ImportSummary, ServiceError> {
+        let request: ImportRequest = ron::de::from_str(&body)
+            .map_err(|e| ServiceError::GenericError(e.to_string()))?;
+        let username = credentials.as_ref().map(|c| c.username.to_string());
+        let db_url = global::get_db_url()?;
+        let sql_options = global::get_sql_guard()?
+            .options_for(username.as_ref().map(String::as_str), &db_url);
+        if !request.dry_run && !sql_options.allow_write {
+            return Err(ServiceError::from(IntelError::SqlViolation(
+                SqlViolation::WriteNotAllowed(StatementKind::Insert),
+            )));
+        }
+        let context = session::create_context(credentials.clone())?;
+        let (_em, mut dm) = crate::session::get_em_dm(credentials)?;
+        let table_name = TableName::from(&table_name_param.to_string());
+        let summary =
+            data_import::import_csv(&context, &mut dm, &table_name, &request)?;
+        Ok(summary)
+    })
+    .then(ron_response_with_violation)
+}
+
+/// execute the ad-hoc sql with the restrictions imposed to the user
+/// and record it in the query history of the user
+fn execute_and_record_sql(
+    credentials: Option<Credentials>,
+    sql: &str,
+    params: &[Value],
+) -> Result<QueryResult, ServiceError> {
+    let username = credentials.as_ref().map(|c| c.username.to_string());
+    let context = session::create_context(credentials)?;
+    let db_url = global::get_db_url()?;
+    let sql_options = global::get_sql_guard()?
+        .options_for(username.as_ref().map(String::as_str), &db_url);
+
+    let mut dm = global::get_pool_dm()?;
+    let mut em = global::get_pool_em()?;
+    let start = Instant::now();
+    let result = data_read::execute_sql_query(
+        &context,
+        &mut em,
+        &mut dm,
+        sql,
+        params,
+        &sql_options,
+    );
+    let history = QueryHistory {
+        id: None,
+        username,
+        db_url: store::redact_db_url(&db_url),
+        sql: sql.to_string(),
+        duration: start.elapsed().as_millis() as u64,
+        row_count: result.as_ref().ok().map(|result| result.rows.data.len()),
+        error: result.as_ref().err().map(|e| {
+            match e {
+                IntelError::SqlViolation(violation) => violation.to_string(),
+                e => format!("{:?}", e),
             }
-            Err(e) => Err(e),
+        }),
+        created: None,
+    };
+    // failing to record the history should not fail the query
+    if let Err(e) = store::record_history(&history) {
+        warn!("Unable to record the query history: {}", e);
+    }
+    result.map_err(ServiceError::from)
+}
+
+/// serialize the result into ron,
+/// violations of the sql guard are responded as BAD_REQUEST
+/// with the serialized violation in the body
+pub(crate) fn ron_response_with_violation<T: Serialize>(
+    result: Result<T, BlockingError<ServiceError>>,
+) -> Result<HttpResponse, Error> {
+    match result {
+        Ok(result) => {
+            Ok(HttpResponse::Ok().body(
+                ron::ser::to_string(&result)
+                    .expect("unable to serialize to ron"),
+            ))
         }
+        Err(BlockingError::Error(ServiceError::IntelError(
+            IntelError::SqlViolation(violation),
+        ))) => {
+            Ok(HttpResponse::BadRequest().body(
+                ron::ser::to_string(&violation)
+                    .expect("unable to serialize to ron"),
+            ))
+        }
+        Err(e) => Err(Error::from(e)),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ExplainParam {
+    sql: String,
+    params: Option<String>,
+    #[serde(default)]
+    analyze: bool,
+}
+
+/// explain the ad-hoc sql query
+pub fn explain(
+    req: HttpRequest,
+    explain_param: web::Query<ExplainParam>,
+) -> impl Future<Item = HttpResponse, Error = Error> {
+    require_credentials(&req).expect("Should have credentials");
+    let credentials: Result<Credentials, ServiceError> =
+        TryFrom::try_from(&req);
+    let credentials = credentials.ok();
+
+    web::block(move || -> Result<QueryPlan, ServiceError> {
+        let username = credentials.as_ref().map(|c| c.username.to_string());
+        let db_url = global::get_db_url()?;
+        let sql_options = global::get_sql_guard()?
+            .options_for(username.as_ref().map(String::as_str), &db_url);
+        let params = deserialize_params(&explain_param.params)?;
+        let mut dm = global::get_pool_dm()?;
+        let plan = data_read::explain_sql_query(
+            &mut dm,
+            &explain_param.sql,
+            &params,
+            explain_param.analyze,
+            &sql_options,
+        )?;
+        Ok(plan)
+    })
+    .then(ron_response_with_violation)
+}
+
+#[derive(Deserialize)]
+pub struct AnalyzeParam {
+    #[serde(default)]
+    analyze: bool,
+}
+
+/// explain the query used in retrieving the page of the window
+pub fn explain_main_data(
+    req: HttpRequest,
+    param: web::Path<(String, usize)>,
+    analyze_param: web::Query<AnalyzeParam>,
+) -> impl Future<Item = HttpResponse, Error = Error> {
+    require_credentials(&req).expect("Should have credentials");
+    let credentials: Result<Credentials, ServiceError> =
+        TryFrom::try_from(&req);
+
+    let credentials = credentials.ok();
+
+    web::block(move || -> Result<QueryPlan, ServiceError> {
+        let username = credentials.as_ref().map(|c| c.username.to_string());
+        let db_url = global::get_db_url()?;
+        let sql_options = global::get_sql_guard()?
+            .options_for(username.as_ref().map(String::as_str), &db_url);
+        let context = session::create_context(credentials.clone())?;
+        let (_em, mut dm) = crate::session::get_em_dm(credentials)?;
+        let table_name = TableName::from(&param.0);
+        let plan = data_read::explain_window_main_table_query(
+            &context,
+            &mut dm,
+            &table_name,
+            param.1,
+            global::PAGE_SIZE,
+            analyze_param.analyze,
+            &sql_options,
+        )?;
+        Ok(plan)
+    })
+    .then(ron_response_with_violation)
+}
+
+#[derive(Deserialize)]
+pub struct AggregateParam {
+    aggregates: String,
+    filter: Option<String>,
+}
+
+/// aggregate the columns of the table over the rows that matches the filter
+pub fn aggregate(
+    req: HttpRequest,
+    table_name_param: web::Path<String>,
+    param: web::Query<AggregateParam>,
+) -> impl Future<Item = HttpResponse, Error = Error> {
+    require_credentials(&req).expect("Should have credentials");
+    let credentials: Result<Credentials, ServiceError> =
+        TryFrom::try_from(&req);
+    let credentials = credentials.ok();
+
+    web::block(move || -> Result<Vec<Value>, ServiceError> {
+        let aggregates: Vec<ColumnAggregate> =
+            ron::de::from_str(&param.aggregates)
+                .map_err(|e| ServiceError::GenericError(e.to_string()))?;
+        let filter: Filter = match param.filter {
+            Some(ref filter) => {
+                ron::de::from_str(filter)
+                    .map_err(|e| ServiceError::GenericError(e.to_string()))?
+            }
+            None => Filter::default(),
+        };
+        let context = session::create_context(credentials.clone())?;
+        let (_em, mut dm) = session::get_em_dm(credentials)?;
+        let table_name = TableName::from(&table_name_param.to_string());
+        let values = data_aggregate::aggregate_table(
+            &context,
+            &mut dm,
+            &table_name,
+            &filter,
+            &aggregates,
+        )?;
+        Ok(values)
+    })
+    .then(ron_response_with_violation)
+}
+
+#[derive(Deserialize)]
+pub struct GroupParam {
+    request: String,
+}
+
+/// group the rows of the table by the columns in the request
+pub fn group(
+    req: HttpRequest,
+    table_name_param: web::Path<String>,
+    param: web::Query<GroupParam>,
+) -> impl Future<Item = HttpResponse, Error = Error> {
+    require_credentials(&req).expect("Should have credentials");
+    let credentials: Result<Credentials, ServiceError> =
+        TryFrom::try_from(&req);
+    let credentials = credentials.ok();
+
+    web::block(move || -> Result<GroupResult, ServiceError> {
+        let request: GroupRequest = ron::de::from_str(&param.request)
+            .map_err(|e| ServiceError::GenericError(e.to_string()))?;
+        let context = session::create_context(credentials.clone())?;
+        let (_em, mut dm) = session::get_em_dm(credentials)?;
+        let table_name = TableName::from(&table_name_param.to_string());
+        let result =
+            data_group::group_table(&context, &mut dm, &table_name, &request)?;
+        Ok(result)
+    })
+    .then(ron_response_with_violation)
+}
+
+#[derive(Deserialize)]
+pub struct TreeParam {
+    /// the record id of the parent, the roots are retrieved when not set
+    parent: Option<String>,
+}
+
+/// the children of the record in the table which refers to itself
+pub fn tree_children(
+    req: HttpRequest,
+    table_name_param: web::Path<String>,
+    param: web::Query<TreeParam>,
+) -> impl Future<Item = HttpResponse, Error = Error> {
+    require_credentials(&req).expect("Should have credentials");
+    let credentials: Result<Credentials, ServiceError> =
+        TryFrom::try_from(&req);
+    let credentials = credentials.ok();
+
+    web::block(move || -> Result<Vec<TreeNode>, ServiceError> {
+        let context = session::create_context(credentials.clone())?;
+        let (_em, mut dm) = session::get_em_dm(credentials)?;
+        let table_name = TableName::from(&table_name_param.to_string());
+        let children = data_tree::get_tree_children(
+            &context,
+            &mut dm,
+            &table_name,
+            param.parent.as_ref().map(String::as_str),
+        )?;
+        Ok(children)
+    })
+    .then(ron_response_with_violation)
+}
+
+/// the record and its ancestors, in the table which refers to itself
+pub fn tree_ancestors(
+    req: HttpRequest,
+    path_param: web::Path<(String, String)>,
+) -> impl Future<Item = HttpResponse, Error = Error> {
+    require_credentials(&req).expect("Should have credentials");
+    let credentials: Result<Credentials, ServiceError> =
+        TryFrom::try_from(&req);
+    let credentials = credentials.ok();
+
+    web::block(move || -> Result<Vec<TreeNode>, ServiceError> {
+        let context = session::create_context(credentials.clone())?;
+        let (_em, mut dm) = session::get_em_dm(credentials)?;
+        let table_name = TableName::from(&path_param.0);
+        let ancestors = data_tree::get_tree_ancestors(
+            &context,
+            &mut dm,
+            &table_name,
+            &path_param.1,
+        )?;
+        Ok(ancestors)
+    })
+    .then(ron_response_with_violation)
+}
+
+#[derive(Deserialize)]
+pub struct SearchParam {
+    #[serde(default)]
+    search: String,
+}
+
+/// the recent ad-hoc queries executed by the user
+pub fn query_history(
+    req: HttpRequest,
+    search_param: web::Query<SearchParam>,
+) -> impl Future<Item = HttpResponse, Error = Error> {
+    require_credentials(&req).expect("Should have credentials");
+    let credentials: Result<Credentials, ServiceError> =
+        TryFrom::try_from(&req);
+    let username = credentials.ok().map(|c| c.username);
+
+    web::block(move || -> Result<Vec<QueryHistory>, ServiceError> {
+        let db_url = store::redact_db_url(&global::get_db_url()?);
+        store::search_history(
+            username.as_ref().map(String::as_str),
+            &db_url,
+            &search_param.search,
+        )
+    })
+    .then(ron_response_with_violation)
+}
+
+/// the saved queries of the user including the ones shared by other users
+pub fn saved_queries(
+    req: HttpRequest,
+    search_param: web::Query<SearchParam>,
+) -> impl Future<Item = HttpResponse, Error = Error> {
+    require_credentials(&req).expect("Should have credentials");
+    let credentials: Result<Credentials, ServiceError> =
+        TryFrom::try_from(&req);
+    let username = credentials.ok().map(|c| c.username);
+
+    web::block(move || -> Result<Vec<SavedQuery>, ServiceError> {
+        let db_url = store::redact_db_url(&global::get_db_url()?);
+        store::search_saved_queries(
+            username.as_ref().map(String::as_str),
+            &db_url,
+            &search_param.search,
+        )
+    })
+    .then(ron_response_with_violation)
+}
+
+/// save the ron serialized SavedQuery which is posted in the body,
+/// the owner is always the user in the request
+pub fn save_query(
+    req: HttpRequest,
+    body: String,
+) -> impl Future<Item = HttpResponse, Error = Error> {
+    require_credentials(&req).expect("Should have credentials");
+    let credentials: Result<Credentials, ServiceError> =
+        TryFrom::try_from(&req);
+    let username = credentials.ok().map(|c| c.username);
+
+    web::block(move || -> Result<SavedQuery, ServiceError> {
+        let mut saved_query: SavedQuery = ron::de::from_str(&body)
+            .map_err(|e| ServiceError::GenericError(e.to_string()))?;
+        saved_query.username = username;
+        saved_query.db_url = Some(store::redact_db_url(&global::get_db_url()?));
+        store::save_query(&saved_query)
+    })
+    .then(ron_response_with_violation)
+}
+
+#[derive(Deserialize)]
+pub struct SavedQueryParam {
+    /// ron serialized values of the placeholders in the saved sql
+    params: Option<String>,
+}
+
+/// execute the sql of the saved query,
+/// with the values of its placeholders when it has placeholders
+pub fn open_saved_query(
+    req: HttpRequest,
+    id: web::Path<i64>,
+    saved_query_param: web::Query<SavedQueryParam>,
+) -> impl Future<Item = HttpResponse, Error = Error> {
+    require_credentials(&req).expect("Should have credentials");
+    let credentials: Result<Credentials, ServiceError> =
+        TryFrom::try_from(&req);
+    let credentials = credentials.ok();
+
+    web::block(move || -> Result<QueryResult, ServiceError> {
+        let username = credentials.as_ref().map(|c| c.username.to_string());
+        let db_url = store::redact_db_url(&global::get_db_url()?);
+        let saved_query = store::get_saved_query(
+            username.as_ref().map(String::as_str),
+            &db_url,
+            *id,
+        )?;
+        let params = deserialize_params(&saved_query_param.params)?;
+        execute_and_record_sql(credentials, &saved_query.sql, &params)
     })
+    .then(ron_response_with_violation)
+}
+
+pub fn delete_saved_query(
+    req: HttpRequest,
+    id: web::Path<i64>,
+) -> impl Future<Item = HttpResponse, Error = Error> {
+    require_credentials(&req).expect("Should have credentials");
+    let credentials: Result<Credentials, ServiceError> =
+        TryFrom::try_from(&req);
+    let username = credentials.ok().map(|c| c.username);
+
+    web::block(move || -> Result<(), ServiceError> {
+        let db_url = store::redact_db_url(&global::get_db_url()?);
+        store::delete_saved_query(
+            username.as_ref().map(String::as_str),
+            &db_url,
+            *id,
+        )
+    })
+    .then(ron_response_with_violation)
 }
 
 #[derive(Debug, Deserialize)]
@@ -151,9 +684,18 @@ pub fn record_detail(
     })
 }
 
+#[derive(Deserialize)]
+pub struct MainDataParam {
+    filter: Option<String>,
+    sort: Option<String>,
+}
+
+/// the page of the rows of the window main tab,
+/// which matches the filter and in the order of the sort when supplied
 pub fn main_data(
     req: HttpRequest,
     param: web::Path<(String, usize)>,
+    main_data_param: web::Query<MainDataParam>,
 ) -> impl Future<Item = HttpResponse, Error = Error> {
     require_credentials(&req).expect("Should have credentials");
     let credentials: Result<Credentials, ServiceError> =
@@ -161,32 +703,36 @@ pub fn main_data(
 
     let credentials = credentials.ok();
 
-    web::block(move || {
-        let context = session::create_context(credentials.clone())
-            .expect("unable to create context");
-        let (mut em, mut dm) =
-            crate::session::get_em_dm(credentials).expect("must not error");
+    web::block(move || -> Result<QueryResult, ServiceError> {
+        let filter: Filter =
+            export::deserialize_or_default(&main_data_param.filter)?;
+        let sort: Sort = export::deserialize_or_default(&main_data_param.sort)?;
+        let context = session::create_context(credentials.clone())?;
+        let (mut em, mut dm) = crate::session::get_em_dm(credentials)?;
         let table_name = TableName::from(&param.0);
-        let res = data_read::get_window_main_table_data(
-            &context,
-            &mut em,
-            &mut dm,
-            &table_name,
-            param.1,
-            global::PAGE_SIZE,
-        );
-        res
-    })
-    .from_err()
-    .then(move |res| {
-        match res {
-            Ok(res) => {
-                Ok(HttpResponse::Ok().body(
-                    ron::ser::to_string(&res)
-                        .expect("unable to serialize to ron"),
-                ))
-            }
-            Err(e) => Err(e),
-        }
+        let is_unfiltered =
+            filter == Filter::default() && sort == Sort::default();
+        let result = if is_unfiltered {
+            data_read::get_window_main_table_data(
+                &context,
+                &mut em,
+                &mut dm,
+                &table_name,
+                param.1,
+                global::PAGE_SIZE,
+            )?
+        } else {
+            data_read::get_filtered_main_table_data(
+                &context,
+                &mut dm,
+                &table_name,
+                &filter,
+                &sort,
+                param.1,
+                global::PAGE_SIZE,
+            )?
+        };
+        Ok(result)
     })
+    .then(ron_response_with_violation)
 }
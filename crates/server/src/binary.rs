@@ -0,0 +1,141 @@
+//! download and upload the content of the binary columns of the records,
+//! the record is identified by the dao of its primary columns
+//! the same way as in the record detail
+use crate::{
+    api::{
+        self,
+        require_credentials,
+    },
+    credentials::Credentials,
+    error::ServiceError,
+    global,
+    session,
+};
+use actix_web::{
+    http::header,
+    web::{
+        self,
+        Bytes,
+    },
+    Error,
+    HttpRequest,
+    HttpResponse,
+};
+use diwata_intel::{
+    data_image,
+    data_modify,
+    data_read,
+    error::IntelError,
+    sql_guard::{
+        SqlViolation,
+        StatementKind,
+    },
+    Dao,
+    TableName,
+};
+use futures::future::Future;
+use serde::Deserialize;
+use std::convert::TryFrom;
+
+#[derive(Deserialize)]
+pub struct BinaryParam {
+    dao: String,
+}
+
+impl BinaryParam {
+    fn primary_dao(&self) -> Result<Dao, ServiceError> {
+        ron::de::from_str(&self.dao)
+            .map_err(|e| ServiceError::GenericError(e.to_string()))
+    }
+}
+
+/// download the content of the binary column as a file,
+/// the content type is sniffed from the content
+pub fn download(
+    req: HttpRequest,
+    path_param: web::Path<(String, String)>,
+    param: web::Query<BinaryParam>,
+) -> impl Future<Item = HttpResponse, Error = Error> {
+    require_credentials(&req).expect("Should have credentials");
+    let credentials: Result<Credentials, ServiceError> =
+        TryFrom::try_from(&req);
+    let credentials = credentials.ok();
+    let table_name = TableName::from(&path_param.0);
+    let column_name = path_param.1.to_string();
+    let filename = format!("{}_{}", table_name.name, column_name);
+
+    web::block(move || -> Result<Vec<u8>, ServiceError> {
+        let primary_dao = param.primary_dao()?;
+        let context = session::create_context(credentials.clone())?;
+        let (_em, mut dm) = session::get_em_dm(credentials)?;
+        let content = data_read::fetch_binary(
+            &context,
+            &mut dm,
+            &table_name,
+            &column_name,
+            &primary_dao,
+        )?;
+        Ok(content)
+    })
+    .then(move |content| {
+        match content {
+            Ok(content) => {
+                let content_type = data_image::sniff_content_type(&content);
+                Ok(HttpResponse::Ok()
+                    .content_type(content_type)
+                    .header(
+                        header::CONTENT_DISPOSITION,
+                        format!(
+                            "attachment; filename=\"{}.{}\"",
+                            filename,
+                            data_image::file_extension(content_type)
+                        ),
+                    )
+                    .body(content))
+            }
+            Err(e) => api::ron_response_with_violation::<()>(Err(e)),
+        }
+    })
+}
+
+/// replace the content of the binary column with the posted body,
+/// responding with the updated record.
+/// Only the users that are allowed to write can upload
+pub fn upload(
+    req: HttpRequest,
+    path_param: web::Path<(String, String)>,
+    param: web::Query<BinaryParam>,
+    body: Bytes,
+) -> impl Future<Item = HttpResponse, Error = Error> {
+    require_credentials(&req).expect("Should have credentials");
+    let credentials: Result<Credentials, ServiceError> =
+        TryFrom::try_from(&req);
+    let credentials = credentials.ok();
+
+    web::block(move || -> Result<Dao, ServiceError> {
+        let username = credentials.as_ref().map(|c| c.username.to_string());
+        let db_url = global::get_db_url()?;
+        let sql_options = global::get_sql_guard()?
+            .options_for(username.as_ref().map(String::as_str), &db_url);
+        if !sql_options.allow_write {
+            return Err(ServiceError::from(IntelError::SqlViolation(
+                SqlViolation::WriteNotAllowed(StatementKind::Update),
+            )));
+        }
+        let primary_dao = param.primary_dao()?;
+        let context = session::create_context(credentials.clone())?;
+        let (_em, mut dm) = session::get_em_dm(credentials)?;
+        let table_name = TableName::from(&path_param.0);
+        let table =
+            context.get_table(&table_name).ok_or(IntelError::NotFound)?;
+        let record = data_modify::update_binary(
+            &mut dm,
+            table,
+            &primary_dao,
+            &path_param.1,
+            body.to_vec(),
+        )?;
+        Ok(record)
+    })
+    .then(api::ron_response_with_violation)
+}
@@ -0,0 +1,96 @@
+//! the entity relationship diagram of the database, as a graphviz dot,
+//! a json of the nodes and edges or as ron for the webview
+use crate::{
+    api::{
+        self,
+        require_credentials,
+    },
+    credentials::Credentials,
+    error::ServiceError,
+    session,
+};
+use actix_web::{
+    web,
+    Error,
+    HttpRequest,
+    HttpResponse,
+};
+use diwata_intel::{
+    erd::{
+        self,
+        Erd,
+        ErdFilter,
+    },
+    TableName,
+};
+use futures::future::Future;
+use serde::Deserialize;
+use std::convert::TryFrom;
+
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ErdFormat {
+    Dot,
+    Json,
+    Ron,
+}
+
+impl Default for ErdFormat {
+    fn default() -> Self {
+        ErdFormat::Ron
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ErdParam {
+    #[serde(default)]
+    format: ErdFormat,
+    schema: Option<String>,
+    /// the table in the center of the diagram
+    center: Option<String>,
+    depth: Option<usize>,
+}
+
+impl ErdParam {
+    fn filter(&self) -> ErdFilter {
+        ErdFilter {
+            schema: self.schema.clone().filter(|schema| !schema.is_empty()),
+            center: self
+                .center
+                .as_ref()
+                .filter(|center| !center.is_empty())
+                .map(TableName::from),
+            depth: self.depth,
+        }
+    }
+}
+
+/// the diagram of the tables in the schema or around the center table
+pub fn erd(
+    req: HttpRequest,
+    param: web::Query<ErdParam>,
+) -> impl Future<Item = HttpResponse, Error = Error> {
+    require_credentials(&req).expect("Should have credentials");
+    let credentials: Result<Credentials, ServiceError> =
+        TryFrom::try_from(&req);
+    let credentials = credentials.ok();
+    let format = param.format;
+
+    web::block(move || -> Result<Erd, ServiceError> {
+        let context = session::create_context(credentials)?;
+        Ok(erd::get_erd(&context, &param.filter()))
+    })
+    .then(move |erd| {
+        match (format, erd) {
+            (ErdFormat::Dot, Ok(erd)) => {
+                Ok(HttpResponse::Ok()
+                    .content_type("text/vnd.graphviz; charset=utf-8")
+                    .body(erd.to_dot()))
+            }
+            (ErdFormat::Json, Ok(erd)) => {
+                Ok(HttpResponse::Ok().json(erd))
+            }
+            (_, erd) => api::ron_response_with_violation(erd),
+        }
+    })
+}
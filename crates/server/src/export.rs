@@ -0,0 +1,375 @@
+//! streams the exported rows in the body of the response,
+//! the rows are written in the blocking thread pool and each flushed batch
+//! is sent as a chunk of the body
+use crate::{
+    api::{
+        self,
+        require_credentials,
+    },
+    credentials::Credentials,
+    error::ServiceError,
+    global,
+    session,
+};
+use actix_web::{
+    error::{
+        self,
+        BlockingError,
+    },
+    web::{
+        self,
+        Bytes,
+    },
+    Error,
+    HttpRequest,
+    HttpResponse,
+};
+use diwata_intel::{
+    data_export::{
+        self,
+        ExportFormat,
+        ExportQuery,
+        ExportSource,
+    },
+    sql_guard::SqlOptions,
+    Dao,
+    TableName,
+};
+use futures::{
+    future::Future,
+    sink::{
+        Sink,
+        Wait,
+    },
+    stream::Stream,
+    sync::{
+        mpsc,
+        oneshot,
+    },
+};
+use log::info;
+use rustorm::DaoManager;
+use serde::Deserialize;
+use std::{
+    convert::TryFrom,
+    io::{
+        self,
+        Write,
+    },
+};
+
+/// the number of chunks buffered before the export waits for the client
+const CHUNK_BUFFER: usize = 4;
+
+type Chunk = Result<Bytes, String>;
+
+#[derive(Deserialize)]
+pub struct ExportParam {
+    format: Option<String>,
+    filter: Option<String>,
+    sort: Option<String>,
+    #[serde(default)]
+    display: bool,
+}
+
+#[derive(Deserialize)]
+pub struct RelatedExportParam {
+    format: Option<String>,
+    record: String,
+    #[serde(default)]
+    display: bool,
+}
+
+#[derive(Deserialize)]
+pub struct SqlExportParam {
+    format: Option<String>,
+    sql: String,
+    params: Option<String>,
+    #[serde(default)]
+    display: bool,
+}
+
+/// export the rows of the main tab of the window, with the filter and sort
+pub fn export(
+    req: HttpRequest,
+    table_name_param: web::Path<String>,
+    param: web::Query<ExportParam>,
+) -> impl Future<Item = HttpResponse, Error = Error> {
+    require_credentials(&req).expect("Should have credentials");
+    let credentials: Result<Credentials, ServiceError> =
+        TryFrom::try_from(&req);
+    let table_name = TableName::from(&table_name_param.to_string());
+    let filename = table_name.name.to_string();
+
+    stream_export(
+        credentials.ok(),
+        filename,
+        param.format.clone(),
+        param.display,
+        move || {
+            Ok(ExportSource::MainTab {
+                table_name,
+                filter: deserialize_or_default(&param.filter)?,
+                sort: deserialize_or_default(&param.sort)?,
+            })
+        },
+    )
+}
+
+/// export the rows in the has_many tab of the record
+pub fn export_has_many(
+    req: HttpRequest,
+    path_param: web::Path<(String, String)>,
+    param: web::Query<RelatedExportParam>,
+) -> impl Future<Item = HttpResponse, Error = Error> {
+    require_credentials(&req).expect("Should have credentials");
+    let credentials: Result<Credentials, ServiceError> =
+        TryFrom::try_from(&req);
+    let table_name = TableName::from(&path_param.0);
+    let has_many_table = TableName::from(&path_param.1);
+    let filename = has_many_table.name.to_string();
+
+    stream_export(
+        credentials.ok(),
+        filename,
+        param.format.clone(),
+        param.display,
+        move || {
+            Ok(ExportSource::HasMany {
+                table_name,
+                record: deserialize_record(&param.record)?,
+                has_many_table,
+            })
+        },
+    )
+}
+
+/// export the rows in the indirect tab of the record
+pub fn export_indirect(
+    req: HttpRequest,
+    path_param: web::Path<(String, String, String)>,
+    param: web::Query<RelatedExportParam>,
+) -> impl Future<Item = HttpResponse, Error = Error> {
+    require_credentials(&req).expect("Should have credentials");
+    let credentials: Result<Credentials, ServiceError> =
+        TryFrom::try_from(&req);
+    let table_name = TableName::from(&path_param.0);
+    let linker = TableName::from(&path_param.1);
+    let indirect_table = TableName::from(&path_param.2);
+    let filename = indirect_table.name.to_string();
+
+    stream_export(
+        credentials.ok(),
+        filename,
+        param.format.clone(),
+        param.display,
+        move || {
+            Ok(ExportSource::Indirect {
+                table_name,
+                record: deserialize_record(&param.record)?,
+                linker,
+                indirect_table,
+            })
+        },
+    )
+}
+
+/// export the rows of an ad-hoc query,
+/// only SELECT queries are exported
+pub fn export_sql(
+    req: HttpRequest,
+    param: web::Query<SqlExportParam>,
+) -> impl Future<Item = HttpResponse, Error = Error> {
+    require_credentials(&req).expect("Should have credentials");
+    let credentials: Result<Credentials, ServiceError> =
+        TryFrom::try_from(&req);
+
+    stream_export(
+        credentials.ok(),
+        "query".to_string(),
+        param.format.clone(),
+        param.display,
+        move || {
+            Ok(ExportSource::Sql {
+                sql: param.sql.to_string(),
+                params: api::deserialize_params(&param.params)?,
+            })
+        },
+    )
+}
+
+/// the query is built in the blocking thread pool before the response
+/// is started, so that errors such as violations of the sql guard
+/// are responded the same way as the rest of the api.
+/// The export holds one of the bounded threads of the pool
+/// until all of the rows are written
+fn stream_export<F>(
+    credentials: Option<Credentials>,
+    filename: String,
+    format: Option<String>,
+    display: bool,
+    source: F,
+) -> impl Future<Item = HttpResponse, Error = Error>
+where
+    F: FnOnce() -> Result<ExportSource, ServiceError> + Send + 'static,
+{
+    let (ready_sender, ready) = oneshot::channel();
+    let (body_sender, body) = mpsc::channel::<Chunk>(CHUNK_BUFFER);
+    let export_name = filename.clone();
+
+    let export = web::block(move || -> Result<(), ()> {
+        let prepared = parse_format(&format).and_then(|format| {
+            let source = source()?;
+            prepare_export(credentials, &source, display)
+                .map(|(dm, query, options)| (format, dm, query, options))
+        });
+        let (format, mut dm, query, sql_options) = match prepared {
+            Ok(prepared) => prepared,
+            Err(e) => {
+                let _ = ready_sender.send(Err(e));
+                return Ok(());
+            }
+        };
+        if ready_sender.send(Ok(format)).is_err() {
+            return Ok(());
+        }
+        let mut error_sink = body_sender.clone().wait();
+        let writer = ChunkWriter {
+            buffer: vec![],
+            sink: body_sender.wait(),
+        };
+        if let Err(e) = data_export::export_rows(
+            &mut dm,
+            &query,
+            &sql_options,
+            format,
+            writer,
+        ) {
+            // the headers are already sent, the error ends the body
+            info!("Export of {} failed: {:?}", export_name, e);
+            let _ = error_sink.send(Err(format!("{:?}", e)));
+        }
+        Ok(())
+    });
+
+    ready.then(move |ready| {
+        match ready {
+            Ok(Ok(format)) => {
+                // the export is polled along with the body,
+                // the pool skips the work of a dropped future
+                let export = export
+                    .then(|_| -> Result<Option<Bytes>, Error> { Ok(None) })
+                    .into_stream()
+                    .filter_map(|chunk| chunk);
+                let body = body
+                    .map_err(|_| {
+                        error::ErrorInternalServerError("export interrupted")
+                    })
+                    .and_then(|chunk| {
+                        chunk.map_err(error::ErrorInternalServerError)
+                    })
+                    .select(export);
+                Ok(HttpResponse::Ok()
+                    .content_type(format.content_type())
+                    .header(
+                        "Content-Disposition",
+                        format!(
+                            "attachment; filename=\"{}.{}\"",
+                            filename,
+                            format.extension()
+                        ),
+                    )
+                    .streaming(body))
+            }
+            Ok(Err(e)) => {
+                api::ron_response_with_violation::<()>(Err(
+                    BlockingError::Error(e),
+                ))
+            }
+            Err(_canceled) => {
+                api::ron_response_with_violation::<()>(Err(
+                    BlockingError::Canceled,
+                ))
+            }
+        }
+    })
+}
+
+fn prepare_export(
+    credentials: Option<Credentials>,
+    source: &ExportSource,
+    display: bool,
+) -> Result<(DaoManager, ExportQuery, SqlOptions), ServiceError> {
+    let username = credentials.as_ref().map(|c| c.username.to_string());
+    let db_url = global::get_db_url()?;
+    let sql_options = global::get_sql_guard()?
+        .options_for(username.as_ref().map(String::as_str), &db_url);
+    let context = session::create_context(credentials.clone())?;
+    let (_em, mut dm) = session::get_em_dm(credentials)?;
+    let query = data_export::build_export_query(
+        &context,
+        &mut dm,
+        source,
+        display,
+        &sql_options,
+    )?;
+    Ok((dm, query, sql_options))
+}
+
+fn parse_format(format: &Option<String>) -> Result<ExportFormat, ServiceError> {
+    match format {
+        Some(format) => {
+            ExportFormat::from_extension(format).ok_or_else(|| {
+                ServiceError::GenericError(format!(
+                    "Unsupported export format: {}",
+                    format
+                ))
+            })
+        }
+        None => Ok(ExportFormat::Csv),
+    }
+}
+
+pub(crate) fn deserialize_or_default<'de, T>(
+    param: &'de Option<String>,
+) -> Result<T, ServiceError>
+where
+    T: Deserialize<'de> + Default,
+{
+    match param {
+        Some(param) => {
+            ron::de::from_str(param)
+                .map_err(|e| ServiceError::GenericError(e.to_string()))
+        }
+        None => Ok(T::default()),
+    }
+}
+
+fn deserialize_record(record: &str) -> Result<Dao, ServiceError> {
+    ron::de::from_str(record)
+        .map_err(|e| ServiceError::GenericError(e.to_string()))
+}
+
+/// collects the written bytes and sends them as a chunk of the body
+/// when flushed, the send waits while the client is behind
+struct ChunkWriter {
+    buffer: Vec<u8>,
+    sink: Wait<mpsc::Sender<Chunk>>,
+}
+
+impl Write for ChunkWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        let chunk = Bytes::from(std::mem::replace(&mut self.buffer, vec![]));
+        self.sink.send(Ok(chunk)).map_err(|_| {
+            io::Error::new(io::ErrorKind::BrokenPipe, "the client disconnected")
+        })
+    }
+}
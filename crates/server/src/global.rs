@@ -1,6 +1,10 @@
 use crate::error::ServiceError;
-use diwata_intel::cache;
+use diwata_intel::{
+    cache,
+    sql_guard::SqlOptions,
+};
 use lazy_static::lazy_static;
+use log::info;
 use rustorm::{
     pool,
     DaoManager,
@@ -25,6 +29,43 @@ lazy_static! {
     // all the connection pool, key is the db_url
     pub static ref POOL: Arc<RwLock<Pool>> = { Arc::new(RwLock::new(Pool::new())) };
     pub static ref LOGIN_REQUIRED: RwLock<bool> = RwLock::new(false);
+    // the restrictions imposed on the ad-hoc sql queries
+    pub static ref SQL_GUARD: RwLock<SqlGuard> = RwLock::new(SqlGuard::default());
+    // the local sqlite store of query history and saved queries
+    pub static ref STORE_URL: RwLock<Option<String>> = RwLock::new(None);
+}
+
+/// server options on how ad-hoc sql queries are executed
+#[derive(Debug, Clone, Default)]
+pub struct SqlGuard {
+    /// reject statements other than SELECT
+    pub read_only: bool,
+    /// users that are allowed to write even when read_only is set
+    pub writers: Vec<String>,
+    /// statement timeout in milliseconds
+    pub statement_timeout: Option<u64>,
+    /// the maximum number of rows an ad-hoc query can return
+    pub row_limit: Option<usize>,
+}
+
+impl SqlGuard {
+    /// the options imposed for this user on the database of the db_url
+    pub fn options_for(
+        &self,
+        username: Option<&str>,
+        db_url: &str,
+    ) -> SqlOptions {
+        let is_writer = match username {
+            Some(username) => self.writers.iter().any(|w| w == username),
+            None => false,
+        };
+        SqlOptions {
+            allow_write: !self.read_only || is_writer,
+            statement_timeout: self.statement_timeout,
+            row_limit: self.row_limit,
+            is_postgres: db_url.starts_with("postgres"),
+        }
+    }
 }
 
 fn get_db_url_value() -> Result<Option<String>, ServiceError> {
@@ -269,3 +310,77 @@ pub fn set_login_required(required: bool) -> Result<(), ServiceError> {
         Err(e) => Err(ServiceError::GenericError(format!("{}", e))),
     }
 }
+
+pub fn get_sql_guard() -> Result<SqlGuard, ServiceError> {
+    match SQL_GUARD.read() {
+        Ok(sql_guard) => Ok(sql_guard.clone()),
+        Err(e) => Err(ServiceError::GenericError(format!("{}", e))),
+    }
+}
+
+pub fn set_sql_guard(new_guard: SqlGuard) -> Result<(), ServiceError> {
+    match SQL_GUARD.write() {
+        Ok(mut sql_guard) => {
+            *sql_guard = new_guard;
+            Ok(())
+        }
+        Err(e) => Err(ServiceError::GenericError(format!("{}", e))),
+    }
+}
+
+pub fn set_store_url(new_url: &str) -> Result<(), ServiceError> {
+    match STORE_URL.write() {
+        Ok(mut store_url) => {
+            info!("setting store_url to: {}", new_url);
+            *store_url = Some(new_url.to_string());
+            Ok(())
+        }
+        Err(e) => Err(ServiceError::GenericError(format!("{}", e))),
+    }
+}
+
+/// run without the query history and the saved queries
+pub fn disable_store() -> Result<(), ServiceError> {
+    match STORE_URL.write() {
+        Ok(mut store_url) => {
+            *store_url = None;
+            Ok(())
+        }
+        Err(e) => Err(ServiceError::GenericError(format!("{}", e))),
+    }
+}
+
+pub fn is_store_enabled() -> bool {
+    STORE_URL
+        .read()
+        .map(|store_url| store_url.is_some())
+        .unwrap_or(false)
+}
+
+fn get_store_url() -> Result<String, ServiceError> {
+    match STORE_URL.read() {
+        Ok(store_url) => {
+            match *store_url {
+                Some(ref store_url) => Ok(store_url.to_owned()),
+                None => {
+                    Err(ServiceError::GenericError(
+                        "The query history is disabled".into(),
+                    ))
+                }
+            }
+        }
+        Err(e) => Err(ServiceError::GenericError(format!("{}", e))),
+    }
+}
+
+pub fn get_pool_store_dm() -> Result<DaoManager, ServiceError> {
+    let mut pool = match POOL.write() {
+        Ok(pool) => pool,
+        Err(_e) => return Err(ServiceError::PoolResourceError),
+    };
+    let store_url = &get_store_url()?;
+    match pool.dm(store_url) {
+        Ok(dm) => Ok(dm),
+        Err(e) => Err(ServiceError::DbError(e)),
+    }
+}
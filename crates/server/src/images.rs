@@ -0,0 +1,202 @@
+//! serves the images stored in the columns of the records,
+//! the thumbnails are scaled down so the grid does not load the full images
+use crate::{
+    api::{
+        self,
+        require_credentials,
+    },
+    credentials::Credentials,
+    error::ServiceError,
+    session,
+};
+use actix_web::{
+    http::header,
+    web,
+    Error,
+    HttpRequest,
+    HttpResponse,
+};
+use diwata_intel::{
+    data_image::{
+        self,
+        Image,
+    },
+    TableName,
+};
+use futures::future::Future;
+use image::ImageDecoder;
+use serde::Deserialize;
+use std::{
+    convert::TryFrom,
+    io::Cursor,
+};
+
+/// the longest side of the thumbnails in pixels
+const THUMBNAIL_SIZE: u32 = 120;
+
+/// images larger than this are not decoded to make a thumbnail
+const THUMBNAIL_SOURCE_LIMIT: usize = 16 * 1024 * 1024;
+
+/// images with more pixels than this are not decoded to make a thumbnail,
+/// a small compressed file can decode into an image that fills the memory
+const THUMBNAIL_PIXEL_LIMIT: u64 = 40_000_000;
+
+/// the svg is a document which can run scripts, it is downloaded instead of
+/// opened in the site and it can only be shown in an img tag
+const SVG_MIME: &str = "image/svg+xml";
+
+/// the images are not allowed to load or run anything when they are opened
+const IMAGE_CSP: &str = "default-src 'none'; sandbox";
+
+#[derive(Deserialize)]
+pub struct ImageParam {
+    #[serde(default)]
+    thumbnail: bool,
+}
+
+/// the image in the column of the record, the record is identified by
+/// the comma separated values of its primary columns
+pub fn serve_image(
+    req: HttpRequest,
+    path_param: web::Path<(String, String, String)>,
+    param: web::Query<ImageParam>,
+) -> impl Future<Item = HttpResponse, Error = Error> {
+    require_credentials(&req).expect("Should have credentials");
+    let credentials: Result<Credentials, ServiceError> =
+        TryFrom::try_from(&req);
+    let credentials = credentials.ok();
+
+    web::block(move || -> Result<Image, ServiceError> {
+        let context = session::create_context(credentials.clone())?;
+        let (_em, mut dm) = session::get_em_dm(credentials)?;
+        let table_name = TableName::from(&path_param.0);
+        let image = data_image::fetch_image(
+            &context,
+            &mut dm,
+            &table_name,
+            &path_param.1,
+            &path_param.2,
+        )?;
+        let image = match image {
+            Image::Uri(ref uri) if uri.starts_with("data:") => {
+                decode_data_uri(uri)?
+            }
+            image => image,
+        };
+        match image {
+            Image::Bytes { mime, bytes } if param.thumbnail => {
+                thumbnail(mime, bytes)
+            }
+            image => Ok(image),
+        }
+    })
+    .then(|image| {
+        match image {
+            Ok(Image::Bytes { mime, bytes }) => {
+                let mut response = HttpResponse::Ok();
+                response
+                    .content_type(mime)
+                    .header(header::CACHE_CONTROL, "private, max-age=300")
+                    .header(header::CONTENT_SECURITY_POLICY, IMAGE_CSP)
+                    .header(header::X_CONTENT_TYPE_OPTIONS, "nosniff");
+                if mime == SVG_MIME {
+                    response.header(header::CONTENT_DISPOSITION, "attachment");
+                }
+                Ok(response.body(bytes))
+            }
+            // only the http urls and the paths of this site are redirected to
+            Ok(Image::Uri(uri)) if data_image::is_image_uri(&uri) => {
+                Ok(HttpResponse::Found()
+                    .header(header::LOCATION, uri)
+                    .finish())
+            }
+            Ok(Image::Uri(uri)) => {
+                api::ron_response_with_violation::<()>(Err(
+                    ServiceError::GenericError(format!(
+                        "not an image url: {}",
+                        uri
+                    )),
+                ))
+            }
+            Err(e) => api::ron_response_with_violation::<()>(Err(e)),
+        }
+    })
+}
+
+/// the bytes of a base64 data uri such as `data:image/png;base64,iVBOR..`
+fn decode_data_uri(uri: &str) -> Result<Image, ServiceError> {
+    let invalid = || ServiceError::GenericError("invalid data uri".into());
+    let mut parts = uri.splitn(2, ',');
+    let header = parts.next().ok_or_else(invalid)?;
+    let data = parts.next().ok_or_else(invalid)?;
+    if !header.ends_with(";base64") {
+        return Err(invalid());
+    }
+    let bytes = base64::decode(data.trim()).map_err(|_| invalid())?;
+    let mime = data_image::sniff_mime(&bytes).ok_or_else(invalid)?;
+    Ok(Image::Bytes { mime, bytes })
+}
+
+/// the width and height in the header of the image, read without decoding
+/// the pixels. None for the formats that are not decoded such as svg
+fn image_dimensions(mime: &str, bytes: &[u8]) -> Option<(u64, u64)> {
+    let cursor = Cursor::new(bytes);
+    let dimensions = match mime {
+        "image/png" => image::png::PNGDecoder::new(cursor).ok()?.dimensions(),
+        "image/jpeg" => {
+            image::jpeg::JPEGDecoder::new(cursor).ok()?.dimensions()
+        }
+        "image/gif" => image::gif::Decoder::new(cursor).ok()?.dimensions(),
+        "image/webp" => {
+            image::webp::WebpDecoder::new(cursor).ok()?.dimensions()
+        }
+        "image/bmp" => image::bmp::BMPDecoder::new(cursor).ok()?.dimensions(),
+        "image/x-icon" => {
+            image::ico::ICODecoder::new(cursor).ok()?.dimensions()
+        }
+        _ => return None,
+    };
+    Some(dimensions)
+}
+
+/// scale down the image to fit in the thumbnail size, the images that
+/// already fit and the formats that can not be decoded are served as is
+/// and are scaled by the browser
+fn thumbnail(
+    mime: &'static str,
+    bytes: Vec<u8>,
+) -> Result<Image, ServiceError> {
+    if bytes.len() > THUMBNAIL_SOURCE_LIMIT {
+        return Err(ServiceError::GenericError(format!(
+            "The image is too large for a thumbnail: {} bytes",
+            bytes.len()
+        )));
+    }
+    let (width, height) = match image_dimensions(mime, &bytes) {
+        Some(dimensions) => dimensions,
+        None => return Ok(Image::Bytes { mime, bytes }),
+    };
+    if width <= u64::from(THUMBNAIL_SIZE) && height <= u64::from(THUMBNAIL_SIZE)
+    {
+        return Ok(Image::Bytes { mime, bytes });
+    }
+    if width.saturating_mul(height) > THUMBNAIL_PIXEL_LIMIT {
+        return Err(ServiceError::GenericError(format!(
+            "The image is too large for a thumbnail: {}x{} pixels",
+            width, height
+        )));
+    }
+    let decoded = match image::load_from_memory(&bytes) {
+        Ok(decoded) => decoded,
+        Err(_e) => return Ok(Image::Bytes { mime, bytes }),
+    };
+    let mut thumbnail = vec![];
+    decoded
+        .thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+        .write_to(&mut thumbnail, image::ImageOutputFormat::PNG)
+        .map_err(|e| ServiceError::GenericError(e.to_string()))?;
+    Ok(Image::Bytes {
+        mime: "image/png",
+        bytes: thumbnail,
+    })
+}
@@ -5,10 +5,21 @@ pub use error::ServiceError;
 pub use global::{
     set_db_url,
     set_login_required,
+    set_sql_guard,
+    SqlGuard,
+};
+use diwata_intel::{
+    config::Config,
+    data_modify,
+    sql_guard,
 };
 use std::{
     env,
-    io,
+    fs::File,
+    io::{
+        self,
+        Read,
+    },
 };
 
 use actix_files as fs;
@@ -18,19 +29,37 @@ use actix_web::{
         errhandlers::ErrorHandlers,
         Logger,
     },
-    web,
+    web::{
+        self,
+        Bytes,
+    },
     App,
+    FromRequest,
     HttpServer,
 };
 
 use dotenv::dotenv;
+use log::{
+    info,
+    warn,
+};
+
+/// the maximum size of the posted csv to be imported
+const IMPORT_SIZE_LIMIT: usize = 10 * 1024 * 1024;
+/// the maximum size of the posted edited rows to be saved
+const SAVE_CONTAINER_SIZE_LIMIT: usize = 10 * 1024 * 1024;
 
 mod api;
+mod binary;
 mod credentials;
+mod erd;
 pub mod error;
+mod export;
 mod global;
+mod images;
 mod page;
 pub mod session;
+mod store;
 
 pub fn start() -> io::Result<()> {
     dotenv().ok();
@@ -44,6 +73,27 @@ pub fn start() -> io::Result<()> {
     let port = env::var("PORT").expect("PORT must be set");
     println!("PORT: {}", port);
     global::set_db_url(&database_url).expect("unable to set global db_url");
+    let guard = sql_guard_from_env();
+    // the timeout can not be enforced in the other databases
+    let options = guard.options_for(None, &database_url);
+    sql_guard::check_options(&options).map_err(|e| {
+        io::Error::new(io::ErrorKind::InvalidInput, e.to_string())
+    })?;
+    global::set_sql_guard(guard).expect("unable to set global sql guard");
+    let store_url = env::var("DIWATA_STORE")
+        .unwrap_or_else(|_| "sqlite://diwata_store.db".to_string());
+    global::set_store_url(&store_url).expect("unable to set global store_url");
+    // the history is optional, such as when the home is read only
+    if let Err(e) = store::init_store() {
+        warn!(
+            "The query history is disabled, unable to create the store {}: {}",
+            store_url, e
+        );
+        global::disable_store().expect("unable to disable the store");
+    }
+    if let Ok(config_path) = env::var("DIWATA_CONFIG") {
+        diwata_intel::config::set_config(read_config(&config_path)?);
+    }
     global::precache().expect("unable to precache");
     let app = move || {
         let error_handlers = ErrorHandlers::new()
@@ -61,9 +111,105 @@ pub fn start() -> io::Result<()> {
                 web::resource("/{table_name}")
                     .route(web::get().to_async(page::index_with_table)),
             )
+            .service(
+                web::resource("/{table_name}/record/{record_id}")
+                    .route(web::get().to_async(page::index_with_record)),
+            )
             .service(
                 web::resource("/sql/").route(web::get().to_async(api::sql)),
             )
+            .service(
+                web::resource("/sql_params/")
+                    .route(web::get().to_async(api::sql_params)),
+            )
+            .service(
+                web::resource("/save_container/")
+                    .data(String::configure(|cfg| {
+                        cfg.limit(SAVE_CONTAINER_SIZE_LIMIT)
+                    }))
+                    .route(web::post().to_async(api::save_container)),
+            )
+            .service(
+                web::resource("/export_sql/")
+                    .route(web::get().to_async(export::export_sql)),
+            )
+            .service(
+                web::resource("/export/{table_name}/")
+                    .route(web::get().to_async(export::export)),
+            )
+            .service(
+                web::resource(
+                    "/export/{table_name}/has_many/{has_many_table}/",
+                )
+                .route(web::get().to_async(export::export_has_many)),
+            )
+            .service(
+                web::resource(
+                    "/export/{table_name}/indirect/{linker}/{indirect_table}/",
+                )
+                .route(web::get().to_async(export::export_indirect)),
+            )
+            .service(
+                web::resource("/import/{table_name}/")
+                    .data(String::configure(|cfg| cfg.limit(IMPORT_SIZE_LIMIT)))
+                    .route(web::post().to_async(api::import)),
+            )
+            .service(
+                web::resource("/aggregate/{table_name}/")
+                    .route(web::get().to_async(api::aggregate)),
+            )
+            .service(
+                web::resource("/group/{table_name}/")
+                    .route(web::get().to_async(api::group)),
+            )
+            .service(
+                web::resource("/tree/{table_name}/children/")
+                    .route(web::get().to_async(api::tree_children)),
+            )
+            .service(
+                web::resource("/tree/{table_name}/ancestors/{record_id}")
+                    .route(web::get().to_async(api::tree_ancestors)),
+            )
+            .service(
+                web::resource("/image/{table_name}/{column_name}/{record_id}")
+                    .route(web::get().to_async(images::serve_image)),
+            )
+            .service(
+                web::resource("/binary/{table_name}/{column_name}/")
+                    .data(Bytes::configure(|cfg| {
+                        cfg.limit(data_modify::BINARY_SIZE_LIMIT)
+                    }))
+                    .route(web::get().to_async(binary::download))
+                    .route(web::post().to_async(binary::upload)),
+            )
+            .service(
+                web::resource("/history/")
+                    .route(web::get().to_async(api::query_history)),
+            )
+            .service(
+                web::resource("/saved_queries/")
+                    .route(web::get().to_async(api::saved_queries)),
+            )
+            .service(
+                web::resource("/save_query/")
+                    .route(web::post().to_async(api::save_query)),
+            )
+            .service(
+                web::resource("/saved_query/{id}")
+                    .route(web::get().to_async(api::open_saved_query))
+                    .route(web::delete().to_async(api::delete_saved_query)),
+            )
+            .service(
+                web::resource("/explain/")
+                    .route(web::get().to_async(api::explain)),
+            )
+            .service(
+                web::resource("/explain/{table_name}/page/{page}")
+                    .route(web::get().to_async(api::explain_main_data)),
+            )
+            .service(
+                web::resource("/erd/").route(web::get().to_async(erd::erd)),
+            )
             .service(
                 web::resource("/windows")
                     .route(web::get().to_async(api::windows)),
@@ -83,3 +229,76 @@ pub fn start() -> io::Result<()> {
         .bind(format!("0.0.0.0:{}", port))?
         .run()
 }
+
+/// read the configuration of the windows from the ron file
+/// set in DIWATA_CONFIG, such as the identifier display of the tables
+/// ```ron
+/// (displays: {"public.film": (columns: ["title", "release_year"])})
+/// ```
+fn read_config(config_path: &str) -> io::Result<Config> {
+    let mut content = String::new();
+    File::open(config_path)
+        .and_then(|mut file| file.read_to_string(&mut content))
+        .map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!("unable to read DIWATA_CONFIG {}: {}", config_path, e),
+            )
+        })?;
+    let config: Config = ron::de::from_str(&content).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "unable to parse DIWATA_CONFIG {}: {}",
+                config_path,
+                config_error(e)
+            ),
+        )
+    })?;
+    info!("DIWATA_CONFIG: {}", config_path);
+    Ok(config)
+}
+
+/// the syntax errors are reported with their position in the file
+fn config_error(e: ron::de::Error) -> String {
+    match e {
+        ron::de::Error::Parser(code, position) => {
+            format!(
+                "{:?} at line {} column {}",
+                code, position.line, position.col
+            )
+        }
+        e => e.to_string(),
+    }
+}
+
+/// read the restrictions of ad-hoc sql queries from these env variables
+/// SQL_READ_ONLY=true
+/// SQL_WRITERS=user1,user2
+/// SQL_STATEMENT_TIMEOUT=5000 (ms)
+/// SQL_ROW_LIMIT=10000
+fn sql_guard_from_env() -> SqlGuard {
+    let read_only = env::var("SQL_READ_ONLY")
+        .map(|v| v == "true" || v == "1")
+        .unwrap_or(false);
+    let writers = env::var("SQL_WRITERS")
+        .map(|v| {
+            v.split(',')
+                .map(|w| w.trim().to_string())
+                .filter(|w| !w.is_empty())
+                .collect()
+        })
+        .unwrap_or_else(|_| vec![]);
+    let statement_timeout = env::var("SQL_STATEMENT_TIMEOUT")
+        .ok()
+        .and_then(|v| v.parse().ok());
+    let row_limit = env::var("SQL_ROW_LIMIT").ok().and_then(|v| v.parse().ok());
+    let sql_guard = SqlGuard {
+        read_only,
+        writers,
+        statement_timeout,
+        row_limit,
+    };
+    info!("SQL_GUARD: {:?}", sql_guard);
+    sql_guard
+}
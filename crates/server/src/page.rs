@@ -8,6 +8,7 @@ use crate::{
 use actix_files::NamedFile;
 use actix_web::{
     dev,
+    error::BlockingError,
     middleware::errhandlers::ErrorHandlerResponse,
     web,
     Error,
@@ -17,7 +18,9 @@ use actix_web::{
     Result,
 };
 use diwata_intel::{
+    data_container::AppData,
     data_read,
+    error::IntelError,
     Context,
     TableName,
 };
@@ -54,8 +57,12 @@ fn get_index_html(
         global::PAGE_SIZE,
     )
     .expect("there should be app data");
+    render_index_html(&app_data)
+}
+
+fn render_index_html(app_data: &AppData) -> String {
     let app_data_serialized =
-        ron::ser::to_string(&app_data).expect("unable to serialize to ron");
+        ron::ser::to_string(app_data).expect("unable to serialize to ron");
     let view: Node<()> = html::html(
         vec![lang("en")],
         vec![
@@ -187,6 +194,50 @@ pub fn index_with_table(
     )
 }
 
+/// the index with the window of the table opened in the detail view
+/// of the record, so the url of the record can be shared
+pub fn index_with_record(
+    req: HttpRequest,
+    path_param: web::Path<(String, String)>,
+) -> impl Future<Item = HttpResponse, Error = Error> {
+    api::require_credentials(&req).expect("Should have credentials");
+    let credentials: Result<Credentials, ServiceError> =
+        TryFrom::try_from(&req);
+    let credentials = credentials.ok();
+
+    web::block(move || -> Result<String, ServiceError> {
+        let context = session::create_context(credentials.clone())?;
+        let (mut em, mut dm) = session::get_em_dm(credentials)?;
+        let table_name = TableName::from(&path_param.0);
+        let app_data = data_read::retrieve_app_data_with_record(
+            &context,
+            &mut em,
+            &mut dm,
+            Some(table_name),
+            Some(&path_param.1),
+            global::PAGE_SIZE,
+        )?;
+        Ok(render_index_html(&app_data))
+    })
+    .then(|index_html| {
+        match index_html {
+            Ok(index_html) => {
+                Ok(HttpResponse::Ok()
+                    .content_type("text/html")
+                    .body(index_html))
+            }
+            // the record may have been deleted since the url was shared
+            Err(BlockingError::Error(ServiceError::IntelError(
+                IntelError::NotFound,
+            )))
+            | Err(BlockingError::Error(ServiceError::IntelError(
+                IntelError::ParamParseError(_),
+            ))) => Ok(HttpResponse::NotFound().finish()),
+            Err(e) => Err(Error::from(e)),
+        }
+    })
+}
+
 pub fn bad_request<B>(
     res: dev::ServiceResponse<B>,
 ) -> Result<ErrorHandlerResponse<B>> {
@@ -58,11 +58,14 @@ pub fn create_context(
 
     let windows = cache_pool.get_cached_windows(&mut active_em, &db_url)?;
     let tables = cache_pool.get_cached_tables(&mut active_em, &db_url)?;
+    let statistics =
+        cache_pool.get_cached_statistics(&mut active_em, &db_url)?;
     let grouped_window = get_grouped_windows(&mut active_em, &tables)?;
     Ok(Context {
         tables: to_hashmap_tables(tables),
         windows: to_hashmap_windows(windows),
         grouped_window,
+        statistics,
     })
 }
 
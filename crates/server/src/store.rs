@@ -0,0 +1,312 @@
+//! A local sqlite store of the query history and saved queries
+//! of the users, this is separate from the database being browsed
+use crate::{
+    error::ServiceError,
+    global,
+};
+use diwata_intel::query_history::{
+    QueryHistory,
+    SavedQuery,
+};
+use rustorm::{
+    Dao,
+    Value,
+};
+use url::Url;
+
+/// the maximum number of history entries returned in a listing
+pub static HISTORY_LIMIT: usize = 200;
+
+/// create the tables of the store if they don't exist yet
+pub fn init_store() -> Result<(), ServiceError> {
+    let mut dm = global::get_pool_store_dm()?;
+    dm.execute_sql_with_return(
+        "CREATE TABLE IF NOT EXISTS query_history(
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            username TEXT,
+            db_url TEXT NOT NULL,
+            sql TEXT NOT NULL,
+            duration INTEGER NOT NULL,
+            row_count INTEGER,
+            error TEXT,
+            created TEXT NOT NULL DEFAULT (datetime('now'))
+        )",
+        &[],
+    )?;
+    dm.execute_sql_with_return(
+        "CREATE TABLE IF NOT EXISTS saved_query(
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            username TEXT,
+            db_url TEXT,
+            name TEXT NOT NULL,
+            sql TEXT NOT NULL,
+            tags TEXT NOT NULL DEFAULT '',
+            is_shared INTEGER NOT NULL DEFAULT 0,
+            created TEXT NOT NULL DEFAULT (datetime('now'))
+        )",
+        &[],
+    )?;
+    Ok(())
+}
+
+/// remove the password from the db_url, so it is not recorded in the store
+pub fn redact_db_url(db_url: &str) -> String {
+    match Url::parse(db_url) {
+        Ok(mut url) => {
+            if url.password().is_some() {
+                url.set_password(None).expect("must remove password");
+            }
+            url.to_string()
+        }
+        Err(_e) => db_url.to_string(),
+    }
+}
+
+/// the history is not recorded when the store is disabled
+pub fn record_history(history: &QueryHistory) -> Result<(), ServiceError> {
+    if !global::is_store_enabled() {
+        return Ok(());
+    }
+    let mut dm = global::get_pool_store_dm()?;
+    dm.execute_sql_with_return(
+        "INSERT INTO query_history(username, db_url, sql, duration, row_count, error)
+        VALUES (?, ?, ?, ?, ?, ?)",
+        &[
+            &optional_text(&history.username),
+            &Value::Text(history.db_url.to_string()),
+            &Value::Text(history.sql.to_string()),
+            &Value::Bigint(history.duration as i64),
+            &history
+                .row_count
+                .map(|count| Value::Bigint(count as i64))
+                .unwrap_or(Value::Nil),
+            &optional_text(&history.error),
+        ],
+    )?;
+    Ok(())
+}
+
+/// the recent queries of the user on this database,
+/// filtered with the sql containing the search
+pub fn search_history(
+    username: Option<&str>,
+    db_url: &str,
+    search: &str,
+) -> Result<Vec<QueryHistory>, ServiceError> {
+    let mut dm = global::get_pool_store_dm()?;
+    let rows = dm.execute_sql_with_return(
+        "SELECT * FROM query_history
+        WHERE (username = ? OR (username IS NULL AND ? IS NULL))
+        AND db_url = ?
+        AND sql LIKE ? ESCAPE '\\'
+        ORDER BY id DESC
+        LIMIT ?",
+        &[
+            &optional_text(&username.map(ToString::to_string)),
+            &optional_text(&username.map(ToString::to_string)),
+            &Value::Text(db_url.to_string()),
+            &Value::Text(like_pattern(search)),
+            &Value::Bigint(HISTORY_LIMIT as i64),
+        ],
+    )?;
+    Ok(rows.iter().map(|dao| history_from_dao(&dao)).collect())
+}
+
+/// the saved queries of the user and the ones shared by the other users
+pub fn search_saved_queries(
+    username: Option<&str>,
+    db_url: &str,
+    search: &str,
+) -> Result<Vec<SavedQuery>, ServiceError> {
+    let mut dm = global::get_pool_store_dm()?;
+    let rows = dm.execute_sql_with_return(
+        "SELECT * FROM saved_query
+        WHERE (username = ? OR (username IS NULL AND ? IS NULL) OR is_shared = 1)
+        AND (db_url = ? OR db_url IS NULL)
+        AND (name LIKE ? ESCAPE '\\' OR sql LIKE ? ESCAPE '\\'
+            OR tags LIKE ? ESCAPE '\\')
+        ORDER BY name",
+        &[
+            &optional_text(&username.map(ToString::to_string)),
+            &optional_text(&username.map(ToString::to_string)),
+            &Value::Text(db_url.to_string()),
+            &Value::Text(like_pattern(search)),
+            &Value::Text(like_pattern(search)),
+            &Value::Text(like_pattern(search)),
+        ],
+    )?;
+    Ok(rows.iter().map(|dao| saved_query_from_dao(&dao)).collect())
+}
+
+/// get the saved query if it is owned by the user or is shared,
+/// and is saved for the same database as the db_url
+pub fn get_saved_query(
+    username: Option<&str>,
+    db_url: &str,
+    id: i64,
+) -> Result<SavedQuery, ServiceError> {
+    let mut dm = global::get_pool_store_dm()?;
+    let rows = dm.execute_sql_with_return(
+        "SELECT * FROM saved_query WHERE id = ?
+        AND (db_url = ? OR db_url IS NULL)",
+        &[&Value::Bigint(id), &Value::Text(db_url.to_string())],
+    )?;
+    let saved_query = rows
+        .iter()
+        .map(|dao| saved_query_from_dao(&dao))
+        .next()
+        .ok_or(ServiceError::NotFound)?;
+    if saved_query.is_shared
+        || saved_query.username.as_ref().map(String::as_str) == username
+    {
+        Ok(saved_query)
+    } else {
+        Err(ServiceError::NotFound)
+    }
+}
+
+/// insert the query when it has no id yet, otherwise update
+/// the existing query owned by the user
+pub fn save_query(
+    saved_query: &SavedQuery,
+) -> Result<SavedQuery, ServiceError> {
+    let mut dm = global::get_pool_store_dm()?;
+    let username = saved_query.username.as_ref().map(String::as_str);
+    let db_url = saved_query
+        .db_url
+        .as_ref()
+        .map(String::as_str)
+        .unwrap_or("");
+    let tags = saved_query.tags.join(",");
+    let is_shared = Value::Int(if saved_query.is_shared { 1 } else { 0 });
+    match saved_query.id {
+        Some(id) => {
+            let existing = get_saved_query(username, db_url, id)?;
+            if existing.username != saved_query.username {
+                return Err(ServiceError::NotFound);
+            }
+            dm.execute_sql_with_return(
+                "UPDATE saved_query
+                SET name = ?, sql = ?, tags = ?, is_shared = ?
+                WHERE id = ?",
+                &[
+                    &Value::Text(saved_query.name.to_string()),
+                    &Value::Text(saved_query.sql.to_string()),
+                    &Value::Text(tags),
+                    &is_shared,
+                    &Value::Bigint(id),
+                ],
+            )?;
+            get_saved_query(username, db_url, id)
+        }
+        None => {
+            dm.execute_sql_with_return(
+                "INSERT INTO saved_query(username, db_url, name, sql, tags, is_shared)
+                VALUES (?, ?, ?, ?, ?, ?)",
+                &[
+                    &optional_text(&saved_query.username),
+                    &optional_text(&saved_query.db_url),
+                    &Value::Text(saved_query.name.to_string()),
+                    &Value::Text(saved_query.sql.to_string()),
+                    &Value::Text(tags),
+                    &is_shared,
+                ],
+            )?;
+            let rows = dm.execute_sql_with_return(
+                "SELECT * FROM saved_query WHERE id = last_insert_rowid()",
+                &[],
+            )?;
+            rows.iter()
+                .map(|dao| saved_query_from_dao(&dao))
+                .next()
+                .ok_or(ServiceError::NotFound)
+        }
+    }
+}
+
+/// only the owner can delete the saved query
+pub fn delete_saved_query(
+    username: Option<&str>,
+    db_url: &str,
+    id: i64,
+) -> Result<(), ServiceError> {
+    let mut dm = global::get_pool_store_dm()?;
+    let existing = get_saved_query(username, db_url, id)?;
+    if existing.username.as_ref().map(String::as_str) != username {
+        return Err(ServiceError::NotFound);
+    }
+    dm.execute_sql_with_return(
+        "DELETE FROM saved_query WHERE id = ?",
+        &[&Value::Bigint(id)],
+    )?;
+    Ok(())
+}
+
+/// the search is matched as is, so the wildcards of LIKE
+/// and the escape character in it are escaped
+fn like_pattern(search: &str) -> String {
+    let escaped = search
+        .trim()
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{}%", escaped)
+}
+
+fn optional_text(value: &Option<String>) -> Value {
+    match value {
+        Some(value) => Value::Text(value.to_string()),
+        None => Value::Nil,
+    }
+}
+
+fn get_text(dao: &Dao, column: &str) -> Option<String> {
+    match dao.get_value(column) {
+        Some(Value::Text(value)) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+/// sqlite integers may come as any of the integer values
+fn get_integer(dao: &Dao, column: &str) -> Option<i64> {
+    match dao.get_value(column) {
+        Some(Value::Tinyint(v)) => Some(i64::from(*v)),
+        Some(Value::Smallint(v)) => Some(i64::from(*v)),
+        Some(Value::Int(v)) => Some(i64::from(*v)),
+        Some(Value::Bigint(v)) => Some(*v),
+        _ => None,
+    }
+}
+
+fn history_from_dao(dao: &Dao) -> QueryHistory {
+    QueryHistory {
+        id: get_integer(dao, "id"),
+        username: get_text(dao, "username"),
+        db_url: get_text(dao, "db_url").unwrap_or_default(),
+        sql: get_text(dao, "sql").unwrap_or_default(),
+        duration: get_integer(dao, "duration").unwrap_or(0) as u64,
+        row_count: get_integer(dao, "row_count").map(|count| count as usize),
+        error: get_text(dao, "error"),
+        created: get_text(dao, "created"),
+    }
+}
+
+fn saved_query_from_dao(dao: &Dao) -> SavedQuery {
+    SavedQuery {
+        id: get_integer(dao, "id"),
+        username: get_text(dao, "username"),
+        db_url: get_text(dao, "db_url"),
+        name: get_text(dao, "name").unwrap_or_default(),
+        sql: get_text(dao, "sql").unwrap_or_default(),
+        tags: get_text(dao, "tags")
+            .map(|tags| {
+                tags.split(',')
+                    .map(|tag| tag.trim().to_string())
+                    .filter(|tag| !tag.is_empty())
+                    .collect()
+            })
+            .unwrap_or_else(|| vec![]),
+        is_shared: get_integer(dao, "is_shared").unwrap_or(0) != 0,
+        created: get_text(dao, "created"),
+    }
+}
@@ -1,27 +1,44 @@
-use crate::rest_api;
+use crate::{rest_api, route::Route};
 use diwata_intel::{
     data_container::{AppData, QueryResult, WindowData},
+    data_group::GroupResult,
+    data_import::ImportSummary,
+    data_tree::TreeNode,
+    erd::Erd,
+    query_history::{QueryHistory, SavedQuery},
+    query_plan::QueryPlan,
+    sql_params::{ParameterizedSql, Placeholder},
     window::GroupedWindow,
-    RecordDetail,
+    Dao, RecordDetail, Value,
 };
 use sauron::{
     html::{attributes::*, events::*, *},
-    Browser, Component, Node,
+    Browser, Component, Dispatch, Node,
 };
 
-use wasm_bindgen::JsValue;
+use wasm_bindgen::{closure::Closure, JsCast, JsValue};
 
+use erd_view::ErdView;
+use query_history_view::QueryHistoryView;
 use window_list_view::WindowListView;
 use window_view::WindowView;
 
+mod chart_view;
 mod column_view;
 mod detail_view;
+mod erd_view;
 mod field_view;
+mod group_view;
+mod import_view;
 mod page_view;
+mod plan_view;
+mod query_history_view;
 mod row_view;
+mod summary_view;
 mod tab_view;
 mod table_view;
 mod toolbar_view;
+mod tree_view;
 mod window_list_view;
 mod window_view;
 
@@ -40,7 +57,32 @@ pub enum Msg {
     ReceivedWindowQueryResult(usize, Result<QueryResult, JsValue>),
     ReceivedWindowData(Result<QueryResult, JsValue>),
     ReceivedWindowDataNextPage(usize, usize, Result<QueryResult, JsValue>),
+    /// the first page of the rows with the changed filter or sort,
+    /// with the record to be shown in the detail view
+    ReceivedFilteredWindowData(usize, Option<String>, Result<QueryResult, JsValue>),
+    /// the url is changed with the back and forward of the browser
+    UrlChanged(Route),
     ReceivedWindowMainTabDetail(usize, usize, usize, Result<RecordDetail, JsValue>),
+    ReceivedQueryPlan(usize, Result<QueryPlan, JsValue>),
+    ReceivedSqlPlaceholders(usize, String, Result<Vec<Placeholder>, JsValue>),
+    ReceivedSavedRecords(usize, Result<Vec<Dao>, JsValue>),
+    ReceivedImportSummary(usize, Result<ImportSummary, JsValue>),
+    ReceivedAggregates(usize, Result<Vec<Value>, JsValue>),
+    ReceivedGroups(usize, Result<GroupResult, JsValue>),
+    /// the children of the record in the tree view, the roots when there is no parent
+    ReceivedTreeChildren(usize, Option<String>, Result<Vec<TreeNode>, JsValue>),
+    ReceivedTreeAncestors(usize, Result<Vec<TreeNode>, JsValue>),
+    ToggleQueryHistory,
+    QueryHistoryMsg(query_history_view::Msg),
+    ReceivedQueryHistory(Result<Vec<QueryHistory>, JsValue>),
+    ReceivedSavedQueries(Result<Vec<SavedQuery>, JsValue>),
+    ReceivedSavedQuery(Result<SavedQuery, JsValue>),
+    ReceivedDeletedSavedQuery(Result<(), JsValue>),
+    /// the result of the history or saved query, opened as a new window
+    ReceivedOpenedQuery(String, Result<QueryResult, JsValue>),
+    ToggleErd,
+    ErdMsg(erd_view::Msg),
+    ReceivedErd(Result<Erd, JsValue>),
 }
 
 pub struct App {
@@ -50,6 +92,8 @@ pub struct App {
     browser_height: i32,
     browser_width: i32,
     window_list_view: WindowListView,
+    query_history_view: QueryHistoryView,
+    erd_view: ErdView,
     is_page_request_in_flight: bool,
 }
 
@@ -61,11 +105,19 @@ impl App {
                 .into_iter()
                 .zip(app_data.window_data.iter())
                 .map(|(window, window_data)| {
-                    WindowView::new(window, &window_data, browser_width, browser_height)
+                    let mut window_view =
+                        WindowView::new(window, &window_data, browser_width, browser_height);
+                    // the window of a shared record url opens in its detail view
+                    if let Some(record_detail) = &window_data.record_detail {
+                        window_view.show_main_tab_record(&record_detail.record);
+                    }
+                    window_view
                 })
                 .collect(),
             window_data: app_data.window_data,
             window_list_view: WindowListView::new(app_data.grouped_window),
+            query_history_view: QueryHistoryView::new(),
+            erd_view: ErdView::new(),
             active_window: 0,
             browser_width,
             browser_height,
@@ -118,9 +170,232 @@ impl App {
         self.activate_window(0);
     }
 
+    /// add a new window from the query result, the sql_query is shown in the toolbar
+    fn add_window_from_query_result(&mut self, query_result: QueryResult, sql_query: String) {
+        if let Some(window) = query_result.window {
+            let mut window_data = WindowData::from_rows(query_result.rows);
+            window_data.sql_query = Some(sql_query);
+            let mut new_window = WindowView::new(
+                window,
+                &window_data,
+                self.browser_width,
+                self.browser_height,
+            );
+            new_window.set_is_editable(query_result.is_editable);
+            self.window_data.push(window_data);
+            self.window_views.push(new_window);
+            self.activate_last_added_window();
+        } else {
+            trace!("No window returned in query result");
+        }
+    }
+
+    /// fetch the history or the saved queries, whichever is shown in the panel
+    fn refresh_query_history(&self) -> Cmd {
+        let search = &self.query_history_view.search;
+        if self.query_history_view.show_saved_queries {
+            rest_api::fetch_saved_queries(search, Msg::ReceivedSavedQueries)
+        } else {
+            rest_api::fetch_query_history(search, Msg::ReceivedQueryHistory)
+        }
+    }
+
+    /// the query with placeholders is put in the sql of the active window instead
+    /// of executing it, so the values of the placeholders can be filled in first
+    fn edit_parameterized_query(&mut self, sql: String) -> Cmd {
+        self.update(Msg::WindowMsg(
+            self.active_window,
+            window_view::Msg::ToolbarMsg(toolbar_view::Msg::QueryChanged(sql)),
+        ))
+    }
+
+    /// fetch the diagram of the tables picked in the erd view
+    fn fetch_erd(&self) -> Cmd {
+        rest_api::fetch_erd(
+            &self.erd_view.schema,
+            &self.erd_view.center,
+            self.erd_view.depth,
+            Msg::ReceivedErd,
+        )
+    }
+
+    /// import the csv selected in the import view of the window
+    fn import_csv(&self, index: usize, dry_run: bool) -> Cmd {
+        let import_view = self.window_views[index].import_view();
+        rest_api::import_csv(
+            &import_view.file_input_id,
+            &import_view.table_name,
+            import_view.mappings(),
+            dry_run,
+            move |summary| Msg::ReceivedImportSummary(index, summary),
+        )
+    }
+
+    /// upload the file selected in the binary field of the detail view
+    fn upload_binary(&self, index: usize, field_index: usize) -> Cmd {
+        let detail_view = self.window_views[index].main_tab.detail_view();
+        match detail_view.upload_target(field_index) {
+            Some((file_input_id, url)) => {
+                rest_api::upload_binary(&file_input_id, &url, move |record| {
+                    Msg::ReceivedSavedRecords(index, record.map(|record| vec![record]))
+                })
+            }
+            None => Cmd::none(),
+        }
+    }
+
+    /// the aggregates picked in the summary of the main tab
+    fn fetch_aggregates(&self, index: usize) -> Cmd {
+        let table_view = &self.window_views[index].main_tab.table_view;
+        let aggregates = table_view.summary_view.column_aggregates();
+        if aggregates.is_empty() {
+            return Cmd::none();
+        }
+        rest_api::fetch_aggregates(&table_view.table_name, &aggregates, move |values| {
+            Msg::ReceivedAggregates(index, values)
+        })
+    }
+
+    /// group the rows of the table of the window as picked in its group view
+    fn fetch_groups(&self, index: usize) -> Cmd {
+        let group_view = self.window_views[index].group_view();
+        if !group_view.can_group() {
+            return Cmd::none();
+        }
+        rest_api::fetch_groups(
+            &group_view.table_name,
+            &group_view.group_request(),
+            move |result| Msg::ReceivedGroups(index, result),
+        )
+    }
+
+    /// the children of the parent in the tree view of the window
+    fn fetch_tree_children(&self, index: usize, parent: Option<String>) -> Cmd {
+        let table_name = &self.window_views[index].main_tab.table_name;
+        rest_api::fetch_tree_children(
+            table_name,
+            parent.as_ref().map(String::as_str),
+            move |children| Msg::ReceivedTreeChildren(index, parent.clone(), children),
+        )
+    }
+
     fn setup_window_resize_listener(&self) -> Cmd {
         Browser::onresize(Msg::BrowserResized)
     }
+
+    fn setup_popstate_listener(&self) -> Cmd {
+        Cmd::new(|program| {
+            let program = program.clone();
+            let onpopstate = Closure::wrap(Box::new(move |_event: web_sys::Event| {
+                program.dispatch(Msg::UrlChanged(Route::from_location()));
+            }) as Box<dyn Fn(web_sys::Event)>);
+            sauron::window()
+                .add_event_listener_with_callback("popstate", onpopstate.as_ref().unchecked_ref())
+                .expect("unable to listen to popstate");
+            onpopstate.forget();
+        })
+    }
+
+    /// restore the route in the url, such as when loaded with a filter in the url
+    fn restore_initial_route(&self) -> Cmd {
+        Cmd::new(|program| program.dispatch(Msg::UrlChanged(Route::from_location())))
+    }
+
+    /// add the route of the active window to the browser history
+    fn push_route(&self) {
+        if let Some(window_view) = self.window_views.get(self.active_window) {
+            let route = window_view.route();
+            if route != Route::from_location() {
+                route.push();
+            }
+        }
+    }
+
+    /// show the window, the record and the filter and sort in the route,
+    /// the window which is not opened is rendered by the server
+    fn restore_route(&mut self, route: Route) -> Cmd {
+        let table_name = match route.table_name {
+            Some(ref table_name) => table_name,
+            None => return Cmd::none(),
+        };
+        let index = self
+            .window_views
+            .iter()
+            .position(|window_view| window_view.main_tab.table_name == *table_name);
+        let index = match index {
+            Some(index) => index,
+            None => {
+                if let Err(e) = sauron::window().location().set_href(&route.to_url()) {
+                    trace!("unable to open the url: {:?}", e);
+                }
+                return Cmd::none();
+            }
+        };
+        self.activate_window(index);
+        let table_view = &mut self.window_views[index].main_tab.table_view;
+        if table_view.filter() != route.filter || table_view.sort() != route.sort {
+            table_view.set_filter_and_sort(&route.filter, &route.sort);
+            // the record is shown when the rows are received
+            self.fetch_filtered_window_data(index, route.record_id)
+        } else {
+            self.show_route_record(index, route.record_id.as_ref().map(String::as_str))
+        }
+    }
+
+    /// show the record in the detail view, or close the detail view when there is none
+    fn show_route_record(&mut self, index: usize, record_id: Option<&str>) -> Cmd {
+        let window_view = &mut self.window_views[index];
+        if window_view.detail_record_id().as_ref().map(String::as_str) == record_id {
+            return Cmd::none();
+        }
+        match record_id {
+            Some(record_id) => match window_view.main_tab.table_view.find_record_id(record_id) {
+                Some((page_index, row_index)) => {
+                    self.open_main_tab_detail(index, page_index, row_index)
+                }
+                None => {
+                    trace!("The record {} is not in the rows", record_id);
+                    Cmd::none()
+                }
+            },
+            None if window_view.in_detail_view() => {
+                window_view.update(window_view::Msg::CloseDetailView)
+            }
+            None => Cmd::none(),
+        }
+    }
+
+    /// show the row in the detail view while its detail is retrieved
+    fn open_main_tab_detail(
+        &mut self,
+        window_index: usize,
+        page_index: usize,
+        row_index: usize,
+    ) -> Cmd {
+        let main_tab_view = &mut self.window_views[window_index].main_tab;
+        main_tab_view.show_detail_view(page_index, row_index);
+        let table_name = &main_tab_view.table_name;
+        let dao = &main_tab_view
+            .table_view
+            .get_row_primary_dao(page_index, row_index);
+        rest_api::retrieve_detail_for_main_tab(table_name, dao, move |detail| {
+            Msg::ReceivedWindowMainTabDetail(window_index, page_index, row_index, detail)
+        })
+    }
+
+    /// the first page of the rows of the window with its filter and sort
+    fn fetch_filtered_window_data(&self, index: usize, record_id: Option<String>) -> Cmd {
+        let table_view = &self.window_views[index].main_tab.table_view;
+        rest_api::fetch_window_data_page(
+            &table_view.table_name,
+            &table_view.filter(),
+            &table_view.sort(),
+            1,
+            move |query_result| {
+                Msg::ReceivedFilteredWindowData(index, record_id.clone(), query_result)
+            },
+        )
+    }
 }
 
 impl Component<Msg> for App {
@@ -128,6 +403,8 @@ impl Component<Msg> for App {
         Cmd::batch(vec![
             //rest_api::fetch_window_list(),
             self.setup_window_resize_listener(),
+            self.setup_popstate_listener(),
+            self.restore_initial_route(),
         ])
     }
 
@@ -135,12 +412,15 @@ impl Component<Msg> for App {
         match msg {
             Msg::ActivateWindow(index) => {
                 self.activate_window(index);
+                self.push_route();
                 Cmd::none()
             }
             Msg::RemoveWindow(index) => {
                 self.remove_window(index);
+                self.push_route();
                 Cmd::none()
             }
+            Msg::UrlChanged(route) => self.restore_route(route),
 
             Msg::WindowMsg(
                 window_index,
@@ -157,33 +437,191 @@ impl Component<Msg> for App {
                     ),
                 ));
 
-                let window_view = &mut self.window_views[window_index];
-                window_view.update(window_msg);
-                let main_tab_view = &mut window_view.main_tab;
+                self.window_views[window_index].update(window_msg);
                 // show the row first, while the detail is loading
-                main_tab_view.show_detail_view(page_index, row_index);
+                let cmd = self.open_main_tab_detail(window_index, page_index, row_index);
+                self.push_route();
+                cmd
+            }
 
-                let table_name = &main_tab_view.table_name;
-                let dao = &main_tab_view
-                    .table_view
-                    .get_row_primary_dao(page_index, row_index);
-                rest_api::retrieve_detail_for_main_tab(table_name, dao, move |detail| {
-                    Msg::ReceivedWindowMainTabDetail(window_index, page_index, row_index, detail)
-                })
+            Msg::WindowMsg(index, window_view::Msg::CloseDetailView) => {
+                let cmd = self.window_views[index].update(window_view::Msg::CloseDetailView);
+                self.push_route();
+                cmd
+            }
+
+            Msg::WindowMsg(
+                index,
+                window_view::Msg::MainTabMsg(tab_view::Msg::TableMsg(table_view::Msg::ColumnMsg(
+                    column_index,
+                    column_msg,
+                ))),
+            ) => {
+                self.window_views[index].update(window_view::Msg::MainTabMsg(
+                    tab_view::Msg::TableMsg(table_view::Msg::ColumnMsg(column_index, column_msg)),
+                ));
+                self.push_route();
+                let record_id = self.window_views[index].detail_record_id();
+                self.fetch_filtered_window_data(index, record_id)
             }
 
             Msg::WindowMsg(index, window_view::Msg::ToolbarMsg(toolbar_view::Msg::RunQuery)) => {
-                let sql = self.window_views[index].sql_query();
+                let window_view = &mut self.window_views[index];
+                window_view.update(window_view::Msg::ToolbarMsg(toolbar_view::Msg::RunQuery));
+                let sql = window_view.sql_query();
                 if let Some(sql) = sql {
                     trace!("In app.rs Run the query: {}", sql);
-                    rest_api::execute_sql_query(&sql, move |window_rows| {
-                        Msg::ReceivedWindowQueryResult(index, window_rows)
-                    })
+                    match window_view.sql_params() {
+                        Ok(params) => {
+                            rest_api::execute_sql_query(&sql, &params, move |window_rows| {
+                                Msg::ReceivedWindowQueryResult(index, window_rows)
+                            })
+                        }
+                        Err(err) => {
+                            window_view.set_query_error(Some(err));
+                            Cmd::none()
+                        }
+                    }
                 } else {
                     trace!("Nothing to execute!");
                     Cmd::none()
                 }
             }
+            Msg::WindowMsg(index, window_view::Msg::ToolbarMsg(toolbar_view::Msg::SaveChanges)) => {
+                let window_view = &mut self.window_views[index];
+                window_view.update(window_view::Msg::ToolbarMsg(toolbar_view::Msg::SaveChanges));
+                match window_view.save_container() {
+                    Some(container) => rest_api::save_container(&container, move |records| {
+                        Msg::ReceivedSavedRecords(index, records)
+                    }),
+                    None => {
+                        trace!("There are no changes to be saved");
+                        Cmd::none()
+                    }
+                }
+            }
+            Msg::WindowMsg(
+                index,
+                window_view::Msg::MainTabMsg(tab_view::Msg::TableMsg(table_view::Msg::SummaryMsg(
+                    summary_msg,
+                ))),
+            ) => {
+                self.window_views[index].update(window_view::Msg::MainTabMsg(
+                    tab_view::Msg::TableMsg(table_view::Msg::SummaryMsg(summary_msg)),
+                ));
+                self.fetch_aggregates(index)
+            }
+            Msg::WindowMsg(index, window_view::Msg::GroupMsg(group_view::Msg::Run)) => {
+                self.window_views[index].update(window_view::Msg::GroupMsg(group_view::Msg::Run));
+                self.fetch_groups(index)
+            }
+            Msg::WindowMsg(index, window_view::Msg::ToolbarMsg(toolbar_view::Msg::ToggleTree)) => {
+                let window_view = &mut self.window_views[index];
+                window_view.update(window_view::Msg::ToolbarMsg(toolbar_view::Msg::ToggleTree));
+                let needs_roots = window_view
+                    .tree_view()
+                    .map_or(false, |tree_view| tree_view.needs_roots());
+                if needs_roots {
+                    self.fetch_tree_children(index, None)
+                } else {
+                    Cmd::none()
+                }
+            }
+            Msg::WindowMsg(
+                index,
+                window_view::Msg::TreeMsg(tree_view::Msg::ToggleExpand(record_id)),
+            ) => {
+                let window_view = &mut self.window_views[index];
+                window_view.update(window_view::Msg::TreeMsg(tree_view::Msg::ToggleExpand(
+                    record_id.clone(),
+                )));
+                let needs_children = window_view
+                    .tree_view()
+                    .map_or(false, |tree_view| tree_view.needs_children(&record_id));
+                if needs_children {
+                    self.fetch_tree_children(index, Some(record_id))
+                } else {
+                    Cmd::none()
+                }
+            }
+            Msg::WindowMsg(index, window_view::Msg::TreeMsg(tree_view::Msg::Select(record_id))) => {
+                self.window_views[index].update(window_view::Msg::TreeMsg(tree_view::Msg::Select(
+                    record_id.clone(),
+                )));
+                let show_record = self.show_route_record(index, Some(&record_id));
+                self.push_route();
+                let table_name = &self.window_views[index].main_tab.table_name;
+                Cmd::batch(vec![
+                    show_record,
+                    rest_api::fetch_tree_ancestors(table_name, &record_id, move |ancestors| {
+                        Msg::ReceivedTreeAncestors(index, ancestors)
+                    }),
+                ])
+            }
+            Msg::WindowMsg(
+                index,
+                window_view::Msg::MainTabMsg(tab_view::Msg::DetailViewMsg(
+                    detail_view::Msg::Upload(field_index),
+                )),
+            ) => self.upload_binary(index, field_index),
+            Msg::WindowMsg(index, window_view::Msg::ImportMsg(import_view::Msg::DryRun)) => {
+                self.window_views[index]
+                    .update(window_view::Msg::ImportMsg(import_view::Msg::DryRun));
+                self.import_csv(index, true)
+            }
+            Msg::WindowMsg(index, window_view::Msg::ImportMsg(import_view::Msg::Import)) => {
+                self.window_views[index]
+                    .update(window_view::Msg::ImportMsg(import_view::Msg::Import));
+                self.import_csv(index, false)
+            }
+            Msg::WindowMsg(
+                index,
+                window_view::Msg::ToolbarMsg(toolbar_view::Msg::QueryChanged(sql_query)),
+            ) => {
+                let window_view = &mut self.window_views[index];
+                window_view.update(window_view::Msg::ToolbarMsg(
+                    toolbar_view::Msg::QueryChanged(sql_query.to_string()),
+                ));
+                if window_view.has_placeholders() {
+                    rest_api::fetch_sql_placeholders(&sql_query, move |placeholders| {
+                        Msg::ReceivedSqlPlaceholders(index, sql_query.to_string(), placeholders)
+                    })
+                } else {
+                    Cmd::none()
+                }
+            }
+            Msg::WindowMsg(
+                index,
+                window_view::Msg::ToolbarMsg(toolbar_view::Msg::ExplainQuery),
+            ) => {
+                let window_view = &mut self.window_views[index];
+                window_view.update(window_view::Msg::ToolbarMsg(
+                    toolbar_view::Msg::ExplainQuery,
+                ));
+                let analyze = window_view.is_analyze();
+                let table_name = &window_view.main_tab.table_name;
+                let window_sql = format!("SELECT * FROM {}", table_name.complete_name());
+                match window_view.sql_query() {
+                    // explain the query used in the window when the sql is not modified
+                    Some(sql) if sql != window_sql => {
+                        trace!("Explaining the query: {}", sql);
+                        match window_view.sql_params() {
+                            Ok(params) => {
+                                rest_api::explain_sql_query(&sql, &params, analyze, move |plan| {
+                                    Msg::ReceivedQueryPlan(index, plan)
+                                })
+                            }
+                            Err(err) => {
+                                window_view.set_query_error(Some(err));
+                                Cmd::none()
+                            }
+                        }
+                    }
+                    _ => rest_api::explain_window_query(table_name, 1, analyze, move |plan| {
+                        Msg::ReceivedQueryPlan(index, plan)
+                    }),
+                }
+            }
             Msg::WindowMsg(window_index, window_view::Msg::MainTabMsg(tab_msg)) => {
                 let main_tab = &mut self.window_views[window_index].main_tab;
                 let main_tab_current_page = self.window_data[window_index].main_tab_current_page;
@@ -199,8 +637,10 @@ impl Component<Msg> for App {
                         "---->>> is a page request in flight: {}",
                         self.is_page_request_in_flight
                     );
-                    rest_api::fetch_window_data_next_page(
+                    rest_api::fetch_window_data_page(
                         &main_tab.table_name,
+                        &main_tab.table_view.filter(),
+                        &main_tab.table_view.sort(),
                         next_page,
                         move |query_result| {
                             Msg::ReceivedWindowDataNextPage(window_index, next_page, query_result)
@@ -228,10 +668,11 @@ impl Component<Msg> for App {
             }
             Msg::WindowListMsg(window_list_view::Msg::ClickedWindow(table_name)) => {
                 trace!("fetching data for {}", table_name.complete_name());
-                let url = format!("/{}", table_name.complete_name());
-                sauron::history()
-                    .replace_state_with_url(&JsValue::NULL, &table_name.complete_name(), Some(&url))
-                    .expect("unable to replace state with url");
+                Route {
+                    table_name: Some(table_name.clone()),
+                    ..Default::default()
+                }
+                .push();
                 rest_api::fetch_window_data(&table_name, move |window_rows| {
                     Msg::ReceivedWindowData(window_rows)
                 })
@@ -250,38 +691,22 @@ impl Component<Msg> for App {
                 Cmd::none()
             }
 
-            Msg::ReceivedWindowData(query_result) => {
-                match query_result {
-                    Ok(query_result) => {
-                        if let Some(window) = query_result.window {
-                            let window_clone = window.clone();
-                            let sql_query =
-                                format!("SELECT * FROM {}", window.table_name().complete_name());
-                            let mut window_data = WindowData::from_rows(query_result.rows);
-                            window_data.sql_query = Some(sql_query.to_string());
-                            let new_window = WindowView::new(
-                                window_clone,
-                                &window_data,
-                                self.browser_width,
-                                self.browser_height,
-                            );
-                            // set the previous sql query
-                            // new_window.set_window_data(window_data);
-                            // replace the previous window
-                            self.window_data.push(window_data);
-                            self.window_views.push(new_window);
-                            self.activate_last_added_window();
-                        } else {
-                            trace!("No window returned in query result");
-                        }
-                        Cmd::none()
-                    }
-                    Err(err) => {
-                        trace!("error fetching window data: {:?}", err);
-                        Cmd::none()
+            Msg::ReceivedWindowData(query_result) => match query_result {
+                Ok(query_result) => {
+                    if let Some(window) = &query_result.window {
+                        let sql_query =
+                            format!("SELECT * FROM {}", window.table_name().complete_name());
+                        self.add_window_from_query_result(query_result, sql_query);
+                    } else {
+                        trace!("No window returned in query result");
                     }
+                    Cmd::none()
                 }
-            }
+                Err(err) => {
+                    trace!("error fetching window data: {:?}", err);
+                    Cmd::none()
+                }
+            },
             Msg::ReceivedWindowDataNextPage(window_index, page, Ok(query_result)) => {
                 trace!("Got data for next page {}: {:#?}", page, query_result);
                 let window_data = &mut self.window_data[window_index];
@@ -295,18 +720,33 @@ impl Component<Msg> for App {
                 trace!("Error retrieving next page {}", page);
                 Cmd::none()
             }
+            Msg::ReceivedFilteredWindowData(index, record_id, Ok(query_result)) => {
+                let window_data = &mut self.window_data[index];
+                let rows = query_result.rows;
+                window_data.main_tab_total_rows = rows.count.unwrap_or(0);
+                window_data.main_tab_current_page = 1;
+                window_data.main_tab_data = vec![];
+                window_data.add_main_data_page(rows);
+                self.window_views[index].set_window_data(window_data);
+                self.show_route_record(index, record_id.as_ref().map(String::as_str))
+            }
+            Msg::ReceivedFilteredWindowData(_index, _record_id, Err(err)) => {
+                trace!("Error retrieving the filtered rows: {:?}", err);
+                Cmd::none()
+            }
 
             Msg::ReceivedWindowQueryResult(index, Ok(query_result)) => {
                 if let Some(window) = query_result.window {
                     let window_clone = window.clone();
                     let window_data = WindowData::from_rows(query_result.rows);
                     //replace the data on this window index
-                    let new_window = WindowView::new(
+                    let mut new_window = WindowView::new(
                         window_clone,
                         &window_data,
                         self.browser_width,
                         self.browser_height,
                     );
+                    new_window.set_is_editable(query_result.is_editable);
                     self.window_data[index] = window_data;
                     // set the previous sql query
                     // replace the previous window
@@ -316,8 +756,249 @@ impl Component<Msg> for App {
                 }
                 Cmd::none()
             }
-            Msg::ReceivedWindowQueryResult(_index, Err(_err)) => {
+            Msg::ReceivedWindowQueryResult(index, Err(err)) => {
                 trace!("Error retrieveing records from sql query");
+                let query_error = err
+                    .as_string()
+                    .unwrap_or_else(|| "Error executing the query".to_string());
+                self.window_views[index].set_query_error(Some(query_error));
+                Cmd::none()
+            }
+            Msg::ReceivedQueryPlan(index, Ok(plan)) => {
+                self.window_views[index].set_query_plan(Some(plan));
+                Cmd::none()
+            }
+            Msg::ReceivedQueryPlan(index, Err(err)) => {
+                trace!("Error explaining the query");
+                let query_error = err
+                    .as_string()
+                    .unwrap_or_else(|| "Error explaining the query".to_string());
+                self.window_views[index].set_query_plan(None);
+                self.window_views[index].set_query_error(Some(query_error));
+                Cmd::none()
+            }
+            Msg::ReceivedSavedRecords(index, Ok(records)) => {
+                self.window_views[index].refresh_rows(&records);
+                Cmd::none()
+            }
+            Msg::ReceivedSavedRecords(index, Err(err)) => {
+                let save_error = err
+                    .as_string()
+                    .unwrap_or_else(|| "Error saving the changes".to_string());
+                self.window_views[index].set_query_error(Some(save_error));
+                Cmd::none()
+            }
+            Msg::ReceivedImportSummary(index, summary) => {
+                let summary = summary.map_err(|err| {
+                    err.as_string()
+                        .unwrap_or_else(|| "Error importing the csv".to_string())
+                });
+                self.window_views[index].set_import_summary(summary);
+                Cmd::none()
+            }
+            Msg::ReceivedGroups(index, result) => {
+                let result = result.map_err(|err| {
+                    err.as_string()
+                        .unwrap_or_else(|| "Error grouping the rows".to_string())
+                });
+                self.window_views[index].set_group_result(result);
+                Cmd::none()
+            }
+            Msg::ReceivedTreeChildren(index, parent, children) => {
+                let children = children.map_err(|err| {
+                    err.as_string()
+                        .unwrap_or_else(|| "Error retrieving the records of the tree".to_string())
+                });
+                if let Some(tree_view) = self.window_views[index].tree_view_mut() {
+                    tree_view.set_children(parent, children);
+                }
+                Cmd::none()
+            }
+            Msg::ReceivedTreeAncestors(index, ancestors) => {
+                let ancestors = ancestors.map_err(|err| {
+                    err.as_string()
+                        .unwrap_or_else(|| "Error retrieving the ancestors".to_string())
+                });
+                match self.window_views[index].tree_view_mut() {
+                    Some(tree_view) => {
+                        tree_view.set_ancestors(ancestors);
+                        // the ancestors which are expanded to show the selected record
+                        let missing = tree_view.missing_children();
+                        Cmd::batch(
+                            missing
+                                .into_iter()
+                                .map(|parent| self.fetch_tree_children(index, Some(parent)))
+                                .collect(),
+                        )
+                    }
+                    None => Cmd::none(),
+                }
+            }
+            Msg::ReceivedAggregates(index, values) => {
+                let values = values.map_err(|err| {
+                    err.as_string()
+                        .unwrap_or_else(|| "Error computing the aggregates".to_string())
+                });
+                self.window_views[index]
+                    .main_tab
+                    .table_view
+                    .summary_view
+                    .set_values(values);
+                Cmd::none()
+            }
+            Msg::ReceivedSqlPlaceholders(index, sql, Ok(placeholders)) => {
+                self.window_views[index].set_placeholder_types(&sql, placeholders);
+                Cmd::none()
+            }
+            Msg::ReceivedSqlPlaceholders(_index, sql, Err(err)) => {
+                trace!("Error inferring the placeholders of {}: {:?}", sql, err);
+                Cmd::none()
+            }
+            Msg::ToggleQueryHistory => {
+                self.query_history_view.toggle();
+                if self.query_history_view.is_visible {
+                    let sql = self
+                        .window_views
+                        .get(self.active_window)
+                        .and_then(|window| window.sql_query());
+                    self.query_history_view.set_sql(&sql);
+                    self.refresh_query_history()
+                } else {
+                    Cmd::none()
+                }
+            }
+            Msg::QueryHistoryMsg(query_history_view::Msg::OpenHistory(index)) => {
+                match self.query_history_view.get_history(index) {
+                    Some(history) if ParameterizedSql::parse(&history.sql).has_placeholders() => {
+                        let sql = history.sql.to_string();
+                        self.edit_parameterized_query(sql)
+                    }
+                    Some(history) => {
+                        let sql = history.sql.to_string();
+                        rest_api::execute_sql_query(&sql, &[], move |query_result| {
+                            Msg::ReceivedOpenedQuery(sql.to_string(), query_result)
+                        })
+                    }
+                    None => Cmd::none(),
+                }
+            }
+            Msg::QueryHistoryMsg(query_history_view::Msg::OpenSavedQuery(index)) => {
+                match self.query_history_view.get_saved_query(index) {
+                    Some(SavedQuery { sql, .. })
+                        if ParameterizedSql::parse(sql).has_placeholders() =>
+                    {
+                        let sql = sql.to_string();
+                        self.edit_parameterized_query(sql)
+                    }
+                    Some(SavedQuery {
+                        id: Some(id), sql, ..
+                    }) => {
+                        let sql = sql.to_string();
+                        rest_api::open_saved_query(*id, move |query_result| {
+                            Msg::ReceivedOpenedQuery(sql.to_string(), query_result)
+                        })
+                    }
+                    _ => Cmd::none(),
+                }
+            }
+            Msg::QueryHistoryMsg(query_history_view::Msg::DeleteSavedQuery(index)) => {
+                match self.query_history_view.get_saved_query(index) {
+                    Some(SavedQuery { id: Some(id), .. }) => {
+                        rest_api::delete_saved_query(*id, Msg::ReceivedDeletedSavedQuery)
+                    }
+                    _ => Cmd::none(),
+                }
+            }
+            Msg::QueryHistoryMsg(query_history_view::Msg::SaveQuery) => {
+                let query_to_save = self.query_history_view.query_to_save();
+                match query_to_save {
+                    Some(query_to_save) => {
+                        self.query_history_view
+                            .update(query_history_view::Msg::SaveQuery);
+                        rest_api::save_query(&query_to_save, Msg::ReceivedSavedQuery)
+                    }
+                    None => {
+                        trace!("The query to be saved must have a name and sql");
+                        Cmd::none()
+                    }
+                }
+            }
+            Msg::QueryHistoryMsg(history_msg) => {
+                let refresh = match history_msg {
+                    query_history_view::Msg::ChangeSearch(_)
+                    | query_history_view::Msg::ShowHistory
+                    | query_history_view::Msg::ShowSavedQueries => true,
+                    _ => false,
+                };
+                self.query_history_view.update(history_msg);
+                if refresh {
+                    self.refresh_query_history()
+                } else {
+                    Cmd::none()
+                }
+            }
+            Msg::ReceivedQueryHistory(Ok(history)) => {
+                self.query_history_view
+                    .update(query_history_view::Msg::ReceivedHistory(history));
+                Cmd::none()
+            }
+            Msg::ReceivedSavedQueries(Ok(saved_queries)) => {
+                self.query_history_view
+                    .update(query_history_view::Msg::ReceivedSavedQueries(saved_queries));
+                Cmd::none()
+            }
+            Msg::ReceivedSavedQuery(Ok(saved_query)) => {
+                trace!("Saved query: {:?}", saved_query);
+                self.query_history_view
+                    .update(query_history_view::Msg::ShowSavedQueries);
+                self.refresh_query_history()
+            }
+            Msg::ReceivedDeletedSavedQuery(Ok(())) => self.refresh_query_history(),
+            Msg::ReceivedQueryHistory(Err(err))
+            | Msg::ReceivedSavedQueries(Err(err))
+            | Msg::ReceivedSavedQuery(Err(err))
+            | Msg::ReceivedDeletedSavedQuery(Err(err)) => {
+                trace!("Error in query history: {:?}", err);
+                Cmd::none()
+            }
+            Msg::ReceivedOpenedQuery(sql, Ok(query_result)) => {
+                self.add_window_from_query_result(query_result, sql);
+                Cmd::none()
+            }
+            Msg::ReceivedOpenedQuery(sql, Err(err)) => {
+                trace!("Error opening the query {}: {:?}", sql, err);
+                Cmd::none()
+            }
+            Msg::ToggleErd => {
+                self.erd_view.toggle();
+                if self.erd_view.is_visible {
+                    if let Some(window) = self.window_views.get(self.active_window) {
+                        self.erd_view
+                            .set_default_center(&window.main_tab.table_name);
+                    }
+                    self.fetch_erd()
+                } else {
+                    Cmd::none()
+                }
+            }
+            Msg::ErdMsg(erd_view::Msg::OpenWindow(table_name)) => self.update(Msg::WindowListMsg(
+                window_list_view::Msg::ClickedWindow(table_name),
+            )),
+            Msg::ErdMsg(erd_view::Msg::Close) => {
+                self.erd_view.update(erd_view::Msg::Close);
+                Cmd::none()
+            }
+            // the diagram is fetched again with the changed schema, center or depth
+            Msg::ErdMsg(erd_msg) => {
+                self.erd_view.update(erd_msg);
+                self.fetch_erd()
+            }
+            Msg::ReceivedErd(Ok(erd)) => {
+                self.erd_view.update(erd_view::Msg::ReceivedErd(erd));
+                Cmd::none()
+            }
+            Msg::ReceivedErd(Err(err)) => {
+                trace!("Error fetching the relationships: {:?}", err);
                 Cmd::none()
             }
             Msg::ReceivedWindowMainTabDetail(
@@ -328,6 +1009,8 @@ impl Component<Msg> for App {
             ) => {
                 trace!("Got window main tab detail: {:#?}", record_detail);
                 let detail_window = record_detail.window.clone();
+                let table_view = &self.window_views[window_index].main_tab.table_view;
+                let (filter, sort) = (table_view.filter(), table_view.sort());
                 let window_data = &mut self.window_data[window_index];
                 window_data.set_record_detail(record_detail);
                 let mut new_window = WindowView::new(
@@ -337,6 +1020,10 @@ impl Component<Msg> for App {
                     self.browser_height,
                 );
                 trace!("Window data: {:#?}", window_data);
+                new_window
+                    .main_tab
+                    .table_view
+                    .set_filter_and_sort(&filter, &sort);
                 new_window.show_main_tab_detail_view(page_index, row_index);
                 new_window.update_size_allocation();
                 self.window_views[window_index] = new_window;
@@ -375,6 +1062,14 @@ impl Component<Msg> for App {
                                 nav(
                                     vec![class("logout")],
                                     vec![
+                                        button(
+                                            vec![onclick(|_| Msg::ToggleQueryHistory)],
+                                            vec![text("Query history")],
+                                        ),
+                                        button(
+                                            vec![onclick(|_| Msg::ToggleErd)],
+                                            vec![text("Relationships")],
+                                        ),
                                         button(vec![], vec![text("logout")]),
                                         button(vec![], vec![text("Connect to database..")]),
                                     ],
@@ -426,6 +1121,8 @@ impl Component<Msg> for App {
                         ),
                     ],
                 ),
+                self.query_history_view.view().map_msg(Msg::QueryHistoryMsg),
+                self.erd_view.view().map_msg(Msg::ErdMsg),
             ],
         )
     }
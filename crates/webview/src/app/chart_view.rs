@@ -0,0 +1,273 @@
+use crate::chart::{self, ChartKind, ChartSpec};
+use data_table::DataTable;
+use diwata_intel::SqlType;
+use sauron::{
+    html::{attributes::*, events::*, *},
+    Cmd, Component, Node,
+};
+
+const CHART_WIDTH: i32 = 800;
+const CHART_HEIGHT: i32 = 240;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Msg {
+    Close,
+    KindChanged(String),
+    XChanged(String),
+    YChanged(String),
+    SeriesChanged(String),
+    ToggleUseGroups,
+}
+
+/// Chart the loaded rows of the window or its groups,
+/// the columns are picked by name so they are kept when the data is reloaded
+pub struct ChartView {
+    kind: ChartKind,
+    x: Option<String>,
+    y: Option<String>,
+    series: Option<String>,
+    rows: Option<DataTable>,
+    groups: Option<DataTable>,
+    /// chart the groups of the group view instead of the rows
+    use_groups: bool,
+    is_visible: bool,
+}
+
+impl ChartView {
+    pub fn new() -> Self {
+        ChartView {
+            kind: ChartKind::Bar,
+            x: None,
+            y: None,
+            series: None,
+            rows: None,
+            groups: None,
+            use_groups: false,
+            is_visible: false,
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.is_visible = !self.is_visible;
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.is_visible
+    }
+
+    pub fn set_rows(&mut self, rows: DataTable) {
+        self.rows = Some(rows);
+    }
+
+    /// the groups are charted as soon as they are available
+    pub fn set_groups(&mut self, groups: Option<DataTable>) {
+        self.use_groups = groups.is_some();
+        self.groups = groups;
+    }
+
+    fn data(&self) -> Option<&DataTable> {
+        if self.use_groups {
+            self.groups.as_ref()
+        } else {
+            self.rows.as_ref()
+        }
+    }
+
+    /// the picked column or the first column the chart accepts
+    fn column_index<F>(
+        &self,
+        table: &DataTable,
+        picked: &Option<String>,
+        accepts: F,
+    ) -> Option<usize>
+    where
+        F: Fn(&SqlType) -> bool,
+    {
+        let accepted = |index: &usize| accepts(&table.columns[*index].data_type);
+        picked
+            .as_ref()
+            .and_then(|name| table.columns.iter().position(|column| column.name == *name))
+            .filter(accepted)
+            .or_else(|| (0..table.columns.len()).find(accepted))
+    }
+
+    fn spec(&self, table: &DataTable) -> Option<ChartSpec> {
+        let kind = self.kind;
+        let x = self.column_index(table, &self.x, |sql_type| kind.accepts_x(sql_type))?;
+        let y = self.column_index(table, &self.y, |sql_type| kind.accepts_y(sql_type))?;
+        let series = self
+            .series
+            .as_ref()
+            .and_then(|name| table.columns.iter().position(|column| column.name == *name));
+        Some(ChartSpec { kind, x, y, series })
+    }
+
+    fn view_column_select<F>(
+        &self,
+        table: &DataTable,
+        selected: Option<usize>,
+        placeholder: Option<&str>,
+        accepts: F,
+        on_change: fn(String) -> Msg,
+    ) -> Node<Msg>
+    where
+        F: Fn(&SqlType) -> bool,
+    {
+        let options = table
+            .columns
+            .iter()
+            .enumerate()
+            .filter(|(_index, column)| accepts(&column.data_type))
+            .map(|(index, column)| {
+                option(vec![value(&column.name)], vec![text(&column.name)]).add_attributes(
+                    attrs_flag(vec![("selected", "selected", selected == Some(index))]),
+                )
+            });
+        select(
+            vec![onchange(move |input| on_change(input.value))],
+            placeholder
+                .map(|placeholder| option(vec![value("")], vec![text(placeholder)]))
+                .into_iter()
+                .chain(options)
+                .collect::<Vec<Node<Msg>>>(),
+        )
+    }
+
+    fn view_controls(&self, table: &DataTable, spec: &Option<ChartSpec>) -> Node<Msg> {
+        let kind = self.kind;
+        let x = spec.as_ref().map(|spec| spec.x);
+        let y = spec.as_ref().map(|spec| spec.y);
+        let series = spec.as_ref().and_then(|spec| spec.series);
+        div(
+            vec![class("chart_controls")],
+            vec![
+                select(
+                    vec![onchange(|input| Msg::KindChanged(input.value))],
+                    ChartKind::all()
+                        .iter()
+                        .map(|chart_kind| {
+                            option(
+                                vec![value(chart_kind.name())],
+                                vec![text(chart_kind.name())],
+                            )
+                            .add_attributes(attrs_flag(vec![(
+                                "selected",
+                                "selected",
+                                kind == *chart_kind,
+                            )]))
+                        })
+                        .collect::<Vec<Node<Msg>>>(),
+                ),
+                text("x"),
+                self.view_column_select(
+                    table,
+                    x,
+                    None,
+                    |sql_type| kind.accepts_x(sql_type),
+                    Msg::XChanged,
+                ),
+                text("y"),
+                self.view_column_select(
+                    table,
+                    y,
+                    None,
+                    |sql_type| kind.accepts_y(sql_type),
+                    Msg::YChanged,
+                ),
+                text("series"),
+                self.view_column_select(
+                    table,
+                    series,
+                    Some("(none)"),
+                    |_| true,
+                    Msg::SeriesChanged,
+                )
+                .add_attributes(attrs_flag(vec![(
+                    "disabled",
+                    "disabled",
+                    !kind.has_series(),
+                )])),
+                label(
+                    vec![],
+                    vec![
+                        input(
+                            vec![r#type("checkbox"), onclick(|_| Msg::ToggleUseGroups)],
+                            vec![],
+                        )
+                        .add_attributes(attrs_flag(vec![
+                            ("checked", "checked", self.use_groups),
+                            ("disabled", "disabled", self.groups.is_none()),
+                        ])),
+                        text("Chart the groups"),
+                    ],
+                ),
+                match spec {
+                    Some(spec) => a(
+                        vec![
+                            href(format!(
+                                "data:image/svg+xml;charset=utf-8,{}",
+                                js_sys::encode_uri_component(&chart::to_svg_file(
+                                    table,
+                                    spec,
+                                    CHART_WIDTH,
+                                    CHART_HEIGHT
+                                ))
+                            )),
+                            attr("download", format!("chart_{}.svg", kind.name())),
+                        ],
+                        vec![text("Export svg")],
+                    ),
+                    None => span(vec![], vec![]),
+                },
+                button(vec![onclick(|_| Msg::Close)], vec![text("Close")]),
+            ],
+        )
+    }
+}
+
+impl Component<Msg> for ChartView {
+    fn update(&mut self, msg: Msg) -> Cmd<Self, Msg> {
+        let picked = |name: String| if name.is_empty() { None } else { Some(name) };
+        match msg {
+            Msg::Close => self.is_visible = false,
+            Msg::KindChanged(name) => {
+                if let Some(kind) = ChartKind::from_name(&name) {
+                    self.kind = kind;
+                }
+            }
+            Msg::XChanged(name) => self.x = picked(name),
+            Msg::YChanged(name) => self.y = picked(name),
+            Msg::SeriesChanged(name) => self.series = picked(name),
+            Msg::ToggleUseGroups => {
+                self.use_groups = !self.use_groups && self.groups.is_some();
+            }
+        }
+        Cmd::none()
+    }
+
+    fn view(&self) -> Node<Msg> {
+        let content = match self.data() {
+            Some(table) => {
+                let spec = self.spec(table);
+                vec![
+                    self.view_controls(table, &spec),
+                    match spec {
+                        Some(ref spec) => chart::chart(table, spec, CHART_WIDTH, CHART_HEIGHT),
+                        None => div(
+                            vec![class("chart_message")],
+                            vec![text("There are no numeric columns to chart")],
+                        ),
+                    },
+                ]
+            }
+            None => vec![],
+        };
+        section(
+            vec![
+                class("chart_view"),
+                styles_flag(vec![("display", "none", !self.is_visible)]),
+            ],
+            content,
+        )
+    }
+}
@@ -1,5 +1,9 @@
 use crate::{assets, widgets};
 use data_table::DataColumn;
+use diwata_intel::{
+    data_container::{Condition, Direction, Operator},
+    ColumnName, Value,
+};
 use sauron::{
     html::{attributes::*, events::*, units::*, *},
     Cmd, Component, Node,
@@ -8,10 +12,15 @@ use sauron::{
 #[derive(Debug, PartialEq, Clone)]
 pub enum Msg {
     ChangeSearch(String),
+    /// sort ascending, then descending, then unsorted
+    ToggleSort,
 }
 
 pub struct ColumnView {
     pub column: DataColumn,
+    /// the rows are filtered to those which contains the search
+    pub search: String,
+    pub direction: Option<Direction>,
     width: i32,
     height: i32,
 }
@@ -20,10 +29,61 @@ impl ColumnView {
     pub fn new(column: DataColumn) -> Self {
         ColumnView {
             column,
+            search: String::new(),
+            direction: None,
             width: 210,
             height: 20,
         }
     }
+
+    pub fn column_name(&self) -> ColumnName {
+        ColumnName {
+            name: self.column.name.to_string(),
+            table: None,
+            alias: None,
+        }
+    }
+
+    /// the condition of the search, matching the column containing the search text
+    pub fn condition(&self) -> Option<Condition> {
+        let search = self.search.trim();
+        if search.is_empty() {
+            return None;
+        }
+        Some(Condition {
+            column_name: self.column_name(),
+            operator: Operator::ILike,
+            value: Value::Text(format!("%{}%", search)),
+        })
+    }
+
+    /// set the search from the condition of the filter
+    pub fn set_condition(&mut self, condition: Option<&Condition>) {
+        self.search = match condition {
+            Some(Condition {
+                value: Value::Text(text),
+                ..
+            }) => text.trim_matches('%').to_string(),
+            _ => String::new(),
+        };
+    }
+
+    fn view_sort_button(&self) -> Node<Msg> {
+        let classes = classes_flag(vec![
+            ("sort_btn", true),
+            ("sort_asc", self.direction == Some(Direction::Asc)),
+            ("sort_desc", self.direction == Some(Direction::Desc)),
+        ]);
+        let color = if self.direction.is_some() {
+            "#333"
+        } else {
+            "#888"
+        };
+        div(
+            vec![classes, onclick(|_| Msg::ToggleSort)],
+            vec![assets::sort_btn_asc(18, 18, color)],
+        )
+    }
 }
 
 impl Component<Msg> for ColumnView {
@@ -31,6 +91,15 @@ impl Component<Msg> for ColumnView {
         match msg {
             Msg::ChangeSearch(search) => {
                 trace!("Search term change: {}", search);
+                self.search = search;
+                Cmd::none()
+            }
+            Msg::ToggleSort => {
+                self.direction = match self.direction {
+                    None => Some(Direction::Asc),
+                    Some(Direction::Asc) => Some(Direction::Desc),
+                    Some(Direction::Desc) => None,
+                };
                 Cmd::none()
             }
         }
@@ -47,14 +116,15 @@ impl Component<Msg> for ColumnView {
                     ],
                     vec![
                         div(vec![class("column_name")], vec![text(&self.column.name)]),
-                        div(vec![], vec![assets::sort_btn_asc(18, 18, "#888")]),
+                        self.view_sort_button(),
                     ],
                 ),
                 div(
                     vec![class("column_name_search_widget_container")],
-                    vec![widgets::search_widget(oninput(|input| {
-                        Msg::ChangeSearch(input.value)
-                    }))],
+                    vec![widgets::search_widget_with_value(
+                        &self.search,
+                        onchange(|input| Msg::ChangeSearch(input.value)),
+                    )],
                 ),
             ],
         )
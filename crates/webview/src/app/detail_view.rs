@@ -1,6 +1,7 @@
 use crate::app::field_view::{self, FieldView};
+use diwata_intel::{Dao, SqlType, TableName};
 use sauron::{
-    html::{attributes::*, *},
+    html::{attributes::*, events::*, *},
     Cmd, Component, Node,
 };
 use std::{cell::RefCell, rc::Rc};
@@ -8,6 +9,8 @@ use std::{cell::RefCell, rc::Rc};
 #[derive(Debug, PartialEq, Clone)]
 pub enum Msg {
     FieldMsg(usize, field_view::Msg),
+    /// upload the selected file into the binary field
+    Upload(usize),
 }
 
 /// When a record from the main tab is clicked, it will show the detailed view of that
@@ -15,6 +18,9 @@ pub enum Msg {
 /// such as one_one tab, has_many and indirect tab
 pub struct DetailView {
     fields: Vec<Rc<RefCell<FieldView>>>,
+    /// the table and the primary columns of the record,
+    /// used in downloading and uploading the binary fields
+    record: Option<(TableName, Dao)>,
     pub is_visible: bool,
 }
 
@@ -22,6 +28,7 @@ impl DetailView {
     pub fn new() -> Self {
         DetailView {
             fields: vec![],
+            record: None,
             is_visible: false,
         }
     }
@@ -37,6 +44,71 @@ impl DetailView {
     pub fn set_fields(&mut self, fields: &[Rc<RefCell<FieldView>>]) {
         self.fields = fields.to_vec();
     }
+
+    pub fn set_record(&mut self, table_name: &TableName, primary_dao: Dao) {
+        self.record = Some((table_name.clone(), primary_dao));
+    }
+
+    /// the primary columns of the record shown in the detail view
+    pub fn primary_dao(&self) -> Option<&Dao> {
+        self.record
+            .as_ref()
+            .map(|(_table_name, primary_dao)| primary_dao)
+    }
+
+    /// the url where the content of the binary field is downloaded from and uploaded to
+    fn binary_url(&self, column_name: &str) -> Option<String> {
+        let (table_name, primary_dao) = self.record.as_ref()?;
+        let dao = ron::ser::to_string(primary_dao).expect("Unable to serialize dao");
+        Some(format!(
+            "/binary/{}/{}/?dao={}",
+            js_sys::encode_uri_component(&table_name.complete_name()),
+            js_sys::encode_uri_component(column_name),
+            js_sys::encode_uri_component(&dao)
+        ))
+    }
+
+    fn file_input_id(&self, column_name: &str) -> String {
+        match self.record {
+            Some((ref table_name, _)) => {
+                format!("upload_{}_{}", table_name.complete_name(), column_name)
+            }
+            None => format!("upload_{}", column_name),
+        }
+    }
+
+    /// the file input and the url of the binary field to be uploaded
+    pub fn upload_target(&self, index: usize) -> Option<(String, String)> {
+        let field = self.fields.get(index)?.borrow();
+        let column_name = &field.column.name;
+        Some((
+            self.file_input_id(column_name),
+            self.binary_url(column_name)?,
+        ))
+    }
+
+    fn view_binary_controls(&self, index: usize, column_name: &str) -> Node<Msg> {
+        div(
+            vec![class("binary_controls")],
+            match self.binary_url(column_name) {
+                Some(url) => vec![
+                    a(
+                        vec![href(url), attr("download", "")],
+                        vec![text("Download")],
+                    ),
+                    input(
+                        vec![r#type("file"), id(self.file_input_id(column_name))],
+                        vec![],
+                    ),
+                    button(
+                        vec![onclick(move |_| Msg::Upload(index))],
+                        vec![text("Upload")],
+                    ),
+                ],
+                None => vec![],
+            },
+        )
+    }
 }
 
 impl Component<Msg> for DetailView {
@@ -46,6 +118,8 @@ impl Component<Msg> for DetailView {
                 self.fields[index].borrow_mut().update(field_msg);
                 Cmd::none()
             }
+            // the upload is done in the app
+            Msg::Upload(_index) => Cmd::none(),
         }
     }
 
@@ -61,10 +135,21 @@ impl Component<Msg> for DetailView {
                     .iter()
                     .enumerate()
                     .map(|(index, field)| {
-                        field
-                            .borrow()
+                        let field = field.borrow();
+                        let field_view = field
                             .view_in_detail()
-                            .map_msg(move |field_msg| Msg::FieldMsg(index, field_msg))
+                            .map_msg(move |field_msg| Msg::FieldMsg(index, field_msg));
+                        if field.column.data_type == SqlType::Blob {
+                            div(
+                                vec![class("binary_field")],
+                                vec![
+                                    field_view,
+                                    self.view_binary_controls(index, &field.column.name),
+                                ],
+                            )
+                        } else {
+                            field_view
+                        }
                     })
                     .collect::<Vec<Node<Msg>>>(),
             )],
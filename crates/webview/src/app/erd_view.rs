@@ -0,0 +1,445 @@
+use crate::rest_api;
+use diwata_intel::{
+    erd::{Erd, ErdEdge, ErdNode, RelationKind, DEFAULT_DEPTH},
+    TableName,
+};
+use sauron::{
+    html::{attributes::*, events::*, *},
+    svg::{
+        self,
+        tags::{line, text as svg_text},
+    },
+    Cmd, Component, Node,
+};
+use std::collections::{HashMap, VecDeque};
+
+/// the width of the boxes of the tables
+const NODE_WIDTH: f64 = 180.0;
+const HEADER_HEIGHT: f64 = 20.0;
+const COLUMN_HEIGHT: f64 = 14.0;
+/// the columns listed in the box, the rest are summarized
+const MAX_COLUMNS: usize = 8;
+const HORIZONTAL_GAP: f64 = 90.0;
+const VERTICAL_GAP: f64 = 30.0;
+const PADDING: f64 = 20.0;
+/// the depths that can be picked when the diagram is centered on a table
+const DEPTHS: [usize; 3] = [1, 2, 3];
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Msg {
+    ChangeSchema(String),
+    ChangeCenter(String),
+    ChangeDepth(String),
+    /// center the diagram on the clicked table
+    CenterOn(TableName),
+    /// open the window of the double clicked table
+    OpenWindow(TableName),
+    ReceivedErd(Erd),
+    Close,
+}
+
+/// A panel drawing the tables and their relations,
+/// to get to know the database at a glance
+pub struct ErdView {
+    pub is_visible: bool,
+    pub schema: String,
+    /// the table in the center of the diagram
+    pub center: String,
+    pub depth: usize,
+    erd: Erd,
+}
+
+impl ErdView {
+    pub fn new() -> Self {
+        ErdView {
+            is_visible: false,
+            schema: String::new(),
+            center: String::new(),
+            depth: DEFAULT_DEPTH,
+            erd: Erd::default(),
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.is_visible = !self.is_visible;
+    }
+
+    /// center the diagram on the table, such as the table of the active window,
+    /// unless a table is already picked
+    pub fn set_default_center(&mut self, table_name: &TableName) {
+        if self.center.is_empty() {
+            self.center = table_name.complete_name();
+        }
+    }
+
+    /// the node of the center table
+    fn center_node(&self) -> Option<&ErdNode> {
+        let center = TableName::from(&self.center);
+        self.erd
+            .nodes
+            .iter()
+            .find(|node| node.table_name == center)
+            .or_else(|| {
+                self.erd
+                    .nodes
+                    .iter()
+                    .find(|node| node.table_name.name == center.name)
+            })
+    }
+
+    /// the tables are arranged in columns by the number of relations away from
+    /// the center table, when there is no center the tables are in a grid
+    fn layout(&self) -> HashMap<TableName, (f64, f64)> {
+        let layers: Vec<usize> = match self.center_node() {
+            Some(center) => {
+                let distances = distances(&self.erd, &center.table_name);
+                self.erd
+                    .nodes
+                    .iter()
+                    .map(|node| distances.get(&node.table_name).cloned().unwrap_or(0))
+                    .collect()
+            }
+            None => {
+                let columns = (self.erd.nodes.len() as f64).sqrt().ceil().max(1.0) as usize;
+                (0..self.erd.nodes.len())
+                    .map(|index| index % columns)
+                    .collect()
+            }
+        };
+        let mut tops: HashMap<usize, f64> = HashMap::new();
+        let mut positions = HashMap::new();
+        for (node, layer) in self.erd.nodes.iter().zip(layers) {
+            let top = tops.entry(layer).or_insert(PADDING);
+            let x = PADDING + layer as f64 * (NODE_WIDTH + HORIZONTAL_GAP);
+            positions.insert(node.table_name.clone(), (x, *top));
+            *top += node_height(node) + VERTICAL_GAP;
+        }
+        positions
+    }
+
+    fn view_controls(&self) -> Node<Msg> {
+        let depth = self.depth;
+        div(
+            vec![class("erd_controls")],
+            vec![
+                input(
+                    vec![
+                        r#type("text"),
+                        attr("placeholder", "schema"),
+                        value(&self.schema),
+                        onchange(|input| Msg::ChangeSchema(input.value)),
+                    ],
+                    vec![],
+                ),
+                input(
+                    vec![
+                        r#type("text"),
+                        attr("placeholder", "center table"),
+                        value(&self.center),
+                        onchange(|input| Msg::ChangeCenter(input.value)),
+                    ],
+                    vec![],
+                ),
+                select(
+                    vec![onchange(|input| Msg::ChangeDepth(input.value))],
+                    DEPTHS
+                        .iter()
+                        .map(|d| {
+                            option(vec![value(d.to_string())], vec![text(d)]).add_attributes(
+                                attrs_flag(vec![("selected", "selected", depth == *d)]),
+                            )
+                        })
+                        .collect::<Vec<Node<Msg>>>(),
+                ),
+                a(
+                    vec![
+                        href(rest_api::erd_url(&self.schema, &self.center, depth, "dot")),
+                        attr("download", "erd.dot"),
+                    ],
+                    vec![text("dot")],
+                ),
+                a(
+                    vec![
+                        href(rest_api::erd_url(&self.schema, &self.center, depth, "json")),
+                        attr("download", "erd.json"),
+                    ],
+                    vec![text("json")],
+                ),
+            ],
+        )
+    }
+
+    fn view_diagram(&self) -> Node<Msg> {
+        let positions = self.layout();
+        let width = positions
+            .values()
+            .map(|(x, _)| x + NODE_WIDTH + PADDING)
+            .fold(PADDING, f64::max);
+        let height = self
+            .erd
+            .nodes
+            .iter()
+            .filter_map(|node| {
+                positions
+                    .get(&node.table_name)
+                    .map(|(_, y)| y + node_height(node) + PADDING)
+            })
+            .fold(PADDING, f64::max);
+        let center = self.center_node().map(|node| node.table_name.clone());
+        let mut nodes: Vec<Node<Msg>> = self
+            .erd
+            .edges
+            .iter()
+            .filter_map(|edge| view_edge(edge, &positions))
+            .collect();
+        nodes.extend(self.erd.nodes.iter().filter_map(|node| {
+            positions.get(&node.table_name).map(|position| {
+                view_node(node, *position, Some(&node.table_name) == center.as_ref())
+            })
+        }));
+        svg::svg(
+            vec![
+                attr("xmlns", "http://www.w3.org/2000/svg"),
+                attr("width", width),
+                attr("height", height),
+                attr("viewBox", format!("0 0 {} {}", width, height)),
+                attr("font-family", "sans-serif"),
+            ],
+            vec![svg::g(vec![attr("class", "erd")], nodes)],
+        )
+    }
+}
+
+/// the number of relations of the tables away from the center table
+fn distances(erd: &Erd, center: &TableName) -> HashMap<TableName, usize> {
+    let mut distances = HashMap::new();
+    distances.insert(center.clone(), 0);
+    let mut queue = VecDeque::new();
+    queue.push_back(center.clone());
+    while let Some(table_name) = queue.pop_front() {
+        let distance = distances[&table_name];
+        for edge in &erd.edges {
+            let related = if edge.from == table_name {
+                &edge.to
+            } else if edge.to == table_name {
+                &edge.from
+            } else {
+                continue;
+            };
+            if !distances.contains_key(related) {
+                distances.insert(related.clone(), distance + 1);
+                queue.push_back(related.clone());
+            }
+        }
+    }
+    distances
+}
+
+fn listed_columns(node: &ErdNode) -> usize {
+    if node.columns.len() > MAX_COLUMNS {
+        MAX_COLUMNS + 1
+    } else {
+        node.columns.len()
+    }
+}
+
+fn node_height(node: &ErdNode) -> f64 {
+    HEADER_HEIGHT + listed_columns(node) as f64 * COLUMN_HEIGHT + 6.0
+}
+
+fn edge_color(kind: RelationKind) -> &'static str {
+    match kind {
+        RelationKind::Owned => "#9467bd",
+        RelationKind::Linker => "#7f7f7f",
+        RelationKind::HasOne => "#2ca02c",
+        RelationKind::HasMany => "#1f77b4",
+        RelationKind::Indirect => "#ff7f0e",
+    }
+}
+
+/// the edge is drawn between the centers of the boxes, under the boxes
+fn view_edge(edge: &ErdEdge, positions: &HashMap<TableName, (f64, f64)>) -> Option<Node<Msg>> {
+    let (x1, y1) = positions.get(&edge.from)?;
+    let (x2, y2) = positions.get(&edge.to)?;
+    let (x1, y1) = (x1 + NODE_WIDTH / 2.0, y1 + HEADER_HEIGHT / 2.0);
+    let (x2, y2) = (x2 + NODE_WIDTH / 2.0, y2 + HEADER_HEIGHT / 2.0);
+    let color = edge_color(edge.kind);
+    let mut line_attributes = vec![
+        attr("x1", x1),
+        attr("y1", y1),
+        attr("x2", x2),
+        attr("y2", y2),
+        attr("stroke", color),
+    ];
+    if edge.kind == RelationKind::Indirect {
+        line_attributes.push(attr("stroke-dasharray", "4 3"));
+    }
+    let label = match edge.via {
+        Some(ref via) => format!("{} via {}", edge.kind.name(), via.name),
+        None => edge.kind.name().to_string(),
+    };
+    Some(svg::g(
+        vec![attr("class", "erd_edge")],
+        vec![
+            line(line_attributes, vec![]),
+            svg_text(
+                vec![
+                    attr("x", (x1 + x2) / 2.0),
+                    attr("y", (y1 + y2) / 2.0 - 3.0),
+                    attr("text-anchor", "middle"),
+                    attr("font-size", 9),
+                    attr("fill", color),
+                ],
+                vec![text(label)],
+            ),
+        ],
+    ))
+}
+
+fn view_node(node: &ErdNode, (x, y): (f64, f64), is_center: bool) -> Node<Msg> {
+    let table_name = node.table_name.clone();
+    let open_table_name = node.table_name.clone();
+    let mut nodes = vec![
+        svg::rect(
+            vec![
+                attr("x", x),
+                attr("y", y),
+                attr("width", NODE_WIDTH),
+                attr("height", node_height(node)),
+                attr("fill", "#fff"),
+                attr("stroke", if is_center { "#d62728" } else { "#888" }),
+                attr(
+                    "stroke-dasharray",
+                    if node.is_linker { "4 3" } else { "none" },
+                ),
+            ],
+            vec![],
+        ),
+        svg::rect(
+            vec![
+                attr("x", x),
+                attr("y", y),
+                attr("width", NODE_WIDTH),
+                attr("height", HEADER_HEIGHT),
+                attr("fill", if node.is_owned { "#e8e0f0" } else { "#e8eef5" }),
+                attr("stroke", "#888"),
+            ],
+            vec![],
+        ),
+        svg_text(
+            vec![
+                attr("x", x + 6.0),
+                attr("y", y + 14.0),
+                attr("font-size", 11),
+                attr("font-weight", "bold"),
+            ],
+            vec![text(node.table_name.complete_name())],
+        ),
+    ];
+    for (index, column) in node.columns.iter().take(MAX_COLUMNS).enumerate() {
+        let mut label = column.name.to_string();
+        if column.is_primary {
+            label += " (pk)";
+        }
+        if column.is_foreign {
+            label += " (fk)";
+        }
+        nodes.push(svg_text(
+            vec![
+                attr("x", x + 6.0),
+                attr(
+                    "y",
+                    y + HEADER_HEIGHT + COLUMN_HEIGHT * (index as f64 + 1.0),
+                ),
+                attr("font-size", 10),
+                attr("fill", "#333"),
+            ],
+            vec![text(label)],
+        ));
+    }
+    if node.columns.len() > MAX_COLUMNS {
+        nodes.push(svg_text(
+            vec![
+                attr("x", x + 6.0),
+                attr(
+                    "y",
+                    y + HEADER_HEIGHT + COLUMN_HEIGHT * (MAX_COLUMNS as f64 + 1.0),
+                ),
+                attr("font-size", 10),
+                attr("fill", "#888"),
+            ],
+            vec![text(format!(
+                "{} more columns..",
+                node.columns.len() - MAX_COLUMNS
+            ))],
+        ));
+    }
+    svg::g(
+        vec![
+            attr("class", "erd_node"),
+            onclick(move |_| Msg::CenterOn(table_name.clone())),
+            ondblclick(move |_| Msg::OpenWindow(open_table_name.clone())),
+        ],
+        nodes,
+    )
+}
+
+impl Component<Msg> for ErdView {
+    fn update(&mut self, msg: Msg) -> Cmd<Self, Msg> {
+        match msg {
+            Msg::ChangeSchema(schema) => self.schema = schema.trim().to_string(),
+            Msg::ChangeCenter(center) => self.center = center.trim().to_string(),
+            Msg::ChangeDepth(depth) => self.depth = depth.parse().unwrap_or(DEFAULT_DEPTH),
+            Msg::CenterOn(table_name) => self.center = table_name.complete_name(),
+            Msg::OpenWindow(_) => trace!("handled in app"),
+            Msg::ReceivedErd(erd) => self.erd = erd,
+            Msg::Close => self.is_visible = false,
+        }
+        Cmd::none()
+    }
+
+    fn view(&self) -> Node<Msg> {
+        aside(
+            vec![
+                class("erd_view"),
+                styles_flag(vec![("display", "none", !self.is_visible)]),
+            ],
+            vec![
+                header(
+                    vec![class("erd_header")],
+                    vec![
+                        text("Relationships"),
+                        button(vec![onclick(|_| Msg::Close)], vec![text("x")]),
+                    ],
+                ),
+                self.view_controls(),
+                if self.erd.nodes.is_empty() {
+                    div(
+                        vec![class("erd_message")],
+                        vec![text("There are no tables to show")],
+                    )
+                } else {
+                    self.view_diagram()
+                },
+                div(
+                    vec![class("erd_legend")],
+                    [
+                        RelationKind::Owned,
+                        RelationKind::Linker,
+                        RelationKind::HasOne,
+                        RelationKind::HasMany,
+                        RelationKind::Indirect,
+                    ]
+                    .iter()
+                    .map(|kind| {
+                        span(
+                            vec![styles(vec![("color", edge_color(*kind))])],
+                            vec![text(kind.name())],
+                        )
+                    })
+                    .collect::<Vec<Node<Msg>>>(),
+                ),
+            ],
+        )
+    }
+}
@@ -1,5 +1,5 @@
 use data_table::{DataColumn, Type};
-use diwata_intel::{Array, Value};
+use diwata_intel::{data_export, data_image, parse_value, Array, Value};
 use sauron::{
     html::{attributes::*, events::*, *},
     Cmd, Component, Node,
@@ -22,6 +22,8 @@ pub struct FieldView {
     /// is part of a frozen column, serves no
     /// other puposed other than coloring in css style
     pub is_frozen_column: bool,
+    /// the url of the record, linked from the primary fields
+    pub record_url: Option<String>,
 }
 
 impl FieldView {
@@ -32,6 +34,7 @@ impl FieldView {
             column: column.clone(),
             is_frozen_row: false,
             is_frozen_column: false,
+            record_url: None,
         }
     }
 
@@ -47,6 +50,17 @@ impl FieldView {
         self.value != self.new_value
     }
 
+    /// replace the value with the refreshed value from the database
+    pub fn set_value(&mut self, value: &Value) {
+        self.value = value.clone();
+        self.new_value = value.clone();
+    }
+
+    /// discard the modified value
+    pub fn cancel_change(&mut self) {
+        self.new_value = self.value.clone();
+    }
+
     pub fn set_is_frozen_row(&mut self, frozen: bool) {
         self.is_frozen_row = frozen;
     }
@@ -61,20 +75,11 @@ impl FieldView {
             ("frozen_row", self.is_frozen_row),
             ("frozen_column", self.is_frozen_column),
         ]);
-        match &self.value {
-            Value::Int(v) => a(
-                vec![
-                    classes,
-                    onclick(|_| Msg::PrimaryClicked),
-                    href(format!("#{}", v)),
-                ],
-                vec![text(v.to_string())],
-            ),
-            _ => {
-                trace!("todo primary: {:?}", self.value);
-                text("unknown")
-            }
-        }
+        let record_url = self.record_url.as_ref().map(String::as_str).unwrap_or("#");
+        a(
+            vec![classes, onclick(|_| Msg::PrimaryClicked), href(record_url)],
+            vec![text(data_export::value_to_text(&self.value))],
+        )
     }
 
     fn view_value(&self) -> Node<Msg> {
@@ -189,7 +194,7 @@ impl FieldView {
                 ],
                 vec![],
             ),
-            Value::ImageUri(v) => img(vec![src(v), classes], vec![]),
+            Value::ImageUri(v) => img(vec![src(thumbnail_uri(v)), classes], vec![]),
             Value::Array(Array::Text(v)) => input(
                 vec![
                     r#type("text"),
@@ -261,22 +266,38 @@ impl FieldView {
                     vec![class("in_detail_column")],
                     vec![text(&self.column.name)],
                 ),
-                if self.column.is_primary {
-                    self.view_value_as_primary()
-                } else {
-                    self.view_value()
+                match &self.value {
+                    _ if self.column.is_primary => self.view_value_as_primary(),
+                    Value::ImageUri(v) => a(
+                        vec![href(v), attr("target", "_blank")],
+                        vec![img(vec![src(v), class("value detail_image")], vec![])],
+                    ),
+                    _ => self.view_value(),
                 },
             ],
         )
     }
 }
 
+/// the images served by the server are scaled down to a thumbnail,
+/// the other image uris are scaled by the browser
+fn thumbnail_uri(uri: &str) -> String {
+    if uri.starts_with(data_image::IMAGE_PATH) {
+        format!("{}?thumbnail=true", uri)
+    } else {
+        uri.to_string()
+    }
+}
+
 impl Component<Msg> for FieldView {
     fn update(&mut self, msg: Msg) -> Cmd<Self, Msg> {
         trace!("field updated: {:?}", msg);
         match msg {
             Msg::TextChange(value) => {
-                self.new_value = Value::Text(value);
+                // keep the text as is when it can not be parsed into the column type,
+                // the server will reject it when it is saved
+                self.new_value =
+                    parse_value(&value, &self.column.data_type).unwrap_or(Value::Text(value));
                 Cmd::none()
             }
             Msg::PrimaryClicked => {
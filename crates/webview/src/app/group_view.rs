@@ -0,0 +1,444 @@
+use diwata_intel::{
+    data_aggregate::{Aggregate, ColumnAggregate},
+    data_export::value_to_text,
+    data_group::{Group, GroupRequest, GroupResult, Pivot},
+    ColumnName, Field, SqlType, TableName, Value,
+};
+use sauron::{
+    html::{attributes::*, events::*, units::*, *},
+    Cmd, Component, Node,
+};
+use std::collections::BTreeSet;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Msg {
+    Close,
+    AddGroupColumn(String),
+    RemoveGroupColumn(usize),
+    AggregateColumnChanged(String),
+    AggregateChanged(String),
+    TogglePivot,
+    /// expand or collapse the group with the key texts
+    ToggleExpand(Vec<String>),
+    Run,
+}
+
+/// a column of the table the rows can be grouped by
+struct GroupColumn {
+    name: String,
+    column_name: ColumnName,
+    data_type: SqlType,
+}
+
+/// Group the rows of the table of the window by one or more columns,
+/// the groups of the first columns can be expanded to show the groups
+/// of the next column. Grouping by 2 columns can be shown as a pivot
+pub struct GroupView {
+    pub table_name: TableName,
+    columns: Vec<GroupColumn>,
+    /// the indexes of the columns the rows are grouped by
+    group_by: Vec<usize>,
+    aggregate_column: Option<usize>,
+    aggregate: Aggregate,
+    show_pivot: bool,
+    is_visible: bool,
+    expanded: BTreeSet<Vec<String>>,
+    result: Option<GroupResult>,
+    error: Option<String>,
+}
+
+impl GroupView {
+    /// only the fields with a single column can be grouped,
+    /// foreign keys are grouped by the identifier display of the referred record
+    pub fn new(table_name: TableName, fields: &[Field]) -> Self {
+        GroupView {
+            table_name,
+            columns: fields
+                .iter()
+                .filter_map(|field| {
+                    let column_names = field.column_names();
+                    if column_names.len() == 1 {
+                        Some(GroupColumn {
+                            name: field.name.to_string(),
+                            column_name: column_names[0].clone(),
+                            data_type: field.get_data_type().clone(),
+                        })
+                    } else {
+                        None
+                    }
+                })
+                .collect(),
+            group_by: vec![],
+            aggregate_column: None,
+            aggregate: Aggregate::Sum,
+            show_pivot: false,
+            is_visible: false,
+            expanded: BTreeSet::new(),
+            result: None,
+            error: None,
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.is_visible = !self.is_visible;
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.is_visible
+    }
+
+    /// the columns and aggregate picked by the user
+    pub fn group_request(&self) -> GroupRequest {
+        let aggregates = match self.aggregate_column {
+            Some(index) if self.aggregate.applies_to(&self.columns[index].data_type) => {
+                vec![ColumnAggregate {
+                    column_name: self.columns[index].column_name.clone(),
+                    aggregate: self.aggregate,
+                }]
+            }
+            _ => vec![],
+        };
+        GroupRequest {
+            group_by: self
+                .group_by
+                .iter()
+                .map(|index| self.columns[*index].column_name.clone())
+                .collect(),
+            aggregates,
+            filter: Default::default(),
+        }
+    }
+
+    pub fn can_group(&self) -> bool {
+        !self.group_by.is_empty()
+    }
+
+    pub fn set_result(&mut self, result: Result<GroupResult, String>) {
+        match result {
+            Ok(result) => {
+                self.expanded.clear();
+                self.result = Some(result);
+                self.error = None;
+            }
+            Err(error) => self.error = Some(error),
+        }
+    }
+
+    fn view_controls(&self) -> Node<Msg> {
+        let ungrouped = self
+            .columns
+            .iter()
+            .enumerate()
+            .filter(|(index, _column)| !self.group_by.contains(index))
+            .map(|(_index, column)| option(vec![value(&column.name)], vec![text(&column.name)]));
+        div(
+            vec![class("group_controls")],
+            vec![
+                span(
+                    vec![class("group_columns")],
+                    self.group_by
+                        .iter()
+                        .enumerate()
+                        .map(|(position, index)| {
+                            button(
+                                vec![
+                                    class("group_column"),
+                                    attr("title", "Remove"),
+                                    onclick(move |_| Msg::RemoveGroupColumn(position)),
+                                ],
+                                vec![text(format!("{} \u{00d7}", self.columns[*index].name))],
+                            )
+                        })
+                        .collect::<Vec<Node<Msg>>>(),
+                ),
+                select(
+                    vec![onchange(|input| Msg::AddGroupColumn(input.value))],
+                    std::iter::once(option(vec![value("")], vec![text("Group by..")]))
+                        .chain(ungrouped)
+                        .collect::<Vec<Node<Msg>>>(),
+                ),
+                select(
+                    vec![onchange(|input| Msg::AggregateChanged(input.value))],
+                    Aggregate::all()
+                        .iter()
+                        .map(|aggregate| {
+                            option(vec![value(aggregate.name())], vec![text(aggregate.name())])
+                                .add_attributes(attrs_flag(vec![(
+                                    "selected",
+                                    "selected",
+                                    self.aggregate == *aggregate,
+                                )]))
+                        })
+                        .collect::<Vec<Node<Msg>>>(),
+                ),
+                select(
+                    vec![onchange(|input| Msg::AggregateColumnChanged(input.value))],
+                    std::iter::once(option(vec![value("")], vec![text("(count only)")]))
+                        .chain(
+                            self.columns
+                                .iter()
+                                .enumerate()
+                                .filter(|(_index, column)| {
+                                    self.aggregate.applies_to(&column.data_type)
+                                })
+                                .map(|(index, column)| {
+                                    option(vec![value(&column.name)], vec![text(&column.name)])
+                                        .add_attributes(attrs_flag(vec![(
+                                            "selected",
+                                            "selected",
+                                            self.aggregate_column == Some(index),
+                                        )]))
+                                }),
+                        )
+                        .collect::<Vec<Node<Msg>>>(),
+                ),
+                label(
+                    vec![],
+                    vec![
+                        input(
+                            vec![r#type("checkbox"), onclick(|_| Msg::TogglePivot)],
+                            vec![],
+                        )
+                        .add_attributes(attrs_flag(vec![
+                            ("checked", "checked", self.show_pivot),
+                            ("disabled", "disabled", self.group_by.len() != 2),
+                        ])),
+                        text("Pivot"),
+                    ],
+                ),
+                button(vec![onclick(|_| Msg::Run)], vec![text("Group")]).add_attributes(
+                    attrs_flag(vec![("disabled", "disabled", !self.can_group())]),
+                ),
+                button(vec![onclick(|_| Msg::Close)], vec![text("Close")]),
+            ],
+        )
+    }
+
+    /// the groups which share the same key at the level, the groups of
+    /// the last level are the rows with the aggregate
+    fn view_level(&self, groups: &[Group], level: usize, path: &[String]) -> Vec<Node<Msg>> {
+        let mut nodes = vec![];
+        let mut start = 0;
+        while start < groups.len() {
+            let key = &groups[start].keys[level];
+            let end = groups[start..]
+                .iter()
+                .position(|group| group.keys[level] != *key)
+                .map(|position| start + position)
+                .unwrap_or_else(|| groups.len());
+            let subgroups = &groups[start..end];
+            let mut key_path = path.to_vec();
+            key_path.push(value_to_text(key));
+            nodes.extend(self.view_group(subgroups, level, key_path));
+            start = end;
+        }
+        nodes
+    }
+
+    fn view_group(&self, groups: &[Group], level: usize, key_path: Vec<String>) -> Vec<Node<Msg>> {
+        let is_leaf = level + 1 >= groups[0].keys.len();
+        let is_expanded = self.expanded.contains(&key_path);
+        let count: i64 = groups.iter().map(|group| group.count).sum();
+        let aggregate = if is_leaf {
+            groups[0]
+                .aggregates
+                .first()
+                .map(value_to_text)
+                .unwrap_or_default()
+        } else {
+            String::new()
+        };
+        let key = key_path.last().cloned().unwrap_or_default();
+        let toggle_path = key_path.clone();
+        let mut nodes = vec![tr(
+            vec![class("group_row")],
+            vec![
+                td(
+                    vec![
+                        class("group_key"),
+                        styles(vec![("padding-left", px(level as i32 * 20))]),
+                    ],
+                    vec![
+                        span(
+                            vec![
+                                class("group_toggle"),
+                                onclick(move |_| Msg::ToggleExpand(toggle_path.clone())),
+                                styles_flag(vec![("visibility", "hidden", is_leaf)]),
+                            ],
+                            vec![text(if is_expanded { "\u{25be}" } else { "\u{25b8}" })],
+                        ),
+                        text(key),
+                    ],
+                ),
+                td(vec![class("group_count")], vec![text(count)]),
+                td(vec![class("group_aggregate")], vec![text(aggregate)]),
+            ],
+        )];
+        if !is_leaf && is_expanded {
+            nodes.extend(self.view_level(groups, level + 1, &key_path));
+        }
+        nodes
+    }
+
+    fn view_groups(&self, result: &GroupResult) -> Node<Msg> {
+        let aggregate_header = result
+            .aggregates
+            .first()
+            .map(|aggregate| {
+                format!(
+                    "{} of {}",
+                    aggregate.aggregate.name(),
+                    aggregate.column_name.name
+                )
+            })
+            .unwrap_or_default();
+        table(
+            vec![class("group_table")],
+            vec![
+                thead(
+                    vec![],
+                    vec![tr(
+                        vec![],
+                        vec![
+                            th(
+                                vec![],
+                                vec![text(
+                                    result
+                                        .group_by
+                                        .iter()
+                                        .map(|column_name| column_name.name.as_str())
+                                        .collect::<Vec<&str>>()
+                                        .join(" / "),
+                                )],
+                            ),
+                            th(vec![], vec![text("count")]),
+                            th(vec![], vec![text(aggregate_header)]),
+                        ],
+                    )],
+                ),
+                tbody(vec![], self.view_level(&result.groups, 0, &[])),
+            ],
+        )
+    }
+
+    fn view_pivot(&self, pivot: &Pivot) -> Node<Msg> {
+        let cell_text = |cell: &Option<Value>| cell.as_ref().map(value_to_text).unwrap_or_default();
+        table(
+            vec![class("group_table pivot_table")],
+            vec![
+                thead(
+                    vec![],
+                    vec![tr(
+                        vec![],
+                        std::iter::once(th(vec![], vec![]))
+                            .chain(
+                                pivot
+                                    .column_keys
+                                    .iter()
+                                    .map(|key| th(vec![], vec![text(value_to_text(key))])),
+                            )
+                            .collect::<Vec<Node<Msg>>>(),
+                    )],
+                ),
+                tbody(
+                    vec![],
+                    pivot
+                        .row_keys
+                        .iter()
+                        .zip(pivot.cells.iter())
+                        .map(|(key, cells)| {
+                            tr(
+                                vec![],
+                                std::iter::once(th(vec![], vec![text(value_to_text(key))]))
+                                    .chain(cells.iter().map(|cell| {
+                                        td(vec![class("pivot_cell")], vec![text(cell_text(cell))])
+                                    }))
+                                    .collect::<Vec<Node<Msg>>>(),
+                            )
+                        })
+                        .collect::<Vec<Node<Msg>>>(),
+                ),
+            ],
+        )
+    }
+
+    fn view_result(&self) -> Node<Msg> {
+        match &self.result {
+            Some(result) => {
+                // the pivot shows the aggregate when there is one, otherwise the count
+                let aggregate = if result.aggregates.is_empty() {
+                    None
+                } else {
+                    Some(0)
+                };
+                match result.pivot(aggregate) {
+                    Some(ref pivot) if self.show_pivot => self.view_pivot(pivot),
+                    _ => self.view_groups(result),
+                }
+            }
+            None => div(vec![], vec![]),
+        }
+    }
+}
+
+impl Component<Msg> for GroupView {
+    fn update(&mut self, msg: Msg) -> Cmd<Self, Msg> {
+        match msg {
+            Msg::Close => self.is_visible = false,
+            Msg::AddGroupColumn(name) => {
+                if let Some(index) = self.columns.iter().position(|column| column.name == name) {
+                    if !self.group_by.contains(&index) {
+                        self.group_by.push(index);
+                    }
+                }
+            }
+            Msg::RemoveGroupColumn(position) => {
+                if position < self.group_by.len() {
+                    self.group_by.remove(position);
+                }
+            }
+            Msg::AggregateColumnChanged(name) => {
+                self.aggregate_column = self.columns.iter().position(|column| column.name == name);
+            }
+            Msg::AggregateChanged(name) => {
+                if let Some(aggregate) = Aggregate::from_name(&name) {
+                    self.aggregate = aggregate;
+                }
+            }
+            Msg::TogglePivot => self.show_pivot = !self.show_pivot,
+            Msg::ToggleExpand(key_path) => {
+                if !self.expanded.remove(&key_path) {
+                    self.expanded.insert(key_path);
+                }
+            }
+            Msg::Run => self.error = None,
+        }
+        Cmd::none()
+    }
+
+    fn view(&self) -> Node<Msg> {
+        section(
+            vec![
+                class("group_view"),
+                styles_flag(vec![("display", "none", !self.is_visible)]),
+            ],
+            vec![
+                header(
+                    vec![class("group_view_header")],
+                    vec![
+                        text(format!("Group {}", self.table_name.complete_name())),
+                        self.view_controls(),
+                    ],
+                ),
+                div(
+                    vec![
+                        class("query_error"),
+                        styles_flag(vec![("display", "none", self.error.is_none())]),
+                    ],
+                    vec![text(self.error.as_ref().unwrap_or(&String::new()))],
+                ),
+                self.view_result(),
+            ],
+        )
+    }
+}
@@ -0,0 +1,278 @@
+use diwata_intel::{
+    data_import::{ColumnMapping, ImportSummary},
+    TableName,
+};
+use sauron::{
+    html::{attributes::*, events::*, *},
+    Cmd, Component, Node,
+};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// only the first errors are listed, the rest are counted
+const MAX_LISTED_ERRORS: usize = 100;
+
+/// each import view has its own file input
+static FILE_INPUT_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Msg {
+    Close,
+    FileChanged(String),
+    MappingChanged(usize, String),
+    ToggleByDisplay(usize),
+    DryRun,
+    Import,
+}
+
+/// Import the rows of a csv file into the table of the window,
+/// the headers are mapped to the columns after the first dry-run
+pub struct ImportView {
+    pub table_name: TableName,
+    pub file_input_id: String,
+    is_visible: bool,
+    summary: Option<ImportSummary>,
+    mappings: Vec<ColumnMapping>,
+    error: Option<String>,
+}
+
+impl ImportView {
+    pub fn new(table_name: TableName) -> Self {
+        let counter = FILE_INPUT_COUNTER.fetch_add(1, Ordering::SeqCst);
+        ImportView {
+            table_name,
+            file_input_id: format!("import_file_{}", counter),
+            is_visible: false,
+            summary: None,
+            mappings: vec![],
+            error: None,
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.is_visible = !self.is_visible;
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.is_visible
+    }
+
+    /// the mappings adjusted by the user, empty before the first dry-run
+    /// so that the server maps the headers automatically
+    pub fn mappings(&self) -> &[ColumnMapping] {
+        &self.mappings
+    }
+
+    pub fn set_summary(&mut self, summary: Result<ImportSummary, String>) {
+        match summary {
+            Ok(summary) => {
+                self.mappings = summary.mappings.clone();
+                self.summary = Some(summary);
+                self.error = None;
+            }
+            Err(error) => self.error = Some(error),
+        }
+    }
+
+    /// rows can only be imported after a dry-run of the current mappings
+    fn can_import(&self) -> bool {
+        match &self.summary {
+            Some(summary) => {
+                summary.is_dry_run && summary.mappings == self.mappings && summary.valid_count > 0
+            }
+            None => false,
+        }
+    }
+
+    fn view_mapping(&self, index: usize, mapping: &ColumnMapping, columns: &[String]) -> Node<Msg> {
+        let selected_column = mapping.column.as_ref();
+        tr(
+            vec![],
+            vec![
+                td(vec![], vec![text(&mapping.header)]),
+                td(
+                    vec![],
+                    vec![select(
+                        vec![onchange(move |input| {
+                            Msg::MappingChanged(index, input.value)
+                        })],
+                        std::iter::once(
+                            option(vec![value("")], vec![text("(skip)")]).add_attributes(
+                                attrs_flag(vec![(
+                                    "selected",
+                                    "selected",
+                                    selected_column.is_none(),
+                                )]),
+                            ),
+                        )
+                        .chain(columns.iter().map(|column| {
+                            option(vec![value(column)], vec![text(column)]).add_attributes(
+                                attrs_flag(vec![(
+                                    "selected",
+                                    "selected",
+                                    selected_column == Some(column),
+                                )]),
+                            )
+                        }))
+                        .collect::<Vec<Node<Msg>>>(),
+                    )],
+                ),
+                td(
+                    vec![],
+                    vec![label(
+                        vec![],
+                        vec![
+                            input(
+                                vec![
+                                    r#type("checkbox"),
+                                    onclick(move |_| Msg::ToggleByDisplay(index)),
+                                ],
+                                vec![],
+                            )
+                            .add_attributes(attrs_flag(vec![
+                                ("checked", "checked", mapping.by_display),
+                                ("disabled", "disabled", selected_column.is_none()),
+                            ])),
+                            text("by display"),
+                        ],
+                    )],
+                ),
+            ],
+        )
+    }
+
+    fn view_summary(&self, summary: &ImportSummary) -> Node<Msg> {
+        let status = if summary.is_dry_run {
+            format!(
+                "{} of {} rows can be imported",
+                summary.valid_count, summary.row_count
+            )
+        } else {
+            format!(
+                "{} of {} rows are imported",
+                summary.valid_count, summary.row_count
+            )
+        };
+        let unlisted = summary.errors.len().saturating_sub(MAX_LISTED_ERRORS);
+        div(
+            vec![class("import_summary")],
+            vec![
+                div(vec![class("import_status")], vec![text(status)]),
+                table(
+                    vec![class("import_mappings")],
+                    self.mappings
+                        .iter()
+                        .enumerate()
+                        .map(|(index, mapping)| self.view_mapping(index, mapping, &summary.columns))
+                        .collect::<Vec<Node<Msg>>>(),
+                ),
+                ul(
+                    vec![class("import_errors")],
+                    summary
+                        .errors
+                        .iter()
+                        .take(MAX_LISTED_ERRORS)
+                        .map(|error| {
+                            li(
+                                vec![],
+                                vec![text(format!(
+                                    "line {}{}: {}",
+                                    error.line,
+                                    error
+                                        .header
+                                        .as_ref()
+                                        .map(|header| format!(" {}", header))
+                                        .unwrap_or_default(),
+                                    error.reason
+                                ))],
+                            )
+                        })
+                        .collect::<Vec<Node<Msg>>>(),
+                ),
+                div(
+                    vec![styles_flag(vec![("display", "none", unlisted == 0)])],
+                    vec![text(format!("and {} more errors", unlisted))],
+                ),
+            ],
+        )
+    }
+}
+
+impl Component<Msg> for ImportView {
+    fn update(&mut self, msg: Msg) -> Cmd<Self, Msg> {
+        match msg {
+            Msg::Close => self.is_visible = false,
+            Msg::FileChanged(file) => {
+                trace!("Import file changed to: {}", file);
+                // the headers of the new file are mapped again
+                self.summary = None;
+                self.mappings = vec![];
+                self.error = None;
+            }
+            Msg::MappingChanged(index, column) => {
+                if let Some(mapping) = self.mappings.get_mut(index) {
+                    if column.is_empty() {
+                        mapping.column = None;
+                        mapping.by_display = false;
+                    } else {
+                        mapping.column = Some(column);
+                    }
+                }
+            }
+            Msg::ToggleByDisplay(index) => {
+                if let Some(mapping) = self.mappings.get_mut(index) {
+                    mapping.by_display = !mapping.by_display;
+                }
+            }
+            Msg::DryRun | Msg::Import => self.error = None,
+        }
+        Cmd::none()
+    }
+
+    fn view(&self) -> Node<Msg> {
+        section(
+            vec![
+                class("import_view"),
+                styles_flag(vec![("display", "none", !self.is_visible)]),
+            ],
+            vec![
+                header(
+                    vec![class("import_view_header")],
+                    vec![
+                        text(format!(
+                            "Import csv into {}",
+                            self.table_name.complete_name()
+                        )),
+                        input(
+                            vec![
+                                r#type("file"),
+                                id(&self.file_input_id),
+                                attr("accept", ".csv,text/csv"),
+                                onchange(|input| Msg::FileChanged(input.value)),
+                            ],
+                            vec![],
+                        ),
+                        button(vec![onclick(|_| Msg::DryRun)], vec![text("Dry run")]),
+                        button(vec![onclick(|_| Msg::Import)], vec![text("Import")])
+                            .add_attributes(attrs_flag(vec![(
+                                "disabled",
+                                "disabled",
+                                !self.can_import(),
+                            )])),
+                        button(vec![onclick(|_| Msg::Close)], vec![text("Close")]),
+                    ],
+                ),
+                div(
+                    vec![
+                        class("query_error"),
+                        styles_flag(vec![("display", "none", self.error.is_none())]),
+                    ],
+                    vec![text(self.error.as_ref().unwrap_or(&String::new()))],
+                ),
+                match &self.summary {
+                    Some(summary) => self.view_summary(summary),
+                    None => div(vec![class("import_summary")], vec![]),
+                },
+            ],
+        )
+    }
+}
@@ -1,6 +1,6 @@
 use crate::app::{self, column_view, row_view, row_view::RowView};
 use data_table::DataColumn;
-use diwata_intel::{data_container::Page, DataRow, Field};
+use diwata_intel::{data_container::Page, DataRow};
 use sauron::{
     html::{attributes::*, units::*, *},
     Node,
@@ -63,21 +63,6 @@ impl PageView {
         self.row_views.len()
     }
 
-    #[allow(unused)]
-    fn fields_to_data_columns(fields: &[Field]) -> Vec<DataColumn> {
-        fields.iter().map(Self::field_to_data_column).collect()
-    }
-
-    fn field_to_data_column(field: &Field) -> DataColumn {
-        DataColumn {
-            name: field.name.clone(),
-            description: field.description.clone(),
-            tags: vec![],
-            data_type: field.get_data_type().clone(),
-            is_primary: field.is_primary,
-        }
-    }
-
     pub fn set_page(&mut self, page: &Page, current_page: usize, total_rows: usize) {
         trace!("setting pages in page_view: {:#?}", page);
         self.set_data_rows(&page.rows, current_page, total_rows);
@@ -224,7 +209,12 @@ impl PageView {
 
     pub fn update(&mut self, msg: Msg) -> app::Cmd {
         match msg {
-            Msg::RowMsg(_row_index, _row_msg) => app::Cmd::none(),
+            Msg::RowMsg(row_index, row_msg) => {
+                match self.row_views.iter_mut().find(|row| row.index == row_index) {
+                    Some(row_view) => row_view.update(row_msg),
+                    None => app::Cmd::none(),
+                }
+            }
             Msg::ColumnMsg(_column_index, _column_msg) => app::Cmd::none(),
         }
     }
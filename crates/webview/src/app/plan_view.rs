@@ -0,0 +1,117 @@
+use diwata_intel::query_plan::{PlanNode, QueryPlan};
+use sauron::{
+    html::{self, attributes::*, events::*, *},
+    Cmd, Component, Node,
+};
+
+/// nodes that takes up at least this fraction of the whole query is highlighted
+const EXPENSIVE_RATIO: f64 = 0.2;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Msg {
+    Close,
+}
+
+/// Displays the query plan as a collapsible tree of nodes
+pub struct PlanView {
+    plan: Option<QueryPlan>,
+}
+
+impl PlanView {
+    pub fn new() -> Self {
+        PlanView { plan: None }
+    }
+
+    pub fn set_plan(&mut self, plan: Option<QueryPlan>) {
+        self.plan = plan;
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.plan.is_some()
+    }
+
+    fn view_node(&self, node: &PlanNode, total: f64) -> Node<Msg> {
+        let is_expensive = total > 0.0 && node.exclusive() / total >= EXPENSIVE_RATIO;
+        let relation = match &node.relation_name {
+            Some(relation_name) => format!(" on {}", relation_name),
+            None => String::new(),
+        };
+        let actual = match (node.actual_total_time, node.actual_rows) {
+            (Some(time), Some(rows)) => format!(
+                " actual time: {:.3}ms rows: {} loops: {}",
+                time,
+                rows,
+                node.actual_loops.unwrap_or(1.0)
+            ),
+            _ => String::new(),
+        };
+        details(
+            vec![class("plan_node"), open(true)],
+            vec![
+                html::summary(
+                    vec![classes_flag(vec![("expensive", is_expensive)])],
+                    vec![
+                        span(
+                            vec![class("plan_node_type")],
+                            vec![text(format!("{}{}", node.node_type, relation))],
+                        ),
+                        span(
+                            vec![class("plan_node_cost")],
+                            vec![text(format!(
+                                " cost: {:.2}..{:.2} rows: {}{}",
+                                node.startup_cost, node.total_cost, node.plan_rows, actual
+                            ))],
+                        ),
+                    ],
+                ),
+                div(
+                    vec![class("plan_node_children")],
+                    node.children
+                        .iter()
+                        .map(|child| self.view_node(child, total))
+                        .collect::<Vec<Node<Msg>>>(),
+                ),
+            ],
+        )
+    }
+}
+
+impl Component<Msg> for PlanView {
+    fn update(&mut self, msg: Msg) -> Cmd<Self, Msg> {
+        match msg {
+            Msg::Close => self.plan = None,
+        }
+        Cmd::none()
+    }
+
+    fn view(&self) -> Node<Msg> {
+        match &self.plan {
+            Some(plan) => {
+                let timing = match (plan.planning_time, plan.execution_time) {
+                    (Some(planning), Some(execution)) => {
+                        format!("planning: {:.3}ms execution: {:.3}ms", planning, execution)
+                    }
+                    (Some(planning), None) => format!("planning: {:.3}ms", planning),
+                    _ => String::new(),
+                };
+                section(
+                    vec![class("plan_view")],
+                    vec![
+                        header(
+                            vec![class("plan_view_header")],
+                            vec![
+                                span(vec![], vec![text(timing)]),
+                                button(vec![onclick(|_| Msg::Close)], vec![text("Close plan")]),
+                            ],
+                        ),
+                        self.view_node(&plan.root, plan.total()),
+                    ],
+                )
+            }
+            None => section(
+                vec![class("plan_view"), styles(vec![("display", "none")])],
+                vec![],
+            ),
+        }
+    }
+}
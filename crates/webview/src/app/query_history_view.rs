@@ -0,0 +1,322 @@
+use crate::widgets;
+use diwata_intel::query_history::{QueryHistory, SavedQuery};
+use sauron::{
+    html::{attributes::*, events::*, *},
+    Cmd, Component, Node,
+};
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Msg {
+    ChangeSearch(String),
+    ShowHistory,
+    ShowSavedQueries,
+    /// execute the sql of the history entry in a new window
+    OpenHistory(usize),
+    /// open the saved query as a new window
+    OpenSavedQuery(usize),
+    /// use the history entry as the sql of the query to be saved
+    EditHistory(usize),
+    DeleteSavedQuery(usize),
+    ChangeName(String),
+    ChangeTags(String),
+    ChangeSql(String),
+    ToggleShared,
+    SaveQuery,
+    ReceivedHistory(Vec<QueryHistory>),
+    ReceivedSavedQueries(Vec<SavedQuery>),
+    Close,
+}
+
+/// A panel listing the recent queries of the user and the saved queries
+pub struct QueryHistoryView {
+    pub is_visible: bool,
+    pub show_saved_queries: bool,
+    pub search: String,
+    history: Vec<QueryHistory>,
+    saved_queries: Vec<SavedQuery>,
+    /// the query being composed to be saved
+    name: String,
+    tags: String,
+    sql: String,
+    is_shared: bool,
+}
+
+impl QueryHistoryView {
+    pub fn new() -> Self {
+        QueryHistoryView {
+            is_visible: false,
+            show_saved_queries: false,
+            search: String::new(),
+            history: vec![],
+            saved_queries: vec![],
+            name: String::new(),
+            tags: String::new(),
+            sql: String::new(),
+            is_shared: false,
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.is_visible = !self.is_visible;
+    }
+
+    /// prefill the sql of the query to be saved, such as the sql in the active window
+    pub fn set_sql(&mut self, sql: &Option<String>) {
+        if let Some(sql) = sql {
+            self.sql = sql.to_string();
+        }
+    }
+
+    pub fn get_history(&self, index: usize) -> Option<&QueryHistory> {
+        self.history.get(index)
+    }
+
+    pub fn get_saved_query(&self, index: usize) -> Option<&SavedQuery> {
+        self.saved_queries.get(index)
+    }
+
+    /// the query to be saved, the id, owner and the database is set by the server
+    pub fn query_to_save(&self) -> Option<SavedQuery> {
+        if self.name.trim().is_empty() || self.sql.trim().is_empty() {
+            return None;
+        }
+        Some(SavedQuery {
+            id: None,
+            username: None,
+            db_url: None,
+            name: self.name.trim().to_string(),
+            sql: self.sql.to_string(),
+            tags: self
+                .tags
+                .split(',')
+                .map(|tag| tag.trim().to_string())
+                .filter(|tag| !tag.is_empty())
+                .collect(),
+            is_shared: self.is_shared,
+            created: None,
+        })
+    }
+
+    fn view_history(&self) -> Node<Msg> {
+        ul(
+            vec![class("query_history_list")],
+            self.history
+                .iter()
+                .enumerate()
+                .map(|(index, history)| {
+                    let status = match &history.error {
+                        Some(error) => error.to_string(),
+                        None => format!("{} rows", history.row_count.unwrap_or(0)),
+                    };
+                    li(
+                        vec![
+                            class("query_history_entry"),
+                            classes_flag(vec![("failed", !history.is_success())]),
+                        ],
+                        vec![
+                            pre(
+                                vec![
+                                    class("query_history_sql"),
+                                    onclick(move |_| Msg::OpenHistory(index)),
+                                ],
+                                vec![text(&history.sql)],
+                            ),
+                            div(
+                                vec![class("query_history_info")],
+                                vec![
+                                    text(format!(
+                                        "{} {}ms {}",
+                                        history.created.as_ref().unwrap_or(&String::new()),
+                                        history.duration,
+                                        status
+                                    )),
+                                    button(
+                                        vec![onclick(move |_| Msg::EditHistory(index))],
+                                        vec![text("Save as..")],
+                                    ),
+                                ],
+                            ),
+                        ],
+                    )
+                })
+                .collect::<Vec<Node<Msg>>>(),
+        )
+    }
+
+    fn view_saved_queries(&self) -> Node<Msg> {
+        ul(
+            vec![class("query_history_list")],
+            self.saved_queries
+                .iter()
+                .enumerate()
+                .map(|(index, saved_query)| {
+                    li(
+                        vec![class("query_history_entry")],
+                        vec![
+                            a(
+                                vec![
+                                    class("saved_query_name"),
+                                    onclick(move |_| Msg::OpenSavedQuery(index)),
+                                ],
+                                vec![text(&saved_query.name)],
+                            ),
+                            span(
+                                vec![class("saved_query_tags")],
+                                saved_query
+                                    .tags
+                                    .iter()
+                                    .map(|tag| {
+                                        span(vec![class("saved_query_tag")], vec![text(tag)])
+                                    })
+                                    .collect::<Vec<Node<Msg>>>(),
+                            ),
+                            pre(
+                                vec![class("query_history_sql")],
+                                vec![text(&saved_query.sql)],
+                            ),
+                            div(
+                                vec![class("query_history_info")],
+                                vec![
+                                    text(format!(
+                                        "{}{}",
+                                        saved_query.username.as_ref().unwrap_or(&String::new()),
+                                        if saved_query.is_shared {
+                                            " (shared)"
+                                        } else {
+                                            ""
+                                        }
+                                    )),
+                                    button(
+                                        vec![onclick(move |_| Msg::DeleteSavedQuery(index))],
+                                        vec![text("Delete")],
+                                    ),
+                                ],
+                            ),
+                        ],
+                    )
+                })
+                .collect::<Vec<Node<Msg>>>(),
+        )
+    }
+
+    fn view_save_form(&self) -> Node<Msg> {
+        section(
+            vec![class("save_query_form")],
+            vec![
+                input(
+                    vec![
+                        r#type("text"),
+                        placeholder("Name"),
+                        value(&self.name),
+                        oninput(|input| Msg::ChangeName(input.value)),
+                    ],
+                    vec![],
+                ),
+                input(
+                    vec![
+                        r#type("text"),
+                        placeholder("Tags, separated by comma"),
+                        value(&self.tags),
+                        oninput(|input| Msg::ChangeTags(input.value)),
+                    ],
+                    vec![],
+                ),
+                textarea(
+                    vec![
+                        value(&self.sql),
+                        placeholder("SELECT * "),
+                        onchange(|input| Msg::ChangeSql(input.value)),
+                    ],
+                    vec![],
+                ),
+                label(
+                    vec![],
+                    vec![
+                        input(
+                            vec![r#type("checkbox"), onclick(|_| Msg::ToggleShared)],
+                            vec![],
+                        )
+                        .add_attributes(attrs_flag(vec![(
+                            "checked",
+                            "checked",
+                            self.is_shared,
+                        )])),
+                        text("Share with other users"),
+                    ],
+                ),
+                button(vec![onclick(|_| Msg::SaveQuery)], vec![text("Save query")]),
+            ],
+        )
+    }
+}
+
+impl Component<Msg> for QueryHistoryView {
+    fn update(&mut self, msg: Msg) -> Cmd<Self, Msg> {
+        match msg {
+            Msg::ChangeSearch(search) => self.search = search,
+            Msg::ShowHistory => self.show_saved_queries = false,
+            Msg::ShowSavedQueries => self.show_saved_queries = true,
+            Msg::OpenHistory(_) | Msg::OpenSavedQuery(_) | Msg::DeleteSavedQuery(_) => {
+                trace!("handled in app");
+            }
+            Msg::EditHistory(index) => {
+                if let Some(history) = self.history.get(index) {
+                    self.sql = history.sql.to_string();
+                }
+            }
+            Msg::ChangeName(name) => self.name = name,
+            Msg::ChangeTags(tags) => self.tags = tags,
+            Msg::ChangeSql(sql) => self.sql = sql,
+            Msg::ToggleShared => self.is_shared = !self.is_shared,
+            Msg::SaveQuery => {
+                self.name.clear();
+                self.tags.clear();
+                self.is_shared = false;
+            }
+            Msg::ReceivedHistory(history) => self.history = history,
+            Msg::ReceivedSavedQueries(saved_queries) => self.saved_queries = saved_queries,
+            Msg::Close => self.is_visible = false,
+        }
+        Cmd::none()
+    }
+
+    fn view(&self) -> Node<Msg> {
+        aside(
+            vec![
+                class("query_history_view"),
+                styles_flag(vec![("display", "none", !self.is_visible)]),
+            ],
+            vec![
+                header(
+                    vec![class("query_history_header")],
+                    vec![
+                        a(
+                            vec![
+                                class("tab_links"),
+                                classes_flag(vec![("active", !self.show_saved_queries)]),
+                                onclick(|_| Msg::ShowHistory),
+                            ],
+                            vec![text("History")],
+                        ),
+                        a(
+                            vec![
+                                class("tab_links"),
+                                classes_flag(vec![("active", self.show_saved_queries)]),
+                                onclick(|_| Msg::ShowSavedQueries),
+                            ],
+                            vec![text("Saved queries")],
+                        ),
+                        button(vec![onclick(|_| Msg::Close)], vec![text("x")]),
+                    ],
+                ),
+                widgets::search_widget(oninput(|input| Msg::ChangeSearch(input.value))),
+                if self.show_saved_queries {
+                    self.view_saved_queries()
+                } else {
+                    self.view_history()
+                },
+                self.view_save_form(),
+            ],
+        )
+    }
+}
@@ -3,7 +3,7 @@ use crate::app::{
     field_view::{self, FieldView},
 };
 use data_table::DataColumn;
-use diwata_intel::{Dao, DataRow};
+use diwata_intel::{format_record_id, record_url, Dao, DataRow, TableName, Value};
 use sauron::{
     html::{attributes::*, events::*, units::*, *},
     Component, Node,
@@ -20,6 +20,8 @@ pub enum Msg {
 pub struct RowView {
     pub index: usize,
     pub fields: Vec<Rc<RefCell<FieldView>>>,
+    /// identifies the record of the row in its url
+    pub record_id: Option<String>,
     frozen_fields: Vec<usize>,
     pub is_frozen: bool,
 }
@@ -33,6 +35,7 @@ impl RowView {
                 .zip(data_columns.iter())
                 .map(|(value, column)| Rc::new(RefCell::new(FieldView::new(value, column))))
                 .collect(),
+            record_id: None,
             frozen_fields: vec![],
             is_frozen: false,
         }
@@ -57,6 +60,61 @@ impl RowView {
             })
     }
 
+    /// link the primary fields to the url of the record of this row,
+    /// the values of the primary columns are in the order of the record id
+    pub fn set_record_url(&mut self, table_name: &TableName, primary_columns: &[String]) {
+        if primary_columns.is_empty() {
+            return;
+        }
+        let pk_values: Option<Vec<Value>> = primary_columns
+            .iter()
+            .map(|pk| {
+                self.fields
+                    .iter()
+                    .find(|field| field.borrow().column.name == *pk)
+                    .map(|field| field.borrow().value.clone())
+            })
+            .collect();
+        let pk_values = match pk_values {
+            Some(pk_values) => pk_values,
+            None => return,
+        };
+        let pk_values: Vec<&Value> = pk_values.iter().collect();
+        let url = record_url(table_name, &pk_values);
+        self.record_id = Some(format_record_id(&pk_values));
+        self.fields
+            .iter()
+            .filter(|field| field.borrow().column.is_primary)
+            .for_each(|field| field.borrow_mut().record_url = Some(url.clone()));
+    }
+
+    /// the record in the database refers to this row
+    /// when it has the same primary column values
+    pub fn is_record(&self, record: &Dao) -> bool {
+        let primary_dao = self.primary_dao();
+        !primary_dao.0.is_empty()
+            && primary_dao
+                .0
+                .iter()
+                .all(|(column, value)| record.get_value(column) == Some(value))
+    }
+
+    /// replace the values of the fields with the refreshed record
+    pub fn set_record(&mut self, record: &Dao) {
+        self.fields.iter().for_each(|field| {
+            let mut field = field.borrow_mut();
+            if let Some(value) = record.get_value(&field.column.name) {
+                field.set_value(value);
+            }
+        })
+    }
+
+    pub fn cancel_changes(&mut self) {
+        self.fields
+            .iter()
+            .for_each(|field| field.borrow_mut().cancel_change())
+    }
+
     pub fn freeze_columns(&mut self, columns: Vec<usize>) {
         self.frozen_fields = columns;
         self.update_frozen_column_fields();
@@ -184,7 +242,6 @@ impl RowView {
         })
     }
 
-    #[allow(unused)]
     pub fn update(&mut self, msg: Msg) -> app::Cmd {
         match msg {
             Msg::FieldMsg(field_index, field_msg) => {
@@ -0,0 +1,184 @@
+use diwata_intel::{
+    data_aggregate::{Aggregate, ColumnAggregate},
+    data_export, ColumnName, Field, SqlType, Value,
+};
+use sauron::{
+    html::{attributes::*, events::*, units::*, *},
+    Cmd, Component, Node,
+};
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Msg {
+    AggregateChanged(usize, String),
+}
+
+/// the aggregate picked for a column and its computed value
+struct SummaryCell {
+    /// the lookup fields of composite foreign keys can not be aggregated
+    column_name: Option<ColumnName>,
+    data_type: SqlType,
+    aggregate: Option<Aggregate>,
+    value: Option<Value>,
+}
+
+/// The footer row of the table, showing an aggregate of each column
+/// over all the rows of the table and not only the loaded pages
+pub struct SummaryView {
+    cells: Vec<SummaryCell>,
+    is_visible: bool,
+    error: Option<String>,
+}
+
+impl SummaryView {
+    pub fn new(fields: &[Field]) -> Self {
+        SummaryView {
+            cells: fields
+                .iter()
+                .map(|field| {
+                    let column_names = field.column_names();
+                    SummaryCell {
+                        column_name: if column_names.len() == 1 {
+                            Some(column_names[0].clone())
+                        } else {
+                            None
+                        },
+                        data_type: field.get_data_type().clone(),
+                        aggregate: None,
+                        value: None,
+                    }
+                })
+                .collect(),
+            is_visible: false,
+            error: None,
+        }
+    }
+
+    pub fn set_visible(&mut self, is_visible: bool) {
+        self.is_visible = is_visible;
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.is_visible
+    }
+
+    /// the picked aggregates in the order of the columns
+    pub fn column_aggregates(&self) -> Vec<ColumnAggregate> {
+        self.cells
+            .iter()
+            .filter_map(|cell| match (&cell.column_name, cell.aggregate) {
+                (Some(column_name), Some(aggregate)) => Some(ColumnAggregate {
+                    column_name: column_name.clone(),
+                    aggregate,
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// the values are in the same order as the picked aggregates
+    pub fn set_values(&mut self, values: Result<Vec<Value>, String>) {
+        match values {
+            Ok(values) => {
+                let mut values = values.into_iter();
+                for cell in self.cells.iter_mut() {
+                    cell.value = if cell.column_name.is_some() && cell.aggregate.is_some() {
+                        values.next()
+                    } else {
+                        None
+                    };
+                }
+                self.error = None;
+            }
+            Err(error) => self.error = Some(error),
+        }
+    }
+
+    /// the cells of the frozen or the normal columns
+    pub fn view_cells(&self, frozen_columns: &[usize], is_frozen: bool) -> Node<Msg> {
+        footer(
+            vec![
+                class("summary_cells"),
+                styles_flag(vec![("display", "none", !self.is_visible)]),
+            ],
+            self.cells
+                .iter()
+                .enumerate()
+                .filter(|(index, _cell)| frozen_columns.contains(index) == is_frozen)
+                .map(|(index, cell)| self.view_cell(index, cell))
+                .collect::<Vec<Node<Msg>>>(),
+        )
+    }
+
+    fn view_cell(&self, index: usize, cell: &SummaryCell) -> Node<Msg> {
+        let options = Aggregate::all()
+            .iter()
+            .filter(|aggregate| aggregate.applies_to(&cell.data_type))
+            .map(|aggregate| {
+                option(vec![value(aggregate.name())], vec![text(aggregate.name())]).add_attributes(
+                    attrs_flag(vec![(
+                        "selected",
+                        "selected",
+                        cell.aggregate == Some(*aggregate),
+                    )]),
+                )
+            });
+        div(
+            vec![
+                class("summary_cell"),
+                styles(vec![("width", px(210))]),
+                attr("title", self.error.as_ref().unwrap_or(&String::new())),
+            ],
+            vec![
+                select(
+                    vec![onchange(move |input| {
+                        Msg::AggregateChanged(index, input.value)
+                    })],
+                    std::iter::once(option(vec![value("")], vec![text("-")]))
+                        .chain(options)
+                        .collect::<Vec<Node<Msg>>>(),
+                )
+                .add_attributes(attrs_flag(vec![(
+                    "disabled",
+                    "disabled",
+                    cell.column_name.is_none(),
+                )])),
+                span(
+                    vec![class("summary_value")],
+                    vec![text(format_value(cell.aggregate, &cell.value))],
+                ),
+            ],
+        )
+    }
+}
+
+/// averages are rounded to 2 decimal places
+fn format_value(aggregate: Option<Aggregate>, value: &Option<Value>) -> String {
+    match value {
+        Some(value) => {
+            let text = data_export::value_to_text(value);
+            match (aggregate, text.parse::<f64>()) {
+                (Some(Aggregate::Avg), Ok(avg)) => format!("{:.2}", avg),
+                _ => text,
+            }
+        }
+        None => String::new(),
+    }
+}
+
+impl Component<Msg> for SummaryView {
+    fn update(&mut self, msg: Msg) -> Cmd<Self, Msg> {
+        match msg {
+            Msg::AggregateChanged(index, name) => {
+                if let Some(cell) = self.cells.get_mut(index) {
+                    cell.aggregate = Aggregate::from_name(&name);
+                    cell.value = None;
+                }
+            }
+        }
+        Cmd::none()
+    }
+
+    fn view(&self) -> Node<Msg> {
+        self.view_cells(&[], false)
+    }
+}
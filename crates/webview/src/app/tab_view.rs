@@ -83,6 +83,12 @@ impl TabView {
         self.detail_view.show();
         let fields = &self.table_view.get_fields(page_index, row_index);
         self.detail_view.set_fields(fields);
+        let primary_dao = self.table_view.get_row_primary_dao(page_index, row_index);
+        self.detail_view.set_record(&self.table_name, primary_dao);
+    }
+
+    pub fn detail_view(&self) -> &DetailView {
+        &self.detail_view
     }
     /// Important NOTE: Don't remove views,
     /// just hide them, otherwise the DOM closures
@@ -4,27 +4,38 @@ use crate::app::{
     field_view::FieldView,
     page_view::{self, PageView},
     row_view::RowView,
+    summary_view::{self, SummaryView},
 };
 use data_table::DataColumn;
-use diwata_intel::{data_container::Page, Dao, Field, Tab, TableName};
+use diwata_intel::{
+    data_container::{Filter, Order, Page, Sort},
+    Dao, Field, Rows, Tab, TableName,
+};
 use sauron::{
     html::{attributes::*, events::*, units::*, *},
     Component, Node,
 };
 use std::{cell::RefCell, rc::Rc};
 
+/// the height of the summary footer
+const SUMMARY_HEIGHT: i32 = 30;
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum Msg {
     ColumnMsg(usize, column_view::Msg),
     PageMsg(usize, page_view::Msg),
+    SummaryMsg(summary_view::Msg),
     Scrolled((i32, i32)),
 }
 
 pub struct TableView {
     pub table_name: TableName,
+    /// the primary columns in the order of the record id
+    primary_columns: Vec<String>,
     pub data_columns: Vec<DataColumn>,
     pub column_views: Vec<ColumnView>,
     pub page_views: Vec<PageView>,
+    pub summary_view: SummaryView,
     /// Which columns of the rows are to be frozen on the left side of the table
     frozen_rows: Vec<(usize, Vec<usize>)>,
     frozen_columns: Vec<usize>,
@@ -41,14 +52,26 @@ pub struct TableView {
 impl TableView {
     pub fn from_tab(tab: Tab) -> Self {
         let data_columns = Self::fields_to_data_columns(&tab.fields);
+        let primary_columns = match tab.display {
+            Some(ref display) if !display.pk.is_empty() => {
+                display.pk.iter().map(|pk| pk.name.to_string()).collect()
+            }
+            _ => data_columns
+                .iter()
+                .filter(|column| column.is_primary)
+                .map(|column| column.name.to_string())
+                .collect(),
+        };
         TableView {
             table_name: tab.table_name.clone(),
+            primary_columns,
             column_views: tab
                 .fields
                 .iter()
-                .map(|field| ColumnView::new(Self::field_to_data_column(field)))
+                .map(|field| ColumnView::new(DataColumn::from(field)))
                 .collect(),
             data_columns,
+            summary_view: SummaryView::new(&tab.fields),
             page_views: vec![],
             frozen_rows: vec![],
             frozen_columns: vec![],
@@ -76,9 +99,100 @@ impl TableView {
             .collect();
         self.total_rows = total_records;
         self.current_page = current_page;
+        self.set_record_urls();
         self.update_visible_pages();
     }
 
+    fn set_record_urls(&mut self) {
+        let table_name = &self.table_name;
+        let primary_columns = &self.primary_columns;
+        for page_view in self.page_views.iter_mut() {
+            for row_view in page_view.row_views.iter_mut() {
+                row_view.set_record_url(table_name, primary_columns);
+            }
+        }
+    }
+
+    /// the page and row index of the row with the record id
+    pub fn find_record_id(&self, record_id: &str) -> Option<(usize, usize)> {
+        self.page_views
+            .iter()
+            .enumerate()
+            .find_map(|(page_index, page_view)| {
+                page_view
+                    .row_views
+                    .iter()
+                    .find(|row_view| {
+                        row_view.record_id.as_ref().map(String::as_str) == Some(record_id)
+                    })
+                    .map(|row_view| (page_index, row_view.index))
+            })
+    }
+
+    /// the record id of the row which refers to the record
+    pub fn record_id(&self, record: &Dao) -> Option<String> {
+        let (page_index, row_index) = self.find_record(record)?;
+        self.get_row(page_index, row_index).record_id.clone()
+    }
+
+    /// the rows are filtered with the searches in the columns
+    pub fn filter(&self) -> Filter {
+        Filter {
+            conditions: self
+                .column_views
+                .iter()
+                .filter_map(|column_view| column_view.condition())
+                .collect(),
+        }
+    }
+
+    /// the rows are sorted by the column with a sort direction
+    pub fn sort(&self) -> Sort {
+        Sort {
+            orders: self
+                .column_views
+                .iter()
+                .filter_map(|column_view| {
+                    column_view.direction.clone().map(|direction| Order {
+                        column_name: column_view.column_name(),
+                        direction,
+                    })
+                })
+                .collect(),
+        }
+    }
+
+    /// show the filter and sort in the columns, such as when restored from the url
+    pub fn set_filter_and_sort(&mut self, filter: &Filter, sort: &Sort) {
+        for column_view in self.column_views.iter_mut() {
+            let column_name = column_view.column_name();
+            let condition = filter
+                .conditions
+                .iter()
+                .find(|condition| condition.column_name.name == column_name.name);
+            column_view.set_condition(condition);
+            column_view.direction = sort
+                .orders
+                .iter()
+                .find(|order| order.column_name.name == column_name.name)
+                .map(|order| order.direction.clone());
+        }
+    }
+
+    /// the page and row index of the row which refers to the record
+    pub fn find_record(&self, record: &Dao) -> Option<(usize, usize)> {
+        self.page_views
+            .iter()
+            .enumerate()
+            .find_map(|(page_index, page_view)| {
+                page_view
+                    .row_views
+                    .iter()
+                    .find(|row_view| row_view.is_record(record))
+                    .map(|row_view| (page_index, row_view.index))
+            })
+    }
+
     pub fn get_row_primary_dao(&self, page_index: usize, row_index: usize) -> Dao {
         self.get_row(page_index, row_index).primary_dao()
     }
@@ -92,20 +206,75 @@ impl TableView {
             .expect("expecting a row")
     }
 
-    fn fields_to_data_columns(fields: &[Field]) -> Vec<DataColumn> {
-        fields.iter().map(Self::field_to_data_column).collect()
+    /// the modified rows with the primary columns and the columns
+    /// modified in any of the rows, the primary columns retain its original value
+    /// so the record can still be identified
+    pub fn changed_rows(&self) -> Rows {
+        let changed_rows: Vec<&RowView> = self
+            .page_views
+            .iter()
+            .flat_map(|page_view| page_view.row_views.iter())
+            .filter(|row_view| row_view.is_changed())
+            .collect();
+        let column_indexes: Vec<usize> = self
+            .data_columns
+            .iter()
+            .enumerate()
+            .filter(|(index, column)| {
+                column.is_primary
+                    || changed_rows
+                        .iter()
+                        .any(|row_view| row_view.fields[*index].borrow().is_changed())
+            })
+            .map(|(index, _column)| index)
+            .collect();
+        let mut rows = Rows::new(
+            column_indexes
+                .iter()
+                .map(|index| self.data_columns[*index].name.to_string())
+                .collect(),
+        );
+        for row_view in changed_rows {
+            rows.push(
+                column_indexes
+                    .iter()
+                    .map(|index| {
+                        let field = row_view.fields[*index].borrow();
+                        if field.column.is_primary {
+                            field.value.clone()
+                        } else {
+                            field.new_value.clone()
+                        }
+                    })
+                    .collect(),
+            );
+        }
+        rows
+    }
+
+    /// update the rows that refers to the saved records
+    pub fn refresh_rows(&mut self, records: &[Dao]) {
+        for page_view in self.page_views.iter_mut() {
+            for row_view in page_view.row_views.iter_mut() {
+                if let Some(record) = records.iter().find(|record| row_view.is_record(record)) {
+                    row_view.set_record(record);
+                }
+            }
+        }
     }
 
-    fn field_to_data_column(field: &Field) -> DataColumn {
-        DataColumn {
-            name: field.name.clone(),
-            description: field.description.clone(),
-            tags: vec![],
-            data_type: field.get_data_type().clone(),
-            is_primary: field.is_primary,
+    pub fn cancel_changes(&mut self) {
+        for page_view in self.page_views.iter_mut() {
+            for row_view in page_view.row_views.iter_mut() {
+                row_view.cancel_changes();
+            }
         }
     }
 
+    fn fields_to_data_columns(fields: &[Field]) -> Vec<DataColumn> {
+        fields.iter().map(DataColumn::from).collect()
+    }
+
     pub fn freeze_rows(&mut self, rows: &Vec<(usize, Vec<usize>)>) {
         self.frozen_rows = rows.clone();
         self.update_frozen_rows();
@@ -168,7 +337,11 @@ impl TableView {
 
     /// height from the columns names, padding, margins and borders
     pub fn calculate_needed_height_for_auxilliary_spaces(&self) -> i32 {
-        120
+        if self.summary_view.is_visible() {
+            120 + SUMMARY_HEIGHT
+        } else {
+            120
+        }
     }
 
     pub fn calculate_needed_width_for_auxilliary_spaces(&self) -> i32 {
@@ -341,10 +514,24 @@ impl TableView {
                 self.page_views[page_index].update(page_msg);
                 app::Cmd::none()
             }
+            Msg::ColumnMsg(column_index, column_view::Msg::ToggleSort) => {
+                // the rows are sorted by one column at a time
+                for (index, column_view) in self.column_views.iter_mut().enumerate() {
+                    if index != column_index {
+                        column_view.direction = None;
+                    }
+                }
+                self.column_views[column_index].update(column_view::Msg::ToggleSort);
+                app::Cmd::none()
+            }
             Msg::ColumnMsg(column_index, column_msg) => {
                 self.column_views[column_index].update(column_msg);
                 app::Cmd::none()
             }
+            Msg::SummaryMsg(summary_msg) => {
+                self.summary_view.update(summary_msg);
+                app::Cmd::none()
+            }
             Msg::Scrolled((scroll_top, scroll_left)) => {
                 self.scroll_top = scroll_top;
                 self.scroll_left = scroll_left;
@@ -441,6 +628,24 @@ impl TableView {
                 ),
                 // BOTTOM-RIGHT: Content 4
                 self.view_normal_rows(),
+                // FOOTER-LEFT: the summary of the frozen columns
+                self.summary_view
+                    .view_cells(&self.frozen_columns, true)
+                    .map_msg(Msg::SummaryMsg),
+                // FOOTER-RIGHT: follows the alignment of the normal columns
+                section(
+                    vec![
+                        class("normal_summary_cells_container"),
+                        styles(vec![("width", px(self.calculate_normal_rows_width()))]),
+                    ],
+                    vec![div(
+                        vec![styles(vec![("margin-left", px(-self.scroll_left))])],
+                        vec![self
+                            .summary_view
+                            .view_cells(&self.frozen_columns, false)
+                            .map_msg(Msg::SummaryMsg)],
+                    )],
+                ),
             ],
         )
     }
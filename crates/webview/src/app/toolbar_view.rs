@@ -1,4 +1,9 @@
-use crate::widgets;
+use crate::{rest_api, widgets};
+use diwata_intel::{
+    parse_value,
+    sql_params::{ParameterizedSql, Placeholder},
+    SqlType, TableName, Value,
+};
 use sauron::{
     html::{attributes::*, events::*, units::*, *},
     Cmd, Component, Node,
@@ -11,7 +16,17 @@ pub enum Msg {
     ToggleShowRelatedTabs,
     ChangeQuickFind(String),
     QueryChanged(String),
+    ParamChanged(usize, String),
     RunQuery,
+    ExplainQuery,
+    ToggleAnalyze,
+    SaveChanges,
+    CancelChanges,
+    ToggleExportDisplay,
+    ToggleImport,
+    ToggleGroup,
+    ToggleChart,
+    ToggleTree,
 }
 
 pub struct ToolbarView {
@@ -22,6 +37,22 @@ pub struct ToolbarView {
     quick_find_search: String,
     pub sql_query: String,
     pub formatted_query: String,
+    /// execute the query when explaining to get the actual timings
+    pub analyze: bool,
+    /// the placeholders detected in the sql query
+    placeholders: Vec<Placeholder>,
+    /// the text input for each of the placeholders
+    param_values: Vec<String>,
+    /// the error message when the query is rejected or failed
+    query_error: Option<String>,
+    /// the rows can be edited and saved
+    pub is_editable: bool,
+    /// the table of the window, exported when there is no sql query
+    pub export_table: Option<TableName>,
+    /// export the foreign keys as the identifier display of the referred record
+    pub export_display: bool,
+    /// the main table refers to itself and its records can be shown as a tree
+    pub has_tree: bool,
 }
 
 impl ToolbarView {
@@ -34,13 +65,114 @@ impl ToolbarView {
             quick_find_search: String::new(),
             sql_query: String::new(),
             formatted_query: String::new(),
+            analyze: false,
+            placeholders: vec![],
+            param_values: vec![],
+            query_error: None,
+            is_editable: false,
+            export_table: None,
+            export_display: false,
+            has_tree: false,
         }
     }
 
+    pub fn set_query_error(&mut self, query_error: Option<String>) {
+        self.query_error = query_error;
+    }
+
     pub fn set_sql_query(&mut self, sql_query: &Option<String>) {
         if let Some(sql_query) = sql_query {
             self.sql_query = sql_query.to_owned();
+            self.detect_placeholders();
+        }
+    }
+
+    /// detect the placeholders as the user types the query,
+    /// the previous input is retained for the placeholders with the same name
+    fn detect_placeholders(&mut self) {
+        let placeholders = ParameterizedSql::parse(&self.sql_query).placeholders;
+        self.param_values = placeholders
+            .iter()
+            .map(|placeholder| {
+                self.placeholders
+                    .iter()
+                    .position(|p| p.name == placeholder.name)
+                    .and_then(|index| self.param_values.get(index).cloned())
+                    .unwrap_or_default()
+            })
+            .collect();
+        self.placeholders = placeholders;
+    }
+
+    pub fn has_placeholders(&self) -> bool {
+        !self.placeholders.is_empty()
+    }
+
+    /// set the placeholders with the types inferred by the server,
+    /// ignored when the sql has since been changed
+    pub fn set_placeholder_types(&mut self, sql: &str, placeholders: Vec<Placeholder>) {
+        if self.sql_query == sql && self.placeholders.len() == placeholders.len() {
+            self.placeholders = placeholders;
+        }
+    }
+
+    /// convert each of the input into a value of the placeholder type
+    pub fn sql_params(&self) -> Result<Vec<Value>, String> {
+        self.placeholders
+            .iter()
+            .zip(self.param_values.iter())
+            .map(|(placeholder, value)| {
+                parse_value(value, &placeholder.sql_type)
+                    .map_err(|e| format!("{}: {}", placeholder.name, e))
+            })
+            .collect()
+    }
+
+    /// the download url of the rows in the format,
+    /// the rows of the table are exported when the sql query is not changed
+    /// from selecting all of the table, otherwise the sql query is exported
+    fn export_url(&self, format: &str) -> Option<String> {
+        match &self.export_table {
+            Some(table_name) if self.is_select_all(table_name) => Some(
+                rest_api::export_window_url(table_name, format, self.export_display),
+            ),
+            _ => self.sql_params().ok().map(|params| {
+                rest_api::export_sql_url(&self.sql_query, &params, format, self.export_display)
+            }),
+        }
+    }
+
+    fn is_select_all(&self, table_name: &TableName) -> bool {
+        let sql_query = self.sql_query.trim();
+        sql_query.is_empty() || sql_query == format!("SELECT * FROM {}", table_name.complete_name())
+    }
+
+    fn view_export_links(&self) -> Node<Msg> {
+        let mut links = vec![text("Export")];
+        for format in rest_api::EXPORT_FORMATS.iter() {
+            if let Some(url) = self.export_url(format) {
+                links.push(a(
+                    vec![class("export_link"), href(url), attr("download", "")],
+                    vec![text(format)],
+                ));
+            }
         }
+        links.push(label(
+            vec![],
+            vec![
+                input(
+                    vec![r#type("checkbox"), onclick(|_| Msg::ToggleExportDisplay)],
+                    vec![],
+                )
+                .add_attributes(attrs_flag(vec![(
+                    "checked",
+                    "checked",
+                    self.export_display,
+                )])),
+                text("Display values"),
+            ],
+        ));
+        div(vec![class("export_links")], links)
     }
 
     /// sql input size is resizable
@@ -131,10 +263,32 @@ impl Component<Msg> for ToolbarView {
                 } else {
                     self.formatted_query = "ERROR".to_string();
                 }
+                self.detect_placeholders();
+            }
+            Msg::ParamChanged(index, value) => {
+                if let Some(param_value) = self.param_values.get_mut(index) {
+                    *param_value = value;
+                }
             }
             Msg::RunQuery => {
                 trace!("Running sql_query: {}", self.sql_query);
+                self.query_error = None;
+            }
+            Msg::ExplainQuery => {
+                trace!("Explaining sql_query: {}", self.sql_query);
+                self.query_error = None;
+            }
+            Msg::ToggleAnalyze => self.analyze = !self.analyze,
+            Msg::SaveChanges => {
+                trace!("Saving the changes");
+                self.query_error = None;
             }
+            Msg::CancelChanges => self.query_error = None,
+            Msg::ToggleExportDisplay => self.export_display = !self.export_display,
+            Msg::ToggleImport => trace!("Toggle the import"),
+            Msg::ToggleGroup => trace!("Toggle the group view"),
+            Msg::ToggleChart => trace!("Toggle the chart view"),
+            Msg::ToggleTree => trace!("Toggle the tree view"),
         }
         Cmd::none()
     }
@@ -149,14 +303,34 @@ impl Component<Msg> for ToolbarView {
                         widgets::quick_find(35, oninput(|input| Msg::ChangeQuickFind(input.value))),
                         button(vec![], vec![text("Create new record")]),
                         button(vec![], vec![text("Insert new record")]),
-                        button(vec![], vec![text("Save")]),
-                        button(vec![], vec![text("Cancel")]),
+                        button(vec![onclick(|_| Msg::SaveChanges)], vec![text("Save")])
+                            .add_attributes(attrs_flag(vec![(
+                                "disabled",
+                                "disabled",
+                                !self.is_editable,
+                            )])),
+                        button(vec![onclick(|_| Msg::CancelChanges)], vec![text("Cancel")])
+                            .add_attributes(attrs_flag(vec![(
+                                "disabled",
+                                "disabled",
+                                !self.is_editable,
+                            )])),
                         button(vec![], vec![text("Delete")]),
                         button(vec![], vec![text("Refresh")]),
                         button(vec![], vec![text("Clear filter")]),
                         button(vec![], vec![text("Filter more..")]),
                         button(vec![], vec![text("Sort..")]),
-                        button(vec![], vec![text("Export")]),
+                        self.view_export_links(),
+                        button(vec![onclick(|_| Msg::ToggleImport)], vec![text("Import")]),
+                        button(vec![onclick(|_| Msg::ToggleGroup)], vec![text("Group..")]),
+                        button(vec![onclick(|_| Msg::ToggleChart)], vec![text("Chart..")]),
+                        button(
+                            vec![
+                                onclick(|_| Msg::ToggleTree),
+                                styles_flag(vec![("display", "none", !self.has_tree)]),
+                            ],
+                            vec![text("Tree")],
+                        ),
                         button(
                             vec![onclick(|_| Msg::ToggleShowQuery)],
                             vec![
@@ -207,6 +381,28 @@ impl Component<Msg> for ToolbarView {
                             ],
                             vec![text("Run query")],
                         ),
+                        div(
+                            vec![class("explain_query")],
+                            vec![
+                                button(vec![onclick(|_| Msg::ExplainQuery)], vec![text("Explain")]),
+                                label(
+                                    vec![],
+                                    vec![
+                                        input(
+                                            vec![
+                                                r#type("checkbox"),
+                                                onclick(|_| Msg::ToggleAnalyze),
+                                            ],
+                                            vec![],
+                                        )
+                                        .add_attributes(
+                                            attrs_flag(vec![("checked", "checked", self.analyze)]),
+                                        ),
+                                        text("Analyze"),
+                                    ],
+                                ),
+                            ],
+                        ),
                         textarea(
                             vec![
                                 class("parsed_sql"),
@@ -220,7 +416,74 @@ impl Component<Msg> for ToolbarView {
                         ),
                     ],
                 ),
+                section(
+                    vec![
+                        class("query_params"),
+                        styles_flag(vec![(
+                            "display",
+                            "none",
+                            !self.show_query || self.placeholders.is_empty(),
+                        )]),
+                    ],
+                    self.placeholders
+                        .iter()
+                        .zip(self.param_values.iter())
+                        .enumerate()
+                        .map(|(index, (placeholder, param_value))| {
+                            label(
+                                vec![class("query_param")],
+                                vec![
+                                    text(&placeholder.name),
+                                    input(
+                                        vec![
+                                            r#type(input_type(&placeholder.sql_type)),
+                                            value(param_value),
+                                            placeholder_attr(&placeholder.sql_type),
+                                            oninput(move |input| {
+                                                Msg::ParamChanged(index, input.value)
+                                            }),
+                                        ],
+                                        vec![],
+                                    ),
+                                ],
+                            )
+                        })
+                        .collect::<Vec<Node<Msg>>>(),
+                ),
+                section(
+                    vec![
+                        class("query_error"),
+                        styles_flag(vec![("display", "none", self.query_error.is_none())]),
+                    ],
+                    vec![text(self.query_error.as_ref().unwrap_or(&String::new()))],
+                ),
             ],
         )
     }
 }
+
+/// the html input type suited for the sql type
+fn input_type(sql_type: &SqlType) -> &'static str {
+    match sql_type {
+        SqlType::Tinyint
+        | SqlType::Smallint
+        | SqlType::Int
+        | SqlType::Bigint
+        | SqlType::Real
+        | SqlType::Float
+        | SqlType::Double
+        | SqlType::Numeric => "number",
+        SqlType::Date => "date",
+        SqlType::Timestamp | SqlType::TimestampTz => "datetime-local",
+        SqlType::Time | SqlType::TimeTz => "time",
+        _ => "text",
+    }
+}
+
+/// show the expected type as the placeholder of the input
+fn placeholder_attr<MSG>(sql_type: &SqlType) -> sauron::Attribute<MSG>
+where
+    MSG: Clone,
+{
+    placeholder(format!("{:?}", sql_type).to_lowercase())
+}
@@ -0,0 +1,211 @@
+use diwata_intel::{data_tree::TreeNode, TableName};
+use sauron::{
+    html::{attributes::*, events::*, *},
+    Cmd, Component, Node,
+};
+use std::collections::{BTreeMap, BTreeSet};
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Msg {
+    Close,
+    /// expand or collapse the children of the record
+    ToggleExpand(String),
+    /// show the record in the detail view and its ancestors as the breadcrumbs
+    Select(String),
+}
+
+/// Show the records of a table which refers to itself, such as the categories
+/// and their parent category, as a tree. The children of a record are
+/// retrieved when it is expanded for the first time
+pub struct TreeView {
+    pub table_name: TableName,
+    is_visible: bool,
+    /// the records which has no parent
+    roots: Option<Vec<TreeNode>>,
+    /// the children of the records which are already retrieved
+    children: BTreeMap<String, Vec<TreeNode>>,
+    expanded: BTreeSet<String>,
+    selected: Option<String>,
+    /// the selected record and its ancestors, from the root
+    breadcrumbs: Vec<TreeNode>,
+    error: Option<String>,
+}
+
+impl TreeView {
+    pub fn new(table_name: TableName) -> Self {
+        TreeView {
+            table_name,
+            is_visible: false,
+            roots: None,
+            children: BTreeMap::new(),
+            expanded: BTreeSet::new(),
+            selected: None,
+            breadcrumbs: vec![],
+            error: None,
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.is_visible = !self.is_visible;
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.is_visible
+    }
+
+    /// the roots are retrieved when the tree is shown for the first time
+    pub fn needs_roots(&self) -> bool {
+        self.is_visible && self.roots.is_none()
+    }
+
+    /// the record is expanded and its children are not retrieved yet
+    pub fn needs_children(&self, record_id: &str) -> bool {
+        self.expanded.contains(record_id) && !self.children.contains_key(record_id)
+    }
+
+    /// the children of the parent, or the roots when there is no parent
+    pub fn set_children(&mut self, parent: Option<String>, result: Result<Vec<TreeNode>, String>) {
+        match (parent, result) {
+            (Some(parent), Ok(children)) => {
+                self.children.insert(parent, children);
+                self.error = None;
+            }
+            (None, Ok(roots)) => {
+                self.roots = Some(roots);
+                self.error = None;
+            }
+            (_, Err(error)) => self.error = Some(error),
+        }
+    }
+
+    /// the ancestors are expanded, so the selected record is visible in the tree
+    pub fn set_ancestors(&mut self, result: Result<Vec<TreeNode>, String>) {
+        match result {
+            Ok(ancestors) => {
+                let parents = ancestors.len().saturating_sub(1);
+                for ancestor in ancestors.iter().take(parents) {
+                    self.expanded.insert(ancestor.record_id.clone());
+                }
+                self.breadcrumbs = ancestors;
+                self.error = None;
+            }
+            Err(error) => self.error = Some(error),
+        }
+    }
+
+    /// the expanded records whose children are not retrieved yet
+    pub fn missing_children(&self) -> Vec<String> {
+        self.expanded
+            .iter()
+            .filter(|record_id| !self.children.contains_key(*record_id))
+            .cloned()
+            .collect()
+    }
+
+    fn view_node(&self, node: &TreeNode) -> Node<Msg> {
+        let record_id = node.record_id.clone();
+        let select_id = node.record_id.clone();
+        let is_expanded = self.expanded.contains(&node.record_id);
+        let is_selected = self.selected.as_ref() == Some(&node.record_id);
+        li(
+            vec![class("tree_node"), key(node.record_id.clone())],
+            vec![
+                span(
+                    vec![
+                        class("tree_toggle"),
+                        styles_flag(vec![("visibility", "hidden", !node.has_children)]),
+                        onclick(move |_| Msg::ToggleExpand(record_id.clone())),
+                    ],
+                    vec![text(if is_expanded { "▾" } else { "▸" })],
+                ),
+                a(
+                    vec![
+                        class("tree_display"),
+                        classes_flag(vec![("selected", is_selected)]),
+                        onclick(move |_| Msg::Select(select_id.clone())),
+                    ],
+                    vec![text(&node.display)],
+                ),
+                if is_expanded {
+                    match self.children.get(&node.record_id) {
+                        Some(children) => self.view_nodes(children),
+                        None => div(vec![class("tree_loading")], vec![text("Loading..")]),
+                    }
+                } else {
+                    text("")
+                },
+            ],
+        )
+    }
+
+    fn view_nodes(&self, nodes: &[TreeNode]) -> Node<Msg> {
+        ul(
+            vec![class("tree_nodes")],
+            nodes
+                .iter()
+                .map(|node| self.view_node(node))
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    fn view_breadcrumbs(&self) -> Node<Msg> {
+        div(
+            vec![class("tree_breadcrumbs")],
+            self.breadcrumbs
+                .iter()
+                .map(|node| {
+                    let record_id = node.record_id.clone();
+                    a(
+                        vec![onclick(move |_| Msg::Select(record_id.clone()))],
+                        vec![text(&node.display)],
+                    )
+                })
+                .collect::<Vec<_>>(),
+        )
+    }
+}
+
+impl Component<Msg> for TreeView {
+    fn update(&mut self, msg: Msg) -> Cmd<Self, Msg> {
+        match msg {
+            Msg::Close => self.is_visible = false,
+            Msg::ToggleExpand(record_id) => {
+                if !self.expanded.remove(&record_id) {
+                    self.expanded.insert(record_id);
+                }
+            }
+            Msg::Select(record_id) => self.selected = Some(record_id),
+        }
+        Cmd::none()
+    }
+
+    fn view(&self) -> Node<Msg> {
+        section(
+            vec![
+                class("tree_view"),
+                styles_flag(vec![("display", "none", !self.is_visible)]),
+            ],
+            vec![
+                header(
+                    vec![class("tree_view_header")],
+                    vec![
+                        text(format!("Tree {}", self.table_name.complete_name())),
+                        self.view_breadcrumbs(),
+                        button(vec![onclick(|_| Msg::Close)], vec![text("Close")]),
+                    ],
+                ),
+                div(
+                    vec![
+                        class("query_error"),
+                        styles_flag(vec![("display", "none", self.error.is_none())]),
+                    ],
+                    vec![text(self.error.as_ref().unwrap_or(&String::new()))],
+                ),
+                match &self.roots {
+                    Some(roots) => self.view_nodes(roots),
+                    None => div(vec![class("tree_loading")], vec![text("Loading..")]),
+                },
+            ],
+        )
+    }
+}
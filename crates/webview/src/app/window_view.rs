@@ -6,14 +6,27 @@ use sauron::{
 use crate::{
     app::{
         self,
+        chart_view::{self, ChartView},
+        group_view::{self, GroupView},
+        import_view::{self, ImportView},
+        plan_view::{self, PlanView},
         tab_view::{self, TabView},
         toolbar_view::{self, ToolbarView},
+        tree_view::{self, TreeView},
     },
-    assets,
+    assets, rest_api,
+    route::Route,
+};
+use data_table::DataTable;
+use diwata_intel::{
+    query_plan::QueryPlan, sql_params::Placeholder, Dao, Rows, TableName, Value, Window,
 };
-use diwata_intel::{TableName, Window};
 
-use diwata_intel::data_container::WindowData;
+use diwata_intel::{
+    data_container::{SaveContainer, WindowData},
+    data_group::GroupResult,
+    data_import::ImportSummary,
+};
 
 pub struct WindowView {
     pub name: String,
@@ -27,6 +40,14 @@ pub struct WindowView {
     browser_height: i32,
     browser_width: i32,
     toolbar_view: ToolbarView,
+    plan_view: PlanView,
+    import_view: ImportView,
+    group_view: GroupView,
+    chart_view: ChartView,
+    /// the records shown as a tree when the main table refers to itself
+    tree_view: Option<TreeView>,
+    /// the primary columns of the record shown in the detail view
+    detail_record: Option<Dao>,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -39,6 +60,11 @@ pub enum Msg {
     ShowIndirectTab(usize),
     BrowserResized(i32, i32),
     ToolbarMsg(toolbar_view::Msg),
+    PlanMsg(plan_view::Msg),
+    ImportMsg(import_view::Msg),
+    GroupMsg(group_view::Msg),
+    ChartMsg(chart_view::Msg),
+    TreeMsg(tree_view::Msg),
     CloseDetailView,
 }
 
@@ -77,11 +103,65 @@ impl WindowView {
                 self.update_size_allocation();
                 app::Cmd::none()
             }
+            Msg::ToolbarMsg(toolbar_view::Msg::CancelChanges) => {
+                self.toolbar_view.update(toolbar_view::Msg::CancelChanges);
+                self.main_tab.table_view.cancel_changes();
+                app::Cmd::none()
+            }
+            Msg::ToolbarMsg(toolbar_view::Msg::ToggleImport) => {
+                self.import_view.toggle();
+                self.update_size_allocation();
+                app::Cmd::none()
+            }
+            Msg::ToolbarMsg(toolbar_view::Msg::ToggleGroup) => {
+                self.group_view.toggle();
+                self.update_size_allocation();
+                app::Cmd::none()
+            }
+            Msg::ToolbarMsg(toolbar_view::Msg::ToggleChart) => {
+                self.chart_view.toggle();
+                self.update_size_allocation();
+                app::Cmd::none()
+            }
+            Msg::ToolbarMsg(toolbar_view::Msg::ToggleTree) => {
+                if let Some(tree_view) = &mut self.tree_view {
+                    tree_view.toggle();
+                }
+                self.update_size_allocation();
+                app::Cmd::none()
+            }
             Msg::ToolbarMsg(toolbar_msg) => {
                 self.toolbar_view.update(toolbar_msg);
                 self.update_size_allocation();
                 app::Cmd::none()
             }
+            Msg::PlanMsg(plan_msg) => {
+                self.plan_view.update(plan_msg);
+                self.update_size_allocation();
+                app::Cmd::none()
+            }
+            Msg::ImportMsg(import_msg) => {
+                self.import_view.update(import_msg);
+                self.update_size_allocation();
+                app::Cmd::none()
+            }
+            Msg::GroupMsg(group_msg) => {
+                self.group_view.update(group_msg);
+                self.update_size_allocation();
+                app::Cmd::none()
+            }
+            Msg::ChartMsg(chart_msg) => {
+                self.chart_view.update(chart_msg);
+                self.update_size_allocation();
+                app::Cmd::none()
+            }
+            Msg::TreeMsg(tree_msg) => {
+                if let Some(tree_view) = &mut self.tree_view {
+                    tree_view.update(tree_msg);
+                }
+                self.update_size_allocation();
+                app::Cmd::none()
+            }
             Msg::CloseDetailView => {
                 self.close_detail_view();
                 self.update_size_allocation();
@@ -104,6 +184,14 @@ impl WindowView {
                     vec![class("toolbar_view")],
                     vec![self.toolbar_view.view().map_msg(Msg::ToolbarMsg)],
                 ),
+                self.plan_view.view().map_msg(Msg::PlanMsg),
+                self.import_view.view().map_msg(Msg::ImportMsg),
+                self.group_view.view().map_msg(Msg::GroupMsg),
+                self.chart_view.view().map_msg(Msg::ChartMsg),
+                match &self.tree_view {
+                    Some(tree_view) => tree_view.view().map_msg(Msg::TreeMsg),
+                    None => text(""),
+                },
                 section(
                     vec![class("main_tab_and_one_one_tabs_and_detail_close_btn")],
                     vec![
@@ -202,6 +290,7 @@ impl WindowView {
                                         })
                                         .collect::<Vec<Node<Msg>>>(),
                                 ),
+                                self.view_related_export_links(),
                             ],
                         ),
                         section(
@@ -251,6 +340,13 @@ impl WindowView {
         browser_width: i32,
         browser_height: i32,
     ) -> Self {
+        let import_view = ImportView::new(window.main_tab.table_name.clone());
+        let group_view =
+            GroupView::new(window.main_tab.table_name.clone(), &window.main_tab.fields);
+        let tree_view = window
+            .self_reference
+            .as_ref()
+            .map(|_| TreeView::new(window.main_tab.table_name.clone()));
         let mut window_view = WindowView {
             name: window.name,
             main_tab: TabView::new(window.main_tab),
@@ -267,7 +363,11 @@ impl WindowView {
             indirect_tabs: window
                 .indirect_tabs
                 .into_iter()
-                .map(|tab| (tab.linker, TabView::new(tab.tab)))
+                .map(|tab| {
+                    // the extra fields of the linker are shown after the indirect fields
+                    let tab_view = TabView::new(tab.tab_with_linker_fields());
+                    (tab.linker, tab_view)
+                })
                 .collect(),
             is_visible: true,
             active_has_many_tab: Some(0),
@@ -275,7 +375,20 @@ impl WindowView {
             browser_width,
             browser_height,
             toolbar_view: ToolbarView::new(),
+            plan_view: PlanView::new(),
+            import_view,
+            group_view,
+            chart_view: ChartView::new(),
+            tree_view,
+            detail_record: None,
         };
+        window_view.toolbar_view.export_table = Some(window_view.main_tab.table_name.clone());
+        window_view.toolbar_view.has_tree = window_view.tree_view.is_some();
+        window_view
+            .main_tab
+            .table_view
+            .summary_view
+            .set_visible(true);
         window_view.set_window_data(window_data);
         window_view.update_active_has_many_or_indirect_tab();
         window_view.update_size_allocation();
@@ -292,6 +405,88 @@ impl WindowView {
         }
     }
 
+    pub fn set_query_error(&mut self, query_error: Option<String>) {
+        self.toolbar_view.set_query_error(query_error);
+    }
+
+    /// the values of the placeholders in the sql query
+    pub fn sql_params(&self) -> Result<Vec<Value>, String> {
+        self.toolbar_view.sql_params()
+    }
+
+    pub fn has_placeholders(&self) -> bool {
+        self.toolbar_view.has_placeholders()
+    }
+
+    pub fn set_placeholder_types(&mut self, sql: &str, placeholders: Vec<Placeholder>) {
+        self.toolbar_view.set_placeholder_types(sql, placeholders);
+    }
+
+    /// the rows of the query result can be edited and saved back to the table
+    pub fn set_is_editable(&mut self, is_editable: bool) {
+        self.toolbar_view.is_editable = is_editable;
+    }
+
+    /// the modified rows of the main tab to be saved to its table
+    pub fn save_container(&self) -> Option<SaveContainer> {
+        if !self.toolbar_view.is_editable {
+            return None;
+        }
+        let changed_rows = self.main_tab.table_view.changed_rows();
+        if changed_rows.data.is_empty() {
+            return None;
+        }
+        let table_name = &self.main_tab.table_name;
+        Some(SaveContainer {
+            for_insert: (table_name.clone(), Rows::new(vec![])),
+            for_update: (table_name.clone(), changed_rows),
+        })
+    }
+
+    /// update the rows with the records saved in the database
+    pub fn refresh_rows(&mut self, records: &[Dao]) {
+        self.main_tab.table_view.refresh_rows(records);
+    }
+
+    /// the file input, table and mappings of the csv to be imported
+    pub fn import_view(&self) -> &ImportView {
+        &self.import_view
+    }
+
+    /// the columns and aggregate of the rows to be grouped
+    pub fn group_view(&self) -> &GroupView {
+        &self.group_view
+    }
+
+    /// the records of the main table shown as a tree, if it refers to itself
+    pub fn tree_view(&self) -> Option<&TreeView> {
+        self.tree_view.as_ref()
+    }
+
+    pub fn tree_view_mut(&mut self) -> Option<&mut TreeView> {
+        self.tree_view.as_mut()
+    }
+
+    pub fn set_group_result(&mut self, result: Result<GroupResult, String>) {
+        self.chart_view
+            .set_groups(result.as_ref().ok().map(DataTable::from));
+        self.group_view.set_result(result);
+    }
+
+    pub fn set_import_summary(&mut self, summary: Result<ImportSummary, String>) {
+        self.import_view.set_summary(summary);
+        self.update_size_allocation();
+    }
+
+    pub fn is_analyze(&self) -> bool {
+        self.toolbar_view.analyze
+    }
+
+    pub fn set_query_plan(&mut self, plan: Option<QueryPlan>) {
+        self.plan_view.set_plan(plan);
+        self.update_size_allocation();
+    }
+
     /// Important: set the data rows first before setting the frozen data
     pub fn set_window_data(&mut self, window_data: &WindowData) {
         trace!("In setting window data");
@@ -321,6 +516,13 @@ impl WindowView {
         self.main_tab
             .set_pages(main_tab_data, *main_tab_current_page, *main_tab_total_rows);
         self.main_tab.set_frozen_data(main_tab_frozen_data);
+        self.chart_view.set_rows(DataTable {
+            columns: self.main_tab.table_view.data_columns.clone(),
+            rows: main_tab_data
+                .iter()
+                .flat_map(|page| page.rows.iter().cloned())
+                .collect(),
+        });
 
         trace!("Setting one_one");
         // one one tab should only have 1 row
@@ -343,7 +545,62 @@ impl WindowView {
     }
 
     pub fn show_main_tab_detail_view(&mut self, page_index: usize, row_index: usize) {
-        self.main_tab.show_detail_view(page_index, row_index)
+        self.main_tab.show_detail_view(page_index, row_index);
+        self.detail_record = Some(
+            self.main_tab
+                .table_view
+                .get_row_primary_dao(page_index, row_index),
+        );
+    }
+
+    /// show the detail view of the record in the main tab
+    pub fn show_main_tab_record(&mut self, record: &Dao) {
+        if let Some((page_index, row_index)) = self.main_tab.table_view.find_record(record) {
+            self.show_main_tab_detail_view(page_index, row_index);
+            self.update_size_allocation();
+        }
+    }
+
+    /// the download url of the rows in the active has_many or indirect tab of the record
+    fn related_export_url(&self, record: &Dao, format: &str) -> Option<String> {
+        let table_name = &self.main_tab.table_name;
+        let display = self.toolbar_view.export_display;
+        if let Some(index) = self.active_has_many_tab {
+            self.has_many_tabs.get(index).map(|tab| {
+                rest_api::export_has_many_url(table_name, &tab.table_name, record, format, display)
+            })
+        } else if let Some(index) = self.active_indirect_tab {
+            self.indirect_tabs.get(index).map(|(linker, tab)| {
+                rest_api::export_indirect_url(
+                    table_name,
+                    linker,
+                    &tab.table_name,
+                    record,
+                    format,
+                    display,
+                )
+            })
+        } else {
+            None
+        }
+    }
+
+    fn view_related_export_links(&self) -> Node<Msg> {
+        let mut links = vec![];
+        if let Some(record) = &self.detail_record {
+            for format in rest_api::EXPORT_FORMATS.iter() {
+                if let Some(url) = self.related_export_url(record, format) {
+                    links.push(a(
+                        vec![class("export_link"), href(url), attr("download", "")],
+                        vec![text(format)],
+                    ));
+                }
+            }
+        }
+        if !links.is_empty() {
+            links.insert(0, text("Export"));
+        }
+        nav(vec![class("export_links")], links)
     }
 
     fn update_active_has_many_or_indirect_tab(&mut self) {
@@ -416,8 +673,14 @@ impl WindowView {
         let (window_width, window_height) = self.calculate_window_size();
         let (_related_tab_width, related_tab_height) = self.calculate_related_tabs_size();
 
-        let main_table_height =
-            window_height - (related_tab_height + self.toolbar_view.get_consumed_height());
+        let main_table_height = window_height
+            - (related_tab_height
+                + self.toolbar_view.get_consumed_height()
+                + self.plan_view_needed_height()
+                + self.import_view_needed_height()
+                + self.group_view_needed_height()
+                + self.chart_view_needed_height()
+                + self.tree_view_needed_height());
 
         let clamped_main_table_height = if main_table_height < 0 {
             0
@@ -427,6 +690,50 @@ impl WindowView {
         (window_width, clamped_main_table_height)
     }
 
+    /// the plan view is given a fixed height and scrolls its content
+    fn plan_view_needed_height(&self) -> i32 {
+        if self.plan_view.is_visible() {
+            300
+        } else {
+            0
+        }
+    }
+
+    /// the import view is given a fixed height and scrolls its content
+    fn import_view_needed_height(&self) -> i32 {
+        if self.import_view.is_visible() {
+            300
+        } else {
+            0
+        }
+    }
+
+    /// the group view is given a fixed height and scrolls its content
+    fn group_view_needed_height(&self) -> i32 {
+        if self.group_view.is_visible() {
+            300
+        } else {
+            0
+        }
+    }
+
+    /// the chart view is given a fixed height and scrolls its content
+    fn chart_view_needed_height(&self) -> i32 {
+        if self.chart_view.is_visible() {
+            300
+        } else {
+            0
+        }
+    }
+
+    /// the tree view is given a fixed height and scrolls its content
+    fn tree_view_needed_height(&self) -> i32 {
+        match &self.tree_view {
+            Some(tree_view) if tree_view.is_visible() => 300,
+            _ => 0,
+        }
+    }
+
     /// the height of the related tab links in has_many and indirect tabs
     fn related_tab_links_needed_height(&self) -> i32 {
         40
@@ -496,15 +803,37 @@ impl WindowView {
             .for_each(|(_table_name, tab)| tab.set_table_size(calculated_related_tabs_size));
     }
 
-    fn in_detail_view(&self) -> bool {
+    pub fn in_detail_view(&self) -> bool {
         self.main_tab.in_detail_view()
     }
 
+    /// the record id of the record shown in the detail view of the main tab
+    pub fn detail_record_id(&self) -> Option<String> {
+        if !self.in_detail_view() {
+            return None;
+        }
+        let primary_dao = self.main_tab.detail_view().primary_dao()?;
+        self.main_tab.table_view.record_id(primary_dao)
+    }
+
+    /// the table, the record in the detail view, and the filter and sort
+    /// of the rows of this window, to be kept in the url
+    pub fn route(&self) -> Route {
+        let table_view = &self.main_tab.table_view;
+        Route {
+            table_name: Some(self.main_tab.table_name.clone()),
+            record_id: self.detail_record_id(),
+            filter: table_view.filter(),
+            sort: table_view.sort(),
+        }
+    }
+
     fn is_show_related_tabs(&self) -> bool {
         self.in_detail_view() && self.toolbar_view.show_related_tabs
     }
 
     fn close_detail_view(&mut self) {
-        self.main_tab.close_detail_view()
+        self.main_tab.close_detail_view();
+        self.detail_record = None;
     }
 }
@@ -0,0 +1,774 @@
+//! Charts of the rows of a data table rendered as pure svg,
+//! no browser api is used so the charts can also be rendered into a string
+//! such as in the server side rendered pages or when exported as a file
+use data_table::DataTable;
+use diwata_intel::{data_export::value_to_text, SqlType, Value};
+use sauron::{
+    html::{self, attributes::attr},
+    svg::{
+        tags::{line, text},
+        *,
+    },
+    Node,
+};
+
+/// the colors of the series, repeated when there are more series
+const PALETTE: [&str; 8] = [
+    "#1f77b4", "#ff7f0e", "#2ca02c", "#d62728", "#9467bd", "#8c564b",
+    "#e377c2", "#7f7f7f",
+];
+
+/// the space for the axis labels around the plot area
+const MARGIN_LEFT: f64 = 60.0;
+const MARGIN_RIGHT: f64 = 120.0;
+const MARGIN_TOP: f64 = 20.0;
+const MARGIN_BOTTOM: f64 = 40.0;
+const Y_TICKS: usize = 5;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ChartKind {
+    Bar,
+    Line,
+    Scatter,
+    Pie,
+}
+
+impl ChartKind {
+    pub fn all() -> [ChartKind; 4] {
+        [
+            ChartKind::Bar,
+            ChartKind::Line,
+            ChartKind::Scatter,
+            ChartKind::Pie,
+        ]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ChartKind::Bar => "bar",
+            ChartKind::Line => "line",
+            ChartKind::Scatter => "scatter",
+            ChartKind::Pie => "pie",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::all().iter().find(|kind| kind.name() == name).cloned()
+    }
+
+    /// the x of a scatter chart is a number, the rest are labels
+    pub fn accepts_x(&self, sql_type: &SqlType) -> bool {
+        match self {
+            ChartKind::Scatter => is_numeric_type(sql_type),
+            _ => true,
+        }
+    }
+
+    pub fn accepts_y(&self, sql_type: &SqlType) -> bool {
+        is_numeric_type(sql_type)
+    }
+
+    /// the values of a pie chart are the slices of a single series
+    pub fn has_series(&self) -> bool {
+        *self != ChartKind::Pie
+    }
+}
+
+/// the columns of the table plotted in the chart
+#[derive(Debug, PartialEq, Clone)]
+pub struct ChartSpec {
+    pub kind: ChartKind,
+    pub x: usize,
+    pub y: usize,
+    /// the rows are split into series by the values of this column
+    pub series: Option<usize>,
+}
+
+fn is_numeric_type(sql_type: &SqlType) -> bool {
+    match sql_type {
+        SqlType::Tinyint
+        | SqlType::Smallint
+        | SqlType::Int
+        | SqlType::Bigint
+        | SqlType::Real
+        | SqlType::Float
+        | SqlType::Double
+        | SqlType::Numeric => true,
+        _ => false,
+    }
+}
+
+fn as_f64(value: &Value) -> Option<f64> {
+    match value {
+        Value::Tinyint(v) => Some(f64::from(*v)),
+        Value::Smallint(v) => Some(f64::from(*v)),
+        Value::Int(v) => Some(f64::from(*v)),
+        Value::Bigint(v) => Some(*v as f64),
+        Value::Float(v) => Some(f64::from(*v)),
+        Value::Double(v) => Some(*v),
+        Value::BigDecimal(v) => v.to_string().parse().ok(),
+        _ => None,
+    }
+}
+
+/// the values of the y column grouped by the values of the series column,
+/// the rows with no y value are skipped
+struct Series {
+    name: String,
+    points: Vec<(Value, f64)>,
+}
+
+fn collect_series(table: &DataTable, spec: &ChartSpec) -> Vec<Series> {
+    let mut series: Vec<Series> = vec![];
+    for row in table.rows.iter() {
+        let (x, y) = match (row.get(spec.x), row.get(spec.y).and_then(as_f64)) {
+            (Some(x), Some(y)) => (x.clone(), y),
+            _ => continue,
+        };
+        let name = match spec.series {
+            Some(series) if spec.kind.has_series() => {
+                row.get(series).map(value_to_text).unwrap_or_default()
+            }
+            _ => table.columns[spec.y].name.clone(),
+        };
+        match series.iter_mut().find(|series| series.name == name) {
+            Some(series) => series.points.push((x, y)),
+            None => series.push(Series {
+                name,
+                points: vec![(x, y)],
+            }),
+        }
+    }
+    series
+}
+
+/// the distinct labels of the x values in the order they first appear
+fn categories(series: &[Series]) -> Vec<String> {
+    let mut categories: Vec<String> = vec![];
+    for (x, _y) in series.iter().flat_map(|series| series.points.iter()) {
+        let label = value_to_text(x);
+        if !categories.contains(&label) {
+            categories.push(label);
+        }
+    }
+    categories
+}
+
+/// maps the values into the pixels of an axis
+struct Scale {
+    min: f64,
+    max: f64,
+    start: f64,
+    end: f64,
+}
+
+impl Scale {
+    /// the range is widened when all the values are the same
+    fn new(
+        values: impl Iterator<Item = f64>,
+        start: f64,
+        end: f64,
+        from_zero: bool,
+    ) -> Self {
+        let (mut min, mut max) = values
+            .fold((std::f64::MAX, std::f64::MIN), |(min, max), v| {
+                (min.min(v), max.max(v))
+            });
+        if min > max {
+            min = 0.0;
+            max = 1.0;
+        }
+        if from_zero {
+            min = min.min(0.0);
+            max = max.max(0.0);
+        }
+        if (max - min).abs() < std::f64::EPSILON {
+            max = min + 1.0;
+        }
+        Scale {
+            min,
+            max,
+            start,
+            end,
+        }
+    }
+
+    fn position(&self, value: f64) -> f64 {
+        self.start
+            + (value - self.min) / (self.max - self.min)
+                * (self.end - self.start)
+    }
+
+    fn ticks(&self) -> Vec<f64> {
+        (0..=Y_TICKS)
+            .map(|i| {
+                self.min + (self.max - self.min) * i as f64 / Y_TICKS as f64
+            })
+            .collect()
+    }
+}
+
+/// integers are shown without the decimal places
+fn format_number(value: f64) -> String {
+    if value.fract().abs() < std::f64::EPSILON {
+        format!("{}", value as i64)
+    } else {
+        format!("{:.2}", value)
+    }
+}
+
+fn color(index: usize) -> &'static str {
+    PALETTE[index % PALETTE.len()]
+}
+
+fn label<MSG: Clone>(
+    x: f64,
+    y: f64,
+    anchor: &str,
+    content: String,
+) -> Node<MSG> {
+    text(
+        vec![
+            attr("x", x),
+            attr("y", y),
+            attr("text-anchor", anchor.to_string()),
+            attr("font-size", 11),
+            attr("fill", "#333"),
+        ],
+        vec![html::text(content)],
+    )
+}
+
+fn segment<MSG: Clone>(
+    (x1, y1): (f64, f64),
+    (x2, y2): (f64, f64),
+    stroke: &str,
+) -> Node<MSG> {
+    line(
+        vec![
+            attr("x1", x1),
+            attr("y1", y1),
+            attr("x2", x2),
+            attr("y2", y2),
+            attr("stroke", stroke.to_string()),
+        ],
+        vec![],
+    )
+}
+
+/// the y axis with its ticks and the x axis at the bottom of the plot area
+fn axes<MSG: Clone>(
+    y_scale: &Scale,
+    width: f64,
+    height: f64,
+) -> Vec<Node<MSG>> {
+    let right = width - MARGIN_RIGHT;
+    let bottom = height - MARGIN_BOTTOM;
+    let mut nodes = vec![
+        segment((MARGIN_LEFT, MARGIN_TOP), (MARGIN_LEFT, bottom), "#999"),
+        segment((MARGIN_LEFT, bottom), (right, bottom), "#999"),
+    ];
+    for tick in y_scale.ticks() {
+        let y = y_scale.position(tick);
+        nodes.push(segment((MARGIN_LEFT - 4.0, y), (right, y), "#eee"));
+        nodes.push(label(
+            MARGIN_LEFT - 6.0,
+            y + 4.0,
+            "end",
+            format_number(tick),
+        ));
+    }
+    nodes
+}
+
+/// the names of the series beside the plot area
+fn legend<MSG: Clone>(series: &[Series], width: f64) -> Vec<Node<MSG>> {
+    series
+        .iter()
+        .enumerate()
+        .flat_map(|(index, series)| {
+            let x = width - MARGIN_RIGHT + 10.0;
+            let y = MARGIN_TOP + index as f64 * 16.0;
+            vec![
+                rect(
+                    vec![
+                        attr("x", x),
+                        attr("y", y),
+                        attr("width", 10),
+                        attr("height", 10),
+                        attr("fill", color(index)),
+                    ],
+                    vec![],
+                ),
+                label(x + 14.0, y + 9.0, "start", series.name.clone()),
+            ]
+        })
+        .collect()
+}
+
+/// the labels of the categories below the x axis
+fn category_labels<MSG: Clone>(
+    categories: &[String],
+    band: f64,
+    height: f64,
+) -> Vec<Node<MSG>> {
+    categories
+        .iter()
+        .enumerate()
+        .map(|(index, category)| {
+            label(
+                MARGIN_LEFT + band * (index as f64 + 0.5),
+                height - MARGIN_BOTTOM + 16.0,
+                "middle",
+                category.to_string(),
+            )
+        })
+        .collect()
+}
+
+fn bar_chart<MSG: Clone>(
+    series: &[Series],
+    width: f64,
+    height: f64,
+) -> Vec<Node<MSG>> {
+    let categories = categories(series);
+    let band =
+        (width - MARGIN_LEFT - MARGIN_RIGHT) / categories.len().max(1) as f64;
+    let bar_width = band * 0.8 / series.len().max(1) as f64;
+    let y_scale = Scale::new(
+        series
+            .iter()
+            .flat_map(|s| s.points.iter().map(|(_x, y)| *y)),
+        height - MARGIN_BOTTOM,
+        MARGIN_TOP,
+        true,
+    );
+    let zero = y_scale.position(0.0);
+    let mut nodes = axes(&y_scale, width, height);
+    for (index, series) in series.iter().enumerate() {
+        for (x, y) in series.points.iter() {
+            let category = categories
+                .iter()
+                .position(|category| *category == value_to_text(x))
+                .unwrap_or(0);
+            let top = y_scale.position(*y);
+            nodes.push(rect(
+                vec![
+                    attr(
+                        "x",
+                        MARGIN_LEFT
+                            + band * (category as f64 + 0.1)
+                            + bar_width * index as f64,
+                    ),
+                    attr("y", top.min(zero)),
+                    attr("width", bar_width),
+                    attr("height", (zero - top).abs()),
+                    attr("fill", color(index)),
+                ],
+                vec![],
+            ));
+        }
+    }
+    nodes.extend(category_labels(&categories, band, height));
+    nodes
+}
+
+fn line_chart<MSG: Clone>(
+    series: &[Series],
+    width: f64,
+    height: f64,
+) -> Vec<Node<MSG>> {
+    let categories = categories(series);
+    let band =
+        (width - MARGIN_LEFT - MARGIN_RIGHT) / categories.len().max(1) as f64;
+    let y_scale = Scale::new(
+        series
+            .iter()
+            .flat_map(|s| s.points.iter().map(|(_x, y)| *y)),
+        height - MARGIN_BOTTOM,
+        MARGIN_TOP,
+        false,
+    );
+    let mut nodes = axes(&y_scale, width, height);
+    for (index, series) in series.iter().enumerate() {
+        let mut points: Vec<(usize, f64)> = series
+            .points
+            .iter()
+            .filter_map(|(x, y)| {
+                let label = value_to_text(x);
+                categories
+                    .iter()
+                    .position(|category| *category == label)
+                    .map(|category| (category, *y))
+            })
+            .collect();
+        points.sort_by_key(|(category, _y)| *category);
+        let points: Vec<String> = points
+            .iter()
+            .map(|(category, y)| {
+                format!(
+                    "{:.1},{:.1}",
+                    MARGIN_LEFT + band * (*category as f64 + 0.5),
+                    y_scale.position(*y)
+                )
+            })
+            .collect();
+        nodes.push(polyline(
+            vec![
+                attr("points", points.join(" ")),
+                attr("fill", "none"),
+                attr("stroke", color(index)),
+                attr("stroke-width", 2),
+            ],
+            vec![],
+        ));
+    }
+    nodes.extend(category_labels(&categories, band, height));
+    nodes
+}
+
+fn scatter_chart<MSG: Clone>(
+    series: &[Series],
+    width: f64,
+    height: f64,
+) -> Vec<Node<MSG>> {
+    let x_scale = Scale::new(
+        series
+            .iter()
+            .flat_map(|s| s.points.iter().filter_map(|(x, _y)| as_f64(x))),
+        MARGIN_LEFT,
+        width - MARGIN_RIGHT,
+        false,
+    );
+    let y_scale = Scale::new(
+        series
+            .iter()
+            .flat_map(|s| s.points.iter().map(|(_x, y)| *y)),
+        height - MARGIN_BOTTOM,
+        MARGIN_TOP,
+        false,
+    );
+    let mut nodes = axes(&y_scale, width, height);
+    for tick in x_scale.ticks() {
+        nodes.push(label(
+            x_scale.position(tick),
+            height - MARGIN_BOTTOM + 16.0,
+            "middle",
+            format_number(tick),
+        ));
+    }
+    for (index, series) in series.iter().enumerate() {
+        for (x, y) in series.points.iter() {
+            if let Some(x) = as_f64(x) {
+                nodes.push(circle(
+                    vec![
+                        attr("cx", x_scale.position(x)),
+                        attr("cy", y_scale.position(*y)),
+                        attr("r", 3),
+                        attr("fill", color(index)),
+                    ],
+                    vec![],
+                ));
+            }
+        }
+    }
+    nodes
+}
+
+/// a slice of the pie, the angles are clockwise from the top in radians
+#[derive(Debug, PartialEq)]
+struct Slice {
+    /// the index of the category, which picks the color of the slice
+    category: usize,
+    start: f64,
+    sweep: f64,
+}
+
+/// the slices are the totals of the positive values of each category
+fn pie_slices(series: &[Series], categories: &[String]) -> Vec<Slice> {
+    let mut totals = vec![0.0; categories.len()];
+    for (x, y) in series.iter().flat_map(|series| series.points.iter()) {
+        if let Some(category) =
+            categories.iter().position(|c| *c == value_to_text(x))
+        {
+            totals[category] += y.max(0.0);
+        }
+    }
+    let sum: f64 = totals.iter().sum();
+    let mut slices = vec![];
+    let mut start = 0.0;
+    for (category, total) in totals.iter().enumerate() {
+        if sum <= 0.0 || *total <= 0.0 {
+            continue;
+        }
+        let sweep = total / sum * 2.0 * std::f64::consts::PI;
+        slices.push(Slice {
+            category,
+            start,
+            sweep,
+        });
+        start += sweep;
+    }
+    slices
+}
+
+fn pie_chart<MSG: Clone>(
+    series: &[Series],
+    width: f64,
+    height: f64,
+) -> Vec<Node<MSG>> {
+    let categories = categories(series);
+    let radius =
+        ((width - MARGIN_RIGHT).min(height) / 2.0 - MARGIN_TOP).max(1.0);
+    let (cx, cy) = ((width - MARGIN_RIGHT) / 2.0, height / 2.0);
+    let point =
+        |angle: f64| (cx + radius * angle.sin(), cy - radius * angle.cos());
+    let mut nodes = vec![];
+    for slice in pie_slices(series, &categories) {
+        let fill = color(slice.category);
+        if slice.sweep / (2.0 * std::f64::consts::PI) > 0.9999 {
+            nodes.push(circle(
+                vec![
+                    attr("cx", cx),
+                    attr("cy", cy),
+                    attr("r", radius),
+                    attr("fill", fill),
+                ],
+                vec![],
+            ));
+        } else {
+            let (x0, y0) = point(slice.start);
+            let (x1, y1) = point(slice.start + slice.sweep);
+            let large_arc = if slice.sweep > std::f64::consts::PI {
+                1
+            } else {
+                0
+            };
+            nodes.push(path(
+                vec![
+                    attr(
+                        "d",
+                        format!(
+                            "M{:.1},{:.1} L{:.1},{:.1} \
+                             A{:.1},{:.1} 0 {} 1 {:.1},{:.1} Z",
+                            cx, cy, x0, y0, radius, radius, large_arc, x1, y1
+                        ),
+                    ),
+                    attr("fill", fill),
+                    attr("stroke", "#fff"),
+                ],
+                vec![],
+            ));
+        }
+    }
+    let slices: Vec<Series> = categories
+        .into_iter()
+        .map(|name| Series {
+            name,
+            points: vec![],
+        })
+        .collect();
+    nodes.extend(legend(&slices, width));
+    nodes
+}
+
+/// the chart of the columns of the table in the spec
+pub fn chart<MSG: Clone>(
+    table: &DataTable,
+    spec: &ChartSpec,
+    width: i32,
+    height: i32,
+) -> Node<MSG> {
+    let series = collect_series(table, spec);
+    let (w, h) = (f64::from(width), f64::from(height));
+    let mut nodes = match spec.kind {
+        ChartKind::Bar => bar_chart(&series, w, h),
+        ChartKind::Line => line_chart(&series, w, h),
+        ChartKind::Scatter => scatter_chart(&series, w, h),
+        ChartKind::Pie => pie_chart(&series, w, h),
+    };
+    if spec.kind.has_series() {
+        nodes.extend(legend(&series, w));
+    }
+    svg(
+        vec![
+            attr("xmlns", "http://www.w3.org/2000/svg"),
+            attr("width", width),
+            attr("height", height),
+            attr("viewBox", format!("0 0 {} {}", width, height)),
+            attr("font-family", "sans-serif"),
+        ],
+        vec![g(vec![attr("class", "chart")], nodes)],
+    )
+}
+
+/// the chart as the content of an svg file
+pub fn to_svg_file(
+    table: &DataTable,
+    spec: &ChartSpec,
+    width: i32,
+    height: i32,
+) -> String {
+    let node: Node<()> = chart(table, spec, width, height);
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n{}",
+        node.to_string()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use data_table::DataColumn;
+    use std::f64::consts::PI;
+
+    fn column(name: &str, data_type: SqlType) -> DataColumn {
+        DataColumn {
+            name: name.to_string(),
+            description: None,
+            tags: vec![],
+            data_type,
+            is_primary: false,
+        }
+    }
+
+    /// the rentals of each rating in each year, one of them has no total
+    fn rentals() -> DataTable {
+        let row = |rating: &str, year: i32, total: Option<i32>| {
+            vec![
+                Value::Text(rating.to_string()),
+                Value::Int(year),
+                total.map(Value::Int).unwrap_or(Value::Nil),
+            ]
+        };
+        DataTable {
+            columns: vec![
+                column("rating", SqlType::Text),
+                column("year", SqlType::Int),
+                column("total", SqlType::Int),
+            ],
+            rows: vec![
+                row("G", 2005, Some(10)),
+                row("PG", 2005, Some(20)),
+                row("G", 2006, Some(30)),
+                row("R", 2006, None),
+            ],
+        }
+    }
+
+    fn spec(kind: ChartKind, series: Option<usize>) -> ChartSpec {
+        ChartSpec {
+            kind,
+            x: 0,
+            y: 2,
+            series,
+        }
+    }
+
+    #[test]
+    fn empty_scale_is_from_0_to_1() {
+        let scale = Scale::new(vec![].into_iter(), 0.0, 100.0, false);
+        assert_eq!((scale.min, scale.max), (0.0, 1.0));
+        assert_eq!(scale.position(0.5), 50.0);
+    }
+
+    #[test]
+    fn constant_scale_is_widened() {
+        let scale = Scale::new(vec![5.0, 5.0].into_iter(), 0.0, 100.0, false);
+        assert_eq!((scale.min, scale.max), (5.0, 6.0));
+        assert_eq!(scale.position(5.0), 0.0);
+
+        let scale = Scale::new(vec![5.0, 5.0].into_iter(), 0.0, 100.0, true);
+        assert_eq!((scale.min, scale.max), (0.0, 5.0));
+        assert_eq!(scale.position(5.0), 100.0);
+    }
+
+    /// the y axis goes up from the bottom of the plot area
+    #[test]
+    fn inverted_scale() {
+        let scale = Scale::new(vec![-10.0, 30.0].into_iter(), 200.0, 0.0, true);
+        assert_eq!(scale.position(-10.0), 200.0);
+        assert_eq!(scale.position(30.0), 0.0);
+        assert_eq!(scale.position(10.0), 100.0);
+        assert_eq!(scale.ticks().len(), Y_TICKS + 1);
+    }
+
+    #[test]
+    fn series_are_split_by_the_series_column() {
+        let series = collect_series(&rentals(), &spec(ChartKind::Bar, Some(1)));
+        let names: Vec<&str> = series.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["2005", "2006"]);
+        assert_eq!(
+            series[0].points,
+            vec![
+                (Value::Text("G".to_string()), 10.0),
+                (Value::Text("PG".to_string()), 20.0)
+            ]
+        );
+        // the row with no total is skipped
+        assert_eq!(series[1].points.len(), 1);
+    }
+
+    /// a pie has a single series named after the y column
+    #[test]
+    fn pie_ignores_the_series_column() {
+        let series = collect_series(&rentals(), &spec(ChartKind::Pie, Some(1)));
+        assert_eq!(series.len(), 1);
+        assert_eq!(series[0].name, "total");
+        assert_eq!(series[0].points.len(), 3);
+    }
+
+    #[test]
+    fn categories_in_the_order_they_appear() {
+        let series = collect_series(&rentals(), &spec(ChartKind::Bar, Some(1)));
+        assert_eq!(categories(&series), vec!["G", "PG"]);
+    }
+
+    #[test]
+    fn integers_have_no_decimal_places() {
+        assert_eq!(format_number(3.0), "3");
+        assert_eq!(format_number(-4.0), "-4");
+        assert_eq!(format_number(2.5), "2.50");
+        assert_eq!(format_number(1.0 / 3.0), "0.33");
+    }
+
+    #[test]
+    fn pie_sweeps_add_up_to_a_circle() {
+        let series = vec![Series {
+            name: "total".to_string(),
+            points: vec![
+                (Value::Text("G".to_string()), 10.0),
+                (Value::Text("PG".to_string()), -5.0),
+                (Value::Text("R".to_string()), 10.0),
+                (Value::Text("G".to_string()), 20.0),
+            ],
+        }];
+        let categories = categories(&series);
+        let slices = pie_slices(&series, &categories);
+        // the negative category has no slice but keeps its color
+        assert_eq!(
+            slices,
+            vec![
+                Slice {
+                    category: 0,
+                    start: 0.0,
+                    sweep: 1.5 * PI,
+                },
+                Slice {
+                    category: 2,
+                    start: 1.5 * PI,
+                    sweep: 0.5 * PI,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn no_slices_without_positive_values() {
+        let series = vec![Series {
+            name: "total".to_string(),
+            points: vec![(Value::Text("G".to_string()), -1.0)],
+        }];
+        assert_eq!(pie_slices(&series, &categories(&series)), vec![]);
+    }
+}
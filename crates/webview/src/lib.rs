@@ -9,7 +9,9 @@ extern crate log;
 
 mod app;
 mod assets;
+mod chart;
 mod rest_api;
+mod route;
 mod widgets;
 
 pub fn setup_program(initial_state: &str) -> Rc<Program<App, Msg>> {
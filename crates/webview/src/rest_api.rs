@@ -1,24 +1,364 @@
 use crate::app::{App, Msg};
-use diwata_intel::{data_container::QueryResult, Dao, RecordDetail, TableName};
-use sauron::{Cmd, Http};
-use wasm_bindgen::JsValue;
+use diwata_intel::{
+    data_aggregate::ColumnAggregate,
+    data_container::{Filter, QueryResult, SaveContainer, Sort},
+    data_group::{GroupRequest, GroupResult},
+    data_import::{ColumnMapping, ImportRequest, ImportSummary},
+    data_modify,
+    data_tree::TreeNode,
+    erd::Erd,
+    query_history::{QueryHistory, SavedQuery},
+    query_plan::QueryPlan,
+    sql_guard::SqlViolation,
+    sql_params::Placeholder,
+    Dao, RecordDetail, TableName, Value,
+};
+use sauron::{Cmd, Dispatch, Http};
+use serde::de::DeserializeOwned;
+use wasm_bindgen::{closure::Closure, JsCast, JsValue};
+use web_sys::{FileReader, HtmlInputElement, RequestInit, Response};
 
-pub fn execute_sql_query<F>(sql: &str, msg_receiver: F) -> Cmd<App, Msg>
+/// A rejected query is responded with a serialized SqlViolation,
+/// which is passed to the msg_receiver as an error message
+pub fn execute_sql_query<F>(sql: &str, params: &[Value], msg_receiver: F) -> Cmd<App, Msg>
 where
     F: Fn(Result<QueryResult, JsValue>) -> Msg + Clone + 'static,
 {
-    let url = format!("/sql/?sql={}", sql);
-    let text_decoder = |v: String| {
-        let value = ron::de::from_str(&v);
-        match value {
-            Ok(value) => value,
+    let url = format!("/sql/?{}", sql_query_string(sql, params));
+    fetch_guarded(&url, msg_receiver)
+}
+
+pub fn explain_sql_query<F>(
+    sql: &str,
+    params: &[Value],
+    analyze: bool,
+    msg_receiver: F,
+) -> Cmd<App, Msg>
+where
+    F: Fn(Result<QueryPlan, JsValue>) -> Msg + Clone + 'static,
+{
+    let url = format!(
+        "/explain/?{}&analyze={}",
+        sql_query_string(sql, params),
+        analyze
+    );
+    fetch_guarded(&url, msg_receiver)
+}
+
+/// the placeholders in the sql with the types inferred by the server
+pub fn fetch_sql_placeholders<F>(sql: &str, msg_receiver: F) -> Cmd<App, Msg>
+where
+    F: Fn(Result<Vec<Placeholder>, JsValue>) -> Msg + Clone + 'static,
+{
+    let url = format!("/sql_params/?sql={}", js_sys::encode_uri_component(sql));
+    let text_decoder = |v: String| ron::de::from_str(&v).expect("Unable to decode ron data");
+    Http::fetch_with_text_response_decoder(&url, text_decoder, msg_receiver)
+}
+
+/// the sql and the ron serialized values of its placeholders
+fn sql_query_string(sql: &str, params: &[Value]) -> String {
+    let sql = js_sys::encode_uri_component(sql);
+    if params.is_empty() {
+        format!("sql={}", sql)
+    } else {
+        let params = ron::ser::to_string(params).expect("Unable to serialize params");
+        format!(
+            "sql={}&params={}",
+            sql,
+            js_sys::encode_uri_component(&params)
+        )
+    }
+}
+
+/// the formats offered for downloading the rows
+pub const EXPORT_FORMATS: [&str; 3] = ["csv", "json", "ndjson"];
+
+/// the download url of the rows in the main tab of the window,
+/// when display is set the foreign keys are exported as their identifier display
+pub fn export_window_url(table_name: &TableName, format: &str, display: bool) -> String {
+    format!(
+        "/export/{}/?format={}&display={}",
+        table_name.complete_name(),
+        format,
+        display
+    )
+}
+
+/// the download url of the rows of the ad-hoc query
+pub fn export_sql_url(sql: &str, params: &[Value], format: &str, display: bool) -> String {
+    format!(
+        "/export_sql/?{}&format={}&display={}",
+        sql_query_string(sql, params),
+        format,
+        display
+    )
+}
+
+/// the download url of the rows in the has_many tab of the record
+pub fn export_has_many_url(
+    table_name: &TableName,
+    has_many_table: &TableName,
+    record: &Dao,
+    format: &str,
+    display: bool,
+) -> String {
+    format!(
+        "/export/{}/has_many/{}/?{}",
+        table_name.complete_name(),
+        has_many_table.complete_name(),
+        record_query_string(record, format, display)
+    )
+}
+
+/// the download url of the rows in the indirect tab of the record
+pub fn export_indirect_url(
+    table_name: &TableName,
+    linker: &TableName,
+    indirect_table: &TableName,
+    record: &Dao,
+    format: &str,
+    display: bool,
+) -> String {
+    format!(
+        "/export/{}/indirect/{}/{}/?{}",
+        table_name.complete_name(),
+        linker.complete_name(),
+        indirect_table.complete_name(),
+        record_query_string(record, format, display)
+    )
+}
+
+fn record_query_string(record: &Dao, format: &str, display: bool) -> String {
+    let record = ron::ser::to_string(record).expect("Unable to serialize record");
+    format!(
+        "record={}&format={}&display={}",
+        js_sys::encode_uri_component(&record),
+        format,
+        display
+    )
+}
+
+/// save the modified rows of the query result, the container is posted
+/// since it can be too large for a url.
+/// The saved records are returned to refresh the rows
+pub fn save_container<F>(container: &SaveContainer, msg_receiver: F) -> Cmd<App, Msg>
+where
+    F: Fn(Result<Vec<Dao>, JsValue>) -> Msg + Clone + 'static,
+{
+    let container = ron::ser::to_string(container).expect("Unable to serialize save container");
+    send_cmd(
+        "POST",
+        "/save_container/".to_string(),
+        Some(container),
+        msg_receiver,
+    )
+}
+
+pub fn explain_window_query<F>(
+    table_name: &TableName,
+    page: usize,
+    analyze: bool,
+    msg_receiver: F,
+) -> Cmd<App, Msg>
+where
+    F: Fn(Result<QueryPlan, JsValue>) -> Msg + Clone + 'static,
+{
+    let url = format!(
+        "/explain/{}/page/{}?analyze={}",
+        table_name.complete_name(),
+        page,
+        analyze
+    );
+    fetch_guarded(&url, msg_receiver)
+}
+
+/// read the csv file selected in the file input and import it into the table,
+/// the csv is posted since it can be too large for a url
+pub fn import_csv<F>(
+    file_input_id: &str,
+    table_name: &TableName,
+    mappings: &[ColumnMapping],
+    dry_run: bool,
+    msg_receiver: F,
+) -> Cmd<App, Msg>
+where
+    F: Fn(Result<ImportSummary, JsValue>) -> Msg + Clone + 'static,
+{
+    let file_input_id = file_input_id.to_string();
+    let url = format!("/import/{}/", table_name.complete_name());
+    let mappings = mappings.to_vec();
+    Cmd::new(move |program| {
+        let file = sauron::document()
+            .get_element_by_id(&file_input_id)
+            .and_then(|element| element.dyn_into::<HtmlInputElement>().ok())
+            .and_then(|file_input| file_input.files())
+            .and_then(|files| files.get(0));
+        let file = match file {
+            Some(file) => file,
+            None => {
+                program.dispatch(msg_receiver(Err(JsValue::from_str(
+                    "Select a csv file to import",
+                ))));
+                return;
+            }
+        };
+        let reader = FileReader::new().expect("unable to create a file reader");
+        let reader_clone = reader.clone();
+        let url = url.clone();
+        let mappings = mappings.clone();
+        let msg_receiver = msg_receiver.clone();
+        let program = program.clone();
+        let onload = Closure::once(move || {
+            let csv = reader_clone
+                .result()
+                .ok()
+                .and_then(|result| result.as_string())
+                .unwrap_or_default();
+            let request = ImportRequest {
+                csv,
+                mappings,
+                dry_run,
+            };
+            let body = ron::ser::to_string(&request).expect("Unable to serialize import request");
+            post_guarded(&url, &JsValue::from_str(&body), move |result| {
+                program.dispatch(msg_receiver(result))
+            });
+        });
+        reader.set_onload(Some(onload.as_ref().unchecked_ref()));
+        onload.forget();
+        reader
+            .read_as_text(&file)
+            .expect("unable to read the csv file");
+    })
+}
+
+/// upload the file selected in the file input into the binary column at the url,
+/// the file is posted as is and the updated record is received
+pub fn upload_binary<F>(file_input_id: &str, url: &str, msg_receiver: F) -> Cmd<App, Msg>
+where
+    F: Fn(Result<Dao, JsValue>) -> Msg + Clone + 'static,
+{
+    let file_input_id = file_input_id.to_string();
+    let url = url.to_string();
+    Cmd::new(move |program| {
+        let file = sauron::document()
+            .get_element_by_id(&file_input_id)
+            .and_then(|element| element.dyn_into::<HtmlInputElement>().ok())
+            .and_then(|file_input| file_input.files())
+            .and_then(|files| files.get(0));
+        let error = match file {
+            None => "Select a file to upload".to_string(),
+            Some(ref file) if file.size() > data_modify::BINARY_SIZE_LIMIT as f64 => format!(
+                "The file is larger than the limit of {} bytes",
+                data_modify::BINARY_SIZE_LIMIT
+            ),
+            Some(file) => {
+                let msg_receiver = msg_receiver.clone();
+                let program = program.clone();
+                post_guarded(&url, &file, move |result| {
+                    program.dispatch(msg_receiver(result))
+                });
+                return;
+            }
+        };
+        program.dispatch(msg_receiver(Err(JsValue::from_str(&error))));
+    })
+}
+
+/// post the body to endpoints which are guarded by the sql guard,
+/// the response is either the expected result or a SqlViolation
+fn post_guarded<T, F>(url: &str, body: &JsValue, receiver: F)
+where
+    T: DeserializeOwned + 'static,
+    F: FnOnce(Result<T, JsValue>) + 'static,
+{
+    send_guarded("POST", url, Some(body), receiver)
+}
+
+/// send the request with the method to endpoints which are guarded by the sql guard,
+/// used for the requests that modifies the data
+fn send_guarded<T, F>(method: &str, url: &str, body: Option<&JsValue>, receiver: F)
+where
+    T: DeserializeOwned + 'static,
+    F: FnOnce(Result<T, JsValue>) + 'static,
+{
+    let mut init = RequestInit::new();
+    init.method(method);
+    init.body(body);
+    let on_text = Closure::once(move |text: JsValue| {
+        let text = text.as_string().unwrap_or_default();
+        let result = match ron::de::from_str(&text) {
+            Ok(result) => Ok(result),
+            Err(_) => match ron::de::from_str::<SqlViolation>(&text) {
+                Ok(violation) => Err(JsValue::from_str(&violation.to_string())),
+                Err(_) => Err(JsValue::from_str(&text)),
+            },
+        };
+        receiver(result);
+    });
+    let on_response = Closure::once(move |response: JsValue| {
+        let response: Response = response.unchecked_into();
+        let _ = response
+            .text()
+            .expect("unable to read the response")
+            .then(&on_text);
+        on_text.forget();
+    });
+    let _ = sauron::window()
+        .fetch_with_str_and_init(url, &init)
+        .then(&on_response);
+    on_response.forget();
+}
+
+/// the command which sends the request with the method and body,
+/// the result is dispatched to the msg receiver
+fn send_cmd<T, F>(
+    method: &'static str,
+    url: String,
+    body: Option<String>,
+    msg_receiver: F,
+) -> Cmd<App, Msg>
+where
+    T: DeserializeOwned + 'static,
+    F: Fn(Result<T, JsValue>) -> Msg + Clone + 'static,
+{
+    Cmd::new(move |program| {
+        let body = body.as_ref().map(|body| JsValue::from_str(body));
+        let msg_receiver = msg_receiver.clone();
+        let program = program.clone();
+        send_guarded(method, &url, body.as_ref(), move |result| {
+            program.dispatch(msg_receiver(result))
+        });
+    })
+}
+
+/// fetch from endpoints which are guarded by the sql guard,
+/// the body is either the expected result or a SqlViolation
+fn fetch_guarded<T, F>(url: &str, msg_receiver: F) -> Cmd<App, Msg>
+where
+    T: DeserializeOwned + 'static,
+    F: Fn(Result<T, JsValue>) -> Msg + Clone + 'static,
+{
+    // the body which is neither the result nor a violation is the error itself
+    let text_decoder = |v: String| -> Result<T, String> {
+        if let Ok(result) = ron::de::from_str(&v) {
+            return Ok(result);
+        }
+        match ron::de::from_str::<SqlViolation>(&v) {
+            Ok(violation) => Err(violation.to_string()),
             Err(e) => {
                 trace!("Error: {}", e);
-                panic!();
+                Err(v)
             }
         }
     };
-    Http::fetch_with_text_response_decoder(&url, text_decoder, msg_receiver)
+    Http::fetch_with_text_response_decoder(
+        url,
+        text_decoder,
+        move |result: Result<Result<T, String>, JsValue>| {
+            msg_receiver(result.and_then(|result| result.map_err(|e| JsValue::from_str(&e))))
+        },
+    )
 }
 
 pub fn fetch_window_data<F>(table_name: &TableName, msg_receiver: F) -> Cmd<App, Msg>
@@ -30,17 +370,100 @@ where
     Http::fetch_with_text_response_decoder(&url, text_decoder, msg_receiver)
 }
 
-pub fn fetch_window_data_next_page<F>(
+/// the page of the rows of the window which matches the filter, in the order of the sort
+pub fn fetch_window_data_page<F>(
     table_name: &TableName,
+    filter: &Filter,
+    sort: &Sort,
     page: usize,
     msg_receiver: F,
 ) -> Cmd<App, Msg>
 where
     F: Fn(Result<QueryResult, JsValue>) -> Msg + Clone + 'static,
 {
-    let url = format!("/main_data/{}/page/{}", table_name.complete_name(), page);
-    let text_decoder = |v: String| ron::de::from_str(&v).expect("Unable to decode ron data");
-    Http::fetch_with_text_response_decoder(&url, text_decoder, msg_receiver)
+    let filter = ron::ser::to_string(filter).expect("Unable to serialize filter");
+    let sort = ron::ser::to_string(sort).expect("Unable to serialize sort");
+    let url = format!(
+        "/main_data/{}/page/{}?filter={}&sort={}",
+        table_name.complete_name(),
+        page,
+        js_sys::encode_uri_component(&filter),
+        js_sys::encode_uri_component(&sort)
+    );
+    fetch_guarded(&url, msg_receiver)
+}
+
+/// the aggregates of the columns over all the rows of the table
+pub fn fetch_aggregates<F>(
+    table_name: &TableName,
+    aggregates: &[ColumnAggregate],
+    msg_receiver: F,
+) -> Cmd<App, Msg>
+where
+    F: Fn(Result<Vec<Value>, JsValue>) -> Msg + Clone + 'static,
+{
+    let aggregates = ron::ser::to_string(aggregates).expect("Unable to serialize aggregates");
+    let url = format!(
+        "/aggregate/{}/?aggregates={}",
+        table_name.complete_name(),
+        js_sys::encode_uri_component(&aggregates)
+    );
+    fetch_guarded(&url, msg_receiver)
+}
+
+/// the rows of the table grouped by the columns in the request
+pub fn fetch_groups<F>(
+    table_name: &TableName,
+    request: &GroupRequest,
+    msg_receiver: F,
+) -> Cmd<App, Msg>
+where
+    F: Fn(Result<GroupResult, JsValue>) -> Msg + Clone + 'static,
+{
+    let request = ron::ser::to_string(request).expect("Unable to serialize group request");
+    let url = format!(
+        "/group/{}/?request={}",
+        table_name.complete_name(),
+        js_sys::encode_uri_component(&request)
+    );
+    fetch_guarded(&url, msg_receiver)
+}
+
+/// the records whose parent is the record, or the roots of the tree when there is no parent
+pub fn fetch_tree_children<F>(
+    table_name: &TableName,
+    parent: Option<&str>,
+    msg_receiver: F,
+) -> Cmd<App, Msg>
+where
+    F: Fn(Result<Vec<TreeNode>, JsValue>) -> Msg + Clone + 'static,
+{
+    let url = match parent {
+        Some(parent) => format!(
+            "/tree/{}/children/?parent={}",
+            table_name.complete_name(),
+            js_sys::encode_uri_component(parent)
+        ),
+        None => format!("/tree/{}/children/", table_name.complete_name()),
+    };
+    fetch_guarded(&url, msg_receiver)
+}
+
+/// the record and its ancestors, from the root of the tree
+pub fn fetch_tree_ancestors<F>(
+    table_name: &TableName,
+    record_id: &str,
+    msg_receiver: F,
+) -> Cmd<App, Msg>
+where
+    F: Fn(Result<Vec<TreeNode>, JsValue>) -> Msg + Clone + 'static,
+{
+    let url = format!(
+        "/tree/{}/ancestors/{}",
+        table_name.complete_name(),
+        js_sys::encode_uri_component(record_id)
+    );
+    fetch_guarded(&url, msg_receiver)
 }
 
 pub fn retrieve_detail_for_main_tab<F>(
@@ -62,3 +485,74 @@ where
     let text_decoder = |v: String| ron::de::from_str(&v).expect("Unable to decode ron data");
     Http::fetch_with_text_response_decoder(&url, text_decoder, msg_receiver)
 }
+
+/// the url of the relationship diagram of the tables in the schema
+/// or around the center table, the format is either dot, json or ron
+pub fn erd_url(schema: &str, center: &str, depth: usize, format: &str) -> String {
+    format!(
+        "/erd/?format={}&schema={}&center={}&depth={}",
+        format,
+        js_sys::encode_uri_component(schema),
+        js_sys::encode_uri_component(center),
+        depth
+    )
+}
+
+pub fn fetch_erd<F>(schema: &str, center: &str, depth: usize, msg_receiver: F) -> Cmd<App, Msg>
+where
+    F: Fn(Result<Erd, JsValue>) -> Msg + Clone + 'static,
+{
+    let url = erd_url(schema, center, depth, "ron");
+    let text_decoder = |v: String| ron::de::from_str(&v).expect("Unable to decode ron data");
+    Http::fetch_with_text_response_decoder(&url, text_decoder, msg_receiver)
+}
+
+pub fn fetch_query_history<F>(search: &str, msg_receiver: F) -> Cmd<App, Msg>
+where
+    F: Fn(Result<Vec<QueryHistory>, JsValue>) -> Msg + Clone + 'static,
+{
+    let url = format!("/history/?search={}", js_sys::encode_uri_component(search));
+    let text_decoder = |v: String| ron::de::from_str(&v).expect("Unable to decode ron data");
+    Http::fetch_with_text_response_decoder(&url, text_decoder, msg_receiver)
+}
+
+pub fn fetch_saved_queries<F>(search: &str, msg_receiver: F) -> Cmd<App, Msg>
+where
+    F: Fn(Result<Vec<SavedQuery>, JsValue>) -> Msg + Clone + 'static,
+{
+    let url = format!(
+        "/saved_queries/?search={}",
+        js_sys::encode_uri_component(search)
+    );
+    let text_decoder = |v: String| ron::de::from_str(&v).expect("Unable to decode ron data");
+    Http::fetch_with_text_response_decoder(&url, text_decoder, msg_receiver)
+}
+
+pub fn save_query<F>(saved_query: &SavedQuery, msg_receiver: F) -> Cmd<App, Msg>
+where
+    F: Fn(Result<SavedQuery, JsValue>) -> Msg + Clone + 'static,
+{
+    let saved_query = ron::ser::to_string(saved_query).expect("Unable to serialize saved query");
+    send_cmd(
+        "POST",
+        "/save_query/".to_string(),
+        Some(saved_query),
+        msg_receiver,
+    )
+}
+
+/// the sql of the saved query is executed with the same restrictions as the ad-hoc sql
+pub fn open_saved_query<F>(id: i64, msg_receiver: F) -> Cmd<App, Msg>
+where
+    F: Fn(Result<QueryResult, JsValue>) -> Msg + Clone + 'static,
+{
+    let url = format!("/saved_query/{}", id);
+    fetch_guarded(&url, msg_receiver)
+}
+
+pub fn delete_saved_query<F>(id: i64, msg_receiver: F) -> Cmd<App, Msg>
+where
+    F: Fn(Result<(), JsValue>) -> Msg + Clone + 'static,
+{
+    send_cmd("DELETE", format!("/saved_query/{}", id), None, msg_receiver)
+}
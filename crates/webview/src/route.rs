@@ -0,0 +1,119 @@
+//! the state of the app which is kept in the url, such as the table of the
+//! active window and its record in the detail view, so the browser history
+//! and reloading the page restores it
+use diwata_intel::{
+    data_container::{Filter, Sort},
+    TableName,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use wasm_bindgen::JsValue;
+
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct Route {
+    pub table_name: Option<TableName>,
+    pub record_id: Option<String>,
+    pub filter: Filter,
+    pub sort: Sort,
+}
+
+impl Route {
+    /// the route of the current url of the browser
+    pub fn from_location() -> Self {
+        let location = sauron::window().location();
+        let pathname = location.pathname().unwrap_or_default();
+        let search = location.search().unwrap_or_default();
+        Self::parse(&pathname, &search)
+    }
+
+    /// the route in the path such as `/{table_name}/record/{record_id}`
+    /// and the filter and sort in the query string
+    fn parse(pathname: &str, search: &str) -> Self {
+        let segments: Vec<String> = pathname
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .map(decode)
+            .collect();
+        let (table_name, record_id) = match segments.as_slice() {
+            [table_name] => (Some(TableName::from(table_name)), None),
+            [table_name, record, record_id] if record == "record" => (
+                Some(TableName::from(table_name)),
+                Some(record_id.to_string()),
+            ),
+            _ => (None, None),
+        };
+        let mut route = Route {
+            table_name,
+            record_id,
+            ..Default::default()
+        };
+        for pair in search.trim_start_matches('?').split('&') {
+            let mut key_value = pair.splitn(2, '=');
+            match (key_value.next(), key_value.next()) {
+                (Some("filter"), Some(filter)) => route.filter = deserialize_or_default(filter),
+                (Some("sort"), Some(sort)) => route.sort = deserialize_or_default(sort),
+                _ => (),
+            }
+        }
+        route
+    }
+
+    pub fn to_url(&self) -> String {
+        let mut url = match (&self.table_name, &self.record_id) {
+            (Some(table_name), Some(record_id)) => format!(
+                "/{}/record/{}",
+                encode(&table_name.complete_name()),
+                encode(record_id)
+            ),
+            (Some(table_name), None) => format!("/{}", encode(&table_name.complete_name())),
+            (None, _) => "/".to_string(),
+        };
+        let mut query = vec![];
+        if !self.filter.conditions.is_empty() {
+            query.push(format!("filter={}", serialize(&self.filter)));
+        }
+        if !self.sort.orders.is_empty() {
+            query.push(format!("sort={}", serialize(&self.sort)));
+        }
+        if !query.is_empty() {
+            url += "?";
+            url += &query.join("&");
+        }
+        url
+    }
+
+    /// add the route to the browser history, as the current url
+    pub fn push(&self) {
+        let url = self.to_url();
+        if let Err(e) =
+            sauron::history().push_state_with_url(&JsValue::NULL, &self.title(), Some(&url))
+        {
+            trace!("unable to push the url {}: {:?}", url, e);
+        }
+    }
+
+    fn title(&self) -> String {
+        match self.table_name {
+            Some(ref table_name) => table_name.complete_name(),
+            None => "Diwata".to_string(),
+        }
+    }
+}
+
+fn encode(text: &str) -> String {
+    String::from(js_sys::encode_uri_component(text))
+}
+
+fn decode(text: &str) -> String {
+    js_sys::decode_uri_component(text)
+        .map(String::from)
+        .unwrap_or_else(|_| text.to_string())
+}
+
+fn serialize<T: Serialize>(value: &T) -> String {
+    encode(&ron::ser::to_string(value).expect("unable to serialize to ron"))
+}
+
+/// the filter and sort which can not be read are ignored
+fn deserialize_or_default<T: DeserializeOwned + Default>(text: &str) -> T {
+    ron::de::from_str(&decode(text)).unwrap_or_default()
+}
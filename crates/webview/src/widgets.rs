@@ -8,6 +8,23 @@ pub fn search_widget<MSG>(event: Attribute<MSG>) -> Node<MSG>
 where
     MSG: Clone,
 {
+    search_box(vec![event])
+}
+
+/// the search widget showing the search, such as when restored from the url
+pub fn search_widget_with_value<MSG>(search: &str, event: Attribute<MSG>) -> Node<MSG>
+where
+    MSG: Clone,
+{
+    search_box(vec![value(search), event])
+}
+
+fn search_box<MSG>(attributes: Vec<Attribute<MSG>>) -> Node<MSG>
+where
+    MSG: Clone,
+{
+    let mut input_attributes = vec![r#type("text"), class("column_filter")];
+    input_attributes.extend(attributes);
     div(
         vec![class("search_icon_and_column_filter")],
         vec![
@@ -15,7 +32,7 @@ where
                 vec![class("search_icon")],
                 vec![assets::svg_search_icon(18, 18, "#888")],
             ),
-            input(vec![r#type("text"), class("column_filter"), event], vec![]),
+            input(input_attributes, vec![]),
         ],
     )
 }